@@ -0,0 +1,118 @@
+//! Checks for urls given by users, notifiers posting to them could otherwise
+//! be used to reach services on the server's own network
+
+use std::net::IpAddr;
+
+use anyhow::anyhow;
+use reqwest::Url;
+
+/// Whether `ip` is reachable from the internet. Loopback, private, link
+/// local and other special purpose addresses are not.
+pub fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let octets = ip.octets();
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || ip.is_multicast()
+                // this network, 0.0.0.0/8
+                || octets[0] == 0
+                // shared address space of carrier grade nat, 100.64.0.0/10
+                || (octets[0] == 100 && octets[1] & 0xc0 == 64))
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public(IpAddr::V4(ip)),
+            None => {
+                let segment = ip.segments()[0];
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    // unique local, fc00::/7
+                    || segment & 0xfe00 == 0xfc00
+                    // link local, fe80::/10
+                    || segment & 0xffc0 == 0xfe80)
+            }
+        },
+    }
+}
+
+/// Check that `url` is http or https and every address its host resolves to
+/// is public, returning a client which only connects to the checked addresses
+/// so the host can't resolve to another address when the request is sent
+pub async fn public_client(url: &str) -> Result<reqwest::Client, anyhow::Error> {
+    let url = Url::parse(url)?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(anyhow!("unsupported url scheme {}", url.scheme()));
+    }
+
+    let host = url
+        .host_str()
+        .ok_or_else(|| anyhow!("url has no host"))?
+        .trim_start_matches('[')
+        .trim_end_matches(']');
+    let port = url.port_or_known_default().unwrap_or(80);
+
+    let addrs: Vec<_> = tokio::net::lookup_host((host, port)).await?.collect();
+    if addrs.is_empty() || addrs.iter().any(|addr| !is_public(addr.ip())) {
+        return Err(anyhow!("{host} is not a public address"));
+    }
+
+    Ok(builder().resolve_to_addrs(host, &addrs).build()?)
+}
+
+/// Client for urls given by users. Redirects are not followed, they could
+/// lead to an address that was not checked.
+pub fn client() -> reqwest::Client {
+    builder().build().expect("failed to build http client")
+}
+
+fn builder() -> reqwest::ClientBuilder {
+    reqwest::Client::builder().redirect(reqwest::redirect::Policy::none())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_public() {
+        for ip in ["1.1.1.1", "2606:4700:4700::1111", "::ffff:8.8.8.8"] {
+            assert!(is_public(ip.parse().unwrap()), "{ip}");
+        }
+
+        for ip in [
+            "127.0.0.1",
+            "10.0.0.1",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "::",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+        ] {
+            assert!(!is_public(ip.parse().unwrap()), "{ip}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_public_client() {
+        assert!(public_client("http://1.1.1.1/").await.is_ok());
+
+        for url in [
+            "http://127.0.0.1:8080/",
+            "http://[::1]/",
+            "https://localhost/",
+            "ftp://1.1.1.1/",
+        ] {
+            assert!(public_client(url).await.is_err(), "{url}");
+        }
+    }
+}
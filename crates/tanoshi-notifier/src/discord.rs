@@ -0,0 +1,122 @@
+use async_trait::async_trait;
+
+use crate::{address, Format, Message, Notifier};
+
+pub const NAME: &str = "discord";
+
+/// Send message through a discord webhook, user key is the webhook url.
+/// Urls resolving to private addresses are rejected unless
+/// `allow_private_addresses` is set.
+#[derive(Debug, Clone)]
+pub struct Discord {
+    client: reqwest::Client,
+    allow_private_addresses: bool,
+}
+
+impl Default for Discord {
+    fn default() -> Self {
+        Self::new(false)
+    }
+}
+
+impl Discord {
+    pub fn new(allow_private_addresses: bool) -> Self {
+        Self {
+            client: address::client(),
+            allow_private_addresses,
+        }
+    }
+
+    async fn send_payload(
+        &self,
+        webhook_url: &str,
+        payload: &serde_json::Value,
+    ) -> Result<(), anyhow::Error> {
+        let client = if self.allow_private_addresses {
+            self.client.clone()
+        } else {
+            address::public_client(webhook_url).await?
+        };
+
+        client
+            .post(webhook_url)
+            .json(payload)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Notifier for Discord {
+    fn format(&self) -> Format {
+        Format::Markdown
+    }
+
+    async fn send_notification_message(
+        &self,
+        webhook_url: &str,
+        message: &Message,
+    ) -> Result<(), anyhow::Error> {
+        let mut embed = serde_json::json!({ "description": message.body });
+        if let Some(title) = message.title.as_ref() {
+            embed["title"] = serde_json::json!(title);
+        }
+        if let Some(url) = message.url.as_ref() {
+            embed["url"] = serde_json::json!(url);
+        }
+        if let Some(image_url) = message.image_url.as_ref() {
+            embed["thumbnail"] = serde_json::json!({ "url": image_url });
+        }
+
+        self.send_payload(webhook_url, &serde_json::json!({ "embeds": [embed] }))
+            .await
+    }
+
+    async fn send_notification(
+        &self,
+        webhook_url: &str,
+        message: &str,
+    ) -> Result<(), anyhow::Error> {
+        self.send_payload(webhook_url, &serde_json::json!({ "content": message }))
+            .await
+    }
+
+    async fn send_notification_with_title(
+        &self,
+        webhook_url: &str,
+        title: &str,
+        message: &str,
+    ) -> Result<(), anyhow::Error> {
+        self.send_payload(
+            webhook_url,
+            &serde_json::json!({
+                "embeds": [{ "title": title, "description": message }]
+            }),
+        )
+        .await
+    }
+
+    async fn send_notification_with_title_and_url(
+        &self,
+        webhook_url: &str,
+        title: &str,
+        message: &str,
+        url: &str,
+        url_title: &str,
+    ) -> Result<(), anyhow::Error> {
+        self.send_payload(
+            webhook_url,
+            &serde_json::json!({
+                "embeds": [{
+                    "title": title,
+                    "description": format!("{message}\n[{url_title}]({url})"),
+                    "url": url
+                }]
+            }),
+        )
+        .await
+    }
+}
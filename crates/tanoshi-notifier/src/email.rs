@@ -0,0 +1,88 @@
+use async_trait::async_trait;
+use lettre::{
+    message::{header::ContentType, Mailbox},
+    transport::smtp::authentication::Credentials,
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+};
+
+use crate::Notifier;
+
+pub const NAME: &str = "email";
+
+/// Send notification as email through smtp, user key is the recipient address
+#[derive(Clone)]
+pub struct Email {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl Email {
+    pub fn new(
+        host: &str,
+        port: Option<u16>,
+        username: Option<String>,
+        password: Option<String>,
+        from: &str,
+        starttls: bool,
+    ) -> Result<Self, anyhow::Error> {
+        let mut builder = if starttls {
+            AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)?
+        } else {
+            AsyncSmtpTransport::<Tokio1Executor>::relay(host)?
+        };
+
+        if let Some(port) = port {
+            builder = builder.port(port);
+        }
+
+        if let Some((username, password)) = username.zip(password) {
+            builder = builder.credentials(Credentials::new(username, password));
+        }
+
+        Ok(Self {
+            transport: builder.build(),
+            from: from.parse()?,
+        })
+    }
+
+    async fn send_email(&self, to: &str, subject: &str, body: String) -> Result<(), anyhow::Error> {
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(to.parse()?)
+            .subject(subject)
+            .header(ContentType::TEXT_PLAIN)
+            .body(body)?;
+
+        self.transport.send(message).await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Notifier for Email {
+    async fn send_notification(&self, to: &str, message: &str) -> Result<(), anyhow::Error> {
+        self.send_email(to, "Tanoshi", message.to_string()).await
+    }
+
+    async fn send_notification_with_title(
+        &self,
+        to: &str,
+        title: &str,
+        message: &str,
+    ) -> Result<(), anyhow::Error> {
+        self.send_email(to, title, message.to_string()).await
+    }
+
+    async fn send_notification_with_title_and_url(
+        &self,
+        to: &str,
+        title: &str,
+        message: &str,
+        url: &str,
+        url_title: &str,
+    ) -> Result<(), anyhow::Error> {
+        self.send_email(to, title, format!("{message}\n\n{url_title}: {url}"))
+            .await
+    }
+}
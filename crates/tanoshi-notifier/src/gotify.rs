@@ -1,6 +1,8 @@
 use async_trait::async_trait;
 
-use crate::Notifier;
+use crate::{Format, Message, Notifier};
+
+pub const NAME: &str = "gotify";
 
 #[derive(Clone)]
 pub struct Gotify {
@@ -19,13 +21,55 @@ impl Gotify {
 
 #[async_trait]
 impl Notifier for Gotify {
+    fn format(&self) -> Format {
+        Format::Markdown
+    }
+
+    async fn send_notification_message(
+        &self,
+        token: &str,
+        message: &Message,
+    ) -> Result<(), anyhow::Error> {
+        let mut body = message.body.clone();
+        if let Some(url) = message.url.as_ref() {
+            let url_title = message.url_title.as_deref().unwrap_or(url);
+            body.push_str(&format!("\n\n[{url_title}]({url})"));
+        }
+
+        let mut notification = serde_json::json!({});
+        if let Some(url) = message.url.as_ref() {
+            notification["click"] = serde_json::json!({ "url": url });
+        }
+        if let Some(image_url) = message.image_url.as_ref() {
+            notification["bigImageUrl"] = serde_json::json!(image_url);
+        }
+
+        self.client
+            .post(format!("{}/message", self.base_url))
+            .query(&[("token", token)])
+            .json(&serde_json::json!({
+                "message": body,
+                "title": message.title,
+                "extras": {
+                    "client::display": { "contentType": "text/markdown" },
+                    "client::notification": notification
+                }
+            }))
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+
     async fn send_notification(&self, token: &str, message: &str) -> Result<(), anyhow::Error> {
         self.client
             .post(&format!("{}/message", self.base_url))
             .query(&[("token", token)])
             .json(&serde_json::json!({ "message": message }))
             .send()
-            .await?;
+            .await?
+            .error_for_status()?;
 
         Ok(())
     }
@@ -41,7 +85,8 @@ impl Notifier for Gotify {
             .query(&[("token", token)])
             .json(&serde_json::json!({ "message": message, "title": title }))
             .send()
-            .await?;
+            .await?
+            .error_for_status()?;
 
         Ok(())
     }
@@ -67,7 +112,8 @@ impl Notifier for Gotify {
                 }
             }))
             .send()
-            .await?;
+            .await?
+            .error_for_status()?;
 
         Ok(())
     }
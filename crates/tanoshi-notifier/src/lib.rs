@@ -1,14 +1,109 @@
 #[macro_use]
 extern crate log;
 
+pub mod address;
+pub mod discord;
+pub mod email;
 pub mod gotify;
+pub mod matrix;
+pub mod ntfy;
 pub mod pushover;
 pub mod telegram;
+pub mod template;
+pub mod webhook;
 
 use async_trait::async_trait;
 
+/// Markup understood by a notifier, used to escape values rendered into a
+/// message template
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Plain,
+    Html,
+    Markdown,
+}
+
+impl Format {
+    pub fn escape(&self, text: &str) -> String {
+        match self {
+            Format::Plain => text.to_string(),
+            Format::Html => text
+                .replace('&', "&amp;")
+                .replace('<', "&lt;")
+                .replace('>', "&gt;")
+                .replace('"', "&quot;"),
+            Format::Markdown => {
+                let mut escaped = String::with_capacity(text.len());
+                for c in text.chars() {
+                    if matches!(
+                        c,
+                        '\\' | '`'
+                            | '*'
+                            | '_'
+                            | '['
+                            | ']'
+                            | '('
+                            | ')'
+                            | '#'
+                            | '!'
+                            | '<'
+                            | '>'
+                            | '|'
+                    ) {
+                        escaped.push('\\');
+                    }
+                    escaped.push(c);
+                }
+                escaped
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Message {
+    pub title: Option<String>,
+    pub body: String,
+    pub url: Option<String>,
+    pub url_title: Option<String>,
+    pub image_url: Option<String>,
+    /// Chapter this message is about, allows notifier to offer actions
+    /// such as marking it as read
+    pub chapter_id: Option<i64>,
+}
+
 #[async_trait]
-pub trait Notifier {
+pub trait Notifier: Send + Sync {
+    fn format(&self) -> Format {
+        Format::Plain
+    }
+
+    /// Send a message already formatted for this notifier, notifiers that
+    /// can attach images override this to include `image_url`
+    async fn send_notification_message(
+        &self,
+        user_key: &str,
+        message: &Message,
+    ) -> Result<(), anyhow::Error> {
+        match (message.title.as_deref(), message.url.as_deref()) {
+            (Some(title), Some(url)) => {
+                self.send_notification_with_title_and_url(
+                    user_key,
+                    title,
+                    &message.body,
+                    url,
+                    message.url_title.as_deref().unwrap_or(url),
+                )
+                .await
+            }
+            (Some(title), None) => {
+                self.send_notification_with_title(user_key, title, &message.body)
+                    .await
+            }
+            (None, _) => self.send_notification(user_key, &message.body).await,
+        }
+    }
+
     async fn send_notification(&self, user_key: &str, message: &str) -> Result<(), anyhow::Error>;
 
     async fn send_notification_with_title(
@@ -27,3 +122,21 @@ pub trait Notifier {
         url_title: &str,
     ) -> Result<(), anyhow::Error>;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_escape() {
+        assert_eq!(Format::Plain.escape("<b>"), "<b>");
+        assert_eq!(
+            Format::Html.escape("Tom & <Jerry>"),
+            "Tom &amp; &lt;Jerry&gt;"
+        );
+        assert_eq!(
+            Format::Markdown.escape("*ch_1* [v2]"),
+            "\\*ch\\_1\\* \\[v2\\]"
+        );
+    }
+}
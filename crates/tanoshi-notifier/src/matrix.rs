@@ -0,0 +1,124 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use reqwest::Url;
+
+use crate::{Format, Notifier};
+
+pub const NAME: &str = "matrix";
+
+/// Send message to a matrix room as the configured bot account, user key is
+/// the room id
+#[derive(Debug, Clone)]
+pub struct Matrix {
+    client: reqwest::Client,
+    homeserver_url: Url,
+    access_token: String,
+    txn_counter: Arc<AtomicU64>,
+}
+
+impl Matrix {
+    pub fn new(homeserver_url: &str, access_token: String) -> Result<Self, anyhow::Error> {
+        Ok(Self {
+            client: reqwest::Client::new(),
+            homeserver_url: Url::parse(homeserver_url)?,
+            access_token,
+            txn_counter: Arc::new(AtomicU64::new(0)),
+        })
+    }
+
+    async fn send_message(
+        &self,
+        room_id: &str,
+        body: String,
+        formatted_body: String,
+    ) -> Result<(), anyhow::Error> {
+        let txn_id = format!(
+            "tanoshi-{}-{}",
+            chrono::Utc::now().timestamp_millis(),
+            self.txn_counter.fetch_add(1, Ordering::Relaxed)
+        );
+
+        let mut url = self.homeserver_url.clone();
+        url.path_segments_mut()
+            .map_err(|_| anyhow!("invalid matrix homeserver url"))?
+            .pop_if_empty()
+            .extend(&[
+                "_matrix",
+                "client",
+                "v3",
+                "rooms",
+                room_id,
+                "send",
+                "m.room.message",
+                txn_id.as_str(),
+            ]);
+
+        self.client
+            .put(url)
+            .bearer_auth(&self.access_token)
+            .json(&serde_json::json!({
+                "msgtype": "m.text",
+                "body": body,
+                "format": "org.matrix.custom.html",
+                "formatted_body": formatted_body,
+            }))
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Notifier for Matrix {
+    async fn send_notification(&self, room_id: &str, message: &str) -> Result<(), anyhow::Error> {
+        self.send_message(room_id, message.to_string(), Format::Html.escape(message))
+            .await
+    }
+
+    async fn send_notification_with_title(
+        &self,
+        room_id: &str,
+        title: &str,
+        message: &str,
+    ) -> Result<(), anyhow::Error> {
+        self.send_message(
+            room_id,
+            format!("{title}\n{message}"),
+            format!(
+                "<b>{}</b><br>{}",
+                Format::Html.escape(title),
+                Format::Html.escape(message)
+            ),
+        )
+        .await
+    }
+
+    async fn send_notification_with_title_and_url(
+        &self,
+        room_id: &str,
+        title: &str,
+        message: &str,
+        url: &str,
+        url_title: &str,
+    ) -> Result<(), anyhow::Error> {
+        self.send_message(
+            room_id,
+            format!("{title}\n{message}\n{url}"),
+            format!(
+                "<b>{}</b><br>{}<br><a href=\"{}\">{}</a>",
+                Format::Html.escape(title),
+                Format::Html.escape(message),
+                Format::Html.escape(url),
+                Format::Html.escape(url_title)
+            ),
+        )
+        .await
+    }
+}
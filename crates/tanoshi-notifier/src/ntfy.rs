@@ -0,0 +1,119 @@
+use async_trait::async_trait;
+
+use crate::{address, Message, Notifier};
+
+pub const NAME: &str = "ntfy";
+
+pub const DEFAULT_BASE_URL: &str = "https://ntfy.sh";
+
+/// Publish message to ntfy, user key is either a topic name on the configured
+/// server or a full topic url. Topic urls resolving to private addresses are
+/// rejected unless `allow_private_addresses` is set.
+#[derive(Debug, Clone)]
+pub struct Ntfy {
+    client: reqwest::Client,
+    base_url: String,
+    token: Option<String>,
+    allow_private_addresses: bool,
+}
+
+impl Ntfy {
+    pub fn new(base_url: String, token: Option<String>, allow_private_addresses: bool) -> Self {
+        Self {
+            client: address::client(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            token,
+            allow_private_addresses,
+        }
+    }
+
+    async fn publish(&self, topic: &str, payload: serde_json::Value) -> Result<(), anyhow::Error> {
+        let (base_url, topic) = match topic.rsplit_once('/') {
+            Some((base_url, topic)) if base_url.starts_with("http") => (base_url, topic),
+            _ => (self.base_url.as_str(), topic),
+        };
+        // topic url given by user could point anywhere, token is only for the
+        // configured server
+        let is_configured_server = base_url == self.base_url;
+        let client = if is_configured_server || self.allow_private_addresses {
+            self.client.clone()
+        } else {
+            address::public_client(base_url).await?
+        };
+
+        let mut payload = payload;
+        payload["topic"] = serde_json::Value::String(topic.to_string());
+
+        let mut req = client.post(base_url).json(&payload);
+        if let Some(token) = self.token.as_ref().filter(|_| is_configured_server) {
+            req = req.bearer_auth(token);
+        }
+
+        req.send().await?.error_for_status()?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Notifier for Ntfy {
+    async fn send_notification_message(
+        &self,
+        topic: &str,
+        message: &Message,
+    ) -> Result<(), anyhow::Error> {
+        let mut payload = serde_json::json!({ "message": message.body });
+        if let Some(title) = message.title.as_ref() {
+            payload["title"] = serde_json::json!(title);
+        }
+        if let Some(url) = message.url.as_ref() {
+            let url_title = message.url_title.as_deref().unwrap_or("Open");
+            payload["click"] = serde_json::json!(url);
+            payload["actions"] =
+                serde_json::json!([{ "action": "view", "label": url_title, "url": url }]);
+        }
+        if let Some(image_url) = message.image_url.as_ref() {
+            payload["attach"] = serde_json::json!(image_url);
+        }
+
+        self.publish(topic, payload).await
+    }
+
+    async fn send_notification(&self, topic: &str, message: &str) -> Result<(), anyhow::Error> {
+        self.publish(topic, serde_json::json!({ "message": message }))
+            .await
+    }
+
+    async fn send_notification_with_title(
+        &self,
+        topic: &str,
+        title: &str,
+        message: &str,
+    ) -> Result<(), anyhow::Error> {
+        self.publish(
+            topic,
+            serde_json::json!({ "title": title, "message": message }),
+        )
+        .await
+    }
+
+    async fn send_notification_with_title_and_url(
+        &self,
+        topic: &str,
+        title: &str,
+        message: &str,
+        url: &str,
+        url_title: &str,
+    ) -> Result<(), anyhow::Error> {
+        self.publish(
+            topic,
+            serde_json::json!({
+                "title": title,
+                "message": message,
+                "click": url,
+                "actions": [{ "action": "view", "label": url_title, "url": url }]
+            }),
+        )
+        .await
+    }
+}
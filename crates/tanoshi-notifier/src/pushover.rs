@@ -4,6 +4,8 @@ use serde::{Deserialize, Serialize};
 
 use crate::Notifier;
 
+pub const NAME: &str = "pushover";
+
 const PUSHOVER_ENDPOINT: &str = "https://api.pushover.net/1/messages.json";
 
 #[derive(Debug, Clone, Default, Deserialize, Serialize)]
@@ -1,8 +1,20 @@
+use std::sync::Arc;
+
 use anyhow::Result;
 use async_trait::async_trait;
-use teloxide::{adaptors::DefaultParseMode, prelude::*, utils::command::BotCommands};
+use chrono::NaiveDateTime;
+use teloxide::{
+    adaptors::DefaultParseMode,
+    prelude::*,
+    types::{InlineKeyboardButton, InlineKeyboardMarkup, InputFile},
+    utils::command::BotCommands,
+};
+
+use crate::{Format, Message as NotificationMessage, Notifier};
+
+pub const NAME: &str = "telegram";
 
-use crate::Notifier;
+const MARK_AS_READ_PREFIX: &str = "read:";
 
 #[derive(Debug, Clone)]
 pub struct Telegram(DefaultParseMode<Bot>);
@@ -18,10 +30,81 @@ impl Telegram {
 
         Ok(())
     }
+
+    pub async fn send_photo(&self, chat_id: i64, photo_url: &str, caption: &str) -> Result<()> {
+        let photo = InputFile::url(reqwest::Url::parse(photo_url)?);
+        self.0
+            .send_photo(ChatId(chat_id), photo)
+            .caption(caption)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn send_message_with_markup(
+        &self,
+        chat_id: i64,
+        text: &str,
+        image_url: Option<&str>,
+        markup: Option<InlineKeyboardMarkup>,
+    ) -> Result<()> {
+        if let Some(image_url) = image_url {
+            let photo = InputFile::url(reqwest::Url::parse(image_url)?);
+            let mut req = self.0.send_photo(ChatId(chat_id), photo).caption(text);
+            if let Some(markup) = markup.clone() {
+                req = req.reply_markup(markup);
+            }
+
+            match req.await {
+                Ok(_) => return Ok(()),
+                Err(e) => debug!("failed to send photo, fallback to text: {e}"),
+            }
+        }
+
+        let mut req = self.0.send_message(ChatId(chat_id), text);
+        if let Some(markup) = markup {
+            req = req.reply_markup(markup);
+        }
+        req.await?;
+
+        Ok(())
+    }
 }
 
 #[async_trait]
 impl Notifier for Telegram {
+    fn format(&self) -> Format {
+        Format::Html
+    }
+
+    async fn send_notification_message(
+        &self,
+        user_key: &str,
+        message: &NotificationMessage,
+    ) -> Result<(), anyhow::Error> {
+        let mut text = String::new();
+        if let Some(title) = message.title.as_ref() {
+            text.push_str(&format!("<b>{title}</b>\n"));
+        }
+        text.push_str(&message.body);
+        if let Some(url) = message.url.as_ref() {
+            let url_title = message.url_title.as_deref().unwrap_or(url);
+            text.push_str(&format!("\n<a href=\"{url}\">{url_title}</a>"));
+        }
+
+        let chat_id = user_key.parse()?;
+
+        let markup = message.chapter_id.map(|chapter_id| {
+            InlineKeyboardMarkup::new([[InlineKeyboardButton::callback(
+                "Mark as read",
+                format!("{MARK_AS_READ_PREFIX}{chapter_id}"),
+            )]])
+        });
+
+        self.send_message_with_markup(chat_id, &text, message.image_url.as_deref(), markup)
+            .await
+    }
+
     async fn send_notification(&self, user_key: &str, message: &str) -> Result<(), anyhow::Error> {
         let chat_id = user_key.parse()?;
 
@@ -63,6 +146,53 @@ impl Notifier for Telegram {
     }
 }
 
+#[derive(Debug, Clone)]
+pub struct UpdateItem {
+    pub chapter_id: i64,
+    pub manga_title: String,
+    pub chapter_title: String,
+    pub uploaded: NaiveDateTime,
+}
+
+#[derive(Debug, Clone)]
+pub struct LibraryItem {
+    pub manga_id: i64,
+    pub title: String,
+    pub unread_count: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct DownloadItem {
+    pub manga_title: String,
+    pub chapter_title: String,
+    pub downloaded: i64,
+    pub total: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct DownloadStatus {
+    pub running: bool,
+    pub queue: Vec<DownloadItem>,
+}
+
+/// Library operations available from the bot, every call except
+/// `link_chat` is made on behalf of the user linked to `chat_id`
+#[async_trait]
+pub trait BotHandler: Send + Sync {
+    /// Link chat to the user who generated `code`, returns the username
+    async fn link_chat(&self, chat_id: i64, code: &str) -> Result<String>;
+
+    async fn recent_updates(&self, chat_id: i64) -> Result<Vec<UpdateItem>>;
+
+    async fn search_library(&self, chat_id: i64, query: &str) -> Result<Vec<LibraryItem>>;
+
+    async fn mark_chapter_as_read(&self, chat_id: i64, chapter_id: i64) -> Result<()>;
+
+    async fn refresh_library(&self, chat_id: i64) -> Result<()>;
+
+    async fn download_status(&self, chat_id: i64) -> Result<DownloadStatus>;
+}
+
 #[derive(BotCommands, Clone)]
 #[command(
     rename_rule = "lowercase",
@@ -71,36 +201,181 @@ impl Notifier for Telegram {
 enum TelegramCommand {
     #[command(description = "display this text.")]
     Help,
-    #[command(description = "notify me when there is an update")]
-    NotifyMe,
+    #[command(description = "link this chat with code from tanoshi profile page.")]
+    Start(String),
+    #[command(description = "link this chat with code from tanoshi profile page.")]
+    Link(String),
+    #[command(description = "list recent chapter updates.")]
+    Updates,
+    #[command(description = "search manga in library.")]
+    Search(String),
+    #[command(description = "refresh chapters of manga in library.")]
+    Refresh,
+    #[command(description = "show download queue status.")]
+    Downloads,
+}
+
+async fn handle_command(
+    handler: &dyn BotHandler,
+    chat_id: i64,
+    command: TelegramCommand,
+) -> Result<String> {
+    let html = Format::Html;
+
+    let text = match command {
+        TelegramCommand::Help => TelegramCommand::descriptions().to_string(),
+        TelegramCommand::Start(code) | TelegramCommand::Link(code) => {
+            let code = code.trim();
+            if code.is_empty() {
+                "Open tanoshi profile page to get a link code, then send /link &lt;code&gt;"
+                    .to_string()
+            } else {
+                let username = handler.link_chat(chat_id, code).await?;
+                format!(
+                    "This chat is now linked to <b>{}</b>",
+                    html.escape(&username)
+                )
+            }
+        }
+        TelegramCommand::Updates => {
+            let updates = handler.recent_updates(chat_id).await?;
+            if updates.is_empty() {
+                "No recent updates".to_string()
+            } else {
+                updates
+                    .iter()
+                    .map(|update| {
+                        format!(
+                            "<b>{}</b>\n{} ({})",
+                            html.escape(&update.manga_title),
+                            html.escape(&update.chapter_title),
+                            update.uploaded.format("%Y-%m-%d")
+                        )
+                    })
+                    .collect::<Vec<String>>()
+                    .join("\n\n")
+            }
+        }
+        TelegramCommand::Search(query) => {
+            let query = query.trim();
+            if query.is_empty() {
+                "Usage: /search &lt;title&gt;".to_string()
+            } else {
+                let manga = handler.search_library(chat_id, query).await?;
+                if manga.is_empty() {
+                    format!("No manga matching <i>{}</i>", html.escape(query))
+                } else {
+                    manga
+                        .iter()
+                        .map(|manga| {
+                            format!(
+                                "<b>{}</b> - {} unread",
+                                html.escape(&manga.title),
+                                manga.unread_count
+                            )
+                        })
+                        .collect::<Vec<String>>()
+                        .join("\n")
+                }
+            }
+        }
+        TelegramCommand::Refresh => {
+            handler.refresh_library(chat_id).await?;
+            "Library refreshed".to_string()
+        }
+        TelegramCommand::Downloads => {
+            let status = handler.download_status(chat_id).await?;
+            let mut text = format!(
+                "Downloads are <b>{}</b>, {} chapter(s) in queue",
+                if status.running { "running" } else { "paused" },
+                status.queue.len()
+            );
+            for item in status.queue.iter().take(10) {
+                text.push_str(&format!(
+                    "\n{} - {} ({}/{})",
+                    html.escape(&item.manga_title),
+                    html.escape(&item.chapter_title),
+                    item.downloaded,
+                    item.total
+                ));
+            }
+            text
+        }
+    };
+
+    Ok(text)
 }
 
 async fn answer(
     bot: DefaultParseMode<Bot>,
     message: Message,
     command: TelegramCommand,
+    handler: Arc<dyn BotHandler>,
 ) -> ResponseResult<()> {
-    match command {
-        TelegramCommand::Help => {
-            bot.send_message(message.chat.id, TelegramCommand::descriptions().to_string())
-                .await?
-        }
-        TelegramCommand::NotifyMe => {
-            bot.send_message(
-                message.chat.id,
-                format!(
-                    "Put the following chat id on tanoshi profile settings: {}",
-                    message.chat.id
-                ),
-            )
-            .await?
+    let text = match handle_command(handler.as_ref(), message.chat.id.0, command).await {
+        Ok(text) => text,
+        Err(e) => Format::Html.escape(&e.to_string()),
+    };
+
+    bot.send_message(message.chat.id, text).await?;
+
+    Ok(())
+}
+
+async fn callback(
+    bot: DefaultParseMode<Bot>,
+    query: CallbackQuery,
+    handler: Arc<dyn BotHandler>,
+) -> ResponseResult<()> {
+    let chapter_id = query
+        .data
+        .as_deref()
+        .and_then(|data| data.strip_prefix(MARK_AS_READ_PREFIX))
+        .and_then(|chapter_id| chapter_id.parse::<i64>().ok());
+
+    let (chapter_id, message) = match chapter_id.zip(query.message.as_ref()) {
+        Some(data) => data,
+        None => {
+            bot.answer_callback_query(query.id).await?;
+            return Ok(());
         }
     };
 
+    match handler
+        .mark_chapter_as_read(message.chat.id.0, chapter_id)
+        .await
+    {
+        Ok(_) => {
+            bot.answer_callback_query(query.id.clone())
+                .text("Marked as read")
+                .await?;
+            bot.edit_message_reply_markup(message.chat.id, message.id)
+                .await?;
+        }
+        Err(e) => {
+            bot.answer_callback_query(query.id.clone())
+                .text(e.to_string())
+                .await?;
+        }
+    }
+
     Ok(())
 }
 
-pub async fn run(bot: Telegram) {
+pub async fn run(bot: Telegram, handler: Arc<dyn BotHandler>) {
     info!("start telegram bot");
-    TelegramCommand::repl(bot.0, answer).await;
+
+    let schema = dptree::entry()
+        .branch(
+            Update::filter_message()
+                .filter_command::<TelegramCommand>()
+                .endpoint(answer),
+        )
+        .branch(Update::filter_callback_query().endpoint(callback));
+
+    Dispatcher::builder(bot.0, schema)
+        .dependencies(dptree::deps![handler])
+        .build()
+        .dispatch()
+        .await;
 }
@@ -0,0 +1,49 @@
+/// Render `{{ name }}` placeholders in `template` using `lookup`.
+///
+/// Unknown placeholders are replaced with an empty string, an unterminated
+/// `{{` is kept as is.
+pub fn render<F>(template: &str, lookup: F) -> String
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let mut output = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        output.push_str(&rest[..start]);
+
+        let after = &rest[start + 2..];
+        if let Some(end) = after.find("}}") {
+            if let Some(value) = lookup(after[..end].trim()) {
+                output.push_str(&value);
+            }
+            rest = &after[end + 2..];
+        } else {
+            output.push_str(&rest[start..]);
+            rest = "";
+        }
+    }
+    output.push_str(rest);
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let lookup = |name: &str| match name {
+            "title" => Some("One Piece".to_string()),
+            "chapter" => Some("1090".to_string()),
+            _ => None,
+        };
+
+        assert_eq!(
+            render("{{title}} - {{ chapter }}{{unknown}}", lookup),
+            "One Piece - 1090"
+        );
+        assert_eq!(render("{{title} {{", lookup), "{{title} {{");
+    }
+}
@@ -0,0 +1,128 @@
+use anyhow::anyhow;
+use async_trait::async_trait;
+
+use crate::{address, template, Notifier};
+
+pub const NAME: &str = "webhook";
+
+const DEFAULT_PAYLOAD_TEMPLATE: &str =
+    r#"{"title": "{{title}}", "message": "{{message}}", "url": "{{url}}"}"#;
+
+/// Post a JSON payload to an arbitrary url, the payload is rendered from a
+/// template where `{{title}}`, `{{message}}`, `{{url}}` and `{{url_title}}`
+/// are replaced with JSON escaped values. Urls resolving to private
+/// addresses are rejected unless `allow_private_addresses` is set.
+#[derive(Debug, Clone)]
+pub struct Webhook {
+    client: reqwest::Client,
+    payload_template: String,
+    allow_private_addresses: bool,
+}
+
+impl Webhook {
+    pub fn new(payload_template: Option<String>, allow_private_addresses: bool) -> Self {
+        Self {
+            client: address::client(),
+            payload_template: payload_template
+                .unwrap_or_else(|| DEFAULT_PAYLOAD_TEMPLATE.to_string()),
+            allow_private_addresses,
+        }
+    }
+
+    fn render_payload(
+        &self,
+        title: &str,
+        message: &str,
+        url: &str,
+        url_title: &str,
+    ) -> Result<serde_json::Value, anyhow::Error> {
+        let payload = template::render(&self.payload_template, |name| {
+            let value = match name {
+                "title" => title,
+                "message" => message,
+                "url" => url,
+                "url_title" => url_title,
+                _ => return None,
+            };
+
+            // serialize as json string then strip the surrounding quotes
+            let escaped = serde_json::to_string(value).ok()?;
+            Some(escaped[1..escaped.len() - 1].to_string())
+        });
+
+        serde_json::from_str(&payload).map_err(|e| anyhow!("invalid webhook payload: {e}"))
+    }
+
+    async fn send_payload(
+        &self,
+        url: &str,
+        payload: &serde_json::Value,
+    ) -> Result<(), anyhow::Error> {
+        let client = if self.allow_private_addresses {
+            self.client.clone()
+        } else {
+            address::public_client(url).await?
+        };
+
+        client
+            .post(url)
+            .json(payload)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Notifier for Webhook {
+    async fn send_notification(&self, url: &str, message: &str) -> Result<(), anyhow::Error> {
+        let payload = self.render_payload("", message, "", "")?;
+
+        self.send_payload(url, &payload).await
+    }
+
+    async fn send_notification_with_title(
+        &self,
+        url: &str,
+        title: &str,
+        message: &str,
+    ) -> Result<(), anyhow::Error> {
+        let payload = self.render_payload(title, message, "", "")?;
+
+        self.send_payload(url, &payload).await
+    }
+
+    async fn send_notification_with_title_and_url(
+        &self,
+        webhook_url: &str,
+        title: &str,
+        message: &str,
+        url: &str,
+        url_title: &str,
+    ) -> Result<(), anyhow::Error> {
+        let payload = self.render_payload(title, message, url, url_title)?;
+
+        self.send_payload(webhook_url, &payload).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_payload_escapes_values() {
+        let webhook = Webhook::new(
+            Some(r#"{"text": "{{title}}: {{message}}"}"#.to_string()),
+            false,
+        );
+
+        let payload = webhook
+            .render_payload("\"Quoted\"", "line\nbreak", "", "")
+            .unwrap();
+
+        assert_eq!(payload["text"], "\"Quoted\": line\nbreak");
+    }
+}
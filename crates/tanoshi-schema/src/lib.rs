@@ -223,6 +223,38 @@ pub struct UninstallSource;
 )]
 pub struct UserLogin;
 
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/schema.graphql",
+    query_path = "graphql/refresh_token.graphql",
+    response_derives = "Debug"
+)]
+pub struct RefreshToken;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/schema.graphql",
+    query_path = "graphql/oidc_login_start.graphql",
+    response_derives = "Debug"
+)]
+pub struct OidcLoginStart;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/schema.graphql",
+    query_path = "graphql/oidc_login_end.graphql",
+    response_derives = "Debug"
+)]
+pub struct OidcLoginEnd;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/schema.graphql",
+    query_path = "graphql/logout.graphql",
+    response_derives = "Debug"
+)]
+pub struct Logout;
+
 #[derive(GraphQLQuery)]
 #[graphql(
     schema_path = "graphql/schema.graphql",
@@ -247,6 +279,38 @@ pub struct FetchMe;
 )]
 pub struct UserRegister;
 
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/schema.graphql",
+    query_path = "graphql/register_with_invite.graphql",
+    response_derives = "Debug"
+)]
+pub struct RegisterWithInvite;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/schema.graphql",
+    query_path = "graphql/fetch_invites.graphql",
+    response_derives = "Debug"
+)]
+pub struct FetchInvites;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/schema.graphql",
+    query_path = "graphql/create_invite.graphql",
+    response_derives = "Debug"
+)]
+pub struct CreateInvite;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/schema.graphql",
+    query_path = "graphql/delete_invite.graphql",
+    response_derives = "Debug"
+)]
+pub struct DeleteInvite;
+
 #[derive(GraphQLQuery)]
 #[graphql(
     schema_path = "graphql/schema.graphql",
@@ -255,6 +319,62 @@ pub struct UserRegister;
 )]
 pub struct DeleteUser;
 
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/schema.graphql",
+    query_path = "graphql/update_user_permissions.graphql",
+    response_derives = "Debug"
+)]
+pub struct UpdateUserPermissions;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/schema.graphql",
+    query_path = "graphql/update_show_nsfw.graphql",
+    response_derives = "Debug"
+)]
+pub struct UpdateShowNsfw;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/schema.graphql",
+    query_path = "graphql/setup_totp.graphql",
+    response_derives = "Debug"
+)]
+pub struct SetupTotp;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/schema.graphql",
+    query_path = "graphql/enable_totp.graphql",
+    response_derives = "Debug"
+)]
+pub struct EnableTotp;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/schema.graphql",
+    query_path = "graphql/regenerate_totp_recovery_codes.graphql",
+    response_derives = "Debug"
+)]
+pub struct RegenerateTotpRecoveryCodes;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/schema.graphql",
+    query_path = "graphql/disable_totp.graphql",
+    response_derives = "Debug"
+)]
+pub struct DisableTotp;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/schema.graphql",
+    query_path = "graphql/reset_user_totp.graphql",
+    response_derives = "Debug"
+)]
+pub struct ResetUserTotp;
+
 #[derive(GraphQLQuery)]
 #[graphql(
     schema_path = "graphql/schema.graphql",
@@ -266,42 +386,90 @@ pub struct ChangeUserPassword;
 #[derive(GraphQLQuery)]
 #[graphql(
     schema_path = "graphql/schema.graphql",
-    query_path = "graphql/update_profile.graphql",
+    query_path = "graphql/fetch_notification_targets.graphql",
     response_derives = "Debug"
 )]
-pub struct UpdateProfile;
+pub struct FetchNotificationTargets;
 
 #[derive(GraphQLQuery)]
 #[graphql(
     schema_path = "graphql/schema.graphql",
-    query_path = "graphql/fetch_server_status.graphql",
+    query_path = "graphql/add_notification_target.graphql",
     response_derives = "Debug"
 )]
-pub struct FetchServerStatus;
+pub struct AddNotificationTarget;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/schema.graphql",
+    query_path = "graphql/remove_notification_target.graphql",
+    response_derives = "Debug"
+)]
+pub struct RemoveNotificationTarget;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/schema.graphql",
+    query_path = "graphql/fetch_api_keys.graphql",
+    response_derives = "Debug"
+)]
+pub struct FetchApiKeys;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/schema.graphql",
+    query_path = "graphql/create_api_key.graphql",
+    response_derives = "Debug"
+)]
+pub struct CreateApiKey;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/schema.graphql",
+    query_path = "graphql/revoke_api_key.graphql",
+    response_derives = "Debug"
+)]
+pub struct RevokeApiKey;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/schema.graphql",
+    query_path = "graphql/update_notification_template.graphql",
+    response_derives = "Debug"
+)]
+pub struct UpdateNotificationTemplate;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/schema.graphql",
+    query_path = "graphql/reset_notification_template.graphql",
+    response_derives = "Debug"
+)]
+pub struct ResetNotificationTemplate;
 
 #[derive(GraphQLQuery)]
 #[graphql(
     schema_path = "graphql/schema.graphql",
-    query_path = "graphql/test_telegram.graphql",
+    query_path = "graphql/create_telegram_link_code.graphql",
     response_derives = "Debug"
 )]
-pub struct TestTelegram;
+pub struct CreateTelegramLinkCode;
 
 #[derive(GraphQLQuery)]
 #[graphql(
     schema_path = "graphql/schema.graphql",
-    query_path = "graphql/test_pushover.graphql",
+    query_path = "graphql/fetch_server_status.graphql",
     response_derives = "Debug"
 )]
-pub struct TestPushover;
+pub struct FetchServerStatus;
 
 #[derive(GraphQLQuery)]
 #[graphql(
     schema_path = "graphql/schema.graphql",
-    query_path = "graphql/test_gotify.graphql",
+    query_path = "graphql/test_notification.graphql",
     response_derives = "Debug"
 )]
-pub struct TestGotify;
+pub struct TestNotification;
 
 #[derive(GraphQLQuery)]
 #[graphql(
@@ -439,6 +607,38 @@ pub struct AnilistLoginStart;
 )]
 pub struct AnilistLoginEnd;
 
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/schema.graphql",
+    query_path = "graphql/shikimori_login_start.graphql",
+    response_derives = "Debug"
+)]
+pub struct ShikimoriLoginStart;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/schema.graphql",
+    query_path = "graphql/shikimori_login_end.graphql",
+    response_derives = "Debug"
+)]
+pub struct ShikimoriLoginEnd;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/schema.graphql",
+    query_path = "graphql/kitsu_login.graphql",
+    response_derives = "Debug"
+)]
+pub struct KitsuLogin;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/schema.graphql",
+    query_path = "graphql/mangaupdates_login.graphql",
+    response_derives = "Debug"
+)]
+pub struct MangaupdatesLogin;
+
 #[derive(GraphQLQuery)]
 #[graphql(
     schema_path = "graphql/schema.graphql",
@@ -479,6 +679,14 @@ pub struct FetchMangaTrackerStatus;
 )]
 pub struct UpdateTrackerStatus;
 
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "graphql/schema.graphql",
+    query_path = "graphql/sync_tracker_progress.graphql",
+    response_derives = "Debug"
+)]
+pub struct SyncTrackerProgress;
+
 #[derive(GraphQLQuery)]
 #[graphql(
     schema_path = "graphql/schema.graphql",
@@ -18,7 +18,14 @@ pub struct User {
     pub id: i64,
     pub username: String,
     pub is_admin: bool,
-    pub telegram_chat_id: Option<i64>,
+    pub permissions: Vec<String>,
+    pub totp_enabled: bool,
+}
+
+impl User {
+    pub fn has_permission(&self, permission: &str) -> bool {
+        self.is_admin || self.permissions.iter().any(|p| p == permission)
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -26,6 +33,7 @@ pub struct ServerStatus {
     pub activated: bool,
     pub version: String,
     pub loggedin: bool,
+    pub oidc_provider: Option<String>,
 }
 
 impl Default for ServerStatus {
@@ -34,6 +42,7 @@ impl Default for ServerStatus {
             activated: false,
             version: "0.0.0".to_string(),
             loggedin: false,
+            oidc_provider: None,
         }
     }
 }
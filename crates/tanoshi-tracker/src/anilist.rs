@@ -11,7 +11,7 @@ use reqwest::StatusCode;
 use serde::Deserialize;
 use serde_json::json;
 
-use crate::{Error, Tracker, TrackerManga, TrackerStatus};
+use crate::{token_request_error, Error, Tracker, TrackerManga, TrackerStatus};
 
 use super::{Session, Token};
 
@@ -37,6 +37,8 @@ pub struct CoverImage {
 #[derive(Debug, Default, Clone, Deserialize)]
 pub struct MediaTitle {
     pub romaji: Option<String>,
+    pub english: Option<String>,
+    pub native: Option<String>,
 }
 
 #[derive(Debug, Default, Clone, Deserialize)]
@@ -55,6 +57,7 @@ pub struct MediaListEntry {
 pub struct Media {
     pub id: i64,
     pub title: Option<MediaTitle>,
+    pub synonyms: Option<Vec<String>>,
     pub description: Option<String>,
     pub cover_image: Option<CoverImage>,
     pub status: Option<String>,
@@ -63,14 +66,21 @@ pub struct Media {
 
 impl From<Media> for TrackerManga {
     fn from(other: Media) -> Self {
-        let title = other
-            .title
-            .and_then(|t| t.romaji)
-            .unwrap_or_else(|| "".to_string());
+        let MediaTitle {
+            romaji,
+            english,
+            native,
+        } = other.title.unwrap_or_default();
+        let title = romaji.unwrap_or_else(|| "".to_string());
         Self {
             tracker: NAME.to_string(),
             tracker_manga_id: other.id.to_string(),
             title: title.clone(),
+            alternative_titles: english
+                .into_iter()
+                .chain(native)
+                .chain(other.synonyms.unwrap_or_default())
+                .collect(),
             synopsis: other.description.unwrap_or_else(|| "".to_string()),
             cover_url: other
                 .cover_image
@@ -111,6 +121,16 @@ impl From<Media> for TrackerManga {
     }
 }
 
+#[derive(Debug, Default, Clone, Deserialize)]
+pub struct MediaList {
+    pub media: Media,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+pub struct MediaListGroup {
+    pub entries: Vec<MediaList>,
+}
+
 pub struct AniList {
     pub oauth_client: BasicClient,
 }
@@ -154,7 +174,7 @@ impl Tracker for AniList {
             .exchange_refresh_token(&RefreshToken::new(refresh_token))
             .request_async(async_http_client)
             .await
-            .map_err(|e| anyhow!("{e}"))?;
+            .map_err(token_request_error)?;
         let token_str = serde_json::to_string(&token).map_err(|e| anyhow!("{e}"))?;
         Ok(serde_json::from_str(&token_str).map_err(|e| anyhow!("{e}"))?)
     }
@@ -166,17 +186,22 @@ impl Tracker for AniList {
     ) -> Result<Vec<TrackerManga>, Error> {
         const QUERY: &str = "
         query SearchManga($search: String!) {
-            Media(search: $search, format_in: [MANGA, ONE_SHOT]) {
-              id
-              title {
-                romaji
-              }
-              description(asHtml: false)
-              coverImage {
-                large
-                medium
+            Page(perPage: 6) {
+              media(search: $search, format_in: [MANGA, ONE_SHOT]) {
+                id
+                title {
+                  romaji
+                  english
+                  native
+                }
+                synonyms
+                description(asHtml: false)
+                coverImage {
+                  large
+                  medium
+                }
+                status
               }
-              status
             }
           }
         ";
@@ -197,12 +222,13 @@ impl Tracker for AniList {
 
         let res = res
             .get("data")
-            .and_then(|data| data.get("Media"))
+            .and_then(|data| data.get("Page"))
+            .and_then(|page| page.get("media"))
             .map(|media| media.to_owned())
             .ok_or_else(|| anyhow!("no data"))?;
 
-        let media: Media = serde_json::from_value(res).map_err(|e| anyhow!("{e}"))?;
-        Ok(vec![media.into()])
+        let media: Vec<Media> = serde_json::from_value(res).map_err(|e| anyhow!("{e}"))?;
+        Ok(media.into_iter().map(|media| media.into()).collect())
     }
 
     async fn get_manga_details(
@@ -214,6 +240,32 @@ impl Tracker for AniList {
         Ok(media.into())
     }
 
+    async fn get_user_manga_list(
+        &self,
+        token: String,
+        status: Option<String>,
+    ) -> Result<Vec<TrackerManga>, Error> {
+        let status = status.and_then(|status| match status.as_str() {
+            "reading" => Some("CURRENT"),
+            "plan_to_read" => Some("PLANNING"),
+            "completed" => Some("COMPLETED"),
+            "dropped" => Some("DROPPED"),
+            "on_hold" => Some("PAUSED"),
+            _ => None,
+        });
+
+        let user_id = self.get_viewer_id(token.clone()).await?;
+        let lists = self
+            .get_media_list_collection(token, user_id, status)
+            .await?;
+
+        Ok(lists
+            .into_iter()
+            .flat_map(|list| list.entries)
+            .map(|entry| entry.media.into())
+            .collect())
+    }
+
     async fn update_tracker_status(
         &self,
         token: String,
@@ -290,7 +342,10 @@ impl AniList {
               id
               title {
                 romaji
+                english
+                native
               }
+              synonyms
               description(asHtml: false)
               coverImage {
                 large
@@ -341,6 +396,100 @@ impl AniList {
         Ok(media)
     }
 
+    async fn get_viewer_id(&self, token: String) -> Result<i64, Error> {
+        const QUERY: &str = "
+        query {
+            Viewer {
+              id
+            }
+          }
+        ";
+
+        let res = self
+            .post_graphql(
+                token,
+                &json!({
+                    "query": QUERY,
+                }),
+            )
+            .await?;
+
+        debug!("res: {res:?}");
+
+        let id = res
+            .get("data")
+            .and_then(|data| data.get("Viewer"))
+            .and_then(|viewer| viewer.get("id"))
+            .and_then(|id| id.as_i64())
+            .ok_or_else(|| anyhow!("no data"))?;
+
+        Ok(id)
+    }
+
+    async fn get_media_list_collection(
+        &self,
+        token: String,
+        user_id: i64,
+        status: Option<&str>,
+    ) -> Result<Vec<MediaListGroup>, Error> {
+        const QUERY: &str = "
+        query GetMangaList($userId: Int!, $status: MediaListStatus) {
+            MediaListCollection(userId: $userId, type: MANGA, status: $status) {
+              lists {
+                entries {
+                  media {
+                    id
+                    title {
+                      romaji
+                      english
+                      native
+                    }
+                    synonyms
+                    description(asHtml: false)
+                    coverImage {
+                      large
+                      medium
+                    }
+                    status
+                    mediaListEntry {
+                      id
+                      status
+                      progress
+                      score(format: POINT_10)
+                    }
+                  }
+                }
+              }
+            }
+          }
+        ";
+
+        let res = self
+            .post_graphql(
+                token,
+                &json!({
+                    "query": QUERY,
+                    "variables": {
+                        "userId": user_id,
+                        "status": status
+                    }
+                }),
+            )
+            .await?;
+
+        debug!("res: {res:?}");
+
+        let res = res
+            .get("data")
+            .and_then(|data| data.get("MediaListCollection"))
+            .and_then(|collection| collection.get("lists"))
+            .map(|lists| lists.to_owned())
+            .ok_or_else(|| anyhow!("no data"))?;
+
+        let lists: Vec<MediaListGroup> = serde_json::from_value(res).map_err(|e| anyhow!("{e}"))?;
+        Ok(lists)
+    }
+
     #[allow(clippy::too_many_arguments)]
     async fn save_entry(
         &self,
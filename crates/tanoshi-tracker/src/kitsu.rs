@@ -0,0 +1,551 @@
+use std::collections::HashMap;
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use reqwest::{header::CONTENT_TYPE, RequestBuilder, StatusCode};
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::json;
+
+use crate::{Error, Tracker, TrackerManga, TrackerStatus};
+
+use super::{Session, Token};
+
+pub const NAME: &str = "kitsu";
+
+const DEFAULT_BASE_URL: &str = "https://kitsu.io/api";
+const JSON_API: &str = "application/vnd.api+json";
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Resource<T> {
+    pub id: String,
+    pub attributes: T,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Document<T> {
+    pub data: T,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct PosterImage {
+    pub small: Option<String>,
+    pub medium: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Manga {
+    pub canonical_title: String,
+    pub titles: HashMap<String, Option<String>>,
+    pub abbreviated_titles: Option<Vec<String>>,
+    pub synopsis: Option<String>,
+    pub poster_image: Option<PosterImage>,
+    pub status: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct LibraryEntry {
+    pub status: Option<String>,
+    pub progress: Option<i64>,
+    pub rating_twenty: Option<i64>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+pub struct User {}
+
+fn into_tracker_manga(manga: Resource<Manga>, entry: Option<LibraryEntry>) -> TrackerManga {
+    let title = manga.attributes.canonical_title;
+    let alternative_titles = manga
+        .attributes
+        .titles
+        .into_values()
+        .flatten()
+        .chain(manga.attributes.abbreviated_titles.unwrap_or_default())
+        .filter(|alternative| alternative != &title)
+        .collect();
+    TrackerManga {
+        tracker: NAME.to_string(),
+        tracker_manga_id: manga.id.clone(),
+        title: title.clone(),
+        alternative_titles,
+        synopsis: manga.attributes.synopsis.unwrap_or_default(),
+        cover_url: manga
+            .attributes
+            .poster_image
+            .and_then(|poster| poster.medium.or(poster.small))
+            .unwrap_or_default(),
+        status: manga.attributes.status.unwrap_or_default(),
+        tracker_status: Some(TrackerStatus {
+            tracker: NAME.to_string(),
+            tracker_manga_id: Some(manga.id),
+            tracker_manga_title: Some(title),
+            status: entry
+                .as_ref()
+                .and_then(|entry| entry.status.as_deref())
+                .and_then(|status| match status {
+                    "current" => Some("reading".to_string()),
+                    "planned" => Some("plan_to_read".to_string()),
+                    "completed" => Some("completed".to_string()),
+                    "on_hold" => Some("on_hold".to_string()),
+                    "dropped" => Some("dropped".to_string()),
+                    _ => None,
+                }),
+            score: entry
+                .as_ref()
+                .and_then(|entry| entry.rating_twenty)
+                .map(|rating| rating / 2),
+            num_chapters_read: entry.as_ref().and_then(|entry| entry.progress),
+            start_date: entry
+                .as_ref()
+                .and_then(|entry| entry.started_at)
+                .map(|at| at.naive_utc()),
+            finish_date: entry
+                .as_ref()
+                .and_then(|entry| entry.finished_at)
+                .map(|at| at.naive_utc()),
+        }),
+    }
+}
+
+/// Kitsu only supports resource owner password grant, so user login with
+/// their kitsu username and password instead of redirecting to kitsu.
+#[derive(Debug, Clone)]
+pub struct Kitsu {
+    client_id: String,
+    client_secret: String,
+    base_url: String,
+    api_client: reqwest::Client,
+}
+
+#[async_trait]
+impl Tracker for Kitsu {
+    fn get_authorize_url(&self) -> Result<Session, Error> {
+        Err(Error::Other(anyhow!(
+            "kitsu login use username and password"
+        )))
+    }
+
+    async fn exchange_code(
+        &self,
+        _code: String,
+        _state: Option<String>,
+        _csrf_state: Option<String>,
+        _pkce_code_verifier: Option<String>,
+    ) -> Result<Token, Error> {
+        Err(Error::Other(anyhow!(
+            "kitsu login use username and password"
+        )))
+    }
+
+    async fn refresh_token(&self, refresh_token: String) -> Result<Token, Error> {
+        self.request_token(&[
+            ("grant_type", "refresh_token"),
+            ("refresh_token", refresh_token.as_str()),
+        ])
+        .await
+    }
+
+    async fn login(&self, username: String, password: String) -> Result<Token, Error> {
+        self.request_token(&[
+            ("grant_type", "password"),
+            ("username", username.as_str()),
+            ("password", password.as_str()),
+        ])
+        .await
+    }
+
+    async fn search_manga(
+        &self,
+        token: String,
+        search: String,
+    ) -> Result<Vec<TrackerManga>, Error> {
+        let res: Document<Vec<Resource<Manga>>> = self
+            .send(
+                self.api_client
+                    .get(format!("{}/edge/manga", self.base_url))
+                    .bearer_auth(token)
+                    .query(&[("filter[text]", search.as_str()), ("page[limit]", "6")]),
+            )
+            .await?;
+
+        Ok(res
+            .data
+            .into_iter()
+            .map(|manga| into_tracker_manga(manga, None))
+            .collect())
+    }
+
+    async fn get_manga_details(
+        &self,
+        token: String,
+        tracker_manga_id: i64,
+    ) -> Result<TrackerManga, Error> {
+        let manga: Document<Resource<Manga>> = self
+            .send(
+                self.api_client
+                    .get(format!("{}/edge/manga/{tracker_manga_id}", self.base_url))
+                    .bearer_auth(&token),
+            )
+            .await?;
+
+        let user_id = self.get_user_id(&token).await?;
+        let entry = self
+            .get_library_entry(&token, &user_id, tracker_manga_id)
+            .await?
+            .map(|entry| entry.attributes);
+
+        Ok(into_tracker_manga(manga.data, entry))
+    }
+
+    async fn update_tracker_status(
+        &self,
+        token: String,
+        tracker_manga_id: i64,
+        status: Option<String>,
+        score: Option<i64>,
+        progress: Option<i64>,
+        started_at: Option<NaiveDateTime>,
+        completed_at: Option<NaiveDateTime>,
+    ) -> Result<(), Error> {
+        let mut attributes = serde_json::Map::new();
+        if let Some(status) = status.as_deref().and_then(|s| match s {
+            "reading" => Some("current"),
+            "completed" => Some("completed"),
+            "on_hold" => Some("on_hold"),
+            "dropped" => Some("dropped"),
+            "plan_to_read" => Some("planned"),
+            _ => None,
+        }) {
+            attributes.insert("status".to_string(), json!(status));
+        }
+        if let Some(score) = score {
+            // kitsu rating is between 2 and 20, null means not rated
+            let rating = (score > 0).then_some((score * 2).clamp(2, 20));
+            attributes.insert("ratingTwenty".to_string(), json!(rating));
+        }
+        if let Some(progress) = progress {
+            attributes.insert("progress".to_string(), json!(progress));
+        }
+        if let Some(started_at) = started_at {
+            attributes.insert(
+                "startedAt".to_string(),
+                json!(Utc.from_utc_datetime(&started_at).to_rfc3339()),
+            );
+        }
+        if let Some(completed_at) = completed_at {
+            attributes.insert(
+                "finishedAt".to_string(),
+                json!(Utc.from_utc_datetime(&completed_at).to_rfc3339()),
+            );
+        }
+
+        let user_id = self.get_user_id(&token).await?;
+        let entry = self
+            .get_library_entry(&token, &user_id, tracker_manga_id)
+            .await?;
+
+        let req = if let Some(entry) = entry {
+            self.api_client
+                .patch(format!(
+                    "{}/edge/library-entries/{}",
+                    self.base_url, entry.id
+                ))
+                .json(&json!({
+                    "data": {
+                        "type": "libraryEntries",
+                        "id": entry.id,
+                        "attributes": attributes
+                    }
+                }))
+        } else {
+            if !attributes.contains_key("status") {
+                attributes.insert("status".to_string(), json!("current"));
+            }
+            self.api_client
+                .post(format!("{}/edge/library-entries", self.base_url))
+                .json(&json!({
+                    "data": {
+                        "type": "libraryEntries",
+                        "attributes": attributes,
+                        "relationships": {
+                            "user": { "data": { "type": "users", "id": user_id } },
+                            "media": { "data": { "type": "manga", "id": tracker_manga_id.to_string() } }
+                        }
+                    }
+                }))
+        };
+
+        let _: serde_json::Value = self
+            .send(req.bearer_auth(token).header(CONTENT_TYPE, JSON_API))
+            .await?;
+
+        Ok(())
+    }
+}
+
+impl Kitsu {
+    pub fn new(client_id: String, client_secret: String) -> Self {
+        Self {
+            client_id,
+            client_secret,
+            base_url: DEFAULT_BASE_URL.to_string(),
+            api_client: reqwest::Client::new(),
+        }
+    }
+
+    /// Override kitsu api url, e.g. to point to a mirror
+    pub fn with_base_url(self, base_url: &str) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            ..self
+        }
+    }
+
+    async fn request_token(&self, params: &[(&str, &str)]) -> Result<Token, Error> {
+        let mut form = params.to_vec();
+        if !self.client_id.is_empty() {
+            form.push(("client_id", self.client_id.as_str()));
+            form.push(("client_secret", self.client_secret.as_str()));
+        }
+
+        let res = self
+            .api_client
+            .post(format!("{}/oauth/token", self.base_url))
+            .form(&form)
+            .send()
+            .await
+            .map_err(|e| anyhow!("{e}"))?;
+
+        // kitsu return 400 invalid_grant on wrong password or revoked refresh token
+        if res.status() == StatusCode::BAD_REQUEST || res.status() == StatusCode::UNAUTHORIZED {
+            return Err(Error::Unauthorized);
+        }
+
+        Ok(res
+            .error_for_status()
+            .map_err(|e| anyhow!("{e}"))?
+            .json()
+            .await
+            .map_err(|e| anyhow!("{e}"))?)
+    }
+
+    async fn get_user_id(&self, token: &str) -> Result<String, Error> {
+        let res: Document<Vec<Resource<User>>> = self
+            .send(
+                self.api_client
+                    .get(format!("{}/edge/users", self.base_url))
+                    .bearer_auth(token)
+                    .query(&[("filter[self]", "true")]),
+            )
+            .await?;
+
+        // kitsu return empty list instead of 401 for invalid token
+        res.data
+            .into_iter()
+            .next()
+            .map(|user| user.id)
+            .ok_or(Error::Unauthorized)
+    }
+
+    async fn get_library_entry(
+        &self,
+        token: &str,
+        user_id: &str,
+        tracker_manga_id: i64,
+    ) -> Result<Option<Resource<LibraryEntry>>, Error> {
+        let res: Document<Vec<Resource<LibraryEntry>>> = self
+            .send(
+                self.api_client
+                    .get(format!("{}/edge/library-entries", self.base_url))
+                    .bearer_auth(token)
+                    .query(&[
+                        ("filter[userId]", user_id.to_string()),
+                        ("filter[mangaId]", tracker_manga_id.to_string()),
+                    ]),
+            )
+            .await?;
+
+        Ok(res.data.into_iter().next())
+    }
+
+    async fn send<T: DeserializeOwned>(&self, req: RequestBuilder) -> Result<T, Error> {
+        let res = req
+            .header(reqwest::header::ACCEPT, JSON_API)
+            .send()
+            .await
+            .map_err(|e| anyhow!("{e}"))?;
+
+        if res.status() == StatusCode::UNAUTHORIZED {
+            return Err(Error::Unauthorized);
+        }
+
+        Ok(res
+            .error_for_status()
+            .map_err(|e| anyhow!("{e}"))?
+            .json()
+            .await
+            .map_err(|e| anyhow!("{e}"))?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::{
+        matchers::{body_string_contains, method, path, query_param},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    fn fixture(name: &str) -> serde_json::Value {
+        let path = format!("{}/tests/fixtures/kitsu/{name}", env!("CARGO_MANIFEST_DIR"));
+        serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap()
+    }
+
+    async fn mock_user(server: &MockServer) {
+        Mock::given(method("GET"))
+            .and(path("/edge/users"))
+            .and(query_param("filter[self]", "true"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(fixture("users_self.json")))
+            .mount(server)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn test_login() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/oauth/token"))
+            .and(body_string_contains("grant_type=password"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(fixture("token.json")))
+            .mount(&server)
+            .await;
+
+        let kitsu = Kitsu::new("".to_string(), "".to_string()).with_base_url(&server.uri());
+        let token = kitsu
+            .login("user".to_string(), "password".to_string())
+            .await
+            .unwrap();
+
+        assert_eq!(token.access_token, "kitsu-access-token");
+        assert_eq!(token.refresh_token, "kitsu-refresh-token");
+    }
+
+    #[tokio::test]
+    async fn test_login_invalid_credential() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/oauth/token"))
+            .respond_with(ResponseTemplate::new(400).set_body_json(fixture("invalid_grant.json")))
+            .mount(&server)
+            .await;
+
+        let kitsu = Kitsu::new("".to_string(), "".to_string()).with_base_url(&server.uri());
+        let res = kitsu.login("user".to_string(), "wrong".to_string()).await;
+
+        assert!(matches!(res, Err(Error::Unauthorized)));
+    }
+
+    #[tokio::test]
+    async fn test_search_manga() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/edge/manga"))
+            .and(query_param("filter[text]", "yotsuba"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(fixture("manga_search.json")))
+            .mount(&server)
+            .await;
+
+        let kitsu = Kitsu::new("".to_string(), "".to_string()).with_base_url(&server.uri());
+        let manga = kitsu
+            .search_manga("token".to_string(), "yotsuba".to_string())
+            .await
+            .unwrap();
+
+        assert_eq!(manga.len(), 2);
+        assert_eq!(manga[0].tracker, NAME);
+        assert_eq!(manga[0].tracker_manga_id, "20");
+        assert_eq!(manga[0].title, "Yotsuba&!");
+        assert!(manga[0]
+            .alternative_titles
+            .contains(&"Yotsuba to!".to_string()));
+        assert!(manga[0].cover_url.ends_with("medium.jpg"));
+    }
+
+    #[tokio::test]
+    async fn test_get_manga_details() {
+        let server = MockServer::start().await;
+        mock_user(&server).await;
+        Mock::given(method("GET"))
+            .and(path("/edge/manga/20"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(fixture("manga.json")))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/edge/library-entries"))
+            .and(query_param("filter[userId]", "1234"))
+            .and(query_param("filter[mangaId]", "20"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(fixture("library_entries.json")))
+            .mount(&server)
+            .await;
+
+        let kitsu = Kitsu::new("".to_string(), "".to_string()).with_base_url(&server.uri());
+        let manga = kitsu
+            .get_manga_details("token".to_string(), 20)
+            .await
+            .unwrap();
+
+        let status = manga.tracker_status.unwrap();
+        assert_eq!(status.status.as_deref(), Some("reading"));
+        assert_eq!(status.score, Some(8));
+        assert_eq!(status.num_chapters_read, Some(42));
+        assert!(status.start_date.is_some());
+        assert!(status.finish_date.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_update_tracker_status_existing_entry() {
+        let server = MockServer::start().await;
+        mock_user(&server).await;
+        Mock::given(method("GET"))
+            .and(path("/edge/library-entries"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(fixture("library_entries.json")))
+            .mount(&server)
+            .await;
+        Mock::given(method("PATCH"))
+            .and(path("/edge/library-entries/5678"))
+            .and(body_string_contains(r#""progress":43"#))
+            .and(body_string_contains(r#""ratingTwenty":18"#))
+            .respond_with(ResponseTemplate::new(200).set_body_json(fixture("library_entry.json")))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let kitsu = Kitsu::new("".to_string(), "".to_string()).with_base_url(&server.uri());
+        kitsu
+            .update_tracker_status("token".to_string(), 20, None, Some(9), Some(43), None, None)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_unauthorized() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/edge/manga"))
+            .respond_with(ResponseTemplate::new(401))
+            .mount(&server)
+            .await;
+
+        let kitsu = Kitsu::new("".to_string(), "".to_string()).with_base_url(&server.uri());
+        let res = kitsu
+            .search_manga("expired".to_string(), "yotsuba".to_string())
+            .await;
+
+        assert!(matches!(res, Err(Error::Unauthorized)));
+    }
+}
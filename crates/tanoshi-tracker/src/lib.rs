@@ -8,8 +8,21 @@ pub use myanimelist::MyAnimeList;
 pub mod anilist;
 pub use anilist::AniList;
 
+pub mod kitsu;
+pub use kitsu::Kitsu;
+
+pub mod mangaupdates;
+pub use mangaupdates::MangaUpdates;
+
+pub mod shikimori;
+pub use shikimori::Shikimori;
+
+use anyhow::anyhow;
 use async_trait::async_trait;
-use oauth2::{CsrfToken, PkceCodeVerifier};
+use oauth2::{
+    basic::{BasicErrorResponse, BasicErrorResponseType},
+    CsrfToken, PkceCodeVerifier, RequestTokenError,
+};
 use serde::Deserialize;
 use thiserror::Error;
 
@@ -21,6 +34,20 @@ pub enum Error {
     Other(#[from] anyhow::Error),
 }
 
+/// Token endpoint responds with invalid_grant when refresh token is revoked or expired
+pub(crate) fn token_request_error<RE: std::error::Error + 'static>(
+    e: RequestTokenError<RE, BasicErrorResponse>,
+) -> Error {
+    match e {
+        RequestTokenError::ServerResponse(res)
+            if *res.error() == BasicErrorResponseType::InvalidGrant =>
+        {
+            Error::Unauthorized
+        }
+        e => Error::Other(anyhow!("{e}")),
+    }
+}
+
 #[derive(Debug)]
 pub struct Session {
     pub authorize_url: String,
@@ -41,6 +68,8 @@ pub struct TrackerManga {
     pub tracker: String,
     pub tracker_manga_id: String,
     pub title: String,
+    /// synonyms and titles in other languages, used to match manga by title
+    pub alternative_titles: Vec<String>,
     pub synopsis: String,
     pub cover_url: String,
     pub status: String,
@@ -73,6 +102,13 @@ pub trait Tracker: Sync + Send {
 
     async fn refresh_token(&self, refresh_token: String) -> Result<Token, Error>;
 
+    /// Login with username and password, for trackers without oauth authorization code flow
+    async fn login(&self, _username: String, _password: String) -> Result<Token, Error> {
+        Err(Error::Other(anyhow!(
+            "tracker does not support password login"
+        )))
+    }
+
     async fn search_manga(&self, token: String, search: String)
         -> Result<Vec<TrackerManga>, Error>;
     async fn get_manga_details(
@@ -81,6 +117,17 @@ pub trait Tracker: Sync + Send {
         tracker_manga_id: i64,
     ) -> Result<TrackerManga, Error>;
 
+    /// Get manga on user's list, filtered by status e.g. reading, plan_to_read, completed
+    async fn get_user_manga_list(
+        &self,
+        _token: String,
+        _status: Option<String>,
+    ) -> Result<Vec<TrackerManga>, Error> {
+        Err(Error::Other(anyhow!(
+            "tracker does not support fetching user manga list"
+        )))
+    }
+
     #[allow(clippy::too_many_arguments)]
     async fn update_tracker_status(
         &self,
@@ -0,0 +1,486 @@
+use anyhow::anyhow;
+use async_trait::async_trait;
+use chrono::NaiveDateTime;
+use reqwest::{RequestBuilder, StatusCode};
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::json;
+
+use crate::{Error, Tracker, TrackerManga, TrackerStatus};
+
+use super::{Session, Token};
+
+pub const NAME: &str = "mangaupdates";
+
+pub const DEFAULT_API_URL: &str = "https://api.mangaupdates.com/v1";
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct ImageUrl {
+    pub original: Option<String>,
+    pub thumb: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct Image {
+    pub url: ImageUrl,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct Series {
+    pub series_id: i64,
+    pub title: String,
+    pub description: Option<String>,
+    pub image: Option<Image>,
+    pub status: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SearchResult {
+    pub record: Series,
+    /// title which matched the search, might be one of associated titles
+    #[serde(default)]
+    pub hit_title: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SearchResponse {
+    pub results: Vec<SearchResult>,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct ListStatus {
+    pub chapter: Option<i64>,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct ListEntry {
+    pub list_id: i64,
+    pub status: ListStatus,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Rating {
+    pub rating: f64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct LoginContext {
+    pub session_token: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct LoginResponse {
+    pub context: LoginContext,
+}
+
+impl From<Series> for TrackerManga {
+    fn from(other: Series) -> Self {
+        Self {
+            tracker: NAME.to_string(),
+            tracker_manga_id: other.series_id.to_string(),
+            title: other.title.clone(),
+            alternative_titles: vec![],
+            synopsis: other.description.unwrap_or_default(),
+            cover_url: other
+                .image
+                .and_then(|image| image.url.original.or(image.url.thumb))
+                .unwrap_or_default(),
+            status: other.status.unwrap_or_default(),
+            tracker_status: Some(TrackerStatus {
+                tracker: NAME.to_string(),
+                tracker_manga_id: Some(other.series_id.to_string()),
+                tracker_manga_title: Some(other.title),
+                ..Default::default()
+            }),
+        }
+    }
+}
+
+/// MangaUpdates login with username and password, the returned session token
+/// can't be refreshed so user has to login again once it expires.
+#[derive(Debug, Clone)]
+pub struct MangaUpdates {
+    api_url: String,
+    api_client: reqwest::Client,
+}
+
+#[async_trait]
+impl Tracker for MangaUpdates {
+    fn get_authorize_url(&self) -> Result<Session, Error> {
+        Err(Error::Other(anyhow!(
+            "mangaupdates login use username and password"
+        )))
+    }
+
+    async fn exchange_code(
+        &self,
+        _code: String,
+        _state: Option<String>,
+        _csrf_state: Option<String>,
+        _pkce_code_verifier: Option<String>,
+    ) -> Result<Token, Error> {
+        Err(Error::Other(anyhow!(
+            "mangaupdates login use username and password"
+        )))
+    }
+
+    async fn refresh_token(&self, _refresh_token: String) -> Result<Token, Error> {
+        Err(Error::Unauthorized)
+    }
+
+    async fn login(&self, username: String, password: String) -> Result<Token, Error> {
+        let res: LoginResponse = self
+            .send(
+                self.api_client
+                    .put(format!("{}/account/login", self.api_url))
+                    .json(&json!({
+                        "username": username,
+                        "password": password
+                    })),
+            )
+            .await?;
+
+        Ok(Token {
+            token_type: "Bearer".to_string(),
+            expires_in: 0,
+            access_token: res.context.session_token,
+            refresh_token: "".to_string(),
+        })
+    }
+
+    async fn search_manga(
+        &self,
+        token: String,
+        search: String,
+    ) -> Result<Vec<TrackerManga>, Error> {
+        let res: SearchResponse = self
+            .send(
+                self.api_client
+                    .post(format!("{}/series/search", self.api_url))
+                    .bearer_auth(token)
+                    .json(&json!({
+                        "search": search,
+                        "perpage": 6
+                    })),
+            )
+            .await?;
+
+        Ok(res
+            .results
+            .into_iter()
+            .map(|result| {
+                let mut manga: TrackerManga = result.record.into();
+                if let Some(hit_title) = result.hit_title.filter(|hit| hit != &manga.title) {
+                    manga.alternative_titles.push(hit_title);
+                }
+                manga
+            })
+            .collect())
+    }
+
+    async fn get_manga_details(
+        &self,
+        token: String,
+        tracker_manga_id: i64,
+    ) -> Result<TrackerManga, Error> {
+        let series: Series = self
+            .send(
+                self.api_client
+                    .get(format!("{}/series/{tracker_manga_id}", self.api_url))
+                    .bearer_auth(&token),
+            )
+            .await?;
+
+        let entry = self.get_list_entry(&token, tracker_manga_id).await?;
+        let rating = self.get_rating(&token, tracker_manga_id).await?;
+
+        let mut manga: TrackerManga = series.into();
+        if let Some(status) = manga.tracker_status.as_mut() {
+            status.status = entry.as_ref().and_then(|entry| match entry.list_id {
+                0 => Some("reading".to_string()),
+                1 => Some("plan_to_read".to_string()),
+                2 => Some("completed".to_string()),
+                3 => Some("dropped".to_string()),
+                4 => Some("on_hold".to_string()),
+                _ => None,
+            });
+            status.num_chapters_read = entry.and_then(|entry| entry.status.chapter);
+            status.score = rating.map(|rating| rating.rating.round() as i64);
+        }
+
+        Ok(manga)
+    }
+
+    async fn update_tracker_status(
+        &self,
+        token: String,
+        tracker_manga_id: i64,
+        status: Option<String>,
+        score: Option<i64>,
+        progress: Option<i64>,
+        _started_at: Option<NaiveDateTime>,
+        _completed_at: Option<NaiveDateTime>,
+    ) -> Result<(), Error> {
+        let list_id = status.as_deref().and_then(|s| match s {
+            "reading" => Some(0),
+            "plan_to_read" => Some(1),
+            "completed" => Some(2),
+            "dropped" => Some(3),
+            "on_hold" => Some(4),
+            _ => None,
+        });
+
+        let entry = self.get_list_entry(&token, tracker_manga_id).await?;
+
+        let mut item = serde_json::Map::new();
+        item.insert("series".to_string(), json!({ "id": tracker_manga_id }));
+        if let Some(list_id) = list_id.or_else(|| entry.is_none().then_some(0)) {
+            item.insert("list_id".to_string(), json!(list_id));
+        }
+        if let Some(chapter) = progress {
+            item.insert("status".to_string(), json!({ "chapter": chapter }));
+        }
+
+        let url = if entry.is_some() {
+            format!("{}/lists/series/update", self.api_url)
+        } else {
+            format!("{}/lists/series", self.api_url)
+        };
+        let _: serde_json::Value = self
+            .send(
+                self.api_client
+                    .post(url)
+                    .bearer_auth(&token)
+                    .json(&json!([item])),
+            )
+            .await?;
+
+        if let Some(score) = score {
+            let req = if score > 0 {
+                self.api_client
+                    .put(format!("{}/series/{tracker_manga_id}/rating", self.api_url))
+                    .json(&json!({ "rating": score }))
+            } else {
+                self.api_client
+                    .delete(format!("{}/series/{tracker_manga_id}/rating", self.api_url))
+            };
+            let _: serde_json::Value = self.send(req.bearer_auth(&token)).await?;
+        }
+
+        Ok(())
+    }
+}
+
+impl MangaUpdates {
+    pub fn new(api_url: &str) -> Self {
+        Self {
+            api_url: api_url.trim_end_matches('/').to_string(),
+            api_client: reqwest::Client::new(),
+        }
+    }
+
+    async fn get_list_entry(
+        &self,
+        token: &str,
+        tracker_manga_id: i64,
+    ) -> Result<Option<ListEntry>, Error> {
+        self.send_optional(
+            self.api_client
+                .get(format!("{}/lists/series/{tracker_manga_id}", self.api_url))
+                .bearer_auth(token),
+        )
+        .await
+    }
+
+    async fn get_rating(
+        &self,
+        token: &str,
+        tracker_manga_id: i64,
+    ) -> Result<Option<Rating>, Error> {
+        self.send_optional(
+            self.api_client
+                .get(format!("{}/series/{tracker_manga_id}/rating", self.api_url))
+                .bearer_auth(token),
+        )
+        .await
+    }
+
+    /// Same as `send`, but return `None` when the resource is not found
+    async fn send_optional<T: DeserializeOwned>(
+        &self,
+        req: RequestBuilder,
+    ) -> Result<Option<T>, Error> {
+        match self.send(req).await {
+            Ok(res) => Ok(Some(res)),
+            Err(Error::Other(e))
+                if e.downcast_ref::<reqwest::Error>().and_then(|e| e.status())
+                    == Some(StatusCode::NOT_FOUND) =>
+            {
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn send<T: DeserializeOwned>(&self, req: RequestBuilder) -> Result<T, Error> {
+        let res = req.send().await.map_err(|e| anyhow!("{e}"))?;
+
+        if res.status() == StatusCode::UNAUTHORIZED {
+            return Err(Error::Unauthorized);
+        }
+
+        Ok(res
+            .error_for_status()
+            .map_err(anyhow::Error::from)?
+            .json()
+            .await
+            .map_err(|e| anyhow!("{e}"))?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::{
+        matchers::{bearer_token, body_json, method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    fn fixture(name: &str) -> serde_json::Value {
+        let path = format!(
+            "{}/tests/fixtures/mangaupdates/{name}",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_login() {
+        let server = MockServer::start().await;
+        Mock::given(method("PUT"))
+            .and(path("/account/login"))
+            .and(body_json(
+                json!({"username": "user", "password": "password"}),
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_json(fixture("login.json")))
+            .mount(&server)
+            .await;
+
+        let mangaupdates = MangaUpdates::new(&server.uri());
+        let token = mangaupdates
+            .login("user".to_string(), "password".to_string())
+            .await
+            .unwrap();
+
+        assert_eq!(token.access_token, "mangaupdates-session-token");
+    }
+
+    #[tokio::test]
+    async fn test_login_invalid_credential() {
+        let server = MockServer::start().await;
+        Mock::given(method("PUT"))
+            .and(path("/account/login"))
+            .respond_with(ResponseTemplate::new(401).set_body_json(fixture("login_failed.json")))
+            .mount(&server)
+            .await;
+
+        let mangaupdates = MangaUpdates::new(&server.uri());
+        let res = mangaupdates
+            .login("user".to_string(), "wrong".to_string())
+            .await;
+
+        assert!(matches!(res, Err(Error::Unauthorized)));
+    }
+
+    #[tokio::test]
+    async fn test_search_manga() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/series/search"))
+            .and(bearer_token("token"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(fixture("series_search.json")))
+            .mount(&server)
+            .await;
+
+        let mangaupdates = MangaUpdates::new(&server.uri());
+        let manga = mangaupdates
+            .search_manga("token".to_string(), "yotsuba".to_string())
+            .await
+            .unwrap();
+
+        assert_eq!(manga.len(), 1);
+        assert_eq!(manga[0].tracker, NAME);
+        assert_eq!(manga[0].tracker_manga_id, "55099564912");
+        assert_eq!(manga[0].title, "Yotsuba to!");
+    }
+
+    #[tokio::test]
+    async fn test_get_manga_details() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/series/55099564912"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(fixture("series.json")))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/lists/series/55099564912"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(fixture("list_series.json")))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/series/55099564912/rating"))
+            .respond_with(ResponseTemplate::new(404).set_body_json(fixture("not_found.json")))
+            .mount(&server)
+            .await;
+
+        let mangaupdates = MangaUpdates::new(&server.uri());
+        let manga = mangaupdates
+            .get_manga_details("token".to_string(), 55099564912)
+            .await
+            .unwrap();
+
+        let status = manga.tracker_status.unwrap();
+        assert_eq!(status.status.as_deref(), Some("reading"));
+        assert_eq!(status.num_chapters_read, Some(104));
+        assert_eq!(status.score, None);
+    }
+
+    #[tokio::test]
+    async fn test_update_tracker_status_add_to_list() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/lists/series/55099564912"))
+            .respond_with(ResponseTemplate::new(404).set_body_json(fixture("not_found.json")))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/lists/series"))
+            .and(body_json(json!([{
+                "series": { "id": 55099564912_i64 },
+                "list_id": 0,
+                "status": { "chapter": 10 }
+            }])))
+            .respond_with(ResponseTemplate::new(200).set_body_json(fixture("list_updated.json")))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let mangaupdates = MangaUpdates::new(&server.uri());
+        mangaupdates
+            .update_tracker_status(
+                "token".to_string(),
+                55099564912,
+                None,
+                None,
+                Some(10),
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+    }
+}
@@ -9,7 +9,7 @@ use oauth2::{
 use reqwest::StatusCode;
 use serde::{Deserialize, Serialize};
 
-use crate::{Error, Tracker, TrackerManga, TrackerStatus};
+use crate::{token_request_error, Error, Tracker, TrackerManga, TrackerStatus};
 
 use super::{Session, Token};
 
@@ -32,11 +32,20 @@ pub struct MainPicture {
     pub large: String,
 }
 
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct AlternativeTitles {
+    pub synonyms: Vec<String>,
+    pub en: String,
+    pub ja: String,
+}
+
 #[derive(Debug, Default, Clone, Deserialize)]
 #[serde(default)]
 pub struct Manga {
     pub id: i64,
     pub title: String,
+    pub alternative_titles: AlternativeTitles,
     pub synopsis: String,
     pub main_picture: MainPicture,
     pub status: String,
@@ -49,6 +58,13 @@ impl From<Manga> for TrackerManga {
             tracker: NAME.to_string(),
             tracker_manga_id: other.id.to_string(),
             title: other.title.clone(),
+            alternative_titles: other
+                .alternative_titles
+                .synonyms
+                .into_iter()
+                .chain([other.alternative_titles.en, other.alternative_titles.ja])
+                .filter(|title| !title.is_empty())
+                .collect(),
             synopsis: other.synopsis,
             cover_url: other.main_picture.medium,
             status: other.status,
@@ -85,6 +101,34 @@ pub struct GetMangaListResponse {
     pub data: Vec<Node<Manga>>,
 }
 
+#[derive(Debug, Clone, Deserialize)]
+pub struct UserMangaListEntry {
+    pub node: Manga,
+    pub list_status: Option<UserListStatus>,
+}
+
+/// List status on user manga list, dates are left out as they are not returned as datetime
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct UserListStatus {
+    pub status: Option<String>,
+    pub score: i64,
+    pub num_chapters_read: i64,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct Paging {
+    pub next: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct GetUserMangaListResponse {
+    pub data: Vec<UserMangaListEntry>,
+    #[serde(default)]
+    pub paging: Paging,
+}
+
 #[derive(Debug, Clone)]
 pub struct MyAnimeList {
     pub oauth_client: BasicClient,
@@ -143,7 +187,7 @@ impl Tracker for MyAnimeList {
             .exchange_refresh_token(&RefreshToken::new(refresh_token))
             .request_async(async_http_client)
             .await
-            .map_err(|e| anyhow!("{e}"))?;
+            .map_err(token_request_error)?;
         let token_str = serde_json::to_string(&token).map_err(|e| anyhow!("{e}"))?;
         Ok(serde_json::from_str(&token_str).map_err(|e| anyhow!("{e}"))?)
     }
@@ -159,7 +203,7 @@ impl Tracker for MyAnimeList {
                 search,
                 6,
                 0,
-                "id,title,main_picture,synopsis,status".to_string(),
+                "id,title,alternative_titles,main_picture,synopsis,status".to_string(),
             )
             .await?;
 
@@ -181,6 +225,66 @@ impl Tracker for MyAnimeList {
         Ok(manga.into())
     }
 
+    async fn get_user_manga_list(
+        &self,
+        token: String,
+        status: Option<String>,
+    ) -> Result<Vec<TrackerManga>, Error> {
+        let mut manga_list = vec![];
+        let mut req = self
+            .api_client
+            .get("https://api.myanimelist.net/v2/users/@me/mangalist")
+            .bearer_auth(&token)
+            .query(&[
+                (
+                    "fields",
+                    "list_status,alternative_titles,main_picture,synopsis,status",
+                ),
+                ("nsfw", "true"),
+                ("limit", "1000"),
+            ]);
+        if let Some(status) = status {
+            req = req.query(&[("status", status)]);
+        }
+
+        loop {
+            let list: GetUserMangaListResponse = req
+                .send()
+                .await
+                .map_err(|e| {
+                    if e.status() == Some(StatusCode::UNAUTHORIZED) {
+                        Error::Unauthorized
+                    } else {
+                        Error::Other(anyhow!("{e}"))
+                    }
+                })?
+                .json()
+                .await
+                .map_err(|e| anyhow!("{e}"))?;
+
+            manga_list.extend(list.data.into_iter().map(|entry| {
+                let mut manga: TrackerManga = entry.node.into();
+                if let Some((tracker_status, list_status)) =
+                    manga.tracker_status.as_mut().zip(entry.list_status)
+                {
+                    tracker_status.status = list_status.status;
+                    tracker_status.score = Some(list_status.score);
+                    tracker_status.num_chapters_read = Some(list_status.num_chapters_read);
+                }
+                manga
+            }));
+
+            match list.paging.next {
+                Some(next) => {
+                    req = self.api_client.get(next).bearer_auth(&token);
+                }
+                None => break,
+            }
+        }
+
+        Ok(manga_list)
+    }
+
     async fn update_tracker_status(
         &self,
         token: String,
@@ -0,0 +1,467 @@
+use anyhow::anyhow;
+use async_trait::async_trait;
+use chrono::NaiveDateTime;
+use oauth2::{
+    basic::BasicClient,
+    http::header::{HeaderValue, USER_AGENT},
+    reqwest::async_http_client,
+    AuthUrl, AuthorizationCode, ClientId, ClientSecret, CsrfToken, HttpRequest, HttpResponse,
+    RedirectUrl, RefreshToken, Scope, TokenUrl,
+};
+use reqwest::{RequestBuilder, StatusCode};
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::json;
+
+use crate::{token_request_error, Error, Tracker, TrackerManga, TrackerStatus};
+
+use super::{Session, Token};
+
+pub const NAME: &str = "shikimori";
+
+const BASE_URL: &str = "https://shikimori.one";
+/// Shikimori reject requests without user agent
+const APP_USER_AGENT: &str = "Tanoshi";
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct Image {
+    pub original: Option<String>,
+    pub preview: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct UserRate {
+    pub id: i64,
+    pub status: Option<String>,
+    pub score: Option<i64>,
+    pub chapters: Option<i64>,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct Manga {
+    pub id: i64,
+    pub name: String,
+    pub russian: Option<String>,
+    pub english: Option<Vec<String>>,
+    pub description: Option<String>,
+    pub image: Option<Image>,
+    pub status: Option<String>,
+    pub user_rate: Option<UserRate>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct User {
+    pub id: i64,
+}
+
+async fn http_client(
+    mut request: HttpRequest,
+) -> Result<HttpResponse, oauth2::reqwest::Error<reqwest::Error>> {
+    request
+        .headers
+        .insert(USER_AGENT, HeaderValue::from_static(APP_USER_AGENT));
+    async_http_client(request).await
+}
+
+#[derive(Debug, Clone)]
+pub struct Shikimori {
+    pub oauth_client: BasicClient,
+    api_url: String,
+    api_client: reqwest::Client,
+}
+
+#[async_trait]
+impl Tracker for Shikimori {
+    fn get_authorize_url(&self) -> Result<Session, Error> {
+        let (authorize_url, csrf_state) = self
+            .oauth_client
+            .authorize_url(CsrfToken::new_random)
+            .add_scope(Scope::new("user_rates".to_string()))
+            .url();
+
+        Ok(Session {
+            authorize_url: authorize_url.to_string(),
+            csrf_state,
+            pkce_code_verifier: None,
+        })
+    }
+
+    async fn exchange_code(
+        &self,
+        code: String,
+        _state: Option<String>,
+        _csrf_state: Option<String>,
+        _pkce_code_verifier: Option<String>,
+    ) -> Result<Token, Error> {
+        let code = AuthorizationCode::new(code);
+
+        let token = self
+            .oauth_client
+            .exchange_code(code)
+            .request_async(http_client)
+            .await
+            .map_err(|e| anyhow!("{e}"))?;
+
+        let token_str = serde_json::to_string(&token).map_err(|e| anyhow!("{e}"))?;
+        Ok(serde_json::from_str(&token_str).map_err(|e| anyhow!("{e}"))?)
+    }
+
+    async fn refresh_token(&self, refresh_token: String) -> Result<Token, Error> {
+        let token = self
+            .oauth_client
+            .exchange_refresh_token(&RefreshToken::new(refresh_token))
+            .request_async(http_client)
+            .await
+            .map_err(token_request_error)?;
+        let token_str = serde_json::to_string(&token).map_err(|e| anyhow!("{e}"))?;
+        Ok(serde_json::from_str(&token_str).map_err(|e| anyhow!("{e}"))?)
+    }
+
+    async fn search_manga(
+        &self,
+        token: String,
+        search: String,
+    ) -> Result<Vec<TrackerManga>, Error> {
+        let manga: Vec<Manga> = self
+            .send(
+                self.api_client
+                    .get(format!("{}/api/mangas", self.api_url))
+                    .bearer_auth(token)
+                    .query(&[("search", search.as_str()), ("limit", "6")]),
+            )
+            .await?;
+
+        Ok(manga.into_iter().map(|m| self.tracker_manga(m)).collect())
+    }
+
+    async fn get_manga_details(
+        &self,
+        token: String,
+        tracker_manga_id: i64,
+    ) -> Result<TrackerManga, Error> {
+        let manga = self.get_manga(&token, tracker_manga_id).await?;
+
+        Ok(self.tracker_manga(manga))
+    }
+
+    async fn update_tracker_status(
+        &self,
+        token: String,
+        tracker_manga_id: i64,
+        status: Option<String>,
+        score: Option<i64>,
+        progress: Option<i64>,
+        _started_at: Option<NaiveDateTime>,
+        _completed_at: Option<NaiveDateTime>,
+    ) -> Result<(), Error> {
+        let mut user_rate = serde_json::Map::new();
+        if let Some(status) = status.as_deref().and_then(|s| match s {
+            "reading" => Some("watching"),
+            "completed" => Some("completed"),
+            "on_hold" => Some("on_hold"),
+            "dropped" => Some("dropped"),
+            "plan_to_read" => Some("planned"),
+            _ => None,
+        }) {
+            user_rate.insert("status".to_string(), json!(status));
+        }
+        if let Some(score) = score {
+            user_rate.insert("score".to_string(), json!(score));
+        }
+        if let Some(chapters) = progress {
+            user_rate.insert("chapters".to_string(), json!(chapters));
+        }
+
+        let manga = self.get_manga(&token, tracker_manga_id).await?;
+
+        let req = if let Some(rate) = manga.user_rate {
+            self.api_client
+                .patch(format!("{}/api/v2/user_rates/{}", self.api_url, rate.id))
+                .json(&json!({ "user_rate": user_rate }))
+        } else {
+            let user: User = self
+                .send(
+                    self.api_client
+                        .get(format!("{}/api/users/whoami", self.api_url))
+                        .bearer_auth(&token),
+                )
+                .await?;
+
+            user_rate.insert("user_id".to_string(), json!(user.id));
+            user_rate.insert("target_id".to_string(), json!(tracker_manga_id));
+            user_rate.insert("target_type".to_string(), json!("Manga"));
+            self.api_client
+                .post(format!("{}/api/v2/user_rates", self.api_url))
+                .json(&json!({ "user_rate": user_rate }))
+        };
+
+        let _: serde_json::Value = self.send(req.bearer_auth(&token)).await?;
+
+        Ok(())
+    }
+}
+
+impl Shikimori {
+    pub fn new(base_url: &str, client_id: String, client_secret: String) -> Result<Self, Error> {
+        let client_id = ClientId::new(client_id);
+        let client_secret = ClientSecret::new(client_secret);
+        let authorization_url =
+            AuthUrl::new(format!("{BASE_URL}/oauth/authorize")).map_err(|e| anyhow!("{e}"))?;
+        let token_url =
+            TokenUrl::new(format!("{BASE_URL}/oauth/token")).map_err(|e| anyhow!("{e}"))?;
+
+        let redirect_url = RedirectUrl::new(format!("{base_url}/tracker/{NAME}/redirect"))
+            .map_err(|e| anyhow!("{e}"))?;
+        let client = BasicClient::new(
+            client_id,
+            Some(client_secret),
+            authorization_url,
+            Some(token_url),
+        )
+        .set_redirect_uri(redirect_url);
+
+        let api_client = reqwest::Client::builder()
+            .user_agent(APP_USER_AGENT)
+            .build()
+            .map_err(|e| anyhow!("{e}"))?;
+
+        Ok(Self {
+            oauth_client: client,
+            api_url: BASE_URL.to_string(),
+            api_client,
+        })
+    }
+
+    /// Override shikimori api url, e.g. to point to a mirror
+    pub fn with_api_url(self, api_url: &str) -> Self {
+        Self {
+            api_url: api_url.trim_end_matches('/').to_string(),
+            ..self
+        }
+    }
+
+    fn tracker_manga(&self, manga: Manga) -> TrackerManga {
+        // shikimori return image path relative to its domain
+        let cover_url = manga
+            .image
+            .and_then(|image| image.original.or(image.preview))
+            .map(|url| {
+                if url.starts_with('/') {
+                    format!("{}{url}", self.api_url)
+                } else {
+                    url
+                }
+            })
+            .unwrap_or_default();
+
+        TrackerManga {
+            tracker: NAME.to_string(),
+            tracker_manga_id: manga.id.to_string(),
+            title: manga.name.clone(),
+            alternative_titles: manga
+                .russian
+                .into_iter()
+                .chain(manga.english.unwrap_or_default())
+                .collect(),
+            synopsis: manga.description.unwrap_or_default(),
+            cover_url,
+            status: manga.status.unwrap_or_default(),
+            tracker_status: Some(TrackerStatus {
+                tracker: NAME.to_string(),
+                tracker_manga_id: Some(manga.id.to_string()),
+                tracker_manga_title: Some(manga.name),
+                status: manga
+                    .user_rate
+                    .as_ref()
+                    .and_then(|rate| rate.status.as_deref())
+                    .and_then(|status| match status {
+                        "watching" | "rewatching" => Some("reading".to_string()),
+                        "planned" => Some("plan_to_read".to_string()),
+                        "completed" => Some("completed".to_string()),
+                        "on_hold" => Some("on_hold".to_string()),
+                        "dropped" => Some("dropped".to_string()),
+                        _ => None,
+                    }),
+                score: manga.user_rate.as_ref().and_then(|rate| rate.score),
+                num_chapters_read: manga.user_rate.as_ref().and_then(|rate| rate.chapters),
+                ..Default::default()
+            }),
+        }
+    }
+
+    async fn get_manga(&self, token: &str, tracker_manga_id: i64) -> Result<Manga, Error> {
+        self.send(
+            self.api_client
+                .get(format!("{}/api/mangas/{tracker_manga_id}", self.api_url))
+                .bearer_auth(token),
+        )
+        .await
+    }
+
+    async fn send<T: DeserializeOwned>(&self, req: RequestBuilder) -> Result<T, Error> {
+        let res = req.send().await.map_err(|e| anyhow!("{e}"))?;
+
+        if res.status() == StatusCode::UNAUTHORIZED {
+            return Err(Error::Unauthorized);
+        }
+
+        Ok(res
+            .error_for_status()
+            .map_err(|e| anyhow!("{e}"))?
+            .json()
+            .await
+            .map_err(|e| anyhow!("{e}"))?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::{
+        matchers::{body_json, header, method, path, query_param},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    fn fixture(name: &str) -> serde_json::Value {
+        let path = format!(
+            "{}/tests/fixtures/shikimori/{name}",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap()
+    }
+
+    fn shikimori(server: &MockServer) -> Shikimori {
+        Shikimori::new(
+            "http://localhost:8080",
+            "client_id".to_string(),
+            "client_secret".to_string(),
+        )
+        .unwrap()
+        .with_api_url(&server.uri())
+    }
+
+    #[test]
+    fn test_get_authorize_url() {
+        let shikimori = Shikimori::new(
+            "http://localhost:8080",
+            "client_id".to_string(),
+            "client_secret".to_string(),
+        )
+        .unwrap();
+
+        let session = shikimori.get_authorize_url().unwrap();
+        assert!(session
+            .authorize_url
+            .starts_with("https://shikimori.one/oauth/authorize"));
+        assert!(session.authorize_url.contains("scope=user_rates"));
+    }
+
+    #[tokio::test]
+    async fn test_search_manga() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api/mangas"))
+            .and(query_param("search", "yotsuba"))
+            .and(header("user-agent", APP_USER_AGENT))
+            .respond_with(ResponseTemplate::new(200).set_body_json(fixture("mangas.json")))
+            .mount(&server)
+            .await;
+
+        let manga = shikimori(&server)
+            .search_manga("token".to_string(), "yotsuba".to_string())
+            .await
+            .unwrap();
+
+        assert_eq!(manga.len(), 1);
+        assert_eq!(manga[0].tracker_manga_id, "104");
+        assert_eq!(manga[0].title, "Yotsuba to!");
+        assert_eq!(manga[0].alternative_titles, vec!["Ёцуба!".to_string()]);
+        assert_eq!(
+            manga[0].cover_url,
+            format!("{}/system/mangas/original/104.jpg", server.uri())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_manga_details() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api/mangas/104"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(fixture("manga.json")))
+            .mount(&server)
+            .await;
+
+        let manga = shikimori(&server)
+            .get_manga_details("token".to_string(), 104)
+            .await
+            .unwrap();
+
+        let status = manga.tracker_status.unwrap();
+        assert_eq!(status.status.as_deref(), Some("reading"));
+        assert_eq!(status.score, Some(9));
+        assert_eq!(status.num_chapters_read, Some(87));
+    }
+
+    #[tokio::test]
+    async fn test_update_tracker_status_create_user_rate() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api/mangas/104"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(fixture("manga_without_rate.json")),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/api/users/whoami"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(fixture("whoami.json")))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/api/v2/user_rates"))
+            .and(body_json(json!({
+                "user_rate": {
+                    "status": "watching",
+                    "chapters": 1,
+                    "user_id": 42,
+                    "target_id": 104,
+                    "target_type": "Manga"
+                }
+            })))
+            .respond_with(ResponseTemplate::new(201).set_body_json(fixture("user_rate.json")))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        shikimori(&server)
+            .update_tracker_status(
+                "token".to_string(),
+                104,
+                Some("reading".to_string()),
+                None,
+                Some(1),
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_unauthorized() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api/mangas/104"))
+            .respond_with(ResponseTemplate::new(401).set_body_json(fixture("invalid_token.json")))
+            .mount(&server)
+            .await;
+
+        let res = shikimori(&server)
+            .get_manga_details("expired".to_string(), 104)
+            .await;
+
+        assert!(matches!(res, Err(Error::Unauthorized)));
+    }
+}
@@ -88,6 +88,62 @@ impl ExtensionManager {
         self.insert(source).await
     }
 
+    /// Replace installed source with the version from `repo_url`, previous
+    /// library is restored if the new one fails to load
+    pub async fn update(&self, repo_url: &str, source_id: i64) -> Result<()> {
+        let name = self.get_source_info(source_id)?.name.to_lowercase();
+
+        let source_file_url = format!(
+            "{}/{}/{}.{}",
+            repo_url,
+            env!("TARGET"),
+            name,
+            PLUGIN_EXTENSION
+        );
+
+        info!("downloading {}", source_file_url);
+
+        let contents = reqwest::get(&source_file_url)
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await?;
+
+        let library_path = self.dir.join(&name).with_extension(PLUGIN_EXTENSION);
+        let backup_path = library_path.with_extension(format!("{PLUGIN_EXTENSION}.bak"));
+
+        // drop loaded library before replacing its file
+        self.write()?.remove(&source_id);
+
+        if let Err(e) = tokio::fs::rename(&library_path, &backup_path).await {
+            self.load(&name).await?;
+            return Err(e.into());
+        }
+
+        let res = match tokio::fs::write(&library_path, contents).await {
+            Ok(_) => self.load(&name).await,
+            Err(e) => Err(e.into()),
+        };
+
+        match res {
+            Ok(_) => {
+                if let Err(e) = tokio::fs::remove_file(&backup_path).await {
+                    warn!("failed to remove {}: {e}", backup_path.display());
+                }
+                Ok(())
+            }
+            Err(e) => {
+                error!("failed to update {name}, restoring previous version: {e}");
+                tokio::fs::rename(&backup_path, &library_path).await?;
+                self.load(&name).await?;
+
+                Err(anyhow!(
+                    "failed to update {name}, previous version restored: {e}"
+                ))
+            }
+        }
+    }
+
     fn load_library(&self, name: &str) -> Result<Source> {
         let library_path = PathBuf::new()
             .join(&self.dir)
@@ -24,7 +24,7 @@ use crate::{
     tracker_login::TrackerLogin,
     tracker_redirect::TrackerRedirect,
     updates::Updates,
-    utils::{local_storage, AsyncLoader},
+    utils::{window, AsyncLoader},
 };
 
 pub struct App {
@@ -49,7 +49,8 @@ impl App {
                     app.server_status.set_neq(Some(ServerStatus {
                         activated: server_status.activated,
                         version: server_status.version,
-                        loggedin: server_status.loggedin
+                        loggedin: server_status.loggedin,
+                        oidc_provider: server_status.oidc_provider,
                     }));
                 }
                 Err(e) => {
@@ -77,10 +78,13 @@ impl App {
                 if let Some(server_status) = server_status {
                     if !server_status.activated {
                         info!("server inactivated, go to login");
-                        local_storage().delete("token").unwrap_throw();
+                        query::clear_token();
                         routing::go_to_url(&Route::Login.url());
                     } else if server_status.activated && !server_status.loggedin {
-                        routing::go_to_url(&Route::Login.url());
+                        // login page may carry oidc code to be exchanged or an invite code
+                        if !window().location().pathname().unwrap_throw().starts_with("/login") {
+                            routing::go_to_url(&Route::Login.url());
+                        }
                     } else if server_status.loggedin {
                         spawn_local(async {
                             loop {
@@ -114,6 +118,14 @@ impl App {
                     Route::Login => Some(
                         Login::render(Login::new(), app.clone())
                     ),
+                    Route::Invite(code) => Some(
+                        Login::render(Login::with_invite(code), app.clone())
+                    ),
+                    Route::OidcRedirect{code, state} => {
+                        let login = Login::new();
+                        Login::oidc_login_end(login.clone(), app.clone(), code, state);
+                        Some(Login::render(login, app.clone()))
+                    }
                     Route::LibraryList => {
                         Some(LibraryList::render(LibraryList::new()))
                     }
@@ -4,7 +4,7 @@ use futures_signals::signal::{self, Mutable, SignalExt};
 use serde::{Deserialize, Serialize};
 
 use crate::common::route::Route;
-use crate::utils::proxied_image_url;
+use crate::utils::{resized_image_url, COVER_WIDTH};
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Cover {
@@ -30,7 +30,7 @@ impl Cover {
         last_read_at: Option<NaiveDateTime>,
         unread_chapter_count: i64,
     ) -> Self {
-        let cover_url = proxied_image_url(&cover_url);
+        let cover_url = resized_image_url(&cover_url, COVER_WIDTH);
         Self {
             id,
             source_id,
@@ -2,26 +2,95 @@ use std::rc::Rc;
 
 use dominator::{clone, html, routing, Dom};
 use dominator::{with_node, EventOptions};
+use futures_signals::map_ref;
 use futures_signals::signal::Mutable;
 use futures_signals::signal::SignalExt;
+use futures_signals::signal_vec::{MutableVec, SignalVecExt};
 use wasm_bindgen::prelude::Closure;
 use wasm_bindgen::{JsValue, UnwrapThrowExt};
-use wasm_bindgen_futures::JsFuture;
-use web_sys::{HtmlInputElement, Notification, NotificationPermission};
+use wasm_bindgen_futures::{spawn_local, JsFuture};
+use web_sys::{
+    HtmlInputElement, HtmlSelectElement, HtmlTextAreaElement, Notification, NotificationPermission,
+};
 
 use crate::common::{events, snackbar, Route};
 use crate::query;
-use crate::utils::{local_storage, AsyncLoader};
+use crate::utils::AsyncLoader;
+
+#[derive(Debug, Clone)]
+struct TelegramLinkCode {
+    code: String,
+    bot_url: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+struct TotpSetup {
+    secret: String,
+    url: String,
+}
+
+#[derive(Debug, Clone)]
+struct NotificationTarget {
+    id: i64,
+    channel: String,
+    target: String,
+}
+
+#[derive(Debug, Clone)]
+struct ApiKey {
+    id: i64,
+    name: String,
+    prefix: String,
+    scopes: Vec<String>,
+    last_used_at: Option<String>,
+}
+
+/// Scopes a new api key can be given besides read
+const API_KEY_SCOPES: [&str; 3] = ["library", "downloads", "admin"];
+
+fn target_placeholder(channel: &str) -> &'static str {
+    match channel {
+        "pushover" => "Pushover user key, get from pushover dashboard",
+        "gotify" => "Gotify token, get from Gotify dashboard",
+        "webhook" => "Webhook url",
+        "ntfy" => "ntfy topic or topic url",
+        "discord" => "Discord webhook url",
+        "matrix" => "Matrix room id",
+        "email" => "Email address",
+        _ => "Target",
+    }
+}
 
 pub struct Profile {
     old_password: Mutable<String>,
     new_password: Mutable<String>,
     confirm_password: Mutable<String>,
-    telegram_chat_id: Mutable<Option<String>>,
-    pushover_user_key: Mutable<Option<String>>,
-    gotify_token: Mutable<Option<String>>,
+    notification_channels: MutableVec<String>,
+    notification_targets: MutableVec<NotificationTarget>,
+    new_channel: Mutable<String>,
+    new_target: Mutable<String>,
+    telegram_link_code: Mutable<Option<TelegramLinkCode>>,
+    notification_placeholders: MutableVec<String>,
+    template_title: Mutable<String>,
+    template_body: Mutable<String>,
+    template_is_default: Mutable<bool>,
     myanimelist_status: Mutable<bool>,
     anilist_status: Mutable<bool>,
+    kitsu_status: Mutable<bool>,
+    mangaupdates_status: Mutable<bool>,
+    shikimori_status: Mutable<bool>,
+    can_browse_nsfw: Mutable<bool>,
+    show_nsfw: Mutable<bool>,
+    totp_enabled: Mutable<bool>,
+    totp_recovery_codes: Mutable<i64>,
+    totp_setup: Mutable<Option<TotpSetup>>,
+    totp_code: Mutable<String>,
+    totp_password: Mutable<String>,
+    new_recovery_codes: Mutable<Option<Vec<String>>>,
+    api_keys: MutableVec<ApiKey>,
+    new_api_key_name: Mutable<String>,
+    new_api_key_scopes: Mutable<Vec<String>>,
+    created_api_key: Mutable<Option<String>>,
     notification_cb: Closure<dyn FnMut(JsValue) -> ()>,
     pub loader: AsyncLoader,
 }
@@ -32,11 +101,32 @@ impl Profile {
             old_password: Mutable::new("".to_string()),
             new_password: Mutable::new("".to_string()),
             confirm_password: Mutable::new("".to_string()),
-            telegram_chat_id: Mutable::new(None),
-            pushover_user_key: Mutable::new(None),
-            gotify_token: Mutable::new(None),
+            notification_channels: MutableVec::new(),
+            notification_targets: MutableVec::new(),
+            new_channel: Mutable::new("".to_string()),
+            new_target: Mutable::new("".to_string()),
+            telegram_link_code: Mutable::new(None),
+            notification_placeholders: MutableVec::new(),
+            template_title: Mutable::new("".to_string()),
+            template_body: Mutable::new("".to_string()),
+            template_is_default: Mutable::new(true),
             myanimelist_status: Mutable::new(false),
             anilist_status: Mutable::new(false),
+            kitsu_status: Mutable::new(false),
+            mangaupdates_status: Mutable::new(false),
+            shikimori_status: Mutable::new(false),
+            can_browse_nsfw: Mutable::new(false),
+            show_nsfw: Mutable::new(true),
+            totp_enabled: Mutable::new(false),
+            totp_recovery_codes: Mutable::new(0),
+            totp_setup: Mutable::new(None),
+            totp_code: Mutable::new("".to_string()),
+            totp_password: Mutable::new("".to_string()),
+            new_recovery_codes: Mutable::new(None),
+            api_keys: MutableVec::new(),
+            new_api_key_name: Mutable::new("".to_string()),
+            new_api_key_scopes: Mutable::new(vec![]),
+            created_api_key: Mutable::new(None),
             notification_cb: Closure::wrap(Box::new(|value| {
                 let permission = NotificationPermission::from_js_value(&value)
                     .unwrap_or(NotificationPermission::Default);
@@ -55,11 +145,17 @@ impl Profile {
         profile.loader.load(clone!(profile => async move {
             match query::fetch_me().await {
                 Ok(result) => {
-                    profile.telegram_chat_id.set(result.telegram_chat_id.map(|id| id.to_string()));
-                    profile.pushover_user_key.set(result.pushover_user_key);
-                    profile.gotify_token.set(result.gotify_token);
                     profile.myanimelist_status.set(result.myanimelist_status);
                     profile.anilist_status.set(result.anilist_status);
+                    profile.kitsu_status.set(result.kitsu_status);
+                    profile.mangaupdates_status.set(result.mangaupdates_status);
+                    profile.shikimori_status.set(result.shikimori_status);
+                    profile.can_browse_nsfw.set(
+                        result.is_admin || result.permissions.iter().any(|p| p == "browse_nsfw"),
+                    );
+                    profile.show_nsfw.set(result.show_nsfw);
+                    profile.totp_enabled.set(result.totp_enabled);
+                    profile.totp_recovery_codes.set(result.totp_recovery_codes);
                 },
                 Err(err) => {
                     snackbar::show(format!("{}", err));
@@ -68,47 +164,244 @@ impl Profile {
         }));
     }
 
-    fn test_telegram(profile: Rc<Self>) {
-        if let Some(chat_id) = profile
-            .telegram_chat_id
-            .get_cloned()
-            .map(|id| id.parse().unwrap_or_default())
-        {
-            profile.loader.load(async move {
-                match query::test_telegram(chat_id).await {
-                    Ok(_) => {}
-                    Err(err) => {
-                        snackbar::show(format!("{}", err));
-                    }
+    fn update_show_nsfw(profile: Rc<Self>, show_nsfw: bool) {
+        profile.loader.load(clone!(profile => async move {
+            match query::update_show_nsfw(show_nsfw).await {
+                Ok(_) => profile.show_nsfw.set(show_nsfw),
+                Err(e) => {
+                    snackbar::show(format!("update nsfw setting error: {e}"));
                 }
-            });
-        }
+            };
+        }));
+    }
+
+    fn setup_totp(profile: Rc<Self>) {
+        profile.loader.load(clone!(profile => async move {
+            match query::setup_totp().await {
+                Ok(setup) => profile.totp_setup.set(Some(TotpSetup {
+                    secret: setup.secret,
+                    url: setup.url,
+                })),
+                Err(e) => {
+                    snackbar::show(format!("setup two-factor authentication error: {e}"));
+                }
+            };
+        }));
+    }
+
+    fn enable_totp(profile: Rc<Self>) {
+        let code = profile.totp_code.get_cloned();
+        profile.loader.load(clone!(profile => async move {
+            match query::enable_totp(code).await {
+                Ok(codes) => {
+                    profile.totp_code.set("".to_string());
+                    profile.totp_setup.set(None);
+                    profile.new_recovery_codes.set(Some(codes));
+                    Self::fetch_me(profile);
+                }
+                Err(e) => {
+                    snackbar::show(format!("enable two-factor authentication error: {e}"));
+                }
+            };
+        }));
+    }
+
+    fn regenerate_totp_recovery_codes(profile: Rc<Self>) {
+        let code = profile.totp_code.get_cloned();
+        profile.loader.load(clone!(profile => async move {
+            match query::regenerate_totp_recovery_codes(code).await {
+                Ok(codes) => {
+                    profile.totp_code.set("".to_string());
+                    profile.new_recovery_codes.set(Some(codes));
+                    Self::fetch_me(profile);
+                }
+                Err(e) => {
+                    snackbar::show(format!("regenerate recovery codes error: {e}"));
+                }
+            };
+        }));
     }
 
-    fn test_pushover(profile: Rc<Self>) {
-        if let Some(user_key) = profile.pushover_user_key.get_cloned() {
-            profile.loader.load(async move {
-                match query::test_pushover(&user_key).await {
-                    Ok(_) => {}
-                    Err(err) => {
-                        snackbar::show(format!("{}", err));
+    fn disable_totp(profile: Rc<Self>) {
+        let password = profile.totp_password.get_cloned();
+        profile.loader.load(clone!(profile => async move {
+            match query::disable_totp(password).await {
+                Ok(_) => {
+                    profile.totp_password.set("".to_string());
+                    profile.new_recovery_codes.set(None);
+                    Self::fetch_me(profile);
+                }
+                Err(e) => {
+                    snackbar::show(format!("disable two-factor authentication error: {e}"));
+                }
+            };
+        }));
+    }
+
+    fn fetch_notification_targets(profile: Rc<Self>) {
+        profile.loader.load(clone!(profile => async move {
+            match query::fetch_notification_targets().await {
+                Ok(result) => {
+                    let channels = result.notification_channels;
+                    // telegram chats are only added with link telegram
+                    if profile.new_channel.get_cloned().is_empty() {
+                        profile.new_channel.set(channels.iter().find(|channel| *channel != "telegram").cloned().unwrap_or_default());
                     }
+                    profile.notification_channels.lock_mut().replace_cloned(channels);
+                    profile.notification_placeholders.lock_mut().replace_cloned(result.notification_placeholders);
+                    profile.notification_targets.lock_mut().replace_cloned(result.me.notification_targets.into_iter().map(|target| NotificationTarget {
+                        id: target.id,
+                        channel: target.channel,
+                        target: target.target,
+                    }).collect());
+                    profile.template_title.set(result.me.notification_template.title);
+                    profile.template_body.set(result.me.notification_template.body);
+                    profile.template_is_default.set(result.me.notification_template.is_default);
+                },
+                Err(err) => {
+                    snackbar::show(format!("{}", err));
                 }
-            });
+            }
+        }));
+    }
+
+    fn test_notification(profile: Rc<Self>, channel: String, target: String) {
+        if target.is_empty() {
+            return;
         }
+
+        profile.loader.load(async move {
+            match query::test_notification(channel, target).await {
+                Ok(_) => {}
+                Err(err) => {
+                    snackbar::show(format!("{}", err));
+                }
+            }
+        });
     }
 
-    fn test_gotify(profile: Rc<Self>) {
-        if let Some(token) = profile.gotify_token.get_cloned() {
-            profile.loader.load(async move {
-                match query::test_gotify(&token).await {
-                    Ok(_) => {}
-                    Err(err) => {
-                        snackbar::show(format!("{}", err));
-                    }
+    fn add_notification_target(profile: Rc<Self>) {
+        let channel = profile.new_channel.get_cloned();
+        let target = profile.new_target.get_cloned();
+        if channel.is_empty() || target.is_empty() {
+            return;
+        }
+
+        profile.loader.load(clone!(profile => async move {
+            match query::add_notification_target(channel, target).await {
+                Ok(_) => {
+                    profile.new_target.set("".to_string());
+                    Self::fetch_notification_targets(profile);
+                },
+                Err(e) => {
+                    snackbar::show(format!("add notification target error: {e}"));
+                }
+            };
+        }));
+    }
+
+    fn create_telegram_link_code(profile: Rc<Self>) {
+        profile.loader.load(clone!(profile => async move {
+            match query::create_telegram_link_code().await {
+                Ok(result) => {
+                    profile.telegram_link_code.set(Some(TelegramLinkCode {
+                        code: result.code,
+                        bot_url: result.bot_url,
+                    }));
+                },
+                Err(e) => {
+                    snackbar::show(format!("create telegram link code error: {e}"));
                 }
-            });
+            };
+        }));
+    }
+
+    fn update_notification_template(profile: Rc<Self>) {
+        let title = profile.template_title.get_cloned();
+        let body = profile.template_body.get_cloned();
+
+        profile.loader.load(clone!(profile => async move {
+            match query::update_notification_template(title, body).await {
+                Ok(_) => Self::fetch_notification_targets(profile),
+                Err(e) => {
+                    snackbar::show(format!("update notification template error: {e}"));
+                }
+            };
+        }));
+    }
+
+    fn reset_notification_template(profile: Rc<Self>) {
+        profile.loader.load(clone!(profile => async move {
+            match query::reset_notification_template().await {
+                Ok(_) => Self::fetch_notification_targets(profile),
+                Err(e) => {
+                    snackbar::show(format!("reset notification template error: {e}"));
+                }
+            };
+        }));
+    }
+
+    fn remove_notification_target(profile: Rc<Self>, id: i64) {
+        profile.loader.load(clone!(profile => async move {
+            match query::remove_notification_target(id).await {
+                Ok(_) => Self::fetch_notification_targets(profile),
+                Err(e) => {
+                    snackbar::show(format!("remove notification target error: {e}"));
+                }
+            };
+        }));
+    }
+
+    fn fetch_api_keys(profile: Rc<Self>) {
+        profile.loader.load(clone!(profile => async move {
+            match query::fetch_api_keys().await {
+                Ok(result) => {
+                    profile.api_keys.lock_mut().replace_cloned(result.into_iter().map(|api_key| ApiKey {
+                        id: api_key.id,
+                        name: api_key.name,
+                        prefix: api_key.prefix,
+                        scopes: api_key.scopes,
+                        last_used_at: api_key.last_used_at,
+                    }).collect());
+                },
+                Err(err) => {
+                    snackbar::show(format!("{}", err));
+                }
+            }
+        }));
+    }
+
+    fn create_api_key(profile: Rc<Self>) {
+        let name = profile.new_api_key_name.get_cloned();
+        let scopes = profile.new_api_key_scopes.get_cloned();
+        if name.is_empty() {
+            return;
         }
+
+        profile.loader.load(clone!(profile => async move {
+            match query::create_api_key(name, scopes).await {
+                Ok(key) => {
+                    profile.new_api_key_name.set("".to_string());
+                    profile.new_api_key_scopes.set(vec![]);
+                    profile.created_api_key.set(Some(key));
+                    Self::fetch_api_keys(profile);
+                },
+                Err(e) => {
+                    snackbar::show(format!("create api key error: {e}"));
+                }
+            };
+        }));
+    }
+
+    fn revoke_api_key(profile: Rc<Self>, id: i64) {
+        profile.loader.load(clone!(profile => async move {
+            match query::revoke_api_key(id).await {
+                Ok(_) => Self::fetch_api_keys(profile),
+                Err(e) => {
+                    snackbar::show(format!("revoke api key error: {e}"));
+                }
+            };
+        }));
     }
 
     fn test_browser_notification(profile: Rc<Self>) {
@@ -145,13 +438,27 @@ impl Profile {
         }))
     }
 
+    fn sync_tracker_progress(profile: Rc<Self>) {
+        profile.loader.load(async move {
+            match query::sync_tracker_progress(true).await {
+                Ok(_) => snackbar::show("Progress synced from trackers".to_string()),
+                Err(err) => {
+                    snackbar::show(format!("{}", err));
+                }
+            }
+        })
+    }
+
     fn change_password(profile: Rc<Self>) {
         profile.loader.load(clone!(profile => async move {
             let old_password = profile.old_password.get_cloned();
             let new_password = profile.new_password.get_cloned();
             match query::change_password(old_password, new_password).await {
                 Ok(_) => {
-                    // routing::go_to_url(Route::Settings(SettingCategory::None).url().as_str());
+                    // every session is revoked when password changes
+                    query::clear_token();
+                    snackbar::show("Password changed, please login again".to_string());
+                    routing::go_to_url("/login");
                 },
                 Err(e) => {
                     snackbar::show(format!("change password error: {}", e));
@@ -160,22 +467,6 @@ impl Profile {
         }));
     }
 
-    fn update_profile(profile: Rc<Self>) {
-        profile.loader.load(clone!(profile => async move {
-            let telegram_chat_id = profile.telegram_chat_id.get_cloned().and_then(|telegram_chat_id| telegram_chat_id.parse().ok());
-            let pushover_user_key = profile.pushover_user_key.get_cloned();
-            let gotify_token = profile.gotify_token.get_cloned();
-            match query::update_profile(telegram_chat_id, pushover_user_key, gotify_token).await {
-                Ok(_) => {
-                    // routing::go_to_url(Route::Settings(SettingCategory::None).url().as_str());
-                },
-                Err(e) => {
-                    snackbar::show(format!("update profile error: {e}"));
-                }
-            };
-        }));
-    }
-
     pub fn render_change_password(profile: Rc<Self>) -> Dom {
         html!("form", {
             .style("display", "flex")
@@ -304,85 +595,343 @@ impl Profile {
                             }))
                         }),
                     ])
+                    .child_signal(profile.notification_channels.signal_vec_cloned().to_signal_map(|channels| channels.iter().any(|channel| channel == "telegram")).map(clone!(profile => move |has_telegram| has_telegram.then(|| html!("input", {
+                        .attr("type", "button")
+                        .attr("value", "Link Telegram")
+                        .event_with_options(&EventOptions::preventable(), clone!(profile => move |e: events::Click| {
+                            e.prevent_default();
+                            Self::create_telegram_link_code(profile.clone());
+                        }))
+                    })))))
                 }),
-                // Telegram
+            ])
+            .child_signal(profile.telegram_link_code.signal_cloned().map(|link_code| link_code.map(|link_code| html!("div", {
+                .style("display", "flex")
+                .style("flex-direction", "column")
+                .style("margin", "0.5rem 0.25rem")
+                .children(&mut [
+                    html!("span", {
+                        .text(&format!("Send /link {} to the telegram bot within 10 minutes", link_code.code))
+                    }),
+                ])
+                .apply(|dom| match link_code.bot_url {
+                    Some(bot_url) => dom.child(html!("a", {
+                        .attr("href", &bot_url)
+                        .attr("target", "_blank")
+                        .text("Open in Telegram")
+                    })),
+                    None => dom,
+                })
+            }))))
+            .children_signal_vec(profile.notification_targets.signal_vec_cloned().map(clone!(profile => move |target| html!("div", {
+                .style("display", "flex")
+                .style("align-items", "center")
+                .style("margin-bottom", "0.25rem")
+                .children(&mut [
+                    html!("span", {
+                        .style("min-width", "5rem")
+                        .style("margin-left", "0.25rem")
+                        .text(&target.channel)
+                    }),
+                    html!("span", {
+                        .style("width", "100%")
+                        .style("overflow", "hidden")
+                        .style("text-overflow", "ellipsis")
+                        .style("white-space", "nowrap")
+                        .text(&target.target)
+                    }),
+                    html!("input", {
+                        .attr("type", "button")
+                        .attr("value", "Test")
+                        .event_with_options(&EventOptions::preventable(), clone!(profile, target => move |e: events::Click| {
+                            e.prevent_default();
+                            Self::test_notification(profile.clone(), target.channel.clone(), target.target.clone());
+                        }))
+                    }),
+                    html!("input", {
+                        .style("color", "red")
+                        .attr("type", "button")
+                        .attr("value", "Remove")
+                        .event_with_options(&EventOptions::preventable(), clone!(profile, target => move |e: events::Click| {
+                            e.prevent_default();
+                            Self::remove_notification_target(profile.clone(), target.id);
+                        }))
+                    }),
+                ])
+            }))))
+            .children(&mut [
                 html!("div", {
                     .style("display", "flex")
+                    .style("margin-top", "0.5rem")
                     .children(&mut [
+                        html!("select" => HtmlSelectElement, {
+                            .children_signal_vec(profile.notification_channels.signal_vec_cloned().filter(|channel| channel != "telegram").map(clone!(profile => move |channel| html!("option", {
+                                .attr("value", &channel)
+                                .attr_signal("selected", profile.new_channel.signal_cloned().map(clone!(channel => move |selected| (selected == channel).then(|| ""))))
+                                .text(&channel)
+                            }))))
+                            .with_node!(select => {
+                                .event(clone!(profile => move |_: events::Change| {
+                                    profile.new_channel.set(select.value());
+                                }))
+                            })
+                        }),
                         html!("input" => HtmlInputElement, {
                             .style("width", "100%")
                             .attr("type", "text")
-                            .attr("placeholder", "Telegram chat id, get from telegram bot")
-                            .prop_signal("value", profile.telegram_chat_id.signal_cloned().map(|id| id.unwrap_or_else(|| "".to_string())))
+                            .attr_signal("placeholder", profile.new_channel.signal_cloned().map(|channel| target_placeholder(&channel)))
+                            .prop_signal("value", profile.new_target.signal_cloned())
                             .with_node!(input => {
                                 .event(clone!(profile => move |_: events::Input| {
-                                    profile.telegram_chat_id.set(Some(input.value()));
+                                    profile.new_target.set(input.value());
                                 }))
                             })
                         }),
                         html!("input", {
                             .attr("type", "button")
                             .attr("value", "Test")
-                            .text("Test Telegram")
                             .event_with_options(&EventOptions::preventable(), clone!(profile => move |e: events::Click| {
                                 e.prevent_default();
-                                Self::test_telegram(profile.clone());
+                                Self::test_notification(profile.clone(), profile.new_channel.get_cloned(), profile.new_target.get_cloned());
                             }))
                         }),
                     ])
                 }),
-                // Pushover
                 html!("div", {
                     .style("display", "flex")
+                    .style("justify-content", "flex-end")
+                    .style("margin-top", "0.5rem")
+                    .children(&mut [
+                        html!("input", {
+                            .attr("type", "submit")
+                            .attr("value", "Add")
+                            .event_with_options(&EventOptions::preventable(), clone!(profile => move |e: events::Click| {
+                                e.prevent_default();
+                                Self::add_notification_target(profile.clone());
+                            }))
+                        })
+                    ])
+                })
+            ])
+        })
+    }
+
+    fn render_api_key_setting(profile: Rc<Self>) -> Dom {
+        html!("form", {
+            .class("content")
+            .style("display", "flex")
+            .style("flex-direction", "column")
+            .style("max-width", "1024px")
+            .style("margin-left", "auto")
+            .style("margin-right", "auto")
+            .style("margin-bottom", "0.5rem")
+            .style("padding", "0.5rem")
+            .style("border-radius", "0.5rem")
+            .style("border", "var(--list-group-border)")
+            .children(&mut [
+                html!("span", {
+                    .style("margin-left", "0.25rem")
+                    .style("margin-bottom", "0.5rem")
+                    .text("API Keys")
+                }),
+            ])
+            .child_signal(profile.created_api_key.signal_cloned().map(|key| key.map(|key| html!("div", {
+                .style("display", "flex")
+                .style("flex-direction", "column")
+                .style("margin", "0.5rem 0.25rem")
+                .children(&mut [
+                    html!("span", {
+                        .text("Copy the key now, it will not be shown again")
+                    }),
+                    html!("code", {
+                        .style("word-break", "break-all")
+                        .text(&key)
+                    }),
+                ])
+            }))))
+            .children_signal_vec(profile.api_keys.signal_vec_cloned().map(clone!(profile => move |api_key| html!("div", {
+                .style("display", "flex")
+                .style("align-items", "center")
+                .style("margin-bottom", "0.25rem")
+                .children(&mut [
+                    html!("div", {
+                        .style("display", "flex")
+                        .style("flex-direction", "column")
+                        .style("width", "100%")
+                        .style("margin-left", "0.25rem")
+                        .children(&mut [
+                            html!("span", {
+                                .text(&format!("{} ({}...)", api_key.name, api_key.prefix))
+                            }),
+                            html!("span", {
+                                .style("font-size", "smaller")
+                                .text(&format!(
+                                    "{}, last used {}",
+                                    api_key.scopes.join(", "),
+                                    api_key.last_used_at.clone().unwrap_or_else(|| "never".to_string())
+                                ))
+                            }),
+                        ])
+                    }),
+                    html!("input", {
+                        .style("color", "red")
+                        .attr("type", "button")
+                        .attr("value", "Revoke")
+                        .event_with_options(&EventOptions::preventable(), clone!(profile, api_key => move |e: events::Click| {
+                            e.prevent_default();
+                            Self::revoke_api_key(profile.clone(), api_key.id);
+                        }))
+                    }),
+                ])
+            }))))
+            .children(&mut [
+                html!("input" => HtmlInputElement, {
+                    .style("margin-top", "0.5rem")
+                    .attr("type", "text")
+                    .attr("placeholder", "Name")
+                    .prop_signal("value", profile.new_api_key_name.signal_cloned())
+                    .with_node!(input => {
+                        .event(clone!(profile => move |_: events::Input| {
+                            profile.new_api_key_name.set(input.value());
+                        }))
+                    })
+                }),
+                html!("div", {
+                    .style("display", "flex")
+                    .style("margin-top", "0.5rem")
+                    .children(API_KEY_SCOPES.into_iter().map(|scope| html!("label", {
+                        .style("margin-right", "0.5rem")
+                        .children(&mut [
+                            html!("input" => HtmlInputElement, {
+                                .attr("type", "checkbox")
+                                .prop_signal("checked", profile.new_api_key_scopes.signal_ref(move |scopes| scopes.iter().any(|s| s == scope)))
+                                .with_node!(input => {
+                                    .event(clone!(profile => move |_: events::Change| {
+                                        let mut scopes = profile.new_api_key_scopes.lock_mut();
+                                        scopes.retain(|s| s != scope);
+                                        if input.checked() {
+                                            scopes.push(scope.to_string());
+                                        }
+                                    }))
+                                })
+                            }),
+                            html!("span", {
+                                .text(scope)
+                            }),
+                        ])
+                    })))
+                }),
+                html!("div", {
+                    .style("display", "flex")
+                    .style("justify-content", "flex-end")
+                    .style("margin-top", "0.5rem")
+                    .children(&mut [
+                        html!("input", {
+                            .attr("type", "submit")
+                            .attr("value", "Create")
+                            .event_with_options(&EventOptions::preventable(), clone!(profile => move |e: events::Click| {
+                                e.prevent_default();
+                                Self::create_api_key(profile.clone());
+                            }))
+                        })
+                    ])
+                })
+            ])
+        })
+    }
+
+    fn render_totp_code_input(profile: Rc<Self>, placeholder: &str) -> Dom {
+        html!("input" => HtmlInputElement, {
+            .style("margin-top", "0.5rem")
+            .attr("type", "text")
+            .attr("autocomplete", "one-time-code")
+            .attr("placeholder", placeholder)
+            .prop_signal("value", profile.totp_code.signal_cloned())
+            .with_node!(input => {
+                .event(clone!(profile => move |_: events::Input| {
+                    profile.totp_code.set(input.value());
+                }))
+            })
+        })
+    }
+
+    fn render_totp_enabled(profile: Rc<Self>) -> Dom {
+        html!("div", {
+            .style("display", "flex")
+            .style("flex-direction", "column")
+            .children(&mut [
+                html!("span", {
+                    .style("margin-left", "0.25rem")
+                    .text_signal(profile.totp_recovery_codes.signal().map(|count| format!("Enabled, {count} recovery codes left")))
+                }),
+                Self::render_totp_code_input(profile.clone(), "Code from authenticator app or recovery code"),
+                html!("div", {
+                    .style("display", "flex")
+                    .style("justify-content", "flex-end")
+                    .style("margin-top", "0.5rem")
                     .children(&mut [
-                        html!("input" => HtmlInputElement, {
-                            .style("width", "100%")
-                            .attr("type", "text")
-                            .attr("placeholder", "Pushover user key, get from pushover dashboard")
-                            .prop_signal("value", profile.pushover_user_key.signal_cloned().map(|id| id.unwrap_or_else(|| "".to_string())))
-                            .with_node!(input => {
-                                .event(clone!(profile => move |_: events::Input| {
-                                    profile.pushover_user_key.set(Some(input.value()));
-                                }))
-                            })
-                        }),
                         html!("input", {
                             .attr("type", "button")
-                            .attr("value", "Test")
-                            .text("Test Pushover")
+                            .attr("value", "Regenerate Recovery Codes")
                             .event_with_options(&EventOptions::preventable(), clone!(profile => move |e: events::Click| {
                                 e.prevent_default();
-                                Self::test_pushover(profile.clone());
+                                Self::regenerate_totp_recovery_codes(profile.clone());
                             }))
-                        }),
+                        })
                     ])
                 }),
-                // Gotify
+                html!("input" => HtmlInputElement, {
+                    .style("margin-top", "0.5rem")
+                    .attr("type", "password")
+                    .attr("placeholder", "Current Password")
+                    .attr("autocomplete", "current-password")
+                    .prop_signal("value", profile.totp_password.signal_cloned())
+                    .with_node!(input => {
+                        .event(clone!(profile => move |_: events::Input| {
+                            profile.totp_password.set(input.value());
+                        }))
+                    })
+                }),
                 html!("div", {
                     .style("display", "flex")
+                    .style("justify-content", "flex-end")
+                    .style("margin-top", "0.5rem")
                     .children(&mut [
-                        html!("input" => HtmlInputElement, {
-                            .style("width", "100%")
-                            .attr("type", "text")
-                            .attr("placeholder", "Gotify token, get from Gotify dashboard")
-                            .prop_signal("value", profile.gotify_token.signal_cloned().map(|id| id.unwrap_or_else(|| "".to_string())))
-                            .with_node!(input => {
-                                .event(clone!(profile => move |_: events::Input| {
-                                    profile.gotify_token.set(Some(input.value()));
-                                }))
-                            })
-                        }),
                         html!("input", {
+                            .style("color", "red")
                             .attr("type", "button")
-                            .attr("value", "Test")
-                            .text("Test Gotify")
+                            .attr("value", "Disable")
                             .event_with_options(&EventOptions::preventable(), clone!(profile => move |e: events::Click| {
                                 e.prevent_default();
-                                Self::test_gotify(profile.clone());
+                                Self::disable_totp(profile.clone());
                             }))
-                        }),
+                        })
                     ])
                 }),
+            ])
+        })
+    }
+
+    fn render_totp_setup(profile: Rc<Self>, setup: TotpSetup) -> Dom {
+        html!("div", {
+            .style("display", "flex")
+            .style("flex-direction", "column")
+            .children(&mut [
+                html!("span", {
+                    .style("margin-left", "0.25rem")
+                    .text("Add this account to your authenticator app with the link or secret, then enter the code it shows")
+                }),
+                html!("a", {
+                    .style("margin", "0.5rem 0.25rem 0")
+                    .attr("href", &setup.url)
+                    .text("Open in authenticator app")
+                }),
+                html!("code", {
+                    .style("margin", "0.5rem 0.25rem 0")
+                    .style("word-break", "break-all")
+                    .text(&setup.secret)
+                }),
+                Self::render_totp_code_input(profile.clone(), "Code from authenticator app"),
                 html!("div", {
                     .style("display", "flex")
                     .style("justify-content", "flex-end")
@@ -390,19 +939,19 @@ impl Profile {
                     .children(&mut [
                         html!("input", {
                             .attr("type", "submit")
-                            .text("Submit")
+                            .attr("value", "Verify")
                             .event_with_options(&EventOptions::preventable(), clone!(profile => move |e: events::Click| {
                                 e.prevent_default();
-                                Self::update_profile(profile.clone());
+                                Self::enable_totp(profile.clone());
                             }))
                         })
                     ])
-                })
+                }),
             ])
         })
     }
 
-    fn render_tracker_setting(profile: Rc<Self>) -> Dom {
+    fn render_totp_setting(profile: Rc<Self>) -> Dom {
         html!("form", {
             .class("content")
             .style("display", "flex")
@@ -418,87 +967,277 @@ impl Profile {
                 html!("span", {
                     .style("margin-left", "0.25rem")
                     .style("margin-bottom", "0.5rem")
-                    .text("Tracker")
+                    .text("Two-Factor Authentication")
+                }),
+            ])
+            .child_signal(profile.new_recovery_codes.signal_cloned().map(|codes| codes.map(|codes| html!("div", {
+                .style("display", "flex")
+                .style("flex-direction", "column")
+                .style("margin", "0.5rem 0.25rem")
+                .children(&mut [
+                    html!("span", {
+                        .text("Save these recovery codes now, each one can login once without authenticator app")
+                    }),
+                ])
+                .children(codes.iter().map(|code| html!("code", {
+                    .text(code)
+                })))
+            }))))
+            .child_signal(profile.totp_enabled.signal().map(clone!(profile => move |enabled| enabled.then(|| Self::render_totp_enabled(profile.clone())))))
+            .child_signal(map_ref! {
+                let enabled = profile.totp_enabled.signal(),
+                let setup = profile.totp_setup.signal_cloned() =>
+                match (enabled, setup) {
+                    (false, Some(setup)) => Some(Self::render_totp_setup(profile.clone(), setup.clone())),
+                    (false, None) => Some(html!("div", {
+                        .style("display", "flex")
+                        .style("justify-content", "flex-end")
+                        .children(&mut [
+                            html!("input", {
+                                .attr("type", "button")
+                                .attr("value", "Enable")
+                                .event_with_options(&EventOptions::preventable(), clone!(profile => move |e: events::Click| {
+                                    e.prevent_default();
+                                    Self::setup_totp(profile.clone());
+                                }))
+                            })
+                        ])
+                    })),
+                    _ => None,
+                }
+            })
+        })
+    }
+
+    fn render_notification_template_setting(profile: Rc<Self>) -> Dom {
+        html!("form", {
+            .class("content")
+            .style("display", "flex")
+            .style("flex-direction", "column")
+            .style("max-width", "1024px")
+            .style("margin-left", "auto")
+            .style("margin-right", "auto")
+            .style("margin-bottom", "0.5rem")
+            .style("padding", "0.5rem")
+            .style("border-radius", "0.5rem")
+            .style("border", "var(--list-group-border)")
+            .children(&mut [
+                html!("span", {
+                    .style("margin-left", "0.25rem")
+                    .style("margin-bottom", "0.5rem")
+                    .text("Notification Template")
+                }),
+                html!("span", {
+                    .style("margin-left", "0.25rem")
+                    .style("margin-bottom", "0.5rem")
+                    .style("font-size", "smaller")
+                    .text_signal(profile.notification_placeholders.signal_vec_cloned().to_signal_cloned().map(|placeholders| {
+                        let placeholders: Vec<String> = placeholders.iter().map(|placeholder| format!("{{{{{placeholder}}}}}")).collect();
+                        format!("Available placeholders: {}", placeholders.join(", "))
+                    }))
+                }),
+                html!("input" => HtmlInputElement, {
+                    .attr("type", "text")
+                    .attr("placeholder", "Title")
+                    .prop_signal("value", profile.template_title.signal_cloned())
+                    .with_node!(input => {
+                        .event(clone!(profile => move |_: events::Input| {
+                            profile.template_title.set(input.value());
+                        }))
+                    })
+                }),
+                html!("textarea" => HtmlTextAreaElement, {
+                    .attr("placeholder", "Body")
+                    .attr("rows", "3")
+                    .prop_signal("value", profile.template_body.signal_cloned())
+                    .with_node!(input => {
+                        .event(clone!(profile => move |_: events::Input| {
+                            profile.template_body.set(input.value());
+                        }))
+                    })
                 }),
                 html!("div", {
-                    .attr("id", "myanimelist")
                     .style("display", "flex")
-                    .style("margin-bottom", "0.5rem")
+                    .style("justify-content", "flex-end")
+                    .style("margin-top", "0.5rem")
+                    .child_signal(profile.template_is_default.signal().map(clone!(profile => move |is_default| (!is_default).then(|| html!("input", {
+                        .attr("type", "button")
+                        .attr("value", "Reset")
+                        .event_with_options(&EventOptions::preventable(), clone!(profile => move |e: events::Click| {
+                            e.prevent_default();
+                            Self::reset_notification_template(profile.clone());
+                        }))
+                    })))))
                     .children(&mut [
-                        html!("div", {
-                            .style("display", "flex")
-                            .style("align-items", "center")
-                            .style("width", "100%")
-                            .children(&mut [
-                                html!("img", {
-                                    .style("height", "20px")
-                                    .style("width", "20px")
-                                    .style("margin-right", "0.5rem")
-                                    .attr("src", "https://myanimelist.net/img/common/pwa/launcher-icon-0-75x.png")
-                                }),
-                                html!("span", {
-                                    .text("MyAnimeList")
-                                })
-                            ])
-                        }),
-                    ])
-                    .child_signal(profile.myanimelist_status.signal_cloned().map(clone!(profile => move |status| if status {
-                        Some(html!("button", {
-                            .style("color", "red")
-                            .text("Logout")
+                        html!("input", {
+                            .attr("type", "submit")
+                            .attr("value", "Save")
                             .event_with_options(&EventOptions::preventable(), clone!(profile => move |e: events::Click| {
                                 e.prevent_default();
-                                Self::tracker_logout(profile.clone(), "myanimelist".to_string());
+                                Self::update_notification_template(profile.clone());
                             }))
-                        }))
-                    } else {
-                        Some(html!("a", {
-                            .class("button")
-                            .attr("href", &Route::TrackerLogin("myanimelist".to_string()).url())
-                            .attr("target", "_blank")
-                            .text("Login")
-                        }))
-                    })))
-                }),
+                        })
+                    ])
+                })
+            ])
+        })
+    }
+
+    fn render_tracker(
+        profile: Rc<Self>,
+        tracker: &'static str,
+        title: &str,
+        icon: &str,
+        status: &Mutable<bool>,
+    ) -> Dom {
+        html!("div", {
+            .attr("id", tracker)
+            .style("display", "flex")
+            .style("margin-bottom", "0.5rem")
+            .children(&mut [
                 html!("div", {
-                    .attr("id", "anilist")
                     .style("display", "flex")
+                    .style("align-items", "center")
+                    .style("width", "100%")
+                    .children(&mut [
+                        html!("img", {
+                            .style("height", "20px")
+                            .style("width", "20px")
+                            .style("margin-right", "0.5rem")
+                            .attr("src", icon)
+                        }),
+                        html!("span", {
+                            .text(title)
+                        })
+                    ])
+                }),
+            ])
+            .child_signal(status.signal_cloned().map(clone!(profile => move |status| if status {
+                Some(html!("button", {
+                    .style("color", "red")
+                    .text("Logout")
+                    .event_with_options(&EventOptions::preventable(), clone!(profile => move |e: events::Click| {
+                        e.prevent_default();
+                        Self::tracker_logout(profile.clone(), tracker.to_string());
+                    }))
+                }))
+            } else {
+                Some(html!("a", {
+                    .class("button")
+                    .attr("href", &Route::TrackerLogin(tracker.to_string()).url())
+                    .attr("target", "_blank")
+                    .text("Login")
+                }))
+            })))
+        })
+    }
+
+    fn render_content_setting(profile: Rc<Self>) -> Dom {
+        html!("form", {
+            .class("content")
+            .style("display", "flex")
+            .style("flex-direction", "column")
+            .style("max-width", "1024px")
+            .style("margin-left", "auto")
+            .style("margin-right", "auto")
+            .style("margin-bottom", "0.5rem")
+            .style("padding", "0.5rem")
+            .style("border-radius", "0.5rem")
+            .style("border", "var(--list-group-border)")
+            .visible_signal(profile.can_browse_nsfw.signal())
+            .children(&mut [
+                html!("span", {
+                    .style("margin-left", "0.25rem")
                     .style("margin-bottom", "0.5rem")
+                    .text("Content")
+                }),
+                html!("label", {
+                    .style("margin-left", "0.25rem")
                     .children(&mut [
-                        html!("div", {
-                            .style("display", "flex")
-                            .style("align-items", "center")
-                            .style("width", "100%")
-                            .children(&mut [
-                                html!("img", {
-                                    .style("height", "20px")
-                                    .style("width", "20px")
-                                    .style("margin-right", "0.5rem")
-                                    .attr("src", "https://upload.wikimedia.org/wikipedia/commons/6/61/AniList_logo.svg")
-                                }),
-                                html!("span", {
-                                    .text("AniList")
-                                })
-                            ])
+                        html!("input" => HtmlInputElement, {
+                            .attr("type", "checkbox")
+                            .prop_signal("checked", profile.show_nsfw.signal())
+                            .with_node!(input => {
+                                .event(clone!(profile => move |_: events::Change| {
+                                    Self::update_show_nsfw(profile.clone(), input.checked());
+                                }))
+                            })
+                        }),
+                        html!("span", {
+                            .text("Show NSFW sources")
                         }),
                     ])
-                    .child_signal(profile.anilist_status.signal_cloned().map(clone!(profile => move |status| if status {
-                        Some(html!("button", {
-                            .style("color", "red")
-                            .text("Logout")
+                }),
+            ])
+        })
+    }
+
+    fn render_tracker_setting(profile: Rc<Self>) -> Dom {
+        html!("form", {
+            .class("content")
+            .style("display", "flex")
+            .style("flex-direction", "column")
+            .style("max-width", "1024px")
+            .style("margin-left", "auto")
+            .style("margin-right", "auto")
+            .style("margin-bottom", "0.5rem")
+            .style("padding", "0.5rem")
+            .style("border-radius", "0.5rem")
+            .style("border", "var(--list-group-border)")
+            .children(&mut [
+                html!("span", {
+                    .style("margin-left", "0.25rem")
+                    .style("margin-bottom", "0.5rem")
+                    .text("Tracker")
+                }),
+                Self::render_tracker(
+                    profile.clone(),
+                    "myanimelist",
+                    "MyAnimeList",
+                    "https://myanimelist.net/img/common/pwa/launcher-icon-0-75x.png",
+                    &profile.myanimelist_status,
+                ),
+                Self::render_tracker(
+                    profile.clone(),
+                    "anilist",
+                    "AniList",
+                    "https://upload.wikimedia.org/wikipedia/commons/6/61/AniList_logo.svg",
+                    &profile.anilist_status,
+                ),
+                Self::render_tracker(
+                    profile.clone(),
+                    "kitsu",
+                    "Kitsu",
+                    "https://kitsu.io/favicon.ico",
+                    &profile.kitsu_status,
+                ),
+                Self::render_tracker(
+                    profile.clone(),
+                    "mangaupdates",
+                    "MangaUpdates",
+                    "https://www.mangaupdates.com/favicon.ico",
+                    &profile.mangaupdates_status,
+                ),
+                Self::render_tracker(
+                    profile.clone(),
+                    "shikimori",
+                    "Shikimori",
+                    "https://shikimori.one/favicon.ico",
+                    &profile.shikimori_status,
+                ),
+                html!("div", {
+                    .style("display", "flex")
+                    .style("justify-content", "flex-end")
+                    .children(&mut [
+                        html!("input", {
+                            .attr("type", "button")
+                            .attr("value", "Sync Progress")
                             .event_with_options(&EventOptions::preventable(), clone!(profile => move |e: events::Click| {
                                 e.prevent_default();
-                                Self::tracker_logout(profile.clone(), "anilist".to_string());
+                                Self::sync_tracker_progress(profile.clone());
                             }))
-                        }))
-                    } else {
-                        Some(html!("a", {
-                            .class("button")
-                            .attr("href", &Route::TrackerLogin("anilist".to_string()).url())
-                            .attr("target", "_blank")
-                            .text("Login")
-                        }))
-                    })))
+                        })
+                    ])
                 }),
             ])
         })
@@ -506,19 +1245,30 @@ impl Profile {
 
     pub fn render(profile: Rc<Self>) -> Dom {
         Self::fetch_me(profile.clone());
+        Self::fetch_notification_targets(profile.clone());
+        Self::fetch_api_keys(profile.clone());
 
         html!("div", {
             .children(&mut [
                 Self::render_change_password(profile.clone()),
+                Self::render_totp_setting(profile.clone()),
                 Self::render_notification_setting(profile.clone()),
-                Self::render_tracker_setting(profile),
+                Self::render_notification_template_setting(profile.clone()),
+                Self::render_content_setting(profile.clone()),
+                Self::render_tracker_setting(profile.clone()),
+                Self::render_api_key_setting(profile),
                 html!("div", {
                     .style("max-width", "1024px")
                     .style("margin-left", "auto")
                     .style("margin-right", "auto")
                     .event(|_: events::Click| {
-                        local_storage().delete("token").unwrap_throw();
-                        routing::go_to_url("/login");
+                        spawn_local(async {
+                            if let Err(e) = query::user_logout().await {
+                                error!("failed to logout: {e}");
+                            }
+                            query::clear_token();
+                            routing::go_to_url("/login");
+                        });
                     })
                     .children(&mut [
                         html!("button", {
@@ -24,6 +24,11 @@ pub enum SettingCategory {
 pub enum Route {
     Root,
     Login,
+    Invite(String),
+    OidcRedirect {
+        code: String,
+        state: String,
+    },
     LibraryList,
     Library(Option<i64>),
     CatalogueList,
@@ -57,7 +62,17 @@ impl Route {
                 paths.retain(|path| !path.is_empty());
 
                 match paths.as_slice() {
-                    ["login"] => Route::Login,
+                    ["login"] => match url.search_params().get("invite") {
+                        Some(code) => Route::Invite(code),
+                        None => Route::Login,
+                    },
+                    ["login", "oidc"] => {
+                        let params = url.search_params();
+                        match (params.get("code"), params.get("state")) {
+                            (Some(code), Some(state)) => Route::OidcRedirect { code, state },
+                            _ => Route::Login,
+                        }
+                    }
                     [] => Route::Root,
                     ["libraries"] => Route::LibraryList,
                     ["library"] => Route::Library(None),
@@ -160,7 +175,7 @@ impl Route {
                                     state,
                                 }
                             }
-                            "anilist" if code.is_some() && state.is_some() => {
+                            "anilist" | "shikimori" if code.is_some() && state.is_some() => {
                                 Route::TrackerRedirect {
                                     tracker: tracker.to_string(),
                                     code: code.unwrap(),
@@ -179,6 +194,8 @@ impl Route {
         match self {
             Route::Root => "/".to_string(),
             Route::Login => "/login".to_string(),
+            Route::Invite(code) => format!("/login?invite={code}"),
+            Route::OidcRedirect { code, state } => format!("/login/oidc?code={code}&state={state}"),
             Route::LibraryList => "/libraries".to_string(),
             Route::Library(category_id) => {
                 if let Some(id) = category_id {
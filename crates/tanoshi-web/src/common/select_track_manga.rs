@@ -290,6 +290,9 @@ impl SelectTrackMangaModal {
                                         .attr("src", match tracker.tracker.as_str() {
                                              "myanimelist" => "https://myanimelist.net/img/common/pwa/launcher-icon-0-75x.png",
                                              "anilist" => "https://upload.wikimedia.org/wikipedia/commons/6/61/AniList_logo.svg",
+                                             "kitsu" => "https://kitsu.io/favicon.ico",
+                                             "mangaupdates" => "https://www.mangaupdates.com/favicon.ico",
+                                             "shikimori" => "https://shikimori.one/favicon.ico",
                                              _ => ""
                                         })
                                     }),
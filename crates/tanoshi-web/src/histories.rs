@@ -2,7 +2,7 @@ use std::rc::Rc;
 
 use crate::common::snackbar;
 use crate::query;
-use crate::utils::{is_tauri_signal, proxied_image_url, AsyncLoader};
+use crate::utils::{is_tauri_signal, resized_image_url, AsyncLoader, COVER_WIDTH};
 use crate::{
     app::App,
     common::{Route, Spinner},
@@ -100,7 +100,7 @@ impl Histories {
                                 .class("update-item-thumbnail")
                                 .children(&mut [
                                     html!("img", {
-                                        .attr("src", &proxied_image_url(&entry.cover_url))
+                                        .attr("src", &resized_image_url(&entry.cover_url, COVER_WIDTH))
                                     })
                                 ])
                             }),
@@ -16,6 +16,7 @@ mod reader;
 mod settings;
 mod settings_categories;
 mod settings_download_queue;
+mod settings_invites;
 mod settings_manage_downloads;
 mod settings_source;
 mod tracker_login;
@@ -2,6 +2,7 @@ use std::rc::Rc;
 
 use dominator::{clone, html, Dom, EventOptions};
 use dominator::{routing, with_node};
+use futures_signals::map_ref;
 use futures_signals::signal::Mutable;
 use futures_signals::signal::SignalExt;
 use wasm_bindgen::UnwrapThrowExt;
@@ -10,12 +11,14 @@ use web_sys::HtmlInputElement;
 use crate::app::App;
 use crate::common::{events, snackbar, Route};
 use crate::query;
-use crate::utils::local_storage;
-use crate::utils::AsyncLoader;
+use crate::utils::{session_storage, window, AsyncLoader};
 
 pub struct Login {
     username: Mutable<String>,
     password: Mutable<String>,
+    totp_code: Mutable<String>,
+    /// `Some` when registering with an invite code instead of login
+    invite_code: Mutable<Option<String>>,
     loader: AsyncLoader,
 }
 
@@ -24,22 +27,80 @@ impl Login {
         Rc::new(Self {
             username: Mutable::new("".to_string()),
             password: Mutable::new("".to_string()),
+            totp_code: Mutable::new("".to_string()),
+            invite_code: Mutable::new(None),
             loader: AsyncLoader::new(),
         })
     }
 
+    pub fn with_invite(code: String) -> Rc<Self> {
+        let login = Self::new();
+        login.invite_code.set(Some(code));
+        login
+    }
+
     pub fn login(login: Rc<Self>, app: Rc<App>) {
         let username = login.username.get_cloned();
         let password = login.password.get_cloned();
+        let totp_code = Some(login.totp_code.get_cloned()).filter(|code| !code.is_empty());
+        login.loader.load(async move {
+            match query::user_login(username, password, totp_code).await {
+                Ok(token) => {
+                    query::store_token(&token.access_token, &token.refresh_token, token.expires_in);
+                    routing::go_to_url(&Route::Root.url());
+                    App::fetch_server_status(app);
+                }
+                Err(e) => {
+                    snackbar::show(format!("Login failed: {}", e));
+                }
+            }
+        });
+    }
+
+    pub fn oidc_login_start(login: Rc<Self>) {
+        login.loader.load(async move {
+            match query::oidc_login_start().await {
+                Ok(session) => {
+                    // server keeps pkce code verifier, state is kept to check
+                    // the redirect belongs to login started in this browser
+                    session_storage()
+                        .set("oidc-csrf-state", &session.csrf_state)
+                        .unwrap_throw();
+                    window()
+                        .location()
+                        .replace(&session.authorize_url)
+                        .unwrap_throw();
+                }
+                Err(e) => {
+                    snackbar::show(format!("Login failed: {}", e));
+                }
+            }
+        });
+    }
+
+    pub fn oidc_login_end(login: Rc<Self>, app: Rc<App>, code: String, state: String) {
         login.loader.load(async move {
-            match query::user_login(username, password).await {
+            let session_storage = session_storage();
+            let csrf_state = session_storage
+                .get("oidc-csrf-state")
+                .unwrap_throw()
+                .unwrap_or_default();
+            session_storage.delete("oidc-csrf-state").unwrap_throw();
+            if csrf_state.is_empty() || csrf_state != state {
+                snackbar::show("Login failed: state mismatch, please login again".to_string());
+                routing::go_to_url(&Route::Login.url());
+                return;
+            }
+
+            match query::oidc_login_end(code, state).await {
                 Ok(token) => {
-                    local_storage().set("token", &token).unwrap_throw();
+                    query::store_token(&token.access_token, &token.refresh_token, token.expires_in);
                     routing::go_to_url(&Route::Root.url());
                     App::fetch_server_status(app);
                 }
                 Err(e) => {
                     snackbar::show(format!("Login failed: {}", e));
+                    routing::go_to_url(&Route::Login.url());
                 }
             }
         });
@@ -62,6 +123,30 @@ impl Login {
         }));
     }
 
+    pub fn register_with_invite(login: Rc<Self>, app: Rc<App>) {
+        let code = login.invite_code.get_cloned().unwrap_or_default();
+        let username = login.username.get_cloned();
+        let password = login.password.get_cloned();
+        login.loader.load(clone!(login => async move {
+            if let Err(e) = query::register_with_invite(code, username.clone(), password.clone()).await {
+                snackbar::show(format!("Register failed: {}", e));
+                return;
+            }
+
+            login.invite_code.set(None);
+            match query::user_login(username, password, None).await {
+                Ok(token) => {
+                    query::store_token(&token.access_token, &token.refresh_token, token.expires_in);
+                    routing::go_to_url(&Route::Root.url());
+                    App::fetch_server_status(app);
+                }
+                Err(e) => {
+                    snackbar::show(format!("Login failed: {}", e));
+                }
+            }
+        }));
+    }
+
     pub fn render_topbar(_login: Rc<Self>) -> Dom {
         html!("div", {
             .class("topbar")
@@ -133,12 +218,53 @@ impl Login {
                                 }))
                             })
                         }),
+                        html!("input" => HtmlInputElement, {
+                            .visible_signal(login.invite_code.signal_ref(|code| code.is_some()))
+                            .attr("type", "text")
+                            .attr("placeholder", "Invite code")
+                            .prop_signal("value", login.invite_code.signal_cloned().map(|code| code.unwrap_or_default()))
+                            .with_node!(input => {
+                                .event(clone!(login => move |_: events::Input| {
+                                    login.invite_code.set(Some(input.value()));
+                                }))
+                            })
+                        }),
+                        html!("input" => HtmlInputElement, {
+                            .visible_signal(map_ref! {
+                                let status = app.server_status.signal_cloned(),
+                                let invite = login.invite_code.signal_ref(|code| code.is_some()) =>
+
+                                status.as_ref().map(|status| status.activated).unwrap_or(false) && !invite
+                            })
+                            .attr("type", "text")
+                            .attr("autocomplete", "one-time-code")
+                            .attr("placeholder", "Two-factor code, if enabled")
+                            .prop_signal("value", login.totp_code.signal_cloned())
+                            .with_node!(input => {
+                                .event(clone!(login => move |_: events::Input| {
+                                    login.totp_code.set(input.value());
+                                }))
+                            })
+                        }),
                         html!("div", {
                             .style("display", "flex")
                             .style("justify-content", "flex-end")
-                            .child_signal(app.server_status.signal_cloned().map(clone!(login, app => move |x| {
+                            .child_signal(map_ref! {
+                                let status = app.server_status.signal_cloned(),
+                                let invite = login.invite_code.signal_ref(|code| code.is_some()) =>
+
+                                (status.clone(), *invite)
+                            }.map(clone!(login, app => move |(x, invite)| {
                                 if let Some(x) = x {
-                                    if x.activated {
+                                    if x.activated && invite {
+                                        Some(html!("button", {
+                                            .text("Register")
+                                            .event_with_options(&EventOptions::preventable(), clone!(login, app => move |e: events::Click| {
+                                                e.prevent_default();
+                                                Self::register_with_invite(login.clone(), app.clone());
+                                            }))
+                                        }))
+                                    } else if x.activated {
                                         Some(html!("button", {
                                             .text("Login")
                                             .event_with_options(&EventOptions::preventable(), clone!(login, app => move |e: events::Click| {
@@ -163,6 +289,40 @@ impl Login {
                     ])
                 })
             ])
+            .child_signal(app.server_status.signal_cloned().map(clone!(login => move |status| {
+                if status.filter(|status| status.activated).is_none() {
+                    return None;
+                }
+
+                Some(html!("button", {
+                    .style("margin", "0.5rem")
+                    .text_signal(login.invite_code.signal_ref(|code| {
+                        if code.is_some() {
+                            "Back to login"
+                        } else {
+                            "Have an invite code?"
+                        }
+                    }))
+                    .event(clone!(login => move |_: events::Click| {
+                        let mut invite_code = login.invite_code.lock_mut();
+                        *invite_code = if invite_code.is_some() {
+                            None
+                        } else {
+                            Some("".to_string())
+                        };
+                    }))
+                }))
+            })))
+            .child_signal(app.server_status.signal_cloned().map(clone!(login => move |status| {
+                let provider = status.filter(|status| status.activated)?.oidc_provider?;
+                Some(html!("button", {
+                    .style("margin", "0.5rem")
+                    .text(&format!("Login with {provider}"))
+                    .event(clone!(login => move |_: events::Click| {
+                        Self::oidc_login_start(login.clone());
+                    }))
+                }))
+            })))
         })
     }
 
@@ -3,7 +3,7 @@ use crate::{
         ChapterSettings, ChapterSort, Filter,  Order, Route, Sort, Spinner, snackbar, SelectCategoryModal, SelectTrackMangaModal, TrackerStatus, icons
     }, 
     query, 
-    utils::{AsyncLoader, proxied_image_url, window}
+    utils::{AsyncLoader, resized_image_url, window, COVER_WIDTH}
 };
 use chrono::NaiveDateTime;
 use dominator::{Dom, EventOptions, clone, events, html, routing, svg, with_node, text_signal};
@@ -498,7 +498,7 @@ impl Manga {
                                     .style("border-radius", "0.375rem")
                                     .style("width", "8rem")
                                     .style("height", "auto")
-                                    .attr("src", &proxied_image_url(&cover_url))
+                                    .attr("src", &resized_image_url(&cover_url, COVER_WIDTH))
                                 }))
                             }))
                         }),
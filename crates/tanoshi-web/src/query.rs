@@ -13,16 +13,91 @@ use crate::{
 
 use tanoshi_schema::*;
 
+/// Access token is refreshed when it expires within this many milliseconds
+const TOKEN_REFRESH_MARGIN: f64 = 60_000.0;
+
 async fn post_graphql<Q>(var: Q::Variables) -> Result<Q::ResponseData, Box<dyn std::error::Error>>
 where
     Q: GraphQLQuery,
 {
-    let url = graphql_host();
+    let token = access_token().await;
 
-    let token = local_storage()
+    post_graphql_with_token::<Q>(var, &token).await
+}
+
+/// Access token from storage, renewed with refresh token first if it is about to expire
+async fn access_token() -> String {
+    let storage = local_storage();
+    let token = storage
         .get("token")
         .unwrap_throw()
         .unwrap_or_else(|| "".to_string());
+
+    let refresh_token = match storage.get("refresh_token").unwrap_throw() {
+        Some(refresh_token) => refresh_token,
+        None => return token,
+    };
+
+    let expires_at = storage
+        .get("token_expires_at")
+        .unwrap_throw()
+        .and_then(|expires_at| expires_at.parse::<f64>().ok())
+        .unwrap_or(0.0);
+    if expires_at - js_sys::Date::now() > TOKEN_REFRESH_MARGIN {
+        return token;
+    }
+
+    let var = refresh_token::Variables {
+        refresh_token: refresh_token.clone(),
+    };
+    match post_graphql_with_token::<RefreshToken>(var, "").await {
+        Ok(data) => {
+            let token = data.refresh_token;
+            store_token(&token.access_token, &token.refresh_token, token.expires_in);
+            token.access_token
+        }
+        Err(e) => {
+            // another tab may have just used the same refresh token
+            if storage.get("refresh_token").unwrap_throw().as_ref() != Some(&refresh_token) {
+                return storage
+                    .get("token")
+                    .unwrap_throw()
+                    .unwrap_or_else(|| "".to_string());
+            }
+
+            error!("failed to refresh token: {e}");
+            token
+        }
+    }
+}
+
+pub fn store_token(access_token: &str, refresh_token: &str, expires_in: i64) {
+    let expires_at = js_sys::Date::now() + (expires_in * 1000) as f64;
+
+    let storage = local_storage();
+    storage.set("token", access_token).unwrap_throw();
+    storage.set("refresh_token", refresh_token).unwrap_throw();
+    storage
+        .set("token_expires_at", &expires_at.to_string())
+        .unwrap_throw();
+}
+
+pub fn clear_token() {
+    let storage = local_storage();
+    storage.delete("token").unwrap_throw();
+    storage.delete("refresh_token").unwrap_throw();
+    storage.delete("token_expires_at").unwrap_throw();
+}
+
+async fn post_graphql_with_token<Q>(
+    var: Q::Variables,
+    token: &str,
+) -> Result<Q::ResponseData, Box<dyn std::error::Error>>
+where
+    Q: GraphQLQuery,
+{
+    let url = graphql_host();
+
     let request_body = Q::build_query(var);
 
     let client = reqwest::Client::new();
@@ -259,10 +334,7 @@ pub async fn subscribe_recent_updates() -> Result<(), Box<dyn Error>> {
             .await?;
     let (sink, stream) = graphql_ws_client::wasm_websocket_combined_split(ws, wsio).await;
 
-    let token = local_storage()
-        .get("token")
-        .unwrap_throw()
-        .unwrap_or_else(|| "".to_string());
+    let token = access_token().await;
     let mut client = GraphQLClientClientBuilder::new()
         .payload(Payload { token })
         .build(stream, sink, async_executors::AsyncStd)
@@ -377,14 +449,44 @@ pub async fn uninstall_source(source_id: i64) -> Result<i64, Box<dyn Error>> {
     Ok(data.uninstall_source)
 }
 
-pub async fn user_login(username: String, password: String) -> Result<String, Box<dyn Error>> {
+pub async fn user_login(
+    username: String,
+    password: String,
+    totp_code: Option<String>,
+) -> Result<user_login::UserLoginLogin, Box<dyn Error>> {
     let var = user_login::Variables {
-        login: user_login::LoginInput { username, password },
+        login: user_login::LoginInput {
+            username,
+            password,
+            totp_code,
+        },
     };
     let data = post_graphql::<UserLogin>(var).await?;
     Ok(data.login)
 }
 
+pub async fn oidc_login_start(
+) -> Result<oidc_login_start::OidcLoginStartOidcLoginStart, Box<dyn Error>> {
+    let var = oidc_login_start::Variables {};
+    let data = post_graphql::<OidcLoginStart>(var).await?;
+    Ok(data.oidc_login_start)
+}
+
+pub async fn oidc_login_end(
+    code: String,
+    state: String,
+) -> Result<oidc_login_end::OidcLoginEndOidcLoginEnd, Box<dyn Error>> {
+    let var = oidc_login_end::Variables { code, state };
+    let data = post_graphql::<OidcLoginEnd>(var).await?;
+    Ok(data.oidc_login_end)
+}
+
+pub async fn user_logout() -> Result<(), Box<dyn Error>> {
+    let var = logout::Variables {};
+    let _ = post_graphql::<Logout>(var).await?;
+    Ok(())
+}
+
 pub async fn fetch_users() -> Result<
     (
         fetch_user_list::FetchUserListMe,
@@ -409,19 +511,116 @@ pub async fn user_register(
     is_admin: bool,
 ) -> Result<(), Box<dyn Error>> {
     let var = user_register::Variables {
-        login: user_register::LoginInput { username, password },
+        login: user_register::LoginInput {
+            username,
+            password,
+            totp_code: None,
+        },
         is_admin,
     };
     let _ = post_graphql::<UserRegister>(var).await?;
     Ok(())
 }
 
+pub async fn register_with_invite(
+    code: String,
+    username: String,
+    password: String,
+) -> Result<(), Box<dyn Error>> {
+    let var = register_with_invite::Variables {
+        code,
+        login: register_with_invite::LoginInput {
+            username,
+            password,
+            totp_code: None,
+        },
+    };
+    let _ = post_graphql::<RegisterWithInvite>(var).await?;
+    Ok(())
+}
+
+pub async fn fetch_invites() -> Result<Vec<fetch_invites::FetchInvitesInvites>, Box<dyn Error>> {
+    let var = fetch_invites::Variables {};
+    let data = post_graphql::<FetchInvites>(var).await?;
+    Ok(data.invites)
+}
+
+pub async fn create_invite(
+    is_admin: bool,
+    permissions: Vec<String>,
+    max_uses: Option<i64>,
+    expires_in_days: Option<i64>,
+) -> Result<create_invite::CreateInviteCreateInvite, Box<dyn Error>> {
+    let var = create_invite::Variables {
+        is_admin,
+        permissions: Some(permissions),
+        max_uses,
+        expires_in_days,
+    };
+    let data = post_graphql::<CreateInvite>(var).await?;
+    Ok(data.create_invite)
+}
+
+pub async fn delete_invite(id: i64) -> Result<(), Box<dyn Error>> {
+    let var = delete_invite::Variables { id };
+    let _ = post_graphql::<DeleteInvite>(var).await?;
+    Ok(())
+}
+
 pub async fn delete_user(user_id: i64) -> Result<(), Box<dyn Error>> {
     let var = delete_user::Variables { user_id };
     let _ = post_graphql::<DeleteUser>(var).await?;
     Ok(())
 }
 
+pub async fn update_user_permissions(
+    user_id: i64,
+    permissions: Vec<String>,
+) -> Result<(), Box<dyn Error>> {
+    let var = update_user_permissions::Variables {
+        user_id,
+        permissions,
+    };
+    let _ = post_graphql::<UpdateUserPermissions>(var).await?;
+    Ok(())
+}
+
+pub async fn update_show_nsfw(show_nsfw: bool) -> Result<(), Box<dyn Error>> {
+    let var = update_show_nsfw::Variables { show_nsfw };
+    let _ = post_graphql::<UpdateShowNsfw>(var).await?;
+    Ok(())
+}
+
+pub async fn setup_totp() -> Result<setup_totp::SetupTotpSetupTotp, Box<dyn Error>> {
+    let var = setup_totp::Variables {};
+    let data = post_graphql::<SetupTotp>(var).await?;
+    Ok(data.setup_totp)
+}
+
+pub async fn enable_totp(code: String) -> Result<Vec<String>, Box<dyn Error>> {
+    let var = enable_totp::Variables { code };
+    let data = post_graphql::<EnableTotp>(var).await?;
+    Ok(data.enable_totp)
+}
+
+pub async fn regenerate_totp_recovery_codes(code: String) -> Result<Vec<String>, Box<dyn Error>> {
+    let var = regenerate_totp_recovery_codes::Variables { code };
+    let data = post_graphql::<RegenerateTotpRecoveryCodes>(var).await?;
+    Ok(data.regenerate_totp_recovery_codes)
+}
+
+pub async fn disable_totp(password: String) -> Result<(), Box<dyn Error>> {
+    let var = disable_totp::Variables { password };
+    let _ = post_graphql::<DisableTotp>(var).await?;
+    Ok(())
+}
+
+pub async fn reset_user_totp(user_id: i64) -> Result<(), Box<dyn Error>> {
+    let var = reset_user_totp::Variables { user_id };
+    let _ = post_graphql::<ResetUserTotp>(var).await?;
+    Ok(())
+}
+
 pub async fn change_password(
     old_password: String,
     new_password: String,
@@ -436,50 +635,79 @@ pub async fn change_password(
     Ok(())
 }
 
-pub async fn update_profile(
-    telegram_chat_id: Option<i64>,
-    pushover_user_key: Option<String>,
-    gotify_token: Option<String>,
+pub async fn fetch_notification_targets(
+) -> Result<fetch_notification_targets::ResponseData, Box<dyn Error>> {
+    let var = fetch_notification_targets::Variables {};
+    let data = post_graphql::<FetchNotificationTargets>(var).await?;
+    Ok(data)
+}
+
+pub async fn add_notification_target(
+    channel: String,
+    target: String,
 ) -> Result<(), Box<dyn Error>> {
-    let var = update_profile::Variables {
-        input: update_profile::ProfileInput {
-            telegram_chat_id,
-            pushover_user_key,
-            gotify_token,
-        },
-    };
-    let _ = post_graphql::<UpdateProfile>(var).await?;
+    let var = add_notification_target::Variables { channel, target };
+    let _ = post_graphql::<AddNotificationTarget>(var).await?;
     Ok(())
 }
 
-pub async fn fetch_server_status(
-) -> Result<fetch_server_status::FetchServerStatusServerStatus, Box<dyn Error>> {
-    let var = fetch_server_status::Variables {};
-    let data = post_graphql::<FetchServerStatus>(var).await?;
-    Ok(data.server_status)
+pub async fn update_notification_template(
+    title: String,
+    body: String,
+) -> Result<(), Box<dyn Error>> {
+    let var = update_notification_template::Variables { title, body };
+    let _ = post_graphql::<UpdateNotificationTemplate>(var).await?;
+    Ok(())
 }
 
-pub async fn test_telegram(chat_id: i64) -> Result<(), Box<dyn Error>> {
-    let var = test_telegram::Variables {
-        chat_id: Some(chat_id),
-    };
-    let _ = post_graphql::<TestTelegram>(var).await?;
+pub async fn reset_notification_template() -> Result<(), Box<dyn Error>> {
+    let var = reset_notification_template::Variables {};
+    let _ = post_graphql::<ResetNotificationTemplate>(var).await?;
     Ok(())
 }
 
-pub async fn test_pushover(user_key: &str) -> Result<(), Box<dyn Error>> {
-    let var = test_pushover::Variables {
-        user_key: Some(user_key.to_string()),
-    };
-    let _ = post_graphql::<TestPushover>(var).await?;
+pub async fn create_telegram_link_code(
+) -> Result<create_telegram_link_code::CreateTelegramLinkCodeCreateTelegramLinkCode, Box<dyn Error>>
+{
+    let var = create_telegram_link_code::Variables {};
+    let data = post_graphql::<CreateTelegramLinkCode>(var).await?;
+    Ok(data.create_telegram_link_code)
+}
+
+pub async fn remove_notification_target(id: i64) -> Result<(), Box<dyn Error>> {
+    let var = remove_notification_target::Variables { id };
+    let _ = post_graphql::<RemoveNotificationTarget>(var).await?;
     Ok(())
 }
 
-pub async fn test_gotify(token: &str) -> Result<(), Box<dyn Error>> {
-    let var = test_gotify::Variables {
-        token: Some(token.to_string()),
-    };
-    let _ = post_graphql::<TestGotify>(var).await?;
+pub async fn fetch_api_keys() -> Result<Vec<fetch_api_keys::FetchApiKeysApiKeys>, Box<dyn Error>> {
+    let var = fetch_api_keys::Variables {};
+    let data = post_graphql::<FetchApiKeys>(var).await?;
+    Ok(data.api_keys)
+}
+
+pub async fn create_api_key(name: String, scopes: Vec<String>) -> Result<String, Box<dyn Error>> {
+    let var = create_api_key::Variables { name, scopes };
+    let data = post_graphql::<CreateApiKey>(var).await?;
+    Ok(data.create_api_key.key)
+}
+
+pub async fn revoke_api_key(id: i64) -> Result<(), Box<dyn Error>> {
+    let var = revoke_api_key::Variables { id };
+    let _ = post_graphql::<RevokeApiKey>(var).await?;
+    Ok(())
+}
+
+pub async fn fetch_server_status(
+) -> Result<fetch_server_status::FetchServerStatusServerStatus, Box<dyn Error>> {
+    let var = fetch_server_status::Variables {};
+    let data = post_graphql::<FetchServerStatus>(var).await?;
+    Ok(data.server_status)
+}
+
+pub async fn test_notification(channel: String, target: String) -> Result<(), Box<dyn Error>> {
+    let var = test_notification::Variables { channel, target };
+    let _ = post_graphql::<TestNotification>(var).await?;
     Ok(())
 }
 
@@ -610,6 +838,12 @@ pub async fn tracker_logout(tracker: String) -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+pub async fn sync_tracker_progress(wait: bool) -> Result<(), Box<dyn Error>> {
+    let var = sync_tracker_progress::Variables { wait };
+    let _ = post_graphql::<SyncTrackerProgress>(var).await?;
+    Ok(())
+}
+
 pub async fn anilist_login_start(
 ) -> Result<anilist_login_start::AnilistLoginStartAnilistLoginStart, Box<dyn Error>> {
     let var = anilist_login_start::Variables {};
@@ -623,6 +857,31 @@ pub async fn anilist_login_end(code: String) -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+pub async fn shikimori_login_start(
+) -> Result<shikimori_login_start::ShikimoriLoginStartShikimoriLoginStart, Box<dyn Error>> {
+    let var = shikimori_login_start::Variables {};
+    let data = post_graphql::<ShikimoriLoginStart>(var).await?;
+    Ok(data.shikimori_login_start)
+}
+
+pub async fn shikimori_login_end(code: String) -> Result<(), Box<dyn Error>> {
+    let var = shikimori_login_end::Variables { code };
+    let _ = post_graphql::<ShikimoriLoginEnd>(var).await?;
+    Ok(())
+}
+
+pub async fn kitsu_login(username: String, password: String) -> Result<(), Box<dyn Error>> {
+    let var = kitsu_login::Variables { username, password };
+    let _ = post_graphql::<KitsuLogin>(var).await?;
+    Ok(())
+}
+
+pub async fn mangaupdates_login(username: String, password: String) -> Result<(), Box<dyn Error>> {
+    let var = mangaupdates_login::Variables { username, password };
+    let _ = post_graphql::<MangaupdatesLogin>(var).await?;
+    Ok(())
+}
+
 pub async fn search_tracker_manga(
     tracker: String,
     title: String,
@@ -1,7 +1,7 @@
 use std::rc::Rc;
 
 use crate::common::{Fit, ReaderSettings, Spinner, events, snackbar};
-use crate::utils::{document, proxied_image_url, window, AsyncLoader, body};
+use crate::utils::{document, reader_image_width, resized_image_url, window, AsyncLoader, body};
 use crate::{
     common::{Background, Direction, DisplayMode, ReaderMode},
     query,
@@ -701,7 +701,7 @@ impl Reader {
     fn image_src_signal(&self, index: usize, preload_prev: usize, preload_next: usize, page: String, status: PageStatus)-> impl Signal<Item = Option<String>> {
         self.current_page.signal_cloned().map(move |current_page| {
             if (index >= current_page.saturating_sub(preload_prev) && index <= current_page + preload_next) || matches!(status, PageStatus::Loaded) {
-                Some(proxied_image_url(&page))
+                Some(resized_image_url(&page, reader_image_width()))
             } else {
                 None
             }
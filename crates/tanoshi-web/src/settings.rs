@@ -16,6 +16,7 @@ use crate::{
     query, 
     settings_categories::SettingsCategories, 
     settings_download_queue::SettingsDownloads, 
+    settings_invites::SettingsInvites, 
     utils::{AsyncLoader, is_tauri, window}, settings_source::SettingsSource
 };
 use dominator::svg;
@@ -24,6 +25,14 @@ use futures_signals::{signal::{Mutable, SignalExt, self}, signal_vec::{MutableSi
 use std::rc::Rc;
 
 
+/// Permissions admins can grant to other users, with their labels
+pub(crate) const USER_PERMISSIONS: [(&str, &str); 4] = [
+    ("manage_sources", "Manage sources"),
+    ("manage_users", "Manage users"),
+    ("download", "Download"),
+    ("browse_nsfw", "Browse NSFW"),
+];
+
 #[derive(Debug, Clone, Copy)]
 enum State {
     Null,
@@ -110,14 +119,16 @@ impl Settings {
                         id: result.0.id,
                         username: result.0.username,
                         is_admin: result.0.is_admin,
-                        telegram_chat_id: result.0.telegram_chat_id
+                        permissions: result.0.permissions,
+                        totp_enabled: result.0.totp_enabled
                     }));
 
                     settings.users.lock_mut().replace_cloned(result.1.iter().map(|u| User{
                         id: u.id,
                         username: u.username.clone(),
                         is_admin: u.is_admin,
-                        telegram_chat_id: None
+                        permissions: u.permissions.clone(),
+                        totp_enabled: u.totp_enabled
                     }).collect());
                 },
                 Err(err) => {
@@ -142,6 +153,33 @@ impl Settings {
         }));
     }
 
+    fn update_user_permissions(settings: Rc<Self>, user_id: i64, permissions: Vec<String>) {
+        settings.loader.load(clone!(settings => async move {
+            match query::update_user_permissions(user_id, permissions).await {
+                Ok(_) => {
+                    Self::fetch_user_list(settings.clone());
+                },
+                Err(err) => {
+                    snackbar::show(format!("{}", err));
+                }
+            }
+        }));
+    }
+
+    fn reset_user_totp(settings: Rc<Self>, user_id: i64) {
+        settings.loader.load(clone!(settings => async move {
+            match query::reset_user_totp(user_id).await {
+                Ok(_) => {
+                    snackbar::show("Two-factor authentication reset".to_string());
+                    Self::fetch_user_list(settings.clone());
+                },
+                Err(err) => {
+                    snackbar::show(format!("{}", err));
+                }
+            }
+        }));
+    }
+
     fn fetch_me(settings: Rc<Self>) {
         settings.loader.load(clone!(settings => async move {
             match query::fetch_me().await {
@@ -150,7 +188,8 @@ impl Settings {
                         id: result.id,
                         username: result.username,
                         is_admin: result.is_admin,
-                        telegram_chat_id: result.telegram_chat_id
+                        permissions: result.permissions,
+                        totp_enabled: result.totp_enabled
                     }))
                 },
                 Err(err) => {
@@ -415,7 +454,7 @@ impl Settings {
             ])
             .child_signal(settings.me.signal_cloned().map(|me| {
                 if let Some(me) = me {
-                    if me.is_admin {
+                    if me.has_permission("manage_users") {
                         Some(link!(Route::Settings(SettingCategory::Users).url(), {
                             .class("list-item")
                             .text("Users")
@@ -429,7 +468,7 @@ impl Settings {
             }))
             .child_signal(settings.me.signal_cloned().map(|me| {
                 if let Some(me) = me {
-                    if me.is_admin {
+                    if me.has_permission("download") {
                         Some(link!(Route::Settings(SettingCategory::DownloadQueue).url(), {
                             .class("list-item")
                             .text("Download Queue")
@@ -479,7 +518,7 @@ impl Settings {
     pub fn render_users_management(settings: Rc<Self>) -> Dom {
         html!("ul", {
             .class(["list", "group"])
-            .visible_signal(settings.me.signal_cloned().map(|me| me.map(|me| me.is_admin).unwrap_or(false)))
+            .visible_signal(settings.me.signal_cloned().map(|me| me.map(|me| me.has_permission("manage_users")).unwrap_or(false)))
             .children_signal_vec(settings.users.signal_vec_cloned().map(clone!(settings => move |user|
                 html!("li", {
                     .class("list-item")
@@ -496,6 +535,15 @@ impl Settings {
                                     .text(&user.username)
                                 }),
                             ])
+                            .child_signal(settings.me.signal_cloned().map(clone!(settings, user => move |me| (!user.is_admin && me.map(|me| me.id != user.id).unwrap_or(false)).then(|| Self::render_user_permissions(settings.clone(), user.clone())))))
+                            .child_signal(settings.me.signal_cloned().map(clone!(settings, user => move |me| (user.totp_enabled && me.map(|me| me.id != user.id && (me.is_admin || !user.is_admin)).unwrap_or(false)).then(|| html!("button", {
+                                .style("margin-right", "0.5rem")
+                                .style("font-size", "smaller")
+                                .text("Reset 2FA")
+                                .event(clone!(settings, user => move |_: events::Click| {
+                                    Self::reset_user_totp(settings.clone(), user.id);
+                                }))
+                            })))))
                             .child_signal(signal::always(user.is_admin).map(|is_admin| is_admin.then(|| html!("div", {
                                 .style("display", "flex")
                                 .style("align-items", "center")
@@ -589,6 +637,34 @@ impl Settings {
         })
     }
 
+    fn render_user_permissions(settings: Rc<Self>, user: User) -> Dom {
+        html!("div", {
+            .style("display", "flex")
+            .style("flex-wrap", "wrap")
+            .style("align-items", "center")
+            .children(USER_PERMISSIONS.into_iter().map(|(permission, label)| html!("label", {
+                .style("margin-right", "0.5rem")
+                .style("font-size", "smaller")
+                .children(&mut [
+                    html!("input", {
+                        .attr("type", "checkbox")
+                        .prop("checked", user.permissions.iter().any(|p| p == permission))
+                        .event(clone!(settings, user => move |e: events::Change| {
+                            let mut permissions: Vec<String> = user.permissions.iter().filter(|p| p.as_str() != permission).cloned().collect();
+                            if e.checked().unwrap_or(false) {
+                                permissions.push(permission.to_string());
+                            }
+                            Self::update_user_permissions(settings.clone(), user.id, permissions);
+                        }))
+                    }),
+                    html!("span", {
+                        .text(label)
+                    }),
+                ])
+            })))
+        })
+    }
+
     pub fn render_user(settings: Rc<Self>) -> Dom {
         link!(Route::Settings(SettingCategory::User).url(), {
             .class("me")
@@ -793,7 +869,12 @@ impl Settings {
                         ])
                     })),
                     SettingCategory::Source(source_id) => Some(SettingsSource::render(Rc::new(SettingsSource::new(source_id)))),
-                    SettingCategory::Users => Some(Self::render_users_management(settings.clone())),
+                    SettingCategory::Users => Some(html!("div", {
+                        .children(&mut [
+                            Self::render_users_management(settings.clone()),
+                        ])
+                        .child_signal(settings.me.signal_cloned().map(|me| me.filter(|me| me.has_permission("manage_users")).map(|me| SettingsInvites::render(SettingsInvites::new(me.is_admin)))))
+                    })),
                     SettingCategory::User => Some(Profile::render(Profile::new())),
                     SettingCategory::CreateUser => Some(Login::render(Login::new())),
                     SettingCategory::DownloadQueue => Some(SettingsDownloads::render(SettingsDownloads::new())),
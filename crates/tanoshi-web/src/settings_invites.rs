@@ -0,0 +1,264 @@
+use std::rc::Rc;
+
+use dominator::{clone, html, with_node, Dom, EventOptions};
+use futures_signals::signal::{Mutable, SignalExt};
+use futures_signals::signal_vec::{MutableVec, SignalVecExt};
+use wasm_bindgen::UnwrapThrowExt;
+use web_sys::HtmlInputElement;
+
+use crate::common::{events, snackbar, Route};
+use crate::query;
+use crate::settings::USER_PERMISSIONS;
+use crate::utils::{window, AsyncLoader};
+
+#[derive(Debug, Clone)]
+struct Invite {
+    id: i64,
+    code: String,
+    is_admin: bool,
+    permissions: Vec<String>,
+    max_uses: Option<i64>,
+    uses: i64,
+    expires_at: Option<String>,
+}
+
+pub struct SettingsInvites {
+    /// only admins create invites for admins
+    can_invite_admin: bool,
+    invites: MutableVec<Invite>,
+    is_admin: Mutable<bool>,
+    permissions: Mutable<Vec<String>>,
+    max_uses: Mutable<String>,
+    expires_in_days: Mutable<String>,
+    loader: AsyncLoader,
+}
+
+impl SettingsInvites {
+    pub fn new(can_invite_admin: bool) -> Rc<Self> {
+        Rc::new(Self {
+            can_invite_admin,
+            invites: MutableVec::new(),
+            is_admin: Mutable::new(false),
+            permissions: Mutable::new(vec!["browse_nsfw".to_string()]),
+            max_uses: Mutable::new("1".to_string()),
+            expires_in_days: Mutable::new("7".to_string()),
+            loader: AsyncLoader::new(),
+        })
+    }
+
+    fn fetch_invites(settings: Rc<Self>) {
+        settings.loader.load(clone!(settings => async move {
+            match query::fetch_invites().await {
+                Ok(result) => {
+                    settings.invites.lock_mut().replace_cloned(result.into_iter().map(|invite| Invite {
+                        id: invite.id,
+                        code: invite.code,
+                        is_admin: invite.is_admin,
+                        permissions: invite.permissions,
+                        max_uses: invite.max_uses,
+                        uses: invite.uses,
+                        expires_at: invite.expires_at,
+                    }).collect());
+                }
+                Err(err) => {
+                    snackbar::show(format!("{}", err));
+                }
+            }
+        }));
+    }
+
+    fn create_invite(settings: Rc<Self>) {
+        let is_admin = settings.is_admin.get();
+        let permissions = settings.permissions.get_cloned();
+        // empty or invalid means unlimited
+        let max_uses = settings.max_uses.get_cloned().trim().parse().ok();
+        let expires_in_days = settings.expires_in_days.get_cloned().trim().parse().ok();
+
+        settings.loader.load(clone!(settings => async move {
+            match query::create_invite(is_admin, permissions, max_uses, expires_in_days).await {
+                Ok(_) => Self::fetch_invites(settings),
+                Err(e) => {
+                    snackbar::show(format!("create invite error: {e}"));
+                }
+            }
+        }));
+    }
+
+    fn delete_invite(settings: Rc<Self>, id: i64) {
+        settings.loader.load(clone!(settings => async move {
+            match query::delete_invite(id).await {
+                Ok(_) => Self::fetch_invites(settings),
+                Err(e) => {
+                    snackbar::show(format!("delete invite error: {e}"));
+                }
+            }
+        }));
+    }
+
+    fn invite_url(code: &str) -> String {
+        let origin = window().location().origin().unwrap_throw();
+        format!("{origin}{}", Route::Invite(code.to_string()).url())
+    }
+
+    fn render_invite(settings: Rc<Self>, invite: Invite) -> Dom {
+        let uses = match invite.max_uses {
+            Some(max_uses) => format!("{}/{max_uses} uses", invite.uses),
+            None => format!("{} uses", invite.uses),
+        };
+        let expires = invite
+            .expires_at
+            .as_ref()
+            .map(|expires_at| format!("expires {expires_at}"))
+            .unwrap_or_else(|| "never expires".to_string());
+        let role = if invite.is_admin {
+            "admin".to_string()
+        } else {
+            invite.permissions.join(", ")
+        };
+
+        html!("div", {
+            .style("display", "flex")
+            .style("align-items", "center")
+            .style("margin-bottom", "0.25rem")
+            .children(&mut [
+                html!("div", {
+                    .style("display", "flex")
+                    .style("flex-direction", "column")
+                    .style("width", "100%")
+                    .style("margin-left", "0.25rem")
+                    .children(&mut [
+                        html!("code", {
+                            .style("word-break", "break-all")
+                            .text(&Self::invite_url(&invite.code))
+                        }),
+                        html!("span", {
+                            .style("font-size", "smaller")
+                            .text(&format!("{role}, {uses}, {expires}"))
+                        }),
+                    ])
+                }),
+                html!("input", {
+                    .style("color", "red")
+                    .attr("type", "button")
+                    .attr("value", "Delete")
+                    .event_with_options(&EventOptions::preventable(), clone!(settings => move |e: events::Click| {
+                        e.prevent_default();
+                        Self::delete_invite(settings.clone(), invite.id);
+                    }))
+                }),
+            ])
+        })
+    }
+
+    pub fn render(settings: Rc<Self>) -> Dom {
+        Self::fetch_invites(settings.clone());
+
+        html!("form", {
+            .class("content")
+            .style("display", "flex")
+            .style("flex-direction", "column")
+            .style("margin-top", "0.5rem")
+            .style("padding", "0.5rem")
+            .style("border-radius", "0.5rem")
+            .style("border", "var(--list-group-border)")
+            .children(&mut [
+                html!("span", {
+                    .style("margin-left", "0.25rem")
+                    .style("margin-bottom", "0.5rem")
+                    .text("Invites")
+                }),
+            ])
+            .children_signal_vec(settings.invites.signal_vec_cloned().map(clone!(settings => move |invite| Self::render_invite(settings.clone(), invite))))
+            .children(&mut [
+                html!("div", {
+                    .style("display", "flex")
+                    .style("margin-top", "0.5rem")
+                    .children(&mut [
+                        html!("input" => HtmlInputElement, {
+                            .style("width", "100%")
+                            .attr("type", "number")
+                            .attr("min", "1")
+                            .attr("placeholder", "Max uses, empty for unlimited")
+                            .prop_signal("value", settings.max_uses.signal_cloned())
+                            .with_node!(input => {
+                                .event(clone!(settings => move |_: events::Input| {
+                                    settings.max_uses.set(input.value());
+                                }))
+                            })
+                        }),
+                        html!("input" => HtmlInputElement, {
+                            .style("width", "100%")
+                            .attr("type", "number")
+                            .attr("min", "1")
+                            .attr("placeholder", "Expires in days, empty for never")
+                            .prop_signal("value", settings.expires_in_days.signal_cloned())
+                            .with_node!(input => {
+                                .event(clone!(settings => move |_: events::Input| {
+                                    settings.expires_in_days.set(input.value());
+                                }))
+                            })
+                        }),
+                    ])
+                }),
+                html!("div", {
+                    .style("display", "flex")
+                    .style("flex-wrap", "wrap")
+                    .style("margin-top", "0.5rem")
+                    .children(USER_PERMISSIONS.into_iter().map(|(permission, label)| html!("label", {
+                        .style("margin-right", "0.5rem")
+                        .children(&mut [
+                            html!("input" => HtmlInputElement, {
+                                .attr("type", "checkbox")
+                                .prop_signal("checked", settings.permissions.signal_ref(move |permissions| permissions.iter().any(|p| p == permission)))
+                                .with_node!(input => {
+                                    .event(clone!(settings => move |_: events::Change| {
+                                        let mut permissions = settings.permissions.lock_mut();
+                                        permissions.retain(|p| p != permission);
+                                        if input.checked() {
+                                            permissions.push(permission.to_string());
+                                        }
+                                    }))
+                                })
+                            }),
+                            html!("span", {
+                                .text(label)
+                            }),
+                        ])
+                    })))
+                    .apply_if(settings.can_invite_admin, |dom| dom.child(html!("label", {
+                        .style("margin-right", "0.5rem")
+                        .children(&mut [
+                            html!("input" => HtmlInputElement, {
+                                .attr("type", "checkbox")
+                                .prop_signal("checked", settings.is_admin.signal())
+                                .with_node!(input => {
+                                    .event(clone!(settings => move |_: events::Change| {
+                                        settings.is_admin.set(input.checked());
+                                    }))
+                                })
+                            }),
+                            html!("span", {
+                                .text("Admin")
+                            }),
+                        ])
+                    })))
+                }),
+                html!("div", {
+                    .style("display", "flex")
+                    .style("justify-content", "flex-end")
+                    .style("margin-top", "0.5rem")
+                    .children(&mut [
+                        html!("input", {
+                            .attr("type", "submit")
+                            .attr("value", "Create Invite")
+                            .event_with_options(&EventOptions::preventable(), clone!(settings => move |e: events::Click| {
+                                e.prevent_default();
+                                Self::create_invite(settings.clone());
+                            }))
+                        }),
+                    ])
+                }),
+            ])
+        })
+    }
+}
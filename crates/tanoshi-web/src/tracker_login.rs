@@ -1,16 +1,29 @@
 use std::sync::Arc;
 
-use dominator::{html, Dom};
+use dominator::{clone, html, with_node, Dom, EventOptions};
+use futures_signals::signal::{Mutable, SignalExt};
 use wasm_bindgen::UnwrapThrowExt;
+use web_sys::HtmlInputElement;
 
 use crate::{
-    common::snackbar,
+    common::{events, snackbar},
     query,
     utils::{session_storage, window, AsyncLoader},
 };
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum LoginState {
+    Idle,
+    LoggingIn,
+    Success,
+    Failed(String),
+}
+
 pub struct TrackerLogin {
     tracker: String,
+    username: Mutable<String>,
+    password: Mutable<String>,
+    login_state: Mutable<LoginState>,
     loader: AsyncLoader,
 }
 
@@ -18,10 +31,18 @@ impl TrackerLogin {
     pub fn new(tracker: String) -> Arc<Self> {
         Arc::new(Self {
             tracker,
+            username: Mutable::new("".to_string()),
+            password: Mutable::new("".to_string()),
+            login_state: Mutable::new(LoginState::Idle),
             loader: AsyncLoader::new(),
         })
     }
 
+    /// Trackers without oauth redirect, user login with their tracker username and password
+    fn is_password_login(&self) -> bool {
+        matches!(self.tracker.as_str(), "kitsu" | "mangaupdates")
+    }
+
     fn fetch_myanimelist_login_start(self: Arc<Self>) {
         self.loader.load(async move {
             match query::myanimelist_login_start().await {
@@ -63,10 +84,125 @@ impl TrackerLogin {
         });
     }
 
+    fn fetch_shikimori_login_start(self: Arc<Self>) {
+        self.loader.load(async move {
+            match query::shikimori_login_start().await {
+                Ok(session) => {
+                    window()
+                        .location()
+                        .replace(&session.authorize_url)
+                        .unwrap_throw();
+                }
+                Err(e) => {
+                    snackbar::show(format!("error redirecting: {e}"));
+                }
+            }
+        });
+    }
+
+    fn password_login(self: Arc<Self>) {
+        let username = self.username.get_cloned();
+        let password = self.password.get_cloned();
+        let tracker_login = self.clone();
+        self.loader.load(async move {
+            tracker_login.login_state.set_neq(LoginState::LoggingIn);
+
+            let res = match tracker_login.tracker.as_str() {
+                "kitsu" => query::kitsu_login(username, password).await,
+                "mangaupdates" => query::mangaupdates_login(username, password).await,
+                _ => return,
+            };
+
+            match res {
+                Ok(()) => tracker_login.login_state.set_neq(LoginState::Success),
+                Err(e) => tracker_login
+                    .login_state
+                    .set_neq(LoginState::Failed(format!("{e}"))),
+            }
+        });
+    }
+
+    fn render_password_login(self: Arc<Self>) -> Dom {
+        let tracker_login = self;
+        html!("div", {
+            .class("content")
+            .style("display", "flex")
+            .style("flex-direction", "column")
+            .style("max-width", "1024px")
+            .style("margin", "auto")
+            .style("padding", "0.5rem")
+            .children(&mut [
+                html!("div", {
+                    .style("padding", "0.5rem")
+                    .style("margin", "0.5rem")
+                    .style("text-align", "center")
+                    .text_signal(tracker_login.login_state.signal_cloned().map({
+                        let tracker = tracker_login.tracker.clone();
+                        move |state| match state {
+                            LoginState::Idle => format!("Login to {tracker}"),
+                            LoginState::LoggingIn => format!("Logging in to {tracker}..."),
+                            LoginState::Success => format!("Login {tracker} success. You can close this window"),
+                            LoginState::Failed(e) => format!("Login {tracker} failed: {e}"),
+                        }
+                    }))
+                }),
+                html!("form", {
+                    .style("display", "flex")
+                    .style("flex-direction", "column")
+                    .event_with_options(&EventOptions::preventable(), |e: events::KeyDown| {
+                        if e.key() == "enter" {
+                            e.prevent_default();
+                        }
+                    })
+                    .children(&mut [
+                        html!("input" => HtmlInputElement, {
+                            .attr("type", "username")
+                            .attr("placeholder", "Username")
+                            .prop_signal("value", tracker_login.username.signal_cloned())
+                            .with_node!(input => {
+                                .event(clone!(tracker_login => move |_: events::Input| {
+                                    tracker_login.username.set(input.value());
+                                }))
+                            })
+                        }),
+                        html!("input" => HtmlInputElement, {
+                            .attr("type", "password")
+                            .attr("placeholder", "Password")
+                            .prop_signal("value", tracker_login.password.signal_cloned())
+                            .with_node!(input => {
+                                .event(clone!(tracker_login => move |_: events::Input| {
+                                    tracker_login.password.set(input.value());
+                                }))
+                            })
+                        }),
+                        html!("div", {
+                            .style("display", "flex")
+                            .style("justify-content", "flex-end")
+                            .children(&mut [
+                                html!("button", {
+                                    .text("Login")
+                                    .event_with_options(&EventOptions::preventable(), clone!(tracker_login => move |e: events::Click| {
+                                        e.prevent_default();
+                                        tracker_login.clone().password_login();
+                                    }))
+                                })
+                            ])
+                        })
+                    ])
+                })
+            ])
+        })
+    }
+
     pub fn render(self: Arc<Self>) -> Dom {
+        if self.is_password_login() {
+            return self.render_password_login();
+        }
+
         match self.tracker.as_str() {
             "myanimelist" => self.clone().fetch_myanimelist_login_start(),
             "anilist" => self.clone().fetch_anilist_login_start(),
+            "shikimori" => self.clone().fetch_shikimori_login_start(),
             _ => {}
         }
 
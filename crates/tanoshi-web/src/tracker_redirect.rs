@@ -92,10 +92,30 @@ impl TrackerRedirect {
         });
     }
 
+    fn fetch_shikimori_login_end(self: Arc<Self>) {
+        let code = self.code.clone();
+        let tracker_redirect = self.clone();
+        self.loader.load(async move {
+            tracker_redirect
+                .authorization_state
+                .set_neq(AuthorizationState::Authorizing);
+
+            match query::shikimori_login_end(code).await {
+                Ok(()) => tracker_redirect
+                    .authorization_state
+                    .set_neq(AuthorizationState::Success),
+                Err(e) => tracker_redirect
+                    .authorization_state
+                    .set_neq(AuthorizationState::Failed(format!("{e}"))),
+            }
+        });
+    }
+
     pub fn render(self: Arc<Self>) -> Dom {
         match self.tracker.as_str() {
             "myanimelist" => self.clone().fetch_myanimelist_login_end(),
             "anilist" => self.clone().fetch_anilist_login_end(),
+            "shikimori" => self.clone().fetch_shikimori_login_end(),
             _ => {}
         }
 
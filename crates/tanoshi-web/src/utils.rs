@@ -102,10 +102,38 @@ impl AsyncLoader {
     }
 }
 
+/// Width of cover images requested for grids and lists
+pub const COVER_WIDTH: u32 = 320;
+const MAX_IMAGE_WIDTH: u32 = 2048;
+
 pub fn proxied_image_url(image_url: &str) -> String {
     format!("{}/{}", image_proxy_host(), image_url)
 }
 
+/// Image url downscaled to width and converted to webp by server
+pub fn resized_image_url(image_url: &str, width: u32) -> String {
+    let separator = if image_url.contains('?') { '&' } else { '?' };
+    format!(
+        "{}{}width={}&format=webp",
+        proxied_image_url(image_url),
+        separator,
+        width
+    )
+}
+
+/// Screen width in device pixels, rounded up so similar screens share cached images
+pub fn reader_image_width() -> u32 {
+    let window = window();
+    let width = window
+        .inner_width()
+        .ok()
+        .and_then(|width| width.as_f64())
+        .unwrap_or(1080.0)
+        * window.device_pixel_ratio();
+
+    ((width / 256.0).ceil() as u32 * 256).min(MAX_IMAGE_WIDTH)
+}
+
 pub fn initialize_urls() {
     match js_sys::eval("window.__TAURI__") {
         Ok(val) if !val.is_undefined() => {
@@ -2,14 +2,19 @@
 extern crate log;
 extern crate argon2;
 
+use std::sync::Arc;
+
 use clap::Parser;
 use futures::future::OptionFuture;
 use tanoshi::{
     application::worker,
-    domain::services::{
-        chapter::ChapterService, download::DownloadService, history::HistoryService,
-        image::ImageService, library::LibraryService, manga::MangaService, source::SourceService,
-        tracker::TrackerService, user::UserService,
+    domain::{
+        entities::notification::NotificationTemplate,
+        services::{
+            chapter::ChapterService, download::DownloadService, history::HistoryService,
+            image::ImageService, library::LibraryService, manga::MangaService,
+            source::SourceService, tracker::TrackerService, user::UserService,
+        },
     },
     infrastructure::{
         config::{self, Config},
@@ -23,10 +28,13 @@ use tanoshi::{
         },
         local, notification,
     },
-    presentation::{graphql::loader::DatabaseLoader, ServerBuilder},
+    presentation::{graphql::loader::DatabaseLoader, telegram::TelegramBotHandler, ServerBuilder},
 };
-use tanoshi_notifier::{gotify::Gotify, pushover::Pushover, telegram::Telegram};
-use tanoshi_tracker::{AniList, MyAnimeList};
+use tanoshi_notifier::{
+    discord::Discord, email::Email, gotify::Gotify, matrix::Matrix, ntfy::Ntfy, pushover::Pushover,
+    telegram::Telegram, webhook::Webhook,
+};
+use tanoshi_tracker::{AniList, Kitsu, MangaUpdates, MyAnimeList, Shikimori};
 use tanoshi_vm::{extension::ExtensionManager, prelude::Source};
 
 #[derive(Parser)]
@@ -78,7 +86,13 @@ async fn main() -> Result<(), anyhow::Error> {
     let libary_svc = LibraryService::new(library_repo.clone());
 
     let history_repo = HistoryRepositoryImpl::new(pool.clone());
-    let history_svc = HistoryService::new(chapter_repo.clone(), history_repo.clone());
+    let (tracker_sync_sender, tracker_sync_receiver) = worker::tracker::channel();
+
+    let history_svc = HistoryService::new(
+        chapter_repo.clone(),
+        history_repo.clone(),
+        tracker_sync_sender.clone(),
+    );
 
     match &config.local_path {
         config::LocalFolders::Single(local_path) => {
@@ -107,10 +121,11 @@ async fn main() -> Result<(), anyhow::Error> {
 
     let mut notifier_builder = notification::Builder::new(user_repo.clone());
 
-    let mut telegram_bot_fut: OptionFuture<_> = None.into();
-    if let Some(telegram_config) = config.telegram.clone() {
-        let bot = Telegram::new(telegram_config.token);
-        telegram_bot_fut = Some(tanoshi_notifier::telegram::run(bot.clone())).into();
+    let telegram_bot = config
+        .telegram
+        .as_ref()
+        .map(|telegram_config| Telegram::new(telegram_config.token.clone()));
+    if let Some(bot) = telegram_bot.clone() {
         notifier_builder = notifier_builder.telegram(bot);
     }
 
@@ -123,12 +138,64 @@ async fn main() -> Result<(), anyhow::Error> {
         notifier_builder = notifier_builder.gotify(Gotify::new(gotify_cfg.base_url.clone()));
     }
 
+    // users choose where these post to, so they are only enabled by config
+    if let Some(webhook_cfg) = config.webhook.as_ref() {
+        notifier_builder = notifier_builder.webhook(Webhook::new(
+            webhook_cfg.payload_template.clone(),
+            webhook_cfg.allow_private_addresses,
+        ));
+    }
+
+    if let Some(discord_cfg) = config.discord.as_ref() {
+        notifier_builder =
+            notifier_builder.discord(Discord::new(discord_cfg.allow_private_addresses));
+    }
+
+    if let Some(ntfy_cfg) = config.ntfy.as_ref() {
+        notifier_builder = notifier_builder.ntfy(Ntfy::new(
+            ntfy_cfg.base_url.clone(),
+            ntfy_cfg.token.clone(),
+            ntfy_cfg.allow_private_addresses,
+        ));
+    }
+
+    if let Some(matrix_cfg) = config.matrix.as_ref() {
+        notifier_builder = notifier_builder.matrix(Matrix::new(
+            &matrix_cfg.homeserver_url,
+            matrix_cfg.access_token.clone(),
+        )?);
+    }
+
+    if let Some(email_cfg) = config.email.as_ref() {
+        notifier_builder = notifier_builder.email(Email::new(
+            &email_cfg.host,
+            email_cfg.port,
+            email_cfg.username.clone(),
+            email_cfg.password.clone(),
+            &email_cfg.from,
+            email_cfg.starttls,
+        )?);
+    }
+
+    if let Some(template_cfg) = config.notification_template.as_ref() {
+        notifier_builder = notifier_builder.template(NotificationTemplate {
+            title: template_cfg.title.clone(),
+            body: template_cfg.body.clone(),
+        });
+    }
+
     if let Some(base_url) = config.base_url.as_ref() {
         notifier_builder = notifier_builder.base_url(base_url.clone());
     }
 
     let notifier = notifier_builder.finish();
 
+    let image_cache_repo = ImageCacheRepositoryImpl::new(
+        &config.cache_path,
+        config.image_cache.max_size * 1024 * 1024,
+        config.image_cache.max_age,
+    );
+
     let (chapter_update_receiver, chapter_update_command_tx, update_worker_handle) =
         worker::updates::start(
             config.update_interval,
@@ -138,7 +205,8 @@ async fn main() -> Result<(), anyhow::Error> {
             extension_manager.clone(),
             notifier.clone(),
             config.extension_repository.clone(),
-            &config.cache_path,
+            config.update_check.clone(),
+            image_cache_repo.clone(),
         );
 
     let (download_sender, download_receiver) = worker::downloads::channel();
@@ -159,6 +227,26 @@ async fn main() -> Result<(), anyhow::Error> {
         config.auto_download_chapters,
     );
 
+    let telegram_bot_fut: OptionFuture<_> = telegram_bot
+        .map(|bot| {
+            let handler = TelegramBotHandler::new(
+                UserService::new(user_repo.clone()),
+                LibraryService::new(library_repo.clone()),
+                HistoryService::new(
+                    chapter_repo.clone(),
+                    history_repo.clone(),
+                    tracker_sync_sender.clone(),
+                ),
+                DownloadService::new(download_repo.clone(), download_sender.clone()),
+                source_svc.clone(),
+                chapter_update_command_tx.clone(),
+                &config.download_path,
+            );
+
+            tanoshi_notifier::telegram::run(bot, Arc::new(handler))
+        })
+        .into();
+
     let mal_client = if let Some(mal_cfg) = config.myanimelist.as_ref() {
         if let Some(base_url) = config.base_url.as_ref() {
             MyAnimeList::new(
@@ -193,13 +281,63 @@ async fn main() -> Result<(), anyhow::Error> {
         None
     };
 
-    let tracker_repo = TrackerRepositoryImpl::new(pool.clone(), mal_client.clone(), al_client);
+    let kitsu_client = config
+        .kitsu
+        .as_ref()
+        .map(|kitsu_cfg| Kitsu::new(kitsu_cfg.client_id.clone(), kitsu_cfg.client_secret.clone()));
+
+    let mangaupdates_client = config
+        .mangaupdates
+        .as_ref()
+        .map(|mu_cfg| MangaUpdates::new(&mu_cfg.api_url));
+
+    let shikimori_client = if let Some(shikimori_cfg) = config.shikimori.as_ref() {
+        if let Some(base_url) = config.base_url.as_ref() {
+            Shikimori::new(
+                base_url,
+                shikimori_cfg.client_id.clone(),
+                shikimori_cfg.client_secret.clone(),
+            )
+            .ok()
+        } else {
+            return Err(anyhow::anyhow!(
+                "Invalid config: Shikimori tracker needs base_url to login"
+            ));
+        }
+    } else {
+        None
+    };
+
+    let tracker_repo = TrackerRepositoryImpl::new(
+        pool.clone(),
+        mal_client.clone(),
+        al_client,
+        kitsu_client,
+        mangaupdates_client,
+        shikimori_client,
+    );
     let tracker_svc = TrackerService::new(tracker_repo.clone());
 
+    let tracker_worker_handle = worker::tracker::start(
+        chapter_repo.clone(),
+        history_repo.clone(),
+        manga_repo.clone(),
+        tracker_repo.clone(),
+        extension_manager.clone(),
+        notifier.clone(),
+        config.tracker_sync.clone(),
+        tracker_sync_receiver,
+    );
+
     let image_repo = ImageRepositoryImpl::new();
-    let image_cache_repo = ImageCacheRepositoryImpl::new(&config.cache_path);
     let image_svc = ImageService::new(image_repo, image_cache_repo);
 
+    let (prefetch_sender, prefetch_worker_handle) = worker::prefetch::start(
+        chapter_repo.clone(),
+        image_svc.clone(),
+        extension_manager.clone(),
+    );
+
     let loader = DatabaseLoader::new(
         history_repo,
         library_repo,
@@ -224,6 +362,8 @@ async fn main() -> Result<(), anyhow::Error> {
         .with_notifier(notifier)
         .with_chapter_update_receiver(chapter_update_receiver)
         .with_chapter_update_command_tx(chapter_update_command_tx)
+        .with_tracker_sync_tx(tracker_sync_sender)
+        .with_prefetch_tx(prefetch_sender)
         .with_loader(loader);
 
     if config.enable_playground {
@@ -242,6 +382,12 @@ async fn main() -> Result<(), anyhow::Error> {
         _ = download_worker_handle => {
             info!("download worker quit");
         }
+        _ = tracker_worker_handle => {
+            info!("tracker worker quit");
+        }
+        _ = prefetch_worker_handle => {
+            info!("prefetch worker quit");
+        }
         Some(_) = telegram_bot_fut => {
             info!("worker shutdown");
         }
@@ -0,0 +1,24 @@
+CREATE TABLE notification_target (
+    id INTEGER PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    channel VARCHAR(32) NOT NULL,
+    target TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE(user_id, channel, target),
+    FOREIGN KEY (user_id) REFERENCES user(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_notification_target_user_id ON notification_target(user_id);
+
+INSERT INTO notification_target(user_id, channel, target)
+SELECT id, 'telegram', CAST(telegram_chat_id AS TEXT) FROM "user" WHERE telegram_chat_id IS NOT NULL;
+
+INSERT INTO notification_target(user_id, channel, target)
+SELECT id, 'pushover', pushover_user_key FROM "user" WHERE pushover_user_key IS NOT NULL AND pushover_user_key != '';
+
+INSERT INTO notification_target(user_id, channel, target)
+SELECT id, 'gotify', gotify_token FROM "user" WHERE gotify_token IS NOT NULL AND gotify_token != '';
+
+ALTER TABLE "user" DROP COLUMN telegram_chat_id;
+ALTER TABLE "user" DROP COLUMN pushover_user_key;
+ALTER TABLE "user" DROP COLUMN gotify_token;
@@ -0,0 +1,13 @@
+CREATE TABLE tracker_sync_queue (
+    user_id INTEGER NOT NULL,
+    manga_id INTEGER NOT NULL,
+    tracker VARCHAR(256) NOT NULL,
+    progress INTEGER NOT NULL,
+    completed BOOLEAN NOT NULL DEFAULT false,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    last_error TEXT,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (user_id, manga_id, tracker),
+    FOREIGN KEY (user_id) REFERENCES user(id) ON DELETE CASCADE,
+    FOREIGN KEY (manga_id) REFERENCES manga(id) ON DELETE CASCADE
+);
\ No newline at end of file
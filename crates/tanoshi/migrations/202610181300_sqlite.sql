@@ -0,0 +1,12 @@
+CREATE TABLE tracker_match_candidate (
+    user_id INTEGER NOT NULL,
+    manga_id INTEGER NOT NULL,
+    tracker VARCHAR(256) NOT NULL,
+    tracker_manga_id VARCHAR(256) NOT NULL,
+    title TEXT NOT NULL,
+    cover_url TEXT NOT NULL DEFAULT '',
+    score REAL NOT NULL,
+    PRIMARY KEY (user_id, manga_id, tracker, tracker_manga_id),
+    FOREIGN KEY (user_id) REFERENCES user(id) ON DELETE CASCADE,
+    FOREIGN KEY (manga_id) REFERENCES manga(id) ON DELETE CASCADE
+);
\ No newline at end of file
@@ -0,0 +1,14 @@
+CREATE TABLE tracker_import_candidate (
+    user_id INTEGER NOT NULL,
+    tracker VARCHAR(256) NOT NULL,
+    tracker_manga_id VARCHAR(256) NOT NULL,
+    tracker_title TEXT NOT NULL,
+    progress INTEGER NOT NULL DEFAULT 0,
+    source_id INTEGER NOT NULL,
+    path VARCHAR(1024) NOT NULL,
+    title TEXT NOT NULL,
+    cover_url TEXT NOT NULL DEFAULT '',
+    score REAL NOT NULL,
+    PRIMARY KEY (user_id, tracker, tracker_manga_id, source_id, path),
+    FOREIGN KEY (user_id) REFERENCES user(id) ON DELETE CASCADE
+);
\ No newline at end of file
@@ -0,0 +1,2 @@
+ALTER TABLE tracker_credential ADD COLUMN expires_at TIMESTAMP;
+ALTER TABLE tracker_credential ADD COLUMN revoked_at TIMESTAMP;
\ No newline at end of file
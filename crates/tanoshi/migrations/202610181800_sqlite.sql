@@ -0,0 +1,3 @@
+ALTER TABLE "user" ADD COLUMN permissions TEXT NOT NULL DEFAULT 'browse_nsfw';
+ALTER TABLE "user" ADD COLUMN allowed_sources TEXT DEFAULT NULL;
+ALTER TABLE "user" ADD COLUMN show_nsfw BOOLEAN NOT NULL DEFAULT true;
\ No newline at end of file
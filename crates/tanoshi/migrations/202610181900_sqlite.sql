@@ -0,0 +1,11 @@
+CREATE TABLE login_attempt (
+    id INTEGER PRIMARY KEY,
+    username TEXT NOT NULL,
+    ip TEXT,
+    user_agent TEXT,
+    result TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_login_attempt_username ON login_attempt(username, created_at);
+CREATE INDEX idx_login_attempt_ip ON login_attempt(ip, created_at);
\ No newline at end of file
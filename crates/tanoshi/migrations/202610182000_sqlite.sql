@@ -0,0 +1,13 @@
+ALTER TABLE "user" ADD COLUMN totp_secret TEXT DEFAULT NULL;
+ALTER TABLE "user" ADD COLUMN totp_enabled BOOLEAN NOT NULL DEFAULT false;
+ALTER TABLE "user" ADD COLUMN totp_last_step INTEGER DEFAULT NULL;
+
+CREATE TABLE totp_recovery_code (
+    id INTEGER PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    code_hash TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (user_id) REFERENCES user(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_totp_recovery_code_user_id ON totp_recovery_code(user_id);
\ No newline at end of file
@@ -0,0 +1,13 @@
+CREATE TABLE invite (
+    id INTEGER PRIMARY KEY,
+    code TEXT NOT NULL UNIQUE,
+    created_by INTEGER,
+    is_admin BOOLEAN NOT NULL DEFAULT false,
+    permissions TEXT NOT NULL DEFAULT 'browse_nsfw',
+    allowed_sources TEXT DEFAULT NULL,
+    max_uses INTEGER DEFAULT NULL,
+    uses INTEGER NOT NULL DEFAULT 0,
+    expires_at TIMESTAMP DEFAULT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (created_by) REFERENCES user(id) ON DELETE SET NULL
+);
\ No newline at end of file
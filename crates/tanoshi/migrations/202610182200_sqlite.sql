@@ -0,0 +1,6 @@
+-- a telegram chat belongs to one user, keep the oldest of duplicates
+DELETE FROM notification_target
+WHERE channel = 'telegram'
+AND id NOT IN (SELECT MIN(id) FROM notification_target WHERE channel = 'telegram' GROUP BY target);
+
+CREATE UNIQUE INDEX idx_notification_target_telegram ON notification_target(channel, target) WHERE channel = 'telegram';
\ No newline at end of file
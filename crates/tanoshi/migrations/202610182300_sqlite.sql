@@ -0,0 +1,10 @@
+CREATE TABLE user_identity (
+    id INTEGER PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    issuer TEXT NOT NULL,
+    subject TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (user_id) REFERENCES user(id) ON DELETE CASCADE
+);
+
+CREATE UNIQUE INDEX idx_user_identity_issuer_subject ON user_identity(issuer, subject);
\ No newline at end of file
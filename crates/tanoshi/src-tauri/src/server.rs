@@ -27,7 +27,7 @@ use tanoshi::{
   },
   presentation::{graphql::schema::DatabaseLoader, ServerBuilder},
 };
-use tanoshi_tracker::{AniList, MyAnimeList};
+use tanoshi_tracker::{AniList, Kitsu, MangaUpdates, MyAnimeList, Shikimori};
 
 pub struct Server {
   port: u16,
@@ -82,7 +82,13 @@ impl<R: Runtime> Plugin<R> for Server {
       let libary_svc = LibraryService::new(library_repo.clone());
 
       let history_repo = HistoryRepositoryImpl::new(pool.clone());
-      let history_svc = HistoryService::new(chapter_repo.clone(), history_repo.clone());
+      let (tracker_sync_sender, tracker_sync_receiver) = worker::tracker::channel();
+
+      let history_svc = HistoryService::new(
+        chapter_repo.clone(),
+        history_repo.clone(),
+        tracker_sync_sender.clone(),
+      );
 
       match &config.local_path {
         config::LocalFolders::Single(local_path) => {
@@ -111,6 +117,12 @@ impl<R: Runtime> Plugin<R> for Server {
 
       let notifier = notification::Builder::new(user_repo.clone()).finish();
 
+      let image_cache_repo = ImageCacheRepositoryImpl::new(
+        &config.cache_path,
+        config.image_cache.max_size * 1024 * 1024,
+        config.image_cache.max_age,
+      );
+
       let (chapter_update_receiver, chapter_update_command_tx, update_worker_handle) =
         worker::updates::start(
           config.update_interval,
@@ -120,7 +132,8 @@ impl<R: Runtime> Plugin<R> for Server {
           extension_manager.clone(),
           notifier.clone(),
           config.extension_repository.clone(),
-          &config.cache_path,
+          config.update_check.clone(),
+          image_cache_repo.clone(),
         );
 
       let (download_sender, download_receiver) = worker::downloads::channel();
@@ -157,13 +170,59 @@ impl<R: Runtime> Plugin<R> for Server {
           AniList::new(&base_url, al_cfg.client_id.clone(), al_cfg.client_secret).ok()
         });
 
-      let tracker_repo = TrackerRepositoryImpl::new(pool.clone(), mal_client.clone(), al_client);
+      let kitsu_client = config
+        .kitsu
+        .clone()
+        .map(|kitsu_cfg| Kitsu::new(kitsu_cfg.client_id, kitsu_cfg.client_secret));
+
+      let mangaupdates_client = config
+        .mangaupdates
+        .clone()
+        .map(|mu_cfg| MangaUpdates::new(&mu_cfg.api_url));
+
+      let shikimori_client = config
+        .base_url
+        .clone()
+        .zip(config.shikimori.clone())
+        .and_then(|(base_url, shikimori_cfg)| {
+          Shikimori::new(
+            &base_url,
+            shikimori_cfg.client_id,
+            shikimori_cfg.client_secret,
+          )
+          .ok()
+        });
+
+      let tracker_repo = TrackerRepositoryImpl::new(
+        pool.clone(),
+        mal_client.clone(),
+        al_client,
+        kitsu_client,
+        mangaupdates_client,
+        shikimori_client,
+      );
       let tracker_svc = TrackerService::new(tracker_repo.clone());
 
+      let tracker_worker_handle = worker::tracker::start(
+        chapter_repo.clone(),
+        history_repo.clone(),
+        manga_repo.clone(),
+        tracker_repo.clone(),
+        extension_manager.clone(),
+        notifier.clone(),
+        config.tracker_sync.clone(),
+        tracker_sync_receiver,
+      );
+
       let image_repo = ImageRepositoryImpl::new();
-      let image_cache_repo = ImageCacheRepositoryImpl::new(&config.cache_path);
       let image_svc = ImageService::new(image_repo, image_cache_repo);
 
+      let (prefetch_sender, prefetch_worker_handle) = worker::prefetch::start(
+        chapter_repo.clone(),
+        image_svc.clone(),
+        extension_manager.clone(),
+      );
+
       let loader = DatabaseLoader::new(
         history_repo,
         library_repo,
@@ -188,6 +247,8 @@ impl<R: Runtime> Plugin<R> for Server {
         .with_notifier(notifier)
         .with_chapter_update_receiver(chapter_update_receiver)
         .with_chapter_update_command_tx(chapter_update_command_tx)
+        .with_tracker_sync_tx(tracker_sync_sender)
+        .with_prefetch_tx(prefetch_sender)
         .with_loader(loader);
 
       if config.enable_playground {
@@ -206,6 +267,12 @@ impl<R: Runtime> Plugin<R> for Server {
           _ = download_worker_handle => {
               println!("download worker quit");
           }
+          _ = tracker_worker_handle => {
+              println!("tracker worker quit");
+          }
+          _ = prefetch_worker_handle => {
+              println!("prefetch worker quit");
+          }
           _ = tokio::signal::ctrl_c() => {
               println!("ctrl+c signal");
           }
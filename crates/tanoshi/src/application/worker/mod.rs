@@ -1,2 +1,4 @@
 pub mod downloads;
+pub mod prefetch;
+pub mod tracker;
 pub mod updates;
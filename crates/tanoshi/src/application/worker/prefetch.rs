@@ -0,0 +1,196 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::domain::{
+    entities::image::{ImageTransform, ImageUri},
+    repositories::{
+        chapter::ChapterRepository, image::ImageRepository, image_cache::ImageCacheRepository,
+    },
+    services::{chapter::ChapterService, image::ImageService},
+};
+use futures::StreamExt;
+use std::convert::TryFrom;
+use tanoshi_vm::extension::ExtensionManager;
+use tokio::{
+    sync::mpsc::{UnboundedReceiver, UnboundedSender},
+    task::JoinHandle,
+};
+
+/// Max images of next chapter fetched at the same time
+const PREFETCH_CONCURRENCY: usize = 4;
+/// Next chapter is prefetched once the last quarter of a chapter is reached
+const PREFETCH_REMAINING_RATIO: usize = 4;
+/// Chapters remembered for page count and prefetch, cleared when exceeded
+const MAX_TRACKED_CHAPTERS: usize = 512;
+
+pub type PrefetchSender = UnboundedSender<Command>;
+type PrefetchReceiver = UnboundedReceiver<Command>;
+
+#[derive(Debug)]
+pub enum Command {
+    /// chapter id, page index read and whether chapter is completed
+    PageRead(i64, i64, bool),
+}
+
+pub struct PrefetchWorker<C, I, R>
+where
+    C: ChapterRepository + 'static,
+    I: ImageCacheRepository + 'static,
+    R: ImageRepository + 'static,
+{
+    chapter_svc: ChapterService<C>,
+    image_svc: ImageService<I, R>,
+    extensions: ExtensionManager,
+    /// page count of chapters being read
+    page_counts: HashMap<i64, usize>,
+    /// chapters whose next chapter has been prefetched
+    prefetched: HashSet<i64>,
+    rx: PrefetchReceiver,
+}
+
+impl<C, I, R> PrefetchWorker<C, I, R>
+where
+    C: ChapterRepository + 'static,
+    I: ImageCacheRepository + 'static,
+    R: ImageRepository + 'static,
+{
+    pub fn new(
+        chapter_repo: C,
+        image_svc: ImageService<I, R>,
+        extensions: ExtensionManager,
+        rx: PrefetchReceiver,
+    ) -> Self {
+        Self {
+            chapter_svc: ChapterService::new(chapter_repo, extensions.clone()),
+            image_svc,
+            extensions,
+            page_counts: HashMap::new(),
+            prefetched: HashSet::new(),
+            rx,
+        }
+    }
+
+    async fn page_read(
+        &mut self,
+        chapter_id: i64,
+        page: i64,
+        is_complete: bool,
+    ) -> anyhow::Result<()> {
+        if self.prefetched.contains(&chapter_id) {
+            return Ok(());
+        }
+
+        let chapter = self.chapter_svc.fetch_chapter_by_id(chapter_id).await?;
+        let next_chapter_id = match chapter.next {
+            Some(next_chapter_id) => next_chapter_id,
+            None => return Ok(()),
+        };
+
+        if !is_complete {
+            let page_count = match self.page_counts.get(&chapter_id) {
+                Some(page_count) => *page_count,
+                None => {
+                    let page_count = self
+                        .chapter_svc
+                        .fetch_chapter_pages(
+                            chapter.source_id,
+                            &chapter.path,
+                            &chapter.downloaded_path,
+                        )
+                        .await?
+                        .len();
+                    if self.page_counts.len() >= MAX_TRACKED_CHAPTERS {
+                        self.page_counts.clear();
+                    }
+                    self.page_counts.insert(chapter_id, page_count);
+                    page_count
+                }
+            };
+
+            let remaining = page_count.saturating_sub(page.max(0) as usize + 1);
+            if remaining > page_count / PREFETCH_REMAINING_RATIO {
+                return Ok(());
+            }
+        }
+
+        if self.prefetched.len() >= MAX_TRACKED_CHAPTERS {
+            self.prefetched.clear();
+        }
+        self.prefetched.insert(chapter_id);
+        self.page_counts.remove(&chapter_id);
+
+        let next_chapter = self
+            .chapter_svc
+            .fetch_chapter_by_id(next_chapter_id)
+            .await?;
+        // downloaded chapter is read from disk which is fast enough
+        if next_chapter.downloaded_path.is_some() {
+            return Ok(());
+        }
+
+        let pages = self
+            .chapter_svc
+            .fetch_chapter_pages(next_chapter.source_id, &next_chapter.path, &None)
+            .await?;
+        let referer = self
+            .extensions
+            .get_source_info(next_chapter.source_id)
+            .map(|source| source.url)
+            .ok();
+
+        debug!(
+            "prefetch {} pages of chapter {next_chapter_id}",
+            pages.len()
+        );
+
+        // only remote images are cached, local files are read on demand anyway
+        let image_svc = &self.image_svc;
+        futures::stream::iter(
+            pages.iter().filter(|page| {
+                matches!(ImageUri::try_from(page.as_str()), Ok(ImageUri::Remote(_)))
+            }),
+        )
+        .for_each_concurrent(PREFETCH_CONCURRENCY, |page| {
+            let referer = referer.as_ref();
+            async move {
+                if let Err(e) = image_svc
+                    .fetch_image(page, referer, ImageTransform::default())
+                    .await
+                {
+                    warn!("failed to prefetch {page}: {e}");
+                }
+            }
+        })
+        .await;
+
+        Ok(())
+    }
+
+    pub async fn run(mut self) {
+        while let Some(cmd) = self.rx.recv().await {
+            match cmd {
+                Command::PageRead(chapter_id, page, is_complete) => {
+                    if let Err(e) = self.page_read(chapter_id, page, is_complete).await {
+                        error!("failed to prefetch next chapter of {chapter_id}: {e}");
+                    }
+                }
+            }
+        }
+    }
+}
+
+pub fn start<C, I, R>(
+    chapter_repo: C,
+    image_svc: ImageService<I, R>,
+    extensions: ExtensionManager,
+) -> (PrefetchSender, JoinHandle<()>)
+where
+    C: ChapterRepository + 'static,
+    I: ImageCacheRepository + 'static,
+    R: ImageRepository + 'static,
+{
+    let (tx, rx) = tokio::sync::mpsc::unbounded_channel::<Command>();
+
+    let worker = PrefetchWorker::new(chapter_repo, image_svc, extensions, rx);
+
+    (tx, tokio::spawn(worker.run()))
+}
@@ -0,0 +1,677 @@
+use std::{
+    collections::{HashMap, HashSet},
+    future::Future,
+};
+
+use anyhow::anyhow;
+
+use crate::{
+    domain::{
+        entities::tracker::TrackerImportCandidate,
+        repositories::{
+            chapter::ChapterRepository, history::HistoryRepository, manga::MangaRepository,
+            tracker::TrackerRepository,
+        },
+        services::tracker::{
+            title_similarity, TrackerMatch, TrackerService, MAX_CANDIDATES, MIN_CANDIDATE_SCORE,
+        },
+    },
+    infrastructure::{
+        config::{TrackerSyncConfig, TrackerSyncPolicy},
+        domain::repositories::user::UserRepositoryImpl,
+        notification::Notification,
+    },
+};
+use tanoshi_vm::extension::ExtensionManager;
+use tokio::{
+    sync::mpsc::{UnboundedReceiver, UnboundedSender},
+    task::JoinHandle,
+    time::{self, Instant},
+};
+
+const RETRY_INTERVAL: u64 = 15 * 60;
+const TOKEN_REFRESH_INTERVAL: u64 = 30 * 60;
+/// Tokens expiring within this are refreshed ahead of time
+const TOKEN_REFRESH_MARGIN: i64 = 24 * 60 * 60;
+
+/// Delay between tracker searches when matching library to stay under rate limit
+const MATCH_SEARCH_DELAY: u64 = 1;
+/// Delay between titles when searching sources for tracker list import
+const IMPORT_SEARCH_DELAY: u64 = 1;
+
+const COMPLETED_STATUS: [&str; 4] = ["completed", "complete", "finished", "ended"];
+
+pub type TrackerSyncSender = UnboundedSender<Command>;
+type TrackerSyncReceiver = UnboundedReceiver<Command>;
+
+#[derive(Debug)]
+pub enum Command {
+    /// user id and chapter ids which have been read to the end
+    ChapterRead(i64, Vec<i64>),
+    /// pull read progress of user from trackers, with local policy lower
+    /// progress is pushed to trackers only if allowed
+    Pull(
+        i64,
+        bool,
+        tokio::sync::oneshot::Sender<Result<(), anyhow::Error>>,
+    ),
+    /// link untracked library manga of user to trackers with confidence threshold
+    Match(
+        i64,
+        f64,
+        tokio::sync::oneshot::Sender<Result<(), anyhow::Error>>,
+    ),
+    /// search sources for manga on user's tracker list with status, every installed source if source ids is none
+    Import(
+        i64,
+        String,
+        Option<String>,
+        Option<Vec<i64>>,
+        tokio::sync::oneshot::Sender<Result<(), anyhow::Error>>,
+    ),
+}
+
+/// Match and import search trackers and sources title by title with a delay
+/// in between, so they run in their own task instead of holding up sync
+#[derive(Clone)]
+struct TrackerSearch<T>
+where
+    T: TrackerRepository + Clone + 'static,
+{
+    tracker_svc: TrackerService<T>,
+    extensions: ExtensionManager,
+}
+
+impl<T> TrackerSearch<T>
+where
+    T: TrackerRepository + Clone + 'static,
+{
+    async fn match_library(&self, user_id: i64, threshold: f64) -> anyhow::Result<()> {
+        // manga waiting for review are not searched again until reviewed or dismissed
+        let pending: HashSet<(i64, String)> = self
+            .tracker_svc
+            .get_tracker_match_candidates(user_id)
+            .await?
+            .into_iter()
+            .map(|candidate| (candidate.manga_id, candidate.tracker))
+            .collect();
+
+        let untracked_manga = self
+            .tracker_svc
+            .get_untracked_library_manga(user_id)
+            .await?
+            .into_iter()
+            .filter(|manga| !pending.contains(&(manga.manga_id, manga.tracker.clone())));
+
+        let (mut linked, mut review) = (0, 0);
+        for manga in untracked_manga {
+            match self
+                .tracker_svc
+                .match_manga(user_id, &manga, threshold)
+                .await
+            {
+                Ok(TrackerMatch::Linked) => linked += 1,
+                Ok(TrackerMatch::Review) => review += 1,
+                Ok(TrackerMatch::NotFound) => {}
+                Err(e) => {
+                    warn!("failed to match {} on {}: {e}", manga.title, manga.tracker);
+                }
+            }
+
+            time::sleep(time::Duration::from_secs(MATCH_SEARCH_DELAY)).await;
+        }
+
+        info!("matched library of user {user_id}, {linked} linked, {review} need review");
+
+        Ok(())
+    }
+
+    async fn import_tracker_list(
+        &self,
+        user_id: i64,
+        tracker: &str,
+        status: Option<String>,
+        source_ids: Option<Vec<i64>>,
+    ) -> anyhow::Result<()> {
+        // manga already linked or waiting for confirmation are not searched again
+        let skipped: HashSet<String> = self
+            .tracker_svc
+            .get_tracked_manga_by_user_id(user_id)
+            .await?
+            .into_iter()
+            .filter(|manga| manga.tracker == tracker)
+            .filter_map(|manga| manga.tracker_manga_id)
+            .chain(
+                self.tracker_svc
+                    .get_tracker_import_candidates(user_id)
+                    .await?
+                    .into_iter()
+                    .filter(|candidate| candidate.tracker == tracker)
+                    .map(|candidate| candidate.tracker_manga_id),
+            )
+            .collect();
+
+        let source_ids = match source_ids {
+            Some(source_ids) => source_ids,
+            None => self
+                .extensions
+                .list()
+                .await?
+                .into_iter()
+                .map(|source| source.id)
+                .collect(),
+        };
+
+        let manga_list = self
+            .tracker_svc
+            .fetch_user_manga_list(user_id, tracker, status)
+            .await?
+            .into_iter()
+            .filter(|manga| !skipped.contains(&manga.tracker_manga_id));
+
+        let (mut proposed, mut not_found) = (0, 0);
+        for manga in manga_list {
+            let progress = manga
+                .tracker_status
+                .as_ref()
+                .and_then(|status| status.num_chapters_read)
+                .unwrap_or(0);
+
+            let mut candidates = vec![];
+            for source_id in source_ids.iter() {
+                let results = match self
+                    .extensions
+                    .search_manga(*source_id, 1, Some(manga.title.clone()), None)
+                    .await
+                {
+                    Ok(results) => results,
+                    Err(e) => {
+                        warn!(
+                            "failed to search {} on source {source_id}: {e}",
+                            manga.title
+                        );
+                        continue;
+                    }
+                };
+
+                candidates.extend(results.into_iter().map(|result| {
+                    let score = std::iter::once(&manga.title)
+                        .chain(manga.alternative_titles.iter())
+                        .map(|title| title_similarity(title, &result.title))
+                        .fold(0.0, f64::max);
+
+                    TrackerImportCandidate {
+                        tracker: tracker.to_string(),
+                        tracker_manga_id: manga.tracker_manga_id.clone(),
+                        tracker_title: manga.title.clone(),
+                        progress,
+                        source_id: result.source_id,
+                        path: result.path,
+                        title: result.title,
+                        cover_url: result.cover_url,
+                        score,
+                    }
+                }));
+            }
+
+            candidates.retain(|candidate| candidate.score >= MIN_CANDIDATE_SCORE);
+            candidates.sort_by(|a, b| b.score.total_cmp(&a.score));
+            candidates.truncate(MAX_CANDIDATES);
+
+            if candidates.is_empty() {
+                not_found += 1;
+            } else {
+                self.tracker_svc
+                    .insert_tracker_import_candidates(
+                        user_id,
+                        tracker,
+                        &manga.tracker_manga_id,
+                        &candidates,
+                    )
+                    .await?;
+                proposed += 1;
+            }
+
+            time::sleep(time::Duration::from_secs(IMPORT_SEARCH_DELAY)).await;
+        }
+
+        info!(
+            "imported {tracker} list of user {user_id}, {proposed} proposed, {not_found} not found"
+        );
+
+        Ok(())
+    }
+}
+
+/// Pulling progress fetches status of tracked manga one by one, so it runs in
+/// its own task instead of holding up sync
+#[derive(Clone)]
+struct TrackerPull<C, H, T>
+where
+    C: ChapterRepository + Clone + 'static,
+    H: HistoryRepository + Clone + 'static,
+    T: TrackerRepository + Clone + 'static,
+{
+    chapter_repo: C,
+    history_repo: H,
+    tracker_svc: TrackerService<T>,
+    policy: TrackerSyncPolicy,
+}
+
+impl<C, H, T> TrackerPull<C, H, T>
+where
+    C: ChapterRepository + Clone + 'static,
+    H: HistoryRepository + Clone + 'static,
+    T: TrackerRepository + Clone + 'static,
+{
+    async fn pull_progress(&self, user_id: i64, allow_decrease: bool) -> anyhow::Result<()> {
+        let tracked_manga = self
+            .tracker_svc
+            .get_tracked_manga_by_user_id(user_id)
+            .await?;
+
+        for manga in tracked_manga {
+            if let Some(tracker_manga_id) = manga.tracker_manga_id {
+                if let Err(e) = self
+                    .pull_manga_progress(
+                        user_id,
+                        manga.manga_id,
+                        &manga.tracker,
+                        &tracker_manga_id,
+                        allow_decrease,
+                    )
+                    .await
+                {
+                    warn!(
+                        "failed to sync progress of manga {} from {}: {e}",
+                        manga.manga_id, manga.tracker
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn pull_manga_progress(
+        &self,
+        user_id: i64,
+        manga_id: i64,
+        tracker: &str,
+        tracker_manga_id: &str,
+        allow_decrease: bool,
+    ) -> anyhow::Result<()> {
+        // unknown remote progress is not the same as nothing read, reconciling
+        // with it would wipe history under remote policy
+        let remote_progress = match self
+            .tracker_svc
+            .fetch_tracker_status(user_id, tracker, tracker_manga_id)
+            .await?
+            .and_then(|status| status.num_chapters_read)
+        {
+            Some(remote_progress) => remote_progress as f64,
+            None => return Ok(()),
+        };
+
+        let chapters = self
+            .chapter_repo
+            .get_chapters_by_manga_id(manga_id, None, None, true)
+            .await?;
+        let read_chapter_ids: HashSet<i64> = self
+            .history_repo
+            .get_history_chapters_by_manga_ids(user_id, &[manga_id])
+            .await?
+            .into_iter()
+            .filter(|history| history.is_complete)
+            .map(|history| history.chapter_id)
+            .collect();
+        let local_progress = chapters
+            .iter()
+            .filter(|chapter| read_chapter_ids.contains(&chapter.id))
+            .map(|chapter| chapter.number)
+            .fold(0.0, f64::max);
+
+        if local_progress == remote_progress {
+            return Ok(());
+        }
+
+        debug!(
+            "manga {manga_id} progress differs, local: {local_progress} {tracker}: {remote_progress}"
+        );
+
+        match (self.policy, local_progress < remote_progress) {
+            (TrackerSyncPolicy::Max | TrackerSyncPolicy::Remote, true) => {
+                let chapter_ids: Vec<i64> = chapters
+                    .iter()
+                    .filter(|chapter| {
+                        chapter.number <= remote_progress && !read_chapter_ids.contains(&chapter.id)
+                    })
+                    .map(|chapter| chapter.id)
+                    .collect();
+
+                if !chapter_ids.is_empty() {
+                    self.history_repo
+                        .insert_history_chapters_as_completed(user_id, &chapter_ids)
+                        .await?;
+                }
+            }
+            (TrackerSyncPolicy::Max, false) => {
+                self.tracker_svc
+                    .push_read_progress(
+                        user_id,
+                        tracker,
+                        tracker_manga_id,
+                        local_progress as i64,
+                        false,
+                    )
+                    .await?;
+            }
+            (TrackerSyncPolicy::Remote, false) => {
+                let chapter_ids: Vec<i64> = chapters
+                    .iter()
+                    .filter(|chapter| {
+                        chapter.number > remote_progress && read_chapter_ids.contains(&chapter.id)
+                    })
+                    .map(|chapter| chapter.id)
+                    .collect();
+
+                if !chapter_ids.is_empty() {
+                    self.history_repo
+                        .delete_chapters_from_history(user_id, &chapter_ids)
+                        .await?;
+                }
+            }
+            // nothing is read here when manga is read on another client, and
+            // progress on tracker is only lowered when user asks for it
+            (TrackerSyncPolicy::Local, local_behind)
+                if read_chapter_ids.is_empty() || (local_behind && !allow_decrease) => {}
+            (TrackerSyncPolicy::Local, _) => {
+                self.tracker_svc
+                    .update_manga_tracking_status(
+                        user_id,
+                        tracker,
+                        tracker_manga_id.to_string(),
+                        None,
+                        None,
+                        Some(local_progress as i64),
+                        None,
+                        None,
+                    )
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn pull_progress_all(&self) -> anyhow::Result<()> {
+        let user_ids = self.tracker_svc.get_user_ids_with_tracked_manga().await?;
+
+        for user_id in user_ids {
+            if let Err(e) = self.pull_progress(user_id, false).await {
+                error!("failed to sync progress of user {user_id} from tracker: {e}");
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub struct TrackerSyncWorker<C, H, M, T>
+where
+    C: ChapterRepository + Clone + 'static,
+    H: HistoryRepository + Clone + 'static,
+    M: MangaRepository + 'static,
+    T: TrackerRepository + Clone + 'static,
+{
+    chapter_repo: C,
+    manga_repo: M,
+    tracker_svc: TrackerService<T>,
+    search: TrackerSearch<T>,
+    pull: TrackerPull<C, H, T>,
+    /// running match, import and pull tasks by user id and command name
+    tasks: HashMap<(i64, &'static str), JoinHandle<()>>,
+    /// running scheduled pull of every user
+    pull_all: Option<JoinHandle<()>>,
+    notifier: Notification<UserRepositoryImpl>,
+    config: TrackerSyncConfig,
+    rx: TrackerSyncReceiver,
+}
+
+impl<C, H, M, T> TrackerSyncWorker<C, H, M, T>
+where
+    C: ChapterRepository + Clone + 'static,
+    H: HistoryRepository + Clone + 'static,
+    M: MangaRepository + 'static,
+    T: TrackerRepository + Clone + 'static,
+{
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        chapter_repo: C,
+        history_repo: H,
+        manga_repo: M,
+        tracker_repo: T,
+        extensions: ExtensionManager,
+        notifier: Notification<UserRepositoryImpl>,
+        config: TrackerSyncConfig,
+        rx: TrackerSyncReceiver,
+    ) -> Self {
+        let tracker_svc = TrackerService::new(tracker_repo);
+        let search = TrackerSearch {
+            tracker_svc: tracker_svc.clone(),
+            extensions,
+        };
+        let pull = TrackerPull {
+            chapter_repo: chapter_repo.clone(),
+            history_repo,
+            tracker_svc: tracker_svc.clone(),
+            policy: config.policy,
+        };
+
+        Self {
+            chapter_repo,
+            manga_repo,
+            tracker_svc,
+            search,
+            pull,
+            tasks: HashMap::new(),
+            pull_all: None,
+            notifier,
+            config,
+            rx,
+        }
+    }
+
+    async fn sync_chapter_read(&self, user_id: i64, chapter_ids: &[i64]) -> anyhow::Result<()> {
+        // manga id to highest chapter number read and whether last chapter is read
+        let mut progress: HashMap<i64, (i64, bool)> = HashMap::new();
+        for chapter_id in chapter_ids {
+            let chapter = self.chapter_repo.get_chapter_by_id(*chapter_id).await?;
+            let entry = progress.entry(chapter.manga_id).or_insert((0, false));
+            entry.0 = entry.0.max(chapter.number as i64);
+            entry.1 |= chapter.next.is_none();
+        }
+
+        for (manga_id, (number, last_chapter_read)) in progress {
+            // manga is only completed when the source says no more chapters to come
+            let completed = last_chapter_read
+                && self
+                    .manga_repo
+                    .get_manga_by_id(manga_id)
+                    .await?
+                    .status
+                    .map(|status| COMPLETED_STATUS.contains(&status.trim().to_lowercase().as_str()))
+                    .unwrap_or(false);
+
+            self.tracker_svc
+                .sync_read_progress(user_id, manga_id, number, completed)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn refresh_tokens(&self) -> anyhow::Result<()> {
+        self.tracker_svc
+            .refresh_expiring_tokens(chrono::Duration::seconds(TOKEN_REFRESH_MARGIN))
+            .await?;
+
+        // credentials are removed once user is told to login again
+        for credential in self.tracker_svc.get_revoked_tracker_credentials().await? {
+            info!(
+                "{} login of user {} is revoked",
+                credential.tracker, credential.user_id
+            );
+
+            if let Err(e) = self
+                .notifier
+                .send_all_to_user(
+                    credential.user_id,
+                    Some("Tracker Login Expired".to_string()),
+                    &format!(
+                        "{} rejected tanoshi login, login to {} again from profile to keep tracking progress",
+                        credential.tracker, credential.tracker
+                    ),
+                )
+                .await
+            {
+                error!("failed to notify revoked tracker login: {e}");
+            }
+
+            self.tracker_svc
+                .logout_tracker(credential.user_id, &credential.tracker)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Spawn `task` for user, only one task of a kind runs for a user at a
+    /// time
+    fn spawn_task<F>(
+        &mut self,
+        user_id: i64,
+        name: &'static str,
+        tx: tokio::sync::oneshot::Sender<Result<(), anyhow::Error>>,
+        task: F,
+    ) where
+        F: Future<Output = anyhow::Result<()>> + Send + 'static,
+    {
+        self.tasks.retain(|_, handle| !handle.is_finished());
+        if self.tasks.contains_key(&(user_id, name)) {
+            let _ = tx.send(Err(anyhow!("{name} is already running")));
+            return;
+        }
+
+        let handle = tokio::spawn(async move {
+            let _ = tx.send(task.await);
+        });
+        self.tasks.insert((user_id, name), handle);
+    }
+
+    pub async fn run(mut self) {
+        let mut retry_interval = time::interval(time::Duration::from_secs(RETRY_INTERVAL));
+        let mut token_refresh_interval =
+            time::interval(time::Duration::from_secs(TOKEN_REFRESH_INTERVAL));
+        // interval of 0 would panic, the tick is ignored below when sync is disabled
+        let sync_period = time::Duration::from_secs(self.config.interval.max(1));
+        let mut sync_interval = time::interval_at(Instant::now() + sync_period, sync_period);
+
+        loop {
+            tokio::select! {
+                Some(cmd) = self.rx.recv() => {
+                    match cmd {
+                        Command::ChapterRead(user_id, chapter_ids) => {
+                            if let Err(e) = self.sync_chapter_read(user_id, &chapter_ids).await {
+                                error!("failed to sync read progress to tracker: {e}");
+                            }
+                        }
+                        Command::Pull(user_id, allow_decrease, tx) => {
+                            let pull = self.pull.clone();
+                            self.spawn_task(user_id, "pull", tx, async move {
+                                pull.pull_progress(user_id, allow_decrease).await
+                            });
+                        }
+                        Command::Match(user_id, threshold, tx) => {
+                            let search = self.search.clone();
+                            self.spawn_task(user_id, "match", tx, async move {
+                                search.match_library(user_id, threshold).await
+                            });
+                        }
+                        Command::Import(user_id, tracker, status, source_ids, tx) => {
+                            let search = self.search.clone();
+                            self.spawn_task(user_id, "import", tx, async move {
+                                search
+                                    .import_tracker_list(user_id, &tracker, status, source_ids)
+                                    .await
+                            });
+                        }
+                    }
+                }
+                _ = retry_interval.tick() => {
+                    if let Err(e) = self.tracker_svc.retry_tracker_sync_queue().await {
+                        error!("failed to retry tracker sync queue: {e}");
+                    }
+                }
+                _ = token_refresh_interval.tick() => {
+                    if let Err(e) = self.refresh_tokens().await {
+                        error!("failed to refresh tracker tokens: {e}");
+                    }
+                }
+                _ = sync_interval.tick() => {
+                    if self.config.interval == 0 {
+                        continue;
+                    }
+
+                    if self.pull_all.as_ref().map(|handle| !handle.is_finished()).unwrap_or(false) {
+                        warn!("previous sync from trackers is still running");
+                        continue;
+                    }
+
+                    info!("start syncing progress from trackers");
+                    let pull = self.pull.clone();
+                    self.pull_all = Some(tokio::spawn(async move {
+                        if let Err(e) = pull.pull_progress_all().await {
+                            error!("failed to sync progress from trackers: {e}");
+                        }
+                    }));
+                }
+            }
+        }
+    }
+}
+
+pub fn channel() -> (TrackerSyncSender, TrackerSyncReceiver) {
+    tokio::sync::mpsc::unbounded_channel::<Command>()
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn start<C, H, M, T>(
+    chapter_repo: C,
+    history_repo: H,
+    manga_repo: M,
+    tracker_repo: T,
+    extensions: ExtensionManager,
+    notifier: Notification<UserRepositoryImpl>,
+    config: TrackerSyncConfig,
+    rx: TrackerSyncReceiver,
+) -> JoinHandle<()>
+where
+    C: ChapterRepository + Clone + 'static,
+    H: HistoryRepository + Clone + 'static,
+    M: MangaRepository + 'static,
+    T: TrackerRepository + Clone + 'static,
+{
+    let worker = TrackerSyncWorker::new(
+        chapter_repo,
+        history_repo,
+        manga_repo,
+        tracker_repo,
+        extensions,
+        notifier,
+        config,
+        rx,
+    );
+
+    tokio::spawn(worker.run())
+}
@@ -1,7 +1,6 @@
 use std::{
     collections::{HashMap, HashSet},
     fmt::Display,
-    path::{Path, PathBuf},
     str::FromStr,
 };
 
@@ -14,12 +13,17 @@ use tanoshi_vm::extension::ExtensionManager;
 
 use crate::{
     domain::{
-        entities::{chapter::Chapter, manga::Manga},
+        entities::{chapter::Chapter, image::cache_key, manga::Manga},
         repositories::{
-            chapter::ChapterRepository, library::LibraryRepository, manga::MangaRepository,
+            chapter::ChapterRepository, image_cache::ImageCacheRepository,
+            library::LibraryRepository, manga::MangaRepository,
         },
     },
-    infrastructure::{domain::repositories::user::UserRepositoryImpl, notification::Notification},
+    infrastructure::{
+        config::UpdateCheckConfig,
+        domain::repositories::user::UserRepositoryImpl,
+        notification::{ChapterNotification, Notification},
+    },
 };
 use tokio::{
     task::JoinHandle,
@@ -65,13 +69,18 @@ pub struct SourceInfo {
     pub version: String,
     pub icon: String,
     pub nsfw: bool,
+    #[serde(default)]
+    pub rustc_version: String,
+    #[serde(default)]
+    pub lib_version: String,
 }
 
-struct UpdatesWorker<C, M, L>
+struct UpdatesWorker<C, M, L, I>
 where
     C: ChapterRepository + 'static,
     M: MangaRepository + 'static,
     L: LibraryRepository + 'static,
+    I: ImageCacheRepository + 'static,
 {
     period: u64,
     client: reqwest::Client,
@@ -81,18 +90,21 @@ where
     extensions: ExtensionManager,
     notifier: Notification<UserRepositoryImpl>,
     extension_repository: String,
-    cache_path: PathBuf,
+    update_check: UpdateCheckConfig,
+    image_cache_repo: I,
     broadcast_tx: ChapterUpdateSender,
     command_rx: ChapterUpdateCommandReceiver,
 }
 
-impl<C, M, L> UpdatesWorker<C, M, L>
+impl<C, M, L, I> UpdatesWorker<C, M, L, I>
 where
     C: ChapterRepository + 'static,
     M: MangaRepository + 'static,
     L: LibraryRepository + 'static,
+    I: ImageCacheRepository + 'static,
 {
-    fn new<P: AsRef<Path>>(
+    #[allow(clippy::too_many_arguments)]
+    fn new(
         period: u64,
         library_repo: L,
         manga_repo: M,
@@ -100,8 +112,9 @@ where
         extensions: ExtensionManager,
         notifier: Notification<UserRepositoryImpl>,
         extension_repository: String,
+        update_check: UpdateCheckConfig,
         broadcast_tx: ChapterUpdateSender,
-        cache_path: P,
+        image_cache_repo: I,
     ) -> (Self, ChapterUpdateCommandSender) {
         #[cfg(not(debug_assertions))]
         let period = if period > 0 && period < 3600 {
@@ -123,7 +136,8 @@ where
                 extensions,
                 notifier,
                 extension_repository,
-                cache_path: PathBuf::new().join(cache_path),
+                update_check,
+                image_cache_repo,
                 broadcast_tx,
                 command_rx,
             },
@@ -265,19 +279,37 @@ where
                 .await
                 .unwrap_or_default();
 
+            let source_name = self
+                .extensions
+                .get_source_info(manga.source_id)
+                .map(|source| source.name)
+                .unwrap_or_default();
+
             for chapter in chapters {
                 #[cfg(feature = "desktop")]
                 self.notifier
                     .send_desktop_notification(Some(manga.title.clone()), &chapter.title)?;
 
                 for user in &users {
+                    let unread_count = self
+                        .library_repo
+                        .get_unread_chapter_count(user.id, manga.id)
+                        .await
+                        .unwrap_or_default();
+
+                    let notification = ChapterNotification {
+                        chapter_id: chapter.id,
+                        manga_title: manga.title.clone(),
+                        chapter_title: chapter.title.clone(),
+                        chapter_number: chapter.number,
+                        scanlator: chapter.scanlator.clone(),
+                        source: source_name.clone(),
+                        cover_url: manga.cover_url.clone(),
+                        unread_count,
+                    };
+
                     self.notifier
-                        .send_chapter_notification(
-                            user.id,
-                            &manga.title,
-                            &chapter.title,
-                            chapter.id,
-                        )
+                        .send_chapter_notification(user.id, &notification)
                         .await?;
                 }
 
@@ -313,30 +345,75 @@ where
         let installed_sources = self.extensions.list().await?;
 
         for source in installed_sources {
-            if available_sources_map
-                .get(&source.id)
-                .and_then(|index| Version::from_str(&index.version).ok())
+            let available_source = match available_sources_map.get(&source.id) {
+                Some(available_source) => available_source,
+                None => continue,
+            };
+
+            if !Version::from_str(&available_source.version)
                 .map(|v| v > Version::from_str(source.version).unwrap_or_default())
                 .unwrap_or(false)
             {
-                let message = format!("{} extension update available", source.name);
-                if let Err(e) = self.notifier.send_all_to_admins(None, &message).await {
-                    error!("failed to send extension update to admin, {}", e);
-                }
+                continue;
+            }
 
-                #[cfg(feature = "desktop")]
-                if let Err(e) = self
-                    .notifier
-                    .send_desktop_notification(Some("Extension Update".to_string()), &message)
-                {
-                    error!("failed to send notification, reason {}", e);
-                }
+            let message = if self.update_check.auto_update_sources.contains(&source.id) {
+                self.auto_update_extension(source.id, &source.name, available_source)
+                    .await
+            } else {
+                format!("{} extension update available", source.name)
+            };
+
+            if let Err(e) = self.notifier.send_all_to_admins(None, &message).await {
+                error!("failed to send extension update to admin, {}", e);
+            }
+
+            #[cfg(feature = "desktop")]
+            if let Err(e) = self
+                .notifier
+                .send_desktop_notification(Some("Extension Update".to_string()), &message)
+            {
+                error!("failed to send notification, reason {}", e);
             }
         }
 
         Ok(())
     }
 
+    /// Install newer version of an opted in source, returns message for admins
+    async fn auto_update_extension(
+        &self,
+        source_id: i64,
+        name: &str,
+        available_source: &SourceInfo,
+    ) -> String {
+        if available_source.rustc_version != tanoshi_lib::RUSTC_VERSION
+            || available_source.lib_version != tanoshi_lib::LIB_VERSION
+        {
+            return format!(
+                "{name} extension {} is incompatible with this server, update tanoshi server",
+                available_source.version
+            );
+        }
+
+        info!("auto update {name} to {}", available_source.version);
+
+        match self
+            .extensions
+            .update(&self.extension_repository, source_id)
+            .await
+        {
+            Ok(_) => format!("{name} extension updated to {}", available_source.version),
+            Err(e) => {
+                error!("failed to auto update {name}: {e}");
+                format!(
+                    "{name} extension failed to update to {}, previous version is kept: {e}",
+                    available_source.version
+                )
+            }
+        }
+    }
+
     async fn check_server_update(&self) -> Result<(), anyhow::Error> {
         #[derive(Debug, Deserialize)]
         struct Release {
@@ -346,7 +423,7 @@ where
 
         let release: Release = self
             .client
-            .get("https://api.github.com/repos/faldez/tanoshi/releases/latest")
+            .get(&self.update_check.server_release_url)
             .header(
                 "User-Agent",
                 format!("Tanoshi/{}", env!("CARGO_PKG_VERSION")).as_str(),
@@ -356,7 +433,7 @@ where
             .json()
             .await?;
 
-        if Version::from_str(&release.tag_name[1..])?
+        if Version::from_str(release.tag_name.trim_start_matches('v'))?
             > Version::from_str(env!("CARGO_PKG_VERSION"))?
         {
             info!("new server update found!");
@@ -371,37 +448,40 @@ where
         Ok(())
     }
 
-    async fn clear_cache(&self) -> Result<(), anyhow::Error> {
-        let mut read_dir = tokio::fs::read_dir(&self.cache_path).await?;
-        while let Ok(Some(entry)) = read_dir.next_entry().await {
-            if let Some(created) = entry
-                .metadata()
-                .await?
-                .created()
-                .ok()
-                .and_then(|created| created.elapsed().ok())
-                .map(|elapsed| {
-                    chrono::Duration::from_std(elapsed)
-                        .unwrap_or_else(|_| chrono::Duration::max_value())
-                })
-            {
-                if created.num_days() >= 10 {
-                    info!("removing {}", entry.path().display());
-                    if let Err(e) = tokio::fs::remove_file(entry.path()).await {
-                        error!("failed to remove {}: {e}", entry.path().display());
-                    }
-                }
+    /// Pin covers of manga in library then evict old and least recently
+    /// used images from cache
+    async fn maintain_image_cache(&self) -> Result<(), anyhow::Error> {
+        let mut pinned = HashSet::new();
+
+        let mut manga_stream = self.library_repo.get_manga_from_all_users_library_stream();
+        while let Some(manga) = manga_stream.next().await {
+            let manga = manga?;
+            if manga.cover_url.starts_with("http") {
+                pinned.insert(cache_key(&manga.cover_url));
             }
         }
 
+        self.image_cache_repo.set_pinned(pinned).await?;
+
+        let removed = self.image_cache_repo.evict().await?;
+        if removed > 0 {
+            info!("evicted {removed} images from cache");
+        }
+
         Ok(())
     }
 
     async fn run(self) {
         let period = if self.period == 0 { 3600 } else { self.period };
         let mut chapter_update_interval = time::interval(time::Duration::from_secs(period));
-        let mut server_update_interval = time::interval(time::Duration::from_secs(86400));
-        let mut clear_cache_interval = time::interval(time::Duration::from_secs(3 * 86400));
+        let update_check_period = if self.update_check.interval == 0 {
+            86400
+        } else {
+            self.update_check.interval
+        };
+        let mut server_update_interval =
+            time::interval(time::Duration::from_secs(update_check_period));
+        let mut image_cache_interval = time::interval(time::Duration::from_secs(3600));
 
         loop {
             tokio::select! {
@@ -448,21 +528,29 @@ where
                     info!("periodic updates done in {:?}", Instant::now() - start);
                 }
                 _ = server_update_interval.tick() => {
-                    info!("check server update");
+                    if self.update_check.interval == 0 {
+                        continue;
+                    }
+
+                    if self.update_check.server {
+                        info!("check server update");
 
-                    if let Err(e) = self.check_server_update().await {
-                        error!("failed check server update: {e}")
+                        if let Err(e) = self.check_server_update().await {
+                            error!("failed check server update: {e}")
+                        }
                     }
 
-                    info!("check extension update");
+                    if self.update_check.extension {
+                        info!("check extension update");
 
-                    if let Err(e) = self.check_extension_update().await {
-                        error!("failed check extension update: {e}")
+                        if let Err(e) = self.check_extension_update().await {
+                            error!("failed check extension update: {e}")
+                        }
                     }
                 }
-                _ = clear_cache_interval.tick() => {
-                    if let Err(e) = self.clear_cache().await {
-                        error!("failed clear cache: {e}")
+                _ = image_cache_interval.tick() => {
+                    if let Err(e) = self.maintain_image_cache().await {
+                        error!("failed maintain image cache: {e}")
                     }
                 }
             }
@@ -470,7 +558,8 @@ where
     }
 }
 
-pub fn start<C, M, L, P>(
+#[allow(clippy::too_many_arguments)]
+pub fn start<C, M, L, I>(
     period: u64,
     library_repo: L,
     manga_repo: M,
@@ -478,7 +567,8 @@ pub fn start<C, M, L, P>(
     extensions: ExtensionManager,
     notifier: Notification<UserRepositoryImpl>,
     extension_repository: String,
-    cache_path: P,
+    update_check: UpdateCheckConfig,
+    image_cache_repo: I,
 ) -> (
     ChapterUpdateReceiver,
     ChapterUpdateCommandSender,
@@ -488,7 +578,7 @@ where
     C: ChapterRepository + 'static,
     M: MangaRepository + 'static,
     L: LibraryRepository + 'static,
-    P: AsRef<Path>,
+    I: ImageCacheRepository + 'static,
 {
     let (broadcast_tx, broadcast_rx) = tokio::sync::broadcast::channel(10);
     let (worker, command_tx) = UpdatesWorker::new(
@@ -499,8 +589,9 @@ where
         extensions,
         notifier,
         extension_repository,
+        update_check,
         broadcast_tx,
-        cache_path,
+        image_cache_repo,
     );
 
     let handle = tokio::spawn(worker.run());
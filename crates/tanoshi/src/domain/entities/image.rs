@@ -1,16 +1,17 @@
-use aes::cipher::{block_padding::Pkcs7, BlockDecryptMut, BlockEncryptMut, KeyIvInit};
+use aes::cipher::{block_padding::Pkcs7, BlockDecryptMut, KeyIvInit};
 use anyhow::anyhow;
 use base64::{engine::general_purpose, Engine};
 use bytes::Bytes;
 use fancy_regex::Regex;
 use itertools::Itertools;
+use jsonwebtoken::{DecodingKey, EncodingKey, Header, Validation};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::convert::TryFrom;
 
 use crate::infrastructure::local::SUPPORTED_FILES;
 
 // create an alias for convenience
-type Aes128CbcEnc = cbc::Encryptor<aes::Aes128>;
 type Aes128CbcDec = cbc::Decryptor<aes::Aes128>;
 
 pub enum ImageUri {
@@ -50,8 +51,65 @@ impl TryFrom<&str> for ImageUri {
     }
 }
 
+/// Claims of a signed image url
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ImageClaims {
+    pub url: String,
+    /// user the url was issued to
+    pub sub: i64,
+    /// login session the url was issued in, url stops working when session
+    /// ends. `None` for api keys and users authenticated by trusted proxy.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sid: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exp: Option<usize>,
+}
+
 impl ImageUri {
-    pub fn from_encrypted(secret: &str, encrypted: &str) -> Result<Self, anyhow::Error> {
+    /// Signed urls are JWTs, legacy encrypted urls never contain a dot
+    pub fn is_signed(token: &str) -> bool {
+        token.contains('.')
+    }
+
+    pub fn into_signed(
+        self,
+        secret: &str,
+        sub: i64,
+        sid: Option<i64>,
+        exp: Option<usize>,
+    ) -> Result<String, anyhow::Error> {
+        let claims = ImageClaims {
+            url: self.to_string(),
+            sub,
+            sid,
+            exp,
+        };
+
+        Ok(jsonwebtoken::encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(secret.as_bytes()),
+        )?)
+    }
+
+    /// Verify signature and expiry, url without exp never expires
+    pub fn verify_signed(
+        secret: &str,
+        token: &str,
+    ) -> Result<ImageClaims, jsonwebtoken::errors::Error> {
+        let mut validation = Validation::default();
+        validation.required_spec_claims.clear();
+
+        Ok(jsonwebtoken::decode::<ImageClaims>(
+            token,
+            &DecodingKey::from_secret(secret.as_bytes()),
+            &validation,
+        )?
+        .claims)
+    }
+
+    /// Decrypt url encrypted with zero iv AES-128-CBC, only issued by older versions
+    pub fn decrypt(secret: &str, encrypted: &str) -> Result<String, anyhow::Error> {
         let mut decoded = general_purpose::URL_SAFE_NO_PAD.decode(encrypted)?;
         trace!("decoded: {:?}", decoded);
 
@@ -62,27 +120,18 @@ impl ImageUri {
             .map_err(|e| anyhow::anyhow!("error decrypt url {e}"))?
             .to_vec();
 
-        let url = String::from_utf8(bytes)?;
-        let uri = ImageUri::try_from(url.as_str())?;
-
-        Ok(uri)
+        Ok(String::from_utf8(bytes)?)
     }
 
-    pub fn into_encrypted(self, secret: &str) -> Result<String, anyhow::Error> {
-        let uri = self.to_string();
-        let pos = uri.len();
-
-        let mut buffer = vec![0_u8; pos * 2];
-        buffer.splice(..pos, uri.as_bytes().to_vec());
-
-        let iv = [0_u8; 16];
-        let chipertext = Aes128CbcEnc::new(secret.as_bytes().into(), &iv.into())
-            .encrypt_padded_mut::<Pkcs7>(&mut buffer, pos)
-            .map_err(|e| anyhow!("error encrypt url {e}"))?;
-
-        let encoded = general_purpose::URL_SAFE_NO_PAD.encode(chipertext);
+    /// Last segment of url or path, used as file name when image is saved
+    pub fn file_name(&self) -> Option<String> {
+        let name = match self {
+            ImageUri::Remote(url) => url.split(['?', '#']).next()?.rsplit('/').next()?,
+            ImageUri::File(path) => path.rsplit(['/', '\\']).next()?,
+            ImageUri::Archive(_, filename) => filename.rsplit(['/', '\\']).next()?,
+        };
 
-        Ok(encoded)
+        (!name.is_empty()).then(|| name.to_string())
     }
 }
 
@@ -101,3 +150,132 @@ pub struct Image {
     pub content_type: String,
     pub data: Bytes,
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageFormat {
+    Jpeg,
+    Webp,
+    Avif,
+}
+
+impl ImageFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Jpeg => "jpg",
+            ImageFormat::Webp => "webp",
+            ImageFormat::Avif => "avif",
+        }
+    }
+}
+
+/// Resize and conversion of an image, images are only scaled down to fit
+/// within width and height keeping their aspect ratio
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ImageTransform {
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub quality: Option<u8>,
+    pub format: Option<ImageFormat>,
+}
+
+impl ImageTransform {
+    pub const MAX_DIMENSION: u32 = 4096;
+    pub const DEFAULT_QUALITY: u8 = 80;
+    /// Requested width and height are rounded up to one of these, so only a
+    /// few variants of an image are generated and cached
+    const DIMENSIONS: [u32; 12] = [
+        160, 320, 480, 640, 800, 1080, 1280, 1600, 1920, 2560, 3200, 4096,
+    ];
+    /// Requested quality is rounded up to one of these
+    const QUALITIES: [u8; 5] = [50, 65, 80, 90, 100];
+
+    pub fn new(
+        width: Option<u32>,
+        height: Option<u32>,
+        quality: Option<u8>,
+        format: Option<ImageFormat>,
+    ) -> Self {
+        Self {
+            width: width.map(Self::snap_dimension),
+            height: height.map(Self::snap_dimension),
+            quality: quality.map(Self::snap_quality),
+            format,
+        }
+    }
+
+    fn snap_dimension(dimension: u32) -> u32 {
+        Self::DIMENSIONS
+            .into_iter()
+            .find(|snapped| *snapped >= dimension)
+            .unwrap_or(Self::MAX_DIMENSION)
+    }
+
+    fn snap_quality(quality: u8) -> u8 {
+        Self::QUALITIES
+            .into_iter()
+            .find(|snapped| *snapped >= quality)
+            .unwrap_or(100)
+    }
+
+    /// Original image is returned as is
+    pub fn is_empty(&self) -> bool {
+        self.width.is_none() && self.height.is_none() && self.format.is_none()
+    }
+
+    pub fn quality(&self) -> u8 {
+        self.quality.unwrap_or(Self::DEFAULT_QUALITY)
+    }
+
+    /// Appended to cache key of original image to cache each variant separately
+    pub fn cache_suffix(&self) -> String {
+        format!(
+            "{}x{}-q{}-{}",
+            self.width.unwrap_or(0),
+            self.height.unwrap_or(0),
+            self.quality(),
+            self.format
+                .map(|format| format.extension())
+                .unwrap_or("orig")
+        )
+    }
+}
+
+/// Key of a remote image in cache, independent from how its url is encrypted
+pub fn cache_key(url: &str) -> String {
+    Sha256::digest(url.as_bytes())
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ImageCacheStats {
+    pub size: u64,
+    pub max_size: u64,
+    pub entries: u64,
+    pub pinned_entries: u64,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transform_snapped() {
+        let transform = ImageTransform::new(Some(1), Some(700), Some(81), None);
+        assert_eq!(transform.width, Some(160));
+        assert_eq!(transform.height, Some(800));
+        assert_eq!(transform.quality, Some(90));
+
+        let transform = ImageTransform::new(Some(10000), None, Some(0), None);
+        assert_eq!(transform.width, Some(ImageTransform::MAX_DIMENSION));
+        assert_eq!(transform.quality, Some(50));
+        assert_eq!(
+            transform.cache_suffix(),
+            ImageTransform::new(Some(4000), None, Some(1), None).cache_suffix()
+        );
+    }
+}
@@ -4,6 +4,7 @@ pub mod history;
 pub mod image;
 pub mod library;
 pub mod manga;
+pub mod notification;
 pub mod source;
 pub mod tracker;
 pub mod user;
@@ -0,0 +1,27 @@
+use chrono::NaiveDateTime;
+
+#[derive(Debug, Clone)]
+pub struct NotificationTarget {
+    pub id: i64,
+    pub user_id: i64,
+    pub channel: String,
+    pub target: String,
+    pub created_at: NaiveDateTime,
+}
+
+/// Title and body of a chapter notification, see
+/// `infrastructure::notification::PLACEHOLDERS` for available placeholders
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NotificationTemplate {
+    pub title: String,
+    pub body: String,
+}
+
+impl Default for NotificationTemplate {
+    fn default() -> Self {
+        Self {
+            title: "{{manga}}".to_string(),
+            body: "{{chapter}}".to_string(),
+        }
+    }
+}
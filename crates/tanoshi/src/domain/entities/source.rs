@@ -7,6 +7,7 @@ pub struct Source {
     pub lib_version: String,
     pub icon: String,
     pub has_update: bool,
+    pub nsfw: bool,
 }
 
 impl From<tanoshi_lib::models::SourceInfo> for Source {
@@ -20,6 +21,7 @@ impl From<tanoshi_lib::models::SourceInfo> for Source {
             lib_version: "".to_string(),
             icon: s.icon.to_string(),
             has_update: false,
+            nsfw: s.nsfw,
         }
     }
 }
@@ -1,9 +1,20 @@
+use chrono::NaiveDateTime;
+
 #[derive(Debug, Clone)]
 pub struct Token {
     pub token_type: String,
     pub access_token: String,
     pub refresh_token: String,
     pub expires_in: i64,
+    /// absolute expiry of access token, only known for stored tokens
+    pub expires_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TrackerCredential {
+    pub user_id: i64,
+    pub tracker: String,
+    pub expires_at: Option<NaiveDateTime>,
 }
 
 #[derive(Debug, Clone)]
@@ -20,3 +31,48 @@ pub struct TrackedManga {
     pub tracker: String,
     pub tracker_manga_id: Option<String>,
 }
+
+/// Read progress which failed to be pushed to tracker
+#[derive(Debug, Clone)]
+pub struct TrackerSyncQueue {
+    pub user_id: i64,
+    pub manga_id: i64,
+    pub tracker: String,
+    pub progress: i64,
+    pub completed: bool,
+    pub attempts: i64,
+    pub last_error: Option<String>,
+}
+
+/// Library manga not linked to a tracker user is logged in to
+#[derive(Debug, Clone)]
+pub struct UntrackedManga {
+    pub manga_id: i64,
+    pub title: String,
+    pub tracker: String,
+}
+
+/// Tracker manga found by title which needs to be reviewed before linking
+#[derive(Debug, Clone)]
+pub struct TrackerMatchCandidate {
+    pub manga_id: i64,
+    pub tracker: String,
+    pub tracker_manga_id: String,
+    pub title: String,
+    pub cover_url: String,
+    pub score: f64,
+}
+
+/// Source manga found for a manga on user's tracker list, waiting to be imported
+#[derive(Debug, Clone)]
+pub struct TrackerImportCandidate {
+    pub tracker: String,
+    pub tracker_manga_id: String,
+    pub tracker_title: String,
+    pub progress: i64,
+    pub source_id: i64,
+    pub path: String,
+    pub title: String,
+    pub cover_url: String,
+    pub score: f64,
+}
@@ -1,5 +1,10 @@
+use std::{fmt::Display, str::FromStr};
+
+use anyhow::anyhow;
 use chrono::NaiveDateTime;
 
+use super::source::Source;
+
 #[derive(Debug, Clone)]
 pub struct User {
     pub id: i64,
@@ -8,9 +13,44 @@ pub struct User {
     pub is_admin: bool,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
-    pub telegram_chat_id: Option<i64>,
-    pub pushover_user_key: Option<String>,
-    pub gotify_token: Option<String>,
+    /// granted by admin, admins have every permission
+    pub permissions: Vec<Permission>,
+    /// sources user may browse, `None` allows every source
+    pub allowed_sources: Option<Vec<i64>>,
+    /// user preference to show nsfw sources when permitted
+    pub show_nsfw: bool,
+    /// base32 totp secret, set while enrolling and after enabling
+    pub totp_secret: Option<String>,
+    /// login requires a totp or recovery code
+    pub totp_enabled: bool,
+    /// last accepted totp step, a code is only accepted once
+    pub totp_last_step: Option<i64>,
+}
+
+impl User {
+    pub fn has_permission(&self, permission: Permission) -> bool {
+        self.is_admin || self.permissions.contains(&permission)
+    }
+
+    /// Whether user can browse every source without checking each one
+    pub fn is_unrestricted(&self) -> bool {
+        (self.is_admin || self.allowed_sources.is_none())
+            && self.has_permission(Permission::BrowseNsfw)
+            && self.show_nsfw
+    }
+
+    pub fn can_browse_source(&self, source: &Source) -> bool {
+        if source.nsfw && !(self.has_permission(Permission::BrowseNsfw) && self.show_nsfw) {
+            return false;
+        }
+
+        self.is_admin
+            || self
+                .allowed_sources
+                .as_ref()
+                .map(|allowed_sources| allowed_sources.contains(&source.id))
+                .unwrap_or(true)
+    }
 }
 
 impl Default for User {
@@ -22,9 +62,216 @@ impl Default for User {
             is_admin: false,
             created_at: NaiveDateTime::default(),
             updated_at: NaiveDateTime::default(),
-            telegram_chat_id: None,
-            pushover_user_key: None,
-            gotify_token: None,
+            permissions: vec![Permission::BrowseNsfw],
+            allowed_sources: None,
+            show_nsfw: true,
+            totp_secret: None,
+            totp_enabled: false,
+            totp_last_step: None,
+        }
+    }
+}
+
+/// What a user may do besides reading allowed sources and managing own library
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permission {
+    /// install, update and uninstall sources and change their preferences
+    ManageSources,
+    /// create and delete users and change their permissions
+    ManageUsers,
+    Download,
+    BrowseNsfw,
+}
+
+impl Permission {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Permission::ManageSources => "manage_sources",
+            Permission::ManageUsers => "manage_users",
+            Permission::Download => "download",
+            Permission::BrowseNsfw => "browse_nsfw",
         }
     }
 }
+
+impl Display for Permission {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for Permission {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "manage_sources" => Ok(Permission::ManageSources),
+            "manage_users" => Ok(Permission::ManageUsers),
+            "download" => Ok(Permission::Download),
+            "browse_nsfw" => Ok(Permission::BrowseNsfw),
+            _ => Err(anyhow!("unknown permission {s}")),
+        }
+    }
+}
+
+/// Login of a user on a device, kept alive by rotating its refresh token
+#[derive(Debug, Clone)]
+pub struct Session {
+    pub id: i64,
+    pub user_id: i64,
+    pub refresh_token_hash: String,
+    /// hash of the refresh token replaced by last rotation, used to detect reuse
+    pub previous_refresh_token_hash: Option<String>,
+    pub user_agent: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub refreshed_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+}
+
+/// What an api key may do, every key can read and write scopes add mutations
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApiKeyScope {
+    Read,
+    /// library, categories, read progress and tracking
+    Library,
+    Downloads,
+    /// admin operations, only granted to keys of admins and users managing sources or users
+    Admin,
+}
+
+impl ApiKeyScope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApiKeyScope::Read => "read",
+            ApiKeyScope::Library => "library",
+            ApiKeyScope::Downloads => "downloads",
+            ApiKeyScope::Admin => "admin",
+        }
+    }
+}
+
+impl Display for ApiKeyScope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for ApiKeyScope {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "read" => Ok(ApiKeyScope::Read),
+            "library" => Ok(ApiKeyScope::Library),
+            "downloads" => Ok(ApiKeyScope::Downloads),
+            "admin" => Ok(ApiKeyScope::Admin),
+            _ => Err(anyhow!("unknown api key scope {s}")),
+        }
+    }
+}
+
+/// Long lived key for scripts and third party clients, only its hash is stored
+#[derive(Debug, Clone)]
+pub struct ApiKey {
+    pub id: i64,
+    pub user_id: i64,
+    pub name: String,
+    pub key_hash: String,
+    /// start of the key shown to tell keys apart
+    pub prefix: String,
+    pub scopes: Vec<ApiKeyScope>,
+    pub created_at: NaiveDateTime,
+    pub last_used_at: Option<NaiveDateTime>,
+}
+
+/// Outcome of a password login, kept in the audit log
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoginResult {
+    Success,
+    WrongPassword,
+    UnknownUser,
+    /// rejected without checking password because of too many failures
+    Locked,
+    /// password is correct, login continues with a totp code
+    TotpRequired,
+    WrongTotp,
+    /// password is being verified, counted as a failure until the result is known
+    Pending,
+}
+
+impl LoginResult {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LoginResult::Success => "success",
+            LoginResult::WrongPassword => "wrong_password",
+            LoginResult::UnknownUser => "unknown_user",
+            LoginResult::Locked => "locked",
+            LoginResult::TotpRequired => "totp_required",
+            LoginResult::WrongTotp => "wrong_totp",
+            LoginResult::Pending => "pending",
+        }
+    }
+}
+
+impl Display for LoginResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for LoginResult {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "success" => Ok(LoginResult::Success),
+            "wrong_password" => Ok(LoginResult::WrongPassword),
+            "unknown_user" => Ok(LoginResult::UnknownUser),
+            "locked" => Ok(LoginResult::Locked),
+            "totp_required" => Ok(LoginResult::TotpRequired),
+            "wrong_totp" => Ok(LoginResult::WrongTotp),
+            "pending" => Ok(LoginResult::Pending),
+            _ => Err(anyhow!("unknown login result {s}")),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LoginAttempt {
+    pub id: i64,
+    pub username: String,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub result: LoginResult,
+    pub created_at: NaiveDateTime,
+}
+
+/// Code letting anyone register an account with the role and restrictions it grants
+#[derive(Debug, Clone, Default)]
+pub struct Invite {
+    pub id: i64,
+    pub code: String,
+    /// user who created the invite, `None` once that user is deleted
+    pub created_by: Option<i64>,
+    pub is_admin: bool,
+    pub permissions: Vec<Permission>,
+    /// sources registered users may browse, `None` allows every source
+    pub allowed_sources: Option<Vec<i64>>,
+    /// `None` for unlimited uses
+    pub max_uses: Option<i64>,
+    pub uses: i64,
+    pub expires_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+impl Invite {
+    pub fn is_usable(&self, now: NaiveDateTime) -> bool {
+        self.max_uses
+            .map(|max_uses| self.uses < max_uses)
+            .unwrap_or(true)
+            && self
+                .expires_at
+                .map(|expires_at| expires_at > now)
+                .unwrap_or(true)
+    }
+}
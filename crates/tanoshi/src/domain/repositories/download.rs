@@ -19,6 +19,7 @@ pub trait DownloadRepository: Send + Sync {
         before_timestamp: i64,
         before_id: i64,
         first: i32,
+        source_ids: Option<&[i64]>,
     ) -> Result<Vec<DownloadedChapter>, DownloadRepositoryError>;
 
     async fn get_last_downloaded_chapters(
@@ -28,6 +29,7 @@ pub trait DownloadRepository: Send + Sync {
         before_timestamp: i64,
         before_id: i64,
         last: i32,
+        source_ids: Option<&[i64]>,
     ) -> Result<Vec<DownloadedChapter>, DownloadRepositoryError>;
 
     async fn get_downloaded_chapters(
@@ -36,6 +38,7 @@ pub trait DownloadRepository: Send + Sync {
         after_id: i64,
         before_timestamp: i64,
         before_id: i64,
+        source_ids: Option<&[i64]>,
     ) -> Result<Vec<DownloadedChapter>, DownloadRepositoryError>;
 
     async fn get_chapter_downloaded_path(
@@ -20,6 +20,7 @@ pub trait HistoryRepository: Send + Sync {
         after_timestamp: i64,
         before_timestamp: i64,
         first: i32,
+        source_ids: Option<&[i64]>,
     ) -> Result<Vec<HistoryChapter>, HistoryRepositoryError>;
 
     async fn get_last_history_chapters(
@@ -28,6 +29,7 @@ pub trait HistoryRepository: Send + Sync {
         after_timestamp: i64,
         before_timestamp: i64,
         last: i32,
+        source_ids: Option<&[i64]>,
     ) -> Result<Vec<HistoryChapter>, HistoryRepositoryError>;
 
     async fn get_history_chapters(
@@ -35,6 +37,7 @@ pub trait HistoryRepository: Send + Sync {
         user_id: i64,
         after_timestamp: i64,
         before_timestamp: i64,
+        source_ids: Option<&[i64]>,
     ) -> Result<Vec<HistoryChapter>, HistoryRepositoryError>;
 
     async fn get_history_chapters_by_manga_ids(
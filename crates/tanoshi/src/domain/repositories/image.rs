@@ -4,12 +4,16 @@ use async_trait::async_trait;
 
 use thiserror::Error;
 
-use crate::domain::entities::image::Image;
+use crate::domain::entities::image::{Image, ImageTransform};
 
 #[derive(Debug, Error)]
 pub enum ImageRepositoryError {
     #[error("error request image: {0}")]
     RequestError(#[from] reqwest::Error),
+    #[error("image not found")]
+    NotFound,
+    #[error("error process image: {0}")]
+    ProcessError(#[from] image::ImageError),
     #[error("other error: {0}")]
     Other(String),
 }
@@ -31,4 +35,9 @@ pub trait ImageRepository: Send + Sync {
     ) -> Result<Image, ImageRepositoryError>
     where
         P: AsRef<Path> + std::marker::Send;
+    async fn transform_image(
+        &self,
+        image: Image,
+        transform: ImageTransform,
+    ) -> Result<Image, ImageRepositoryError>;
 }
@@ -1,7 +1,9 @@
+use std::collections::HashSet;
+
 use async_trait::async_trait;
 use thiserror::Error;
 
-use crate::domain::entities::image::Image;
+use crate::domain::entities::image::{Image, ImageCacheStats};
 
 #[derive(Debug, Error)]
 pub enum ImageCacheRepositoryError {
@@ -14,8 +16,22 @@ pub enum ImageCacheRepositoryError {
 }
 
 #[async_trait]
-pub trait ImageCacheRepository {
+pub trait ImageCacheRepository: Send + Sync {
     async fn set(&self, key: &str, image: &Image) -> Result<(), ImageCacheRepositoryError>;
 
     async fn get(&self, key: &str) -> Result<Image, ImageCacheRepositoryError>;
+
+    /// Replace set of keys that are never evicted, `{key}-{suffix}` variants
+    /// of pinned keys are kept too
+    async fn set_pinned(&self, keys: HashSet<String>) -> Result<(), ImageCacheRepositoryError>;
+
+    /// Remove expired entries and least recently accessed entries until
+    /// cache fits max size, returns number of removed entries
+    async fn evict(&self) -> Result<u64, ImageCacheRepositoryError>;
+
+    /// Remove every entry, pinned entries are kept unless `include_pinned`,
+    /// returns number of removed entries
+    async fn purge(&self, include_pinned: bool) -> Result<u64, ImageCacheRepositoryError>;
+
+    async fn stats(&self) -> Result<ImageCacheStats, ImageCacheRepositoryError>;
 }
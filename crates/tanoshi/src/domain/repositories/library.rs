@@ -50,6 +50,12 @@ pub trait LibraryRepository: Clone + Send + Sync {
         manga_id: i64,
     ) -> Result<Vec<User>, LibraryRepositoryError>;
 
+    async fn get_unread_chapter_count(
+        &self,
+        user_id: i64,
+        manga_id: i64,
+    ) -> Result<i64, LibraryRepositoryError>;
+
     fn get_manga_from_all_users_library_stream(
         &self,
     ) -> BoxStream<Result<Manga, LibraryRepositoryError>>;
@@ -96,6 +102,7 @@ pub trait LibraryRepository: Clone + Send + Sync {
         before_timestamp: i64,
         before_id: i64,
         first: i32,
+        source_ids: Option<&[i64]>,
     ) -> Result<Vec<LibraryUpdate>, LibraryRepositoryError>;
 
     async fn get_last_library_updates(
@@ -106,6 +113,7 @@ pub trait LibraryRepository: Clone + Send + Sync {
         before_timestamp: i64,
         before_id: i64,
         last: i32,
+        source_ids: Option<&[i64]>,
     ) -> Result<Vec<LibraryUpdate>, LibraryRepositoryError>;
 
     async fn get_library_updates(
@@ -115,5 +123,6 @@ pub trait LibraryRepository: Clone + Send + Sync {
         after_id: i64,
         before_timestamp: i64,
         before_id: i64,
+        source_ids: Option<&[i64]>,
     ) -> Result<Vec<LibraryUpdate>, LibraryRepositoryError>;
 }
@@ -3,7 +3,10 @@ use chrono::NaiveDateTime;
 use tanoshi_tracker::{Session, TrackerManga};
 use thiserror::Error;
 
-use crate::domain::entities::tracker::{Token, TrackedManga};
+use crate::domain::entities::tracker::{
+    Token, TrackedManga, TrackerCredential, TrackerImportCandidate, TrackerMatchCandidate,
+    TrackerSyncQueue, UntrackedManga,
+};
 
 #[derive(Debug, Error)]
 pub enum TrackerRepositoryError {
@@ -30,6 +33,13 @@ pub trait TrackerRepository: Send + Sync {
         pkce_code_verifier: Option<String>,
     ) -> Result<Token, TrackerRepositoryError>;
 
+    async fn login(
+        &self,
+        tracker: &str,
+        username: String,
+        password: String,
+    ) -> Result<Token, TrackerRepositoryError>;
+
     async fn refresh_token(
         &self,
         tracker: &str,
@@ -49,6 +59,23 @@ pub trait TrackerRepository: Send + Sync {
         user_id: i64,
     ) -> Result<Token, TrackerRepositoryError>;
 
+    /// Credentials with access token expiring before given time which have not been revoked
+    async fn get_expiring_tracker_credentials(
+        &self,
+        before: NaiveDateTime,
+    ) -> Result<Vec<TrackerCredential>, TrackerRepositoryError>;
+
+    /// Mark credential as revoked after tracker rejected its refresh token
+    async fn revoke_tracker_credential(
+        &self,
+        user_id: i64,
+        tracker: &str,
+    ) -> Result<(), TrackerRepositoryError>;
+
+    async fn get_revoked_tracker_credentials(
+        &self,
+    ) -> Result<Vec<TrackerCredential>, TrackerRepositoryError>;
+
     async fn delete_user_tracker_login(
         &self,
         tracker: &str,
@@ -74,6 +101,14 @@ pub trait TrackerRepository: Send + Sync {
         manga_ids: &[i64],
     ) -> Result<Vec<TrackedManga>, TrackerRepositoryError>;
 
+    /// Tracked manga of user on trackers user is still logged in to
+    async fn get_tracked_manga_by_user_id(
+        &self,
+        user_id: i64,
+    ) -> Result<Vec<TrackedManga>, TrackerRepositoryError>;
+
+    async fn get_user_ids_with_tracked_manga(&self) -> Result<Vec<i64>, TrackerRepositoryError>;
+
     async fn fetch_manga_details(
         &self,
         token: &str,
@@ -81,6 +116,13 @@ pub trait TrackerRepository: Send + Sync {
         tracker_manga_id: i64,
     ) -> Result<TrackerManga, TrackerRepositoryError>;
 
+    async fn fetch_user_manga_list(
+        &self,
+        token: &str,
+        tracker: &str,
+        status: Option<String>,
+    ) -> Result<Vec<TrackerManga>, TrackerRepositoryError>;
+
     #[allow(clippy::too_many_arguments)]
     async fn update_manga_tracking_status(
         &self,
@@ -108,4 +150,80 @@ pub trait TrackerRepository: Send + Sync {
         manga_id: i64,
         tracker: &str,
     ) -> Result<(), TrackerRepositoryError>;
+
+    /// Queue read progress for retry, progress already in queue is never decreased
+    async fn insert_tracker_sync_queue(
+        &self,
+        user_id: i64,
+        manga_id: i64,
+        tracker: &str,
+        progress: i64,
+        completed: bool,
+        error: &str,
+    ) -> Result<(), TrackerRepositoryError>;
+
+    async fn get_tracker_sync_queue(&self)
+        -> Result<Vec<TrackerSyncQueue>, TrackerRepositoryError>;
+
+    async fn increase_tracker_sync_queue_attempts(
+        &self,
+        user_id: i64,
+        manga_id: i64,
+        tracker: &str,
+        error: &str,
+    ) -> Result<(), TrackerRepositoryError>;
+
+    async fn delete_tracker_sync_queue(
+        &self,
+        user_id: i64,
+        manga_id: i64,
+        tracker: &str,
+    ) -> Result<(), TrackerRepositoryError>;
+
+    async fn get_untracked_library_manga(
+        &self,
+        user_id: i64,
+    ) -> Result<Vec<UntrackedManga>, TrackerRepositoryError>;
+
+    /// Replace match candidates of a manga on a tracker
+    async fn insert_tracker_match_candidates(
+        &self,
+        user_id: i64,
+        manga_id: i64,
+        tracker: &str,
+        candidates: &[TrackerMatchCandidate],
+    ) -> Result<(), TrackerRepositoryError>;
+
+    async fn get_tracker_match_candidates(
+        &self,
+        user_id: i64,
+    ) -> Result<Vec<TrackerMatchCandidate>, TrackerRepositoryError>;
+
+    async fn delete_tracker_match_candidates(
+        &self,
+        user_id: i64,
+        manga_id: i64,
+        tracker: &str,
+    ) -> Result<(), TrackerRepositoryError>;
+
+    /// Replace import candidates of a manga on user's tracker list
+    async fn insert_tracker_import_candidates(
+        &self,
+        user_id: i64,
+        tracker: &str,
+        tracker_manga_id: &str,
+        candidates: &[TrackerImportCandidate],
+    ) -> Result<(), TrackerRepositoryError>;
+
+    async fn get_tracker_import_candidates(
+        &self,
+        user_id: i64,
+    ) -> Result<Vec<TrackerImportCandidate>, TrackerRepositoryError>;
+
+    async fn delete_tracker_import_candidates(
+        &self,
+        user_id: i64,
+        tracker: &str,
+        tracker_manga_id: &str,
+    ) -> Result<(), TrackerRepositoryError>;
 }
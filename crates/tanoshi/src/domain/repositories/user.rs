@@ -1,7 +1,11 @@
 use async_trait::async_trait;
+use chrono::NaiveDateTime;
 use thiserror::Error;
 
-use crate::domain::entities::user::User;
+use crate::domain::entities::{
+    notification::{NotificationTarget, NotificationTemplate},
+    user::{ApiKey, ApiKeyScope, Invite, LoginAttempt, LoginResult, Permission, Session, User},
+};
 
 #[derive(Debug, Error)]
 pub enum UserRepositoryError {
@@ -17,6 +21,15 @@ pub enum UserRepositoryError {
 pub trait UserRepository: Send + Sync {
     async fn insert_user(&self, user: User) -> Result<i64, UserRepositoryError>;
 
+    /// Use up one use of invite and insert user, fails with `NotFound` when
+    /// invite is used up or expired
+    async fn insert_user_with_invite(
+        &self,
+        user: User,
+        invite_id: i64,
+        now: NaiveDateTime,
+    ) -> Result<i64, UserRepositoryError>;
+
     async fn update_password(&self, id: i64, password: String) -> Result<u64, UserRepositoryError>;
 
     async fn update_user_is_admin(
@@ -25,6 +38,57 @@ pub trait UserRepository: Send + Sync {
         is_admin: bool,
     ) -> Result<u64, UserRepositoryError>;
 
+    async fn update_user_permissions(
+        &self,
+        id: i64,
+        permissions: &[Permission],
+    ) -> Result<u64, UserRepositoryError>;
+
+    /// `None` allows every source
+    async fn update_user_allowed_sources(
+        &self,
+        id: i64,
+        allowed_sources: Option<&[i64]>,
+    ) -> Result<u64, UserRepositoryError>;
+
+    async fn update_user_show_nsfw(
+        &self,
+        id: i64,
+        show_nsfw: bool,
+    ) -> Result<u64, UserRepositoryError>;
+
+    /// Set totp secret and whether it is required on login, clears last step
+    async fn update_user_totp(
+        &self,
+        id: i64,
+        secret: Option<&str>,
+        enabled: bool,
+    ) -> Result<u64, UserRepositoryError>;
+
+    /// Set last accepted totp step if `step` is newer, returns 0 when the
+    /// step was already used
+    async fn update_user_totp_last_step(
+        &self,
+        id: i64,
+        step: i64,
+    ) -> Result<u64, UserRepositoryError>;
+
+    /// Replace recovery codes of user with `code_hashes`
+    async fn replace_totp_recovery_codes(
+        &self,
+        user_id: i64,
+        code_hashes: &[String],
+    ) -> Result<(), UserRepositoryError>;
+
+    /// Delete recovery code, returns 0 when it does not exist
+    async fn delete_totp_recovery_code(
+        &self,
+        user_id: i64,
+        code_hash: &str,
+    ) -> Result<u64, UserRepositoryError>;
+
+    async fn count_totp_recovery_codes(&self, user_id: i64) -> Result<i64, UserRepositoryError>;
+
     async fn get_users(&self) -> Result<Vec<User>, UserRepositoryError>;
 
     async fn get_users_count(&self) -> Result<i64, UserRepositoryError>;
@@ -35,7 +99,203 @@ pub trait UserRepository: Send + Sync {
 
     async fn get_user_by_username(&self, username: String) -> Result<User, UserRepositoryError>;
 
-    async fn update_user_setting(&self, user: &User) -> Result<u64, UserRepositoryError>;
+    async fn get_user_by_identity(
+        &self,
+        issuer: &str,
+        subject: &str,
+    ) -> Result<User, UserRepositoryError>;
+
+    async fn insert_user_identity(
+        &self,
+        user_id: i64,
+        issuer: &str,
+        subject: &str,
+    ) -> Result<i64, UserRepositoryError>;
+
+    async fn get_notification_targets(
+        &self,
+        user_id: i64,
+    ) -> Result<Vec<NotificationTarget>, UserRepositoryError>;
+
+    async fn insert_notification_target(
+        &self,
+        user_id: i64,
+        channel: &str,
+        target: &str,
+    ) -> Result<i64, UserRepositoryError>;
+
+    /// Add target for user, a telegram chat linked to another user is moved
+    /// to user
+    async fn upsert_notification_target(
+        &self,
+        user_id: i64,
+        channel: &str,
+        target: &str,
+    ) -> Result<u64, UserRepositoryError>;
+
+    async fn delete_notification_target(
+        &self,
+        user_id: i64,
+        id: i64,
+    ) -> Result<u64, UserRepositoryError>;
+
+    async fn get_user_id_by_notification_target(
+        &self,
+        channel: &str,
+        target: &str,
+    ) -> Result<i64, UserRepositoryError>;
+
+    async fn insert_notification_link_code(
+        &self,
+        user_id: i64,
+        code: &str,
+    ) -> Result<(), UserRepositoryError>;
+
+    /// Remove link code and return the user id and creation time it belongs to
+    async fn take_notification_link_code(
+        &self,
+        code: &str,
+    ) -> Result<(i64, NaiveDateTime), UserRepositoryError>;
+
+    async fn get_notification_template(
+        &self,
+        user_id: i64,
+    ) -> Result<Option<NotificationTemplate>, UserRepositoryError>;
+
+    async fn upsert_notification_template(
+        &self,
+        user_id: i64,
+        template: &NotificationTemplate,
+    ) -> Result<(), UserRepositoryError>;
+
+    async fn delete_notification_template(&self, user_id: i64) -> Result<(), UserRepositoryError>;
 
     async fn delete_user(&self, id: i64) -> Result<(), UserRepositoryError>;
+
+    async fn insert_session(
+        &self,
+        user_id: i64,
+        refresh_token_hash: &str,
+        user_agent: Option<&str>,
+        expires_at: NaiveDateTime,
+    ) -> Result<i64, UserRepositoryError>;
+
+    async fn get_session_by_id(&self, id: i64) -> Result<Session, UserRepositoryError>;
+
+    /// Find session by its current or previous refresh token hash
+    async fn get_session_by_refresh_token_hash(
+        &self,
+        refresh_token_hash: &str,
+    ) -> Result<Session, UserRepositoryError>;
+
+    async fn get_sessions_by_user_id(
+        &self,
+        user_id: i64,
+    ) -> Result<Vec<Session>, UserRepositoryError>;
+
+    /// Replace refresh token only if it is still `old_hash`, returns affected rows
+    async fn rotate_session_refresh_token(
+        &self,
+        id: i64,
+        old_hash: &str,
+        new_hash: &str,
+        refreshed_at: NaiveDateTime,
+        expires_at: NaiveDateTime,
+    ) -> Result<u64, UserRepositoryError>;
+
+    async fn delete_session(&self, user_id: i64, id: i64) -> Result<u64, UserRepositoryError>;
+
+    async fn delete_sessions_by_user_id(&self, user_id: i64) -> Result<u64, UserRepositoryError>;
+
+    async fn delete_expired_sessions(
+        &self,
+        before: NaiveDateTime,
+    ) -> Result<u64, UserRepositoryError>;
+
+    async fn insert_api_key(
+        &self,
+        user_id: i64,
+        name: &str,
+        key_hash: &str,
+        prefix: &str,
+        scopes: &[ApiKeyScope],
+    ) -> Result<i64, UserRepositoryError>;
+
+    async fn get_api_key_by_id(&self, id: i64) -> Result<ApiKey, UserRepositoryError>;
+
+    async fn get_api_key_by_hash(&self, key_hash: &str) -> Result<ApiKey, UserRepositoryError>;
+
+    async fn get_api_keys_by_user_id(
+        &self,
+        user_id: i64,
+    ) -> Result<Vec<ApiKey>, UserRepositoryError>;
+
+    async fn update_api_key_last_used_at(
+        &self,
+        id: i64,
+        last_used_at: NaiveDateTime,
+    ) -> Result<u64, UserRepositoryError>;
+
+    async fn delete_api_key(&self, user_id: i64, id: i64) -> Result<u64, UserRepositoryError>;
+
+    async fn insert_login_attempt(
+        &self,
+        username: &str,
+        ip: Option<&str>,
+        user_agent: Option<&str>,
+        result: LoginResult,
+        created_at: NaiveDateTime,
+    ) -> Result<i64, UserRepositoryError>;
+
+    async fn update_login_attempt_result(
+        &self,
+        id: i64,
+        result: LoginResult,
+    ) -> Result<u64, UserRepositoryError>;
+
+    async fn delete_login_attempt(&self, id: i64) -> Result<u64, UserRepositoryError>;
+
+    async fn delete_login_attempts_before(
+        &self,
+        before: NaiveDateTime,
+    ) -> Result<u64, UserRepositoryError>;
+
+    /// Wrong password, unknown user, wrong totp and pending attempts for
+    /// username after `since`
+    async fn count_login_failures_by_username(
+        &self,
+        username: &str,
+        since: NaiveDateTime,
+    ) -> Result<i64, UserRepositoryError>;
+
+    /// Wrong password, unknown user, wrong totp and pending attempts from ip
+    /// after `since`
+    async fn count_login_failures_by_ip(
+        &self,
+        ip: &str,
+        since: NaiveDateTime,
+    ) -> Result<i64, UserRepositoryError>;
+
+    async fn get_last_login_success_at(
+        &self,
+        username: &str,
+    ) -> Result<Option<NaiveDateTime>, UserRepositoryError>;
+
+    /// Latest attempts first, optionally only for username
+    async fn get_login_attempts(
+        &self,
+        username: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<LoginAttempt>, UserRepositoryError>;
+
+    async fn insert_invite(&self, invite: &Invite) -> Result<i64, UserRepositoryError>;
+
+    async fn get_invite_by_id(&self, id: i64) -> Result<Invite, UserRepositoryError>;
+
+    async fn get_invite_by_code(&self, code: &str) -> Result<Invite, UserRepositoryError>;
+
+    /// Newest invites first
+    async fn get_invites(&self) -> Result<Vec<Invite>, UserRepositoryError>;
+
+    async fn delete_invite(&self, id: i64) -> Result<u64, UserRepositoryError>;
 }
@@ -27,6 +27,7 @@ impl From<JoinError> for ChapterError {
     }
 }
 
+#[derive(Clone)]
 pub struct ChapterService<R>
 where
     R: ChapterRepository,
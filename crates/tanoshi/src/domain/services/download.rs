@@ -18,6 +18,7 @@ pub enum DownloadError {
     OtherError(#[from] anyhow::Error),
 }
 
+#[derive(Clone)]
 pub struct DownloadService<R>
 where
     R: DownloadRepository,
@@ -37,6 +38,9 @@ where
         }
     }
 
+    /// Downloaded chapters by download time, only of sources in
+    /// `source_ids` if given
+    #[allow(clippy::too_many_arguments)]
     pub async fn get_downloaded_chapters(
         &self,
         after_timestamp: i64,
@@ -45,6 +49,7 @@ where
         before_id: i64,
         first: Option<usize>,
         last: Option<usize>,
+        source_ids: Option<&[i64]>,
     ) -> Result<Vec<DownloadedChapter>, DownloadError> {
         let chapters = if let Some(first) = first {
             self.repo
@@ -54,6 +59,7 @@ where
                     before_timestamp,
                     before_id,
                     first as i32,
+                    source_ids,
                 )
                 .await?
         } else if let Some(last) = last {
@@ -64,11 +70,18 @@ where
                     before_timestamp,
                     before_id,
                     last as i32,
+                    source_ids,
                 )
                 .await?
         } else {
             self.repo
-                .get_downloaded_chapters(after_timestamp, after_id, before_timestamp, before_id)
+                .get_downloaded_chapters(
+                    after_timestamp,
+                    after_id,
+                    before_timestamp,
+                    before_id,
+                    source_ids,
+                )
                 .await?
         };
 
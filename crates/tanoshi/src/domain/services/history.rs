@@ -1,10 +1,13 @@
 use thiserror::Error;
 
-use crate::domain::{
-    entities::{chapter::Chapter, history::HistoryChapter},
-    repositories::{
-        chapter::{ChapterRepository, ChapterRepositoryError},
-        history::{HistoryRepository, HistoryRepositoryError},
+use crate::{
+    application::worker::tracker::{Command as TrackerSyncCommand, TrackerSyncSender},
+    domain::{
+        entities::{chapter::Chapter, history::HistoryChapter},
+        repositories::{
+            chapter::{ChapterRepository, ChapterRepositoryError},
+            history::{HistoryRepository, HistoryRepositoryError},
+        },
     },
 };
 
@@ -16,6 +19,7 @@ pub enum HistoryError {
     ChapterRepositoryError(#[from] ChapterRepositoryError),
 }
 
+#[derive(Clone)]
 pub struct HistoryService<C, R>
 where
     C: ChapterRepository,
@@ -23,6 +27,7 @@ where
 {
     chapter_repo: C,
     repo: R,
+    tracker_sync_sender: TrackerSyncSender,
 }
 
 impl<C, R> HistoryService<C, R>
@@ -30,10 +35,16 @@ where
     C: ChapterRepository,
     R: HistoryRepository,
 {
-    pub fn new(chapter_repo: C, repo: R) -> Self {
-        Self { chapter_repo, repo }
+    pub fn new(chapter_repo: C, repo: R, tracker_sync_sender: TrackerSyncSender) -> Self {
+        Self {
+            chapter_repo,
+            repo,
+            tracker_sync_sender,
+        }
     }
 
+    /// Chapters read last of each manga, only of sources in `source_ids` if
+    /// given
     pub async fn get_history_chapters(
         &self,
         user_id: i64,
@@ -41,6 +52,7 @@ where
         before_timestamp: i64,
         first: Option<usize>,
         last: Option<usize>,
+        source_ids: Option<&[i64]>,
     ) -> Result<Vec<HistoryChapter>, HistoryError> {
         let histories = if let Some(first) = first {
             self.repo
@@ -49,21 +61,42 @@ where
                     after_timestamp,
                     before_timestamp,
                     first as i32,
+                    source_ids,
                 )
                 .await?
         } else if let Some(last) = last {
             self.repo
-                .get_last_history_chapters(user_id, after_timestamp, before_timestamp, last as i32)
+                .get_last_history_chapters(
+                    user_id,
+                    after_timestamp,
+                    before_timestamp,
+                    last as i32,
+                    source_ids,
+                )
                 .await?
         } else {
             self.repo
-                .get_history_chapters(user_id, after_timestamp, before_timestamp)
+                .get_history_chapters(user_id, after_timestamp, before_timestamp, source_ids)
                 .await?
         };
 
         Ok(histories)
     }
 
+    /// Read progress of user in chapters, chapters not read yet are left out
+    pub async fn get_history_chapters_by_chapter_ids(
+        &self,
+        user_id: i64,
+        chapter_ids: &[i64],
+    ) -> Result<Vec<HistoryChapter>, HistoryError> {
+        let histories = self
+            .repo
+            .get_history_chapters_by_chapter_ids(user_id, chapter_ids)
+            .await?;
+
+        Ok(histories)
+    }
+
     pub async fn insert_chapter_to_history(
         &self,
         user_id: i64,
@@ -75,6 +108,10 @@ where
             .insert_history_chapter(user_id, chapter_id, page, is_complete)
             .await?;
 
+        if is_complete {
+            self.sync_tracker(user_id, vec![chapter_id]);
+        }
+
         Ok(())
     }
 
@@ -87,9 +124,20 @@ where
             .insert_history_chapters_as_completed(user_id, &chapter_ids)
             .await?;
 
+        self.sync_tracker(user_id, chapter_ids);
+
         Ok(())
     }
 
+    fn sync_tracker(&self, user_id: i64, chapter_ids: Vec<i64>) {
+        if let Err(e) = self
+            .tracker_sync_sender
+            .send(TrackerSyncCommand::ChapterRead(user_id, chapter_ids))
+        {
+            error!("failed to send tracker sync command: {e}");
+        }
+    }
+
     pub async fn delete_chapters_from_history(
         &self,
         user_id: i64,
@@ -1,17 +1,28 @@
 use crate::domain::{
-    entities::image::{Image, ImageUri},
+    entities::image::{cache_key, Image, ImageCacheStats, ImageClaims, ImageTransform, ImageUri},
     repositories::{
         image::{ImageRepository, ImageRepositoryError},
         image_cache::{ImageCacheRepository, ImageCacheRepositoryError},
     },
 };
+use jsonwebtoken::errors::ErrorKind;
 use std::convert::TryFrom;
 use thiserror::Error;
 
+/// Expiry of signed urls is rounded up to a multiple of this, at most a day, so
+/// the same url is issued for a while and browsers can keep it cached
+const MAX_EXPIRY_STEP: u64 = 86400;
+
 #[derive(Debug, Error)]
 pub enum ImageError {
     #[error("error request image")]
     RequestError,
+    #[error("invalid image url: {0}")]
+    InvalidUrl(String),
+    #[error("image not found")]
+    NotFound,
+    #[error("image url not allowed: {0}")]
+    Forbidden(String),
     #[error("repository error: {0}")]
     RepositoryError(#[from] ImageRepositoryError),
     #[error("cache error: {0}")]
@@ -39,24 +50,54 @@ where
         Self { repo, cache_repo }
     }
 
+    /// Fetch image from url returned by [`ImageService::verify_image_url`]
     pub async fn fetch_image(
         &self,
-        secret: &str,
-        encrypted_url: &str,
+        url: &str,
         referer: Option<&String>,
+        transform: ImageTransform,
     ) -> Result<Image, ImageError> {
-        if let Ok(image) = self.cache_repo.get(encrypted_url).await {
+        // url is valid but points to a local file which no longer exists
+        let uri = ImageUri::try_from(url).map_err(|_| ImageError::NotFound)?;
+
+        if transform.is_empty() {
+            return self.fetch_original_image(uri, referer).await;
+        }
+
+        // variants of local images are cached too as resizing is slower than reading them
+        let key = format!(
+            "{}-{}",
+            cache_key(&uri.to_string()),
+            transform.cache_suffix()
+        );
+        if let Ok(image) = self.cache_repo.get(&key).await {
             return Ok(image);
         }
 
-        let uri = ImageUri::from_encrypted(secret, encrypted_url)
-            .map_err(|e| ImageError::Other(anyhow::anyhow!("{e}")))?;
+        let image = self.fetch_original_image(uri, referer).await?;
+        let image = self.repo.transform_image(image, transform).await?;
+        if let Err(e) = self.cache_repo.set(&key, &image).await {
+            error!("error cache image variant {key}: {e}");
+        }
+
+        Ok(image)
+    }
 
+    async fn fetch_original_image(
+        &self,
+        uri: ImageUri,
+        referer: Option<&String>,
+    ) -> Result<Image, ImageError> {
         let image = match uri {
             ImageUri::Remote(url) => {
+                let key = cache_key(&url);
+                if let Ok(image) = self.cache_repo.get(&key).await {
+                    return Ok(image);
+                }
+
                 let image = self.repo.fetch_image_from_url(&url, referer).await?;
-                if let Err(e) = self.cache_repo.set(encrypted_url, &image).await {
-                    error!("error cache image {encrypted_url}: {e}");
+                if let Err(e) = self.cache_repo.set(&key, &image).await {
+                    error!("error cache image {url}: {e}");
                 }
 
                 image
@@ -72,9 +113,73 @@ where
         Ok(image)
     }
 
-    pub fn encrypt_image_url(&self, secret: &str, url: &str) -> Result<String, ImageError> {
+    /// File name of original image, extension may differ from transformed image
+    pub fn get_image_file_name(&self, url: &str) -> Option<String> {
+        ImageUri::try_from(url).ok().and_then(|uri| uri.file_name())
+    }
+
+    /// Returns url and claims of signed url, legacy encrypted urls are not
+    /// bound to any user and only accepted if `accept_legacy` is set
+    pub fn verify_image_url(
+        &self,
+        secret: &str,
+        token: &str,
+        accept_legacy: bool,
+    ) -> Result<(String, Option<ImageClaims>), ImageError> {
+        if ImageUri::is_signed(token) {
+            let claims = ImageUri::verify_signed(secret, token).map_err(|e| match e.kind() {
+                ErrorKind::ExpiredSignature | ErrorKind::InvalidSignature => {
+                    ImageError::Forbidden(format!("{e}"))
+                }
+                _ => ImageError::InvalidUrl(format!("{e}")),
+            })?;
+
+            return Ok((claims.url.clone(), Some(claims)));
+        }
+
+        if !accept_legacy {
+            return Err(ImageError::Forbidden(
+                "unsigned image url is no longer accepted".to_string(),
+            ));
+        }
+
+        let url =
+            ImageUri::decrypt(secret, token).map_err(|e| ImageError::InvalidUrl(format!("{e}")))?;
+
+        Ok((url, None))
+    }
+
+    pub async fn get_cache_stats(&self) -> Result<ImageCacheStats, ImageError> {
+        Ok(self.cache_repo.stats().await?)
+    }
+
+    pub async fn purge_cache(&self, include_pinned: bool) -> Result<u64, ImageError> {
+        Ok(self.cache_repo.purge(include_pinned).await?)
+    }
+
+    /// Sign url for user and its session, 0 if user has no session. Url
+    /// expires after `ttl` seconds rounded up, 0 for never.
+    pub fn sign_image_url(
+        &self,
+        secret: &str,
+        url: &str,
+        user_id: i64,
+        session_id: i64,
+        ttl: u64,
+    ) -> Result<String, ImageError> {
         let image_uri = ImageUri::try_from(url)?;
 
-        Ok(image_uri.into_encrypted(secret)?)
+        let exp = (ttl > 0).then(|| {
+            let step = (ttl / 4).clamp(1, MAX_EXPIRY_STEP);
+            let exp = chrono::Utc::now().timestamp() as u64 + ttl;
+            (exp / step + 1) * step
+        });
+
+        Ok(image_uri.into_signed(
+            secret,
+            user_id,
+            (session_id != 0).then_some(session_id),
+            exp.map(|exp| exp as usize),
+        )?)
     }
 }
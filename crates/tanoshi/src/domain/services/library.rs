@@ -14,6 +14,7 @@ pub enum LibraryError {
     RepositoryError(#[from] LibraryRepositoryError),
 }
 
+#[derive(Clone)]
 pub struct LibraryService<R>
 where
     R: LibraryRepository,
@@ -90,6 +91,31 @@ where
         Ok(manga)
     }
 
+    /// Manga in user's library whose title contains `query`, paired with
+    /// their unread chapter count
+    pub async fn search_library(
+        &self,
+        user_id: i64,
+        query: &str,
+    ) -> Result<Vec<(Manga, i64)>, LibraryError> {
+        let query = query.to_lowercase();
+
+        let mut result = vec![];
+        for manga in self.repo.get_manga_from_library(user_id).await? {
+            if !manga.title.to_lowercase().contains(&query) {
+                continue;
+            }
+
+            let unread_count = self
+                .repo
+                .get_unread_chapter_count(user_id, manga.id)
+                .await?;
+            result.push((manga, unread_count));
+        }
+
+        Ok(result)
+    }
+
     pub async fn insert_manga_to_library(
         &self,
         user_id: i64,
@@ -115,6 +141,9 @@ where
         Ok(())
     }
 
+    /// Chapters of library manga by upload time, only of sources in
+    /// `source_ids` if given
+    #[allow(clippy::too_many_arguments)]
     pub async fn get_library_recent_updates(
         &self,
         user_id: i64,
@@ -124,6 +153,7 @@ where
         before_id: i64,
         first: Option<usize>,
         last: Option<usize>,
+        source_ids: Option<&[i64]>,
     ) -> Result<Vec<LibraryUpdate>, LibraryError> {
         let updates = if let Some(first) = first {
             self.repo
@@ -134,6 +164,7 @@ where
                     before_timestamp,
                     before_id,
                     first as i32,
+                    source_ids,
                 )
                 .await?
         } else if let Some(last) = last {
@@ -145,6 +176,7 @@ where
                     before_timestamp,
                     before_id,
                     last as i32,
+                    source_ids,
                 )
                 .await?
         } else {
@@ -155,6 +187,7 @@ where
                     after_id,
                     before_timestamp,
                     before_id,
+                    source_ids,
                 )
                 .await?
         };
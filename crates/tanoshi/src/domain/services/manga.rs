@@ -22,6 +22,7 @@ impl From<MangaRepositoryError> for MangaError {
     }
 }
 
+#[derive(Clone)]
 pub struct MangaService<R>
 where
     R: MangaRepository,
@@ -1,7 +1,10 @@
-use std::{collections::HashMap, str::FromStr};
+use std::{
+    collections::{HashMap, HashSet},
+    str::FromStr,
+};
 
 use crate::domain::{
-    entities::source::Source,
+    entities::{source::Source, user::User},
     repositories::source::{SourceRepository, SourceRepositoryError},
 };
 
@@ -79,6 +82,42 @@ where
         Ok(source)
     }
 
+    /// Installed sources `user` may browse, `None` if user is not restricted
+    pub async fn get_allowed_source_ids(
+        &self,
+        user: &User,
+    ) -> Result<Option<HashSet<i64>>, SourceError> {
+        if user.is_unrestricted() {
+            return Ok(None);
+        }
+
+        let source_ids = self
+            .repo
+            .installed_sources()
+            .await?
+            .iter()
+            .filter(|source| user.can_browse_source(source))
+            .map(|source| source.id)
+            .collect();
+
+        Ok(Some(source_ids))
+    }
+
+    /// Whether `user` may browse source
+    pub async fn is_source_allowed(
+        &self,
+        user: &User,
+        source_id: i64,
+    ) -> Result<bool, SourceError> {
+        if user.is_unrestricted() {
+            return Ok(true);
+        }
+
+        let source = self.repo.get_source_by_id(source_id).await?;
+
+        Ok(user.can_browse_source(&source))
+    }
+
     pub async fn install_source(&self, repo_url: &str, id: i64) -> Result<(), SourceError> {
         self.repo.install_source(repo_url, id).await?;
 
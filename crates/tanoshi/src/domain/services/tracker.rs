@@ -1,9 +1,12 @@
-use chrono::NaiveDateTime;
+use chrono::{Duration, NaiveDateTime, Utc};
 use tanoshi_tracker::{Session, TrackerManga, TrackerStatus};
 use thiserror::Error;
 
 use crate::domain::{
-    entities::tracker::TrackedManga,
+    entities::tracker::{
+        Token, TrackedManga, TrackerCredential, TrackerImportCandidate, TrackerMatchCandidate,
+        UntrackedManga,
+    },
     repositories::tracker::{TrackerRepository, TrackerRepositoryError},
 };
 
@@ -15,6 +18,57 @@ pub enum TrackerError {
     Other(String),
 }
 
+/// Queued read progress is dropped after this many failed retries
+const MAX_SYNC_ATTEMPTS: i64 = 10;
+
+/// Candidates scoring below this are not worth reviewing
+pub const MIN_CANDIDATE_SCORE: f64 = 0.4;
+pub const MAX_CANDIDATES: usize = 5;
+/// Best candidate has to beat the runner up by this much to be linked automatically
+const AMBIGUITY_MARGIN: f64 = 0.1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrackerMatch {
+    Linked,
+    Review,
+    NotFound,
+}
+
+fn normalize_title(title: &str) -> Vec<char> {
+    title
+        .chars()
+        .flat_map(char::to_lowercase)
+        .filter(|c| c.is_alphanumeric())
+        .collect()
+}
+
+/// Dice coefficient of character bigrams, ignoring case, whitespace and punctuation
+pub fn title_similarity(a: &str, b: &str) -> f64 {
+    let (a, b) = (normalize_title(a), normalize_title(b));
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    if a == b {
+        return 1.0;
+    }
+    if a.len() < 2 || b.len() < 2 {
+        return 0.0;
+    }
+
+    let mut bigrams: Vec<&[char]> = a.windows(2).collect();
+    let total = bigrams.len() + b.len() - 1;
+    let mut matches = 0;
+    for bigram in b.windows(2) {
+        if let Some(pos) = bigrams.iter().position(|other| *other == bigram) {
+            bigrams.swap_remove(pos);
+            matches += 1;
+        }
+    }
+
+    (2 * matches) as f64 / total as f64
+}
+
+#[derive(Clone)]
 pub struct TrackerService<R>
 where
     R: TrackerRepository,
@@ -75,6 +129,103 @@ where
         Ok(())
     }
 
+    pub async fn login_with_password(
+        &self,
+        user_id: i64,
+        tracker: &str,
+        username: String,
+        password: String,
+    ) -> Result<(), TrackerError> {
+        let token = self.repo.login(tracker, username, password).await?;
+
+        self.repo
+            .insert_tracker_credential(user_id, tracker, token)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Token of user on tracker, refreshed first if it is already expired
+    async fn get_user_tracker_token(
+        &self,
+        user_id: i64,
+        tracker: &str,
+    ) -> Result<Token, TrackerError> {
+        let token = self.repo.get_user_tracker_token(tracker, user_id).await?;
+
+        if token
+            .expires_at
+            .map(|expires_at| expires_at <= Utc::now().naive_utc())
+            .unwrap_or(false)
+        {
+            return self.refresh_user_token(user_id, tracker, &token).await;
+        }
+
+        Ok(token)
+    }
+
+    /// Refresh and store token of user, the credential is marked revoked when
+    /// tracker rejects the refresh token so user can be asked to login again
+    async fn refresh_user_token(
+        &self,
+        user_id: i64,
+        tracker: &str,
+        token: &Token,
+    ) -> Result<Token, TrackerError> {
+        match self.repo.refresh_token(tracker, &token.refresh_token).await {
+            Ok(token) => {
+                self.repo
+                    .insert_tracker_credential(user_id, tracker, token)
+                    .await?;
+
+                Ok(self.repo.get_user_tracker_token(tracker, user_id).await?)
+            }
+            Err(TrackerRepositoryError::Unauthorized) => {
+                self.repo
+                    .revoke_tracker_credential(user_id, tracker)
+                    .await?;
+
+                Err(TrackerRepositoryError::Unauthorized.into())
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Refresh tokens which expire within margin
+    pub async fn refresh_expiring_tokens(&self, margin: Duration) -> Result<(), TrackerError> {
+        let credentials = self
+            .repo
+            .get_expiring_tracker_credentials(Utc::now().naive_utc() + margin)
+            .await?;
+
+        for credential in credentials {
+            let token = self
+                .repo
+                .get_user_tracker_token(&credential.tracker, credential.user_id)
+                .await?;
+
+            if let Err(e) = self
+                .refresh_user_token(credential.user_id, &credential.tracker, &token)
+                .await
+            {
+                warn!(
+                    "failed to refresh {} token of user {}: {e}",
+                    credential.tracker, credential.user_id
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    pub async fn get_revoked_tracker_credentials(
+        &self,
+    ) -> Result<Vec<TrackerCredential>, TrackerError> {
+        let credentials = self.repo.get_revoked_tracker_credentials().await?;
+
+        Ok(credentials)
+    }
+
     pub async fn get_tracked_manga_id(
         &self,
         user_id: i64,
@@ -85,13 +236,28 @@ where
         Ok(tracked_manga)
     }
 
+    pub async fn get_tracked_manga_by_user_id(
+        &self,
+        user_id: i64,
+    ) -> Result<Vec<TrackedManga>, TrackerError> {
+        let tracked_manga = self.repo.get_tracked_manga_by_user_id(user_id).await?;
+
+        Ok(tracked_manga)
+    }
+
+    pub async fn get_user_ids_with_tracked_manga(&self) -> Result<Vec<i64>, TrackerError> {
+        let user_ids = self.repo.get_user_ids_with_tracked_manga().await?;
+
+        Ok(user_ids)
+    }
+
     pub async fn search_manga(
         &self,
         user_id: i64,
         tracker: &str,
         title: &str,
     ) -> Result<Vec<TrackerManga>, TrackerError> {
-        let mut tracker_token = self.repo.get_user_tracker_token(tracker, user_id).await?;
+        let mut tracker_token = self.get_user_tracker_token(user_id, tracker).await?;
 
         for _ in 0..2 {
             match self.repo.search_manga(&tracker_token, tracker, title).await {
@@ -99,16 +265,9 @@ where
                     return Ok(manga);
                 }
                 Err(TrackerRepositoryError::Unauthorized) => {
-                    let token = self
-                        .repo
-                        .refresh_token(tracker, &tracker_token.refresh_token)
+                    tracker_token = self
+                        .refresh_user_token(user_id, tracker, &tracker_token)
                         .await?;
-
-                    self.repo
-                        .insert_tracker_credential(user_id, tracker, token)
-                        .await?;
-
-                    tracker_token = self.repo.get_user_tracker_token(tracker, user_id).await?;
                 }
                 Err(e) => {
                     error!("error search manga, retry");
@@ -129,10 +288,7 @@ where
 
         let mut data: Vec<TrackerStatus> = vec![];
         for manga in tracked_manga {
-            let mut tracker_token = self
-                .repo
-                .get_user_tracker_token(&manga.tracker, user_id)
-                .await?;
+            let mut tracker_token = self.get_user_tracker_token(user_id, &manga.tracker).await?;
 
             let mut status: Option<TrackerStatus> = None;
             if let Some(tracker_manga_id) = manga
@@ -152,18 +308,8 @@ where
                     {
                         Ok(res) => status = res.tracker_status,
                         Err(TrackerRepositoryError::Unauthorized) => {
-                            let token = self
-                                .repo
-                                .refresh_token(&manga.tracker, &tracker_token.refresh_token)
-                                .await?;
-
-                            self.repo
-                                .insert_tracker_credential(user_id, &manga.tracker, token)
-                                .await?;
-
                             tracker_token = self
-                                .repo
-                                .get_user_tracker_token(&manga.tracker, user_id)
+                                .refresh_user_token(user_id, &manga.tracker, &tracker_token)
                                 .await?;
                         }
                         Err(e) => {
@@ -194,7 +340,7 @@ where
         started_at: Option<NaiveDateTime>,
         completed_at: Option<NaiveDateTime>,
     ) -> Result<(), TrackerError> {
-        let mut tracker_token = self.repo.get_user_tracker_token(tracker, user_id).await?;
+        let mut tracker_token = self.get_user_tracker_token(user_id, tracker).await?;
 
         let tracker_manga_id: i64 = tracker_manga_id
             .parse()
@@ -219,16 +365,9 @@ where
                     return Ok(());
                 }
                 Err(TrackerRepositoryError::Unauthorized) => {
-                    let token = self
-                        .repo
-                        .refresh_token(tracker, &tracker_token.refresh_token)
-                        .await?;
-
-                    self.repo
-                        .insert_tracker_credential(user_id, tracker, token)
+                    tracker_token = self
+                        .refresh_user_token(user_id, tracker, &tracker_token)
                         .await?;
-
-                    tracker_token = self.repo.get_user_tracker_token(tracker, user_id).await?;
                 }
                 Err(e) => {
                     error!("error search manga, retry");
@@ -253,6 +392,10 @@ where
             .update_tracker_manga_id(user_id, manga_id, tracker, tracker_manga_id)
             .await?;
 
+        self.repo
+            .delete_tracker_match_candidates(user_id, manga_id, tracker)
+            .await?;
+
         Ok(())
     }
 
@@ -268,4 +411,366 @@ where
 
         Ok(())
     }
+
+    pub async fn get_untracked_library_manga(
+        &self,
+        user_id: i64,
+    ) -> Result<Vec<UntrackedManga>, TrackerError> {
+        let manga = self.repo.get_untracked_library_manga(user_id).await?;
+
+        Ok(manga)
+    }
+
+    /// Search tracker by manga title, link the best candidate if it is confident
+    /// enough or keep candidates for user to review
+    pub async fn match_manga(
+        &self,
+        user_id: i64,
+        manga: &UntrackedManga,
+        threshold: f64,
+    ) -> Result<TrackerMatch, TrackerError> {
+        let mut candidates: Vec<TrackerMatchCandidate> = self
+            .search_manga(user_id, &manga.tracker, &manga.title)
+            .await?
+            .into_iter()
+            .map(|result| {
+                let score = std::iter::once(&result.title)
+                    .chain(result.alternative_titles.iter())
+                    .map(|title| title_similarity(&manga.title, title))
+                    .fold(0.0, f64::max);
+
+                TrackerMatchCandidate {
+                    manga_id: manga.manga_id,
+                    tracker: manga.tracker.clone(),
+                    tracker_manga_id: result.tracker_manga_id,
+                    title: result.title,
+                    cover_url: result.cover_url,
+                    score,
+                }
+            })
+            .filter(|candidate| candidate.score >= MIN_CANDIDATE_SCORE)
+            .collect();
+
+        candidates.sort_by(|a, b| b.score.total_cmp(&a.score));
+        candidates.truncate(MAX_CANDIDATES);
+
+        let confident = match candidates.as_slice() {
+            [] => return Ok(TrackerMatch::NotFound),
+            [best] => best.score >= threshold,
+            [best, runner_up, ..] => {
+                best.score >= threshold && best.score - runner_up.score >= AMBIGUITY_MARGIN
+            }
+        };
+
+        if confident {
+            self.track_manga(
+                user_id,
+                manga.manga_id,
+                &manga.tracker,
+                &candidates[0].tracker_manga_id,
+            )
+            .await?;
+
+            Ok(TrackerMatch::Linked)
+        } else {
+            self.repo
+                .insert_tracker_match_candidates(
+                    user_id,
+                    manga.manga_id,
+                    &manga.tracker,
+                    &candidates,
+                )
+                .await?;
+
+            Ok(TrackerMatch::Review)
+        }
+    }
+
+    pub async fn get_tracker_match_candidates(
+        &self,
+        user_id: i64,
+    ) -> Result<Vec<TrackerMatchCandidate>, TrackerError> {
+        let candidates = self.repo.get_tracker_match_candidates(user_id).await?;
+
+        Ok(candidates)
+    }
+
+    pub async fn dismiss_tracker_match(
+        &self,
+        user_id: i64,
+        manga_id: i64,
+        tracker: &str,
+    ) -> Result<(), TrackerError> {
+        self.repo
+            .delete_tracker_match_candidates(user_id, manga_id, tracker)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn fetch_user_manga_list(
+        &self,
+        user_id: i64,
+        tracker: &str,
+        status: Option<String>,
+    ) -> Result<Vec<TrackerManga>, TrackerError> {
+        let mut tracker_token = self.get_user_tracker_token(user_id, tracker).await?;
+
+        for _ in 0..2 {
+            match self
+                .repo
+                .fetch_user_manga_list(&tracker_token.access_token, tracker, status.clone())
+                .await
+            {
+                Ok(manga) => return Ok(manga),
+                Err(TrackerRepositoryError::Unauthorized) => {
+                    tracker_token = self
+                        .refresh_user_token(user_id, tracker, &tracker_token)
+                        .await?;
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        Err(TrackerError::Other(
+            "failed to fetch user manga list".to_string(),
+        ))
+    }
+
+    pub async fn insert_tracker_import_candidates(
+        &self,
+        user_id: i64,
+        tracker: &str,
+        tracker_manga_id: &str,
+        candidates: &[TrackerImportCandidate],
+    ) -> Result<(), TrackerError> {
+        self.repo
+            .insert_tracker_import_candidates(user_id, tracker, tracker_manga_id, candidates)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn get_tracker_import_candidates(
+        &self,
+        user_id: i64,
+    ) -> Result<Vec<TrackerImportCandidate>, TrackerError> {
+        let candidates = self.repo.get_tracker_import_candidates(user_id).await?;
+
+        Ok(candidates)
+    }
+
+    pub async fn dismiss_tracker_import(
+        &self,
+        user_id: i64,
+        tracker: &str,
+        tracker_manga_id: &str,
+    ) -> Result<(), TrackerError> {
+        self.repo
+            .delete_tracker_import_candidates(user_id, tracker, tracker_manga_id)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Push read progress to every tracker linked to the manga, failed pushes
+    /// are queued and retried by `retry_tracker_sync_queue`.
+    pub async fn sync_read_progress(
+        &self,
+        user_id: i64,
+        manga_id: i64,
+        progress: i64,
+        completed: bool,
+    ) -> Result<(), TrackerError> {
+        let tracked_manga = self.repo.get_tracked_manga_id(user_id, manga_id).await?;
+
+        for manga in tracked_manga {
+            if let Some(tracker_manga_id) = manga.tracker_manga_id {
+                if let Err(e) = self
+                    .push_read_progress(
+                        user_id,
+                        &manga.tracker,
+                        &tracker_manga_id,
+                        progress,
+                        completed,
+                    )
+                    .await
+                {
+                    warn!(
+                        "failed to sync progress of manga {manga_id} to {}: {e}, queued for retry",
+                        manga.tracker
+                    );
+                    self.repo
+                        .insert_tracker_sync_queue(
+                            user_id,
+                            manga_id,
+                            &manga.tracker,
+                            progress,
+                            completed,
+                            &e.to_string(),
+                        )
+                        .await?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub async fn retry_tracker_sync_queue(&self) -> Result<(), TrackerError> {
+        let queue = self.repo.get_tracker_sync_queue().await?;
+
+        for entry in queue {
+            // manga might be untracked or user logged out since the entry is queued
+            let tracker_manga_id = self
+                .repo
+                .get_tracked_manga_id(entry.user_id, entry.manga_id)
+                .await?
+                .into_iter()
+                .find(|manga| manga.tracker == entry.tracker)
+                .and_then(|manga| manga.tracker_manga_id);
+
+            let res = if let Some(tracker_manga_id) = tracker_manga_id {
+                self.push_read_progress(
+                    entry.user_id,
+                    &entry.tracker,
+                    &tracker_manga_id,
+                    entry.progress,
+                    entry.completed,
+                )
+                .await
+            } else {
+                Ok(())
+            };
+
+            match res {
+                Ok(()) => {
+                    self.repo
+                        .delete_tracker_sync_queue(entry.user_id, entry.manga_id, &entry.tracker)
+                        .await?;
+                }
+                Err(e) if entry.attempts + 1 >= MAX_SYNC_ATTEMPTS => {
+                    error!(
+                        "giving up syncing progress of manga {} to {}: {e}",
+                        entry.manga_id, entry.tracker
+                    );
+                    self.repo
+                        .delete_tracker_sync_queue(entry.user_id, entry.manga_id, &entry.tracker)
+                        .await?;
+                }
+                Err(e) => {
+                    self.repo
+                        .increase_tracker_sync_queue_attempts(
+                            entry.user_id,
+                            entry.manga_id,
+                            &entry.tracker,
+                            &e.to_string(),
+                        )
+                        .await?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Update tracker progress without ever decreasing it, status and dates
+    /// are only set when reading starts or the manga is completed.
+    pub async fn push_read_progress(
+        &self,
+        user_id: i64,
+        tracker: &str,
+        tracker_manga_id: &str,
+        progress: i64,
+        completed: bool,
+    ) -> Result<(), TrackerError> {
+        let current = self
+            .fetch_tracker_status(user_id, tracker, tracker_manga_id)
+            .await?
+            .unwrap_or_default();
+
+        let current_progress = current.num_chapters_read.unwrap_or(0);
+        let current_status = current.status.as_deref();
+        let is_completed = current_status == Some("completed");
+        if progress <= current_progress && (!completed || is_completed) {
+            return Ok(());
+        }
+
+        let now = Utc::now().naive_utc();
+        let (status, started_at, completed_at) = if completed && !is_completed {
+            (
+                Some("completed".to_string()),
+                current.start_date.is_none().then_some(now),
+                current.finish_date.is_none().then_some(now),
+            )
+        } else if matches!(current_status, None | Some("plan_to_read")) {
+            (
+                Some("reading".to_string()),
+                current.start_date.is_none().then_some(now),
+                None,
+            )
+        } else if current_status != Some("reading") {
+            (Some("reading".to_string()), None, None)
+        } else {
+            (None, None, None)
+        };
+
+        self.update_manga_tracking_status(
+            user_id,
+            tracker,
+            tracker_manga_id.to_string(),
+            status,
+            None,
+            Some(progress.max(current_progress)),
+            started_at,
+            completed_at,
+        )
+        .await
+    }
+
+    pub async fn fetch_tracker_status(
+        &self,
+        user_id: i64,
+        tracker: &str,
+        tracker_manga_id: &str,
+    ) -> Result<Option<TrackerStatus>, TrackerError> {
+        let tracker_manga_id: i64 = tracker_manga_id
+            .parse()
+            .map_err(|e| TrackerError::Other(format!("{e}")))?;
+
+        let mut tracker_token = self.get_user_tracker_token(user_id, tracker).await?;
+
+        for _ in 0..2 {
+            match self
+                .repo
+                .fetch_manga_details(&tracker_token.access_token, tracker, tracker_manga_id)
+                .await
+            {
+                Ok(res) => return Ok(res.tracker_status),
+                Err(TrackerRepositoryError::Unauthorized) => {
+                    tracker_token = self
+                        .refresh_user_token(user_id, tracker, &tracker_token)
+                        .await?;
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        Err(TrackerError::Other(
+            "failed to fetch manga tracking status".to_string(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_title_similarity() {
+        assert_eq!(title_similarity("Yotsuba&!", "yotsuba & !"), 1.0);
+        assert_eq!(title_similarity("", "Yotsuba"), 0.0);
+        assert!(title_similarity("Yotsuba to!", "Yotsubato") > 0.9);
+        assert!(title_similarity("One Piece", "One Punch-Man") < 0.6);
+    }
 }
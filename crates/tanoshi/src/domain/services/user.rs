@@ -1,11 +1,40 @@
+use base64::{engine::general_purpose, Engine};
+use chrono::{Duration, Utc};
 use rand::RngCore;
+use sha2::{Digest, Sha256};
+use tanoshi_notifier::telegram;
 use thiserror::Error;
 
-use crate::domain::{
-    entities::user::User,
-    repositories::user::{UserRepository, UserRepositoryError},
+use crate::{
+    domain::{
+        entities::{
+            notification::{NotificationTarget, NotificationTemplate},
+            user::{
+                ApiKey, ApiKeyScope, Invite, LoginAttempt, LoginResult, Permission, Session, User,
+            },
+        },
+        repositories::user::{UserRepository, UserRepositoryError},
+    },
+    infrastructure::totp,
 };
 
+const LINK_CODE_EXPIRY_MINUTES: i64 = 10;
+/// Session ends when its refresh token is not used for this long
+pub const REFRESH_TOKEN_EXPIRY_DAYS: i64 = 30;
+/// Replaced refresh token presented again within this window is a concurrent
+/// refresh from the same device, after it the token is considered stolen
+const REFRESH_TOKEN_REUSE_GRACE_SECONDS: i64 = 30;
+/// Every api key starts with this, telling them apart from access tokens
+pub const API_KEY_PREFIX: &str = "tanoshi_";
+/// Last use of an api key is written at most this often
+const API_KEY_LAST_USED_INTERVAL_SECONDS: i64 = 60;
+/// Shown as account issuer in authenticator apps
+const TOTP_ISSUER: &str = "Tanoshi";
+const TOTP_RECOVERY_CODES: usize = 10;
+/// Login attempts are kept this long, or as long as the login limit window
+/// when it is longer
+const LOGIN_ATTEMPT_RETENTION_DAYS: i64 = 90;
+
 #[derive(Debug, Error)]
 pub enum UserError {
     #[error("user not found")]
@@ -16,12 +45,38 @@ pub enum UserError {
     Forbidden,
     #[error("insufficient password length")]
     InsufficientPasswordLength,
+    #[error("session expired, please login")]
+    SessionExpired,
+    #[error("refresh token already used")]
+    RefreshTokenReused,
+    #[error("invalid api key")]
+    InvalidApiKey,
+    #[error("invalid or expired invite code")]
+    InvalidInvite,
+    #[error("two-factor code required")]
+    TotpRequired,
+    #[error("wrong two-factor code")]
+    WrongTotpCode,
+    #[error("too many failed logins, try again later")]
+    LoginLocked,
+    /// failed login which locked the account, admins should be told
+    #[error("too many failed logins, try again later")]
+    AccountLocked(String),
     #[error("repository error: {0}")]
     RepositoryError(#[from] UserRepositoryError),
     #[error("other: {0}")]
     Other(String),
 }
 
+/// Failed logins allowed within `window` before a username or address is
+/// locked, a limit of 0 disables it
+#[derive(Debug, Clone, Copy)]
+pub struct LoginLimit {
+    pub max_failures_per_user: i64,
+    pub max_failures_per_ip: i64,
+    pub window: Duration,
+}
+
 #[derive(Clone)]
 pub struct UserService<R>
 where
@@ -44,33 +99,502 @@ where
         password: &str,
         is_admin: bool,
     ) -> Result<i64, UserError> {
-        if password.len() < 8 {
-            return Err(UserError::InsufficientPasswordLength);
-        }
+        let user = User {
+            username: username.to_string(),
+            password: hash_password(password)?,
+            is_admin,
+            ..Default::default()
+        };
 
-        let mut salt: [u8; 32] = [0; 32];
-        rand::thread_rng().fill_bytes(&mut salt);
+        Ok(self.repo.insert_user(user).await?)
+    }
 
-        let hash = {
-            let config = argon2::Config::default();
-            argon2::hash_encoded(password.as_bytes(), &salt, &config)
-                .map_err(|e| UserError::Other(format!("{e}")))?
+    /// Create user with role and source restrictions of invite, returning
+    /// new user id and the redeemed invite
+    pub async fn register_with_invite(
+        &self,
+        code: &str,
+        username: &str,
+        password: &str,
+    ) -> Result<(i64, Invite), UserError> {
+        let invite = match self.repo.get_invite_by_code(code.trim()).await {
+            Ok(invite) => invite,
+            Err(UserRepositoryError::NotFound) => return Err(UserError::InvalidInvite),
+            Err(e) => return Err(e.into()),
         };
 
+        let now = Utc::now().naive_utc();
+        if !invite.is_usable(now) {
+            return Err(UserError::InvalidInvite);
+        }
+
         let user = User {
             username: username.to_string(),
-            password: hash,
-            is_admin,
+            password: hash_password(password)?,
+            is_admin: invite.is_admin,
+            permissions: invite.permissions.clone(),
+            allowed_sources: invite.allowed_sources.clone(),
             ..Default::default()
         };
 
-        Ok(self.repo.insert_user(user).await?)
+        // uses are checked again when inserting, in case invite is redeemed concurrently
+        match self
+            .repo
+            .insert_user_with_invite(user, invite.id, now)
+            .await
+        {
+            Ok(user_id) => Ok((user_id, invite)),
+            Err(UserRepositoryError::NotFound) => Err(UserError::InvalidInvite),
+            Err(e) => Err(e.into()),
+        }
     }
 
-    pub async fn delete_user(&self, user_id: i64) -> Result<(), UserError> {
+    /// Users granted to manage users can create invites with permissions
+    /// they hold, only admins can create invites for admins
+    pub async fn create_invite(
+        &self,
+        manager_id: i64,
+        invite: Invite,
+    ) -> Result<Invite, UserError> {
+        let manager = self.repo.get_user_by_id(manager_id).await?;
+        if !manager.has_permission(Permission::ManageUsers)
+            || (invite.is_admin && !manager.is_admin)
+        {
+            return Err(UserError::Forbidden);
+        }
+        check_can_grant(&manager, &invite.permissions)?;
+
+        if invite.max_uses.is_some_and(|max_uses| max_uses < 1) {
+            return Err(UserError::Other("max uses must be at least 1".to_string()));
+        }
+
+        let mut bytes: [u8; 12] = [0; 12];
+        rand::thread_rng().fill_bytes(&mut bytes);
+
+        let invite = Invite {
+            code: general_purpose::URL_SAFE_NO_PAD.encode(bytes),
+            created_by: Some(manager.id),
+            ..invite
+        };
+        let id = self.repo.insert_invite(&invite).await?;
+
+        Ok(self.repo.get_invite_by_id(id).await?)
+    }
+
+    /// Invites for admins are only shown to admins
+    pub async fn fetch_invites(&self, manager_id: i64) -> Result<Vec<Invite>, UserError> {
+        let manager = self.repo.get_user_by_id(manager_id).await?;
+
+        Ok(self
+            .repo
+            .get_invites()
+            .await?
+            .into_iter()
+            .filter(|invite| manager.is_admin || !invite.is_admin)
+            .collect())
+    }
+
+    pub async fn delete_invite(&self, manager_id: i64, id: i64) -> Result<(), UserError> {
+        let manager = self.repo.get_user_by_id(manager_id).await?;
+        let invite = match self.repo.get_invite_by_id(id).await {
+            Ok(invite) => invite,
+            Err(UserRepositoryError::NotFound) => {
+                return Err(UserError::Other("invite not found".to_string()))
+            }
+            Err(e) => return Err(e.into()),
+        };
+        // admin invites are hidden from other managers
+        if invite.is_admin && !manager.is_admin {
+            return Err(UserError::Other("invite not found".to_string()));
+        }
+
+        if self.repo.delete_invite(id).await? == 0 {
+            return Err(UserError::Other("invite not found".to_string()));
+        }
+
+        Ok(())
+    }
+
+    pub async fn delete_user(&self, manager_id: i64, user_id: i64) -> Result<(), UserError> {
+        self.check_can_manage(manager_id, user_id).await?;
+
         Ok(self.repo.delete_user(user_id).await?)
     }
 
+    /// Users granted to manage users can't change admins or themselves,
+    /// returns the manager
+    async fn check_can_manage(&self, manager_id: i64, user_id: i64) -> Result<User, UserError> {
+        let manager = self.repo.get_user_by_id(manager_id).await?;
+        if manager.is_admin {
+            return Ok(manager);
+        }
+
+        let user = self.repo.get_user_by_id(user_id).await?;
+        if !manager.has_permission(Permission::ManageUsers)
+            || user.is_admin
+            || user.id == manager.id
+        {
+            return Err(UserError::Forbidden);
+        }
+
+        Ok(manager)
+    }
+
+    /// Managers can only grant permissions they hold themselves
+    pub async fn update_permissions(
+        &self,
+        manager_id: i64,
+        user_id: i64,
+        permissions: &[Permission],
+    ) -> Result<(), UserError> {
+        let manager = self.check_can_manage(manager_id, user_id).await?;
+        check_can_grant(&manager, permissions)?;
+
+        self.repo
+            .update_user_permissions(user_id, permissions)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Restrict user to `allowed_sources`, `None` allows every source
+    pub async fn update_allowed_sources(
+        &self,
+        manager_id: i64,
+        user_id: i64,
+        allowed_sources: Option<&[i64]>,
+    ) -> Result<(), UserError> {
+        self.check_can_manage(manager_id, user_id).await?;
+
+        self.repo
+            .update_user_allowed_sources(user_id, allowed_sources)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn update_show_nsfw(&self, user_id: i64, show_nsfw: bool) -> Result<(), UserError> {
+        self.repo.update_user_show_nsfw(user_id, show_nsfw).await?;
+
+        Ok(())
+    }
+
+    /// Record a pending login attempt, counted as a failure until its result
+    /// is known so concurrent attempts can't exceed the limit. Returns its id
+    /// and failures of username before it. Username or ip with too many
+    /// failures within the window is rejected and the attempt recorded as
+    /// locked.
+    async fn begin_login(
+        &self,
+        limit: &LoginLimit,
+        username: &str,
+        ip: Option<&str>,
+        user_agent: Option<&str>,
+    ) -> Result<(i64, i64), UserError> {
+        let now = Utc::now().naive_utc();
+        let window_start = now - limit.window;
+
+        let attempt_id = self
+            .repo
+            .insert_login_attempt(username, ip, user_agent, LoginResult::Pending, now)
+            .await?;
+
+        // counts include this attempt
+        let user_failures = if limit.max_failures_per_user > 0 {
+            let since = match self.repo.get_last_login_success_at(username).await? {
+                Some(last_success_at) if last_success_at > window_start => last_success_at,
+                _ => window_start,
+            };
+            self.repo
+                .count_login_failures_by_username(username, since)
+                .await?
+        } else {
+            0
+        };
+        let ip_failures = match ip {
+            Some(ip) if limit.max_failures_per_ip > 0 => {
+                self.repo
+                    .count_login_failures_by_ip(ip, window_start)
+                    .await?
+            }
+            _ => 0,
+        };
+
+        let user_locked =
+            limit.max_failures_per_user > 0 && user_failures > limit.max_failures_per_user;
+        let ip_locked = limit.max_failures_per_ip > 0 && ip_failures > limit.max_failures_per_ip;
+        if user_locked || ip_locked {
+            self.repo
+                .update_login_attempt_result(attempt_id, LoginResult::Locked)
+                .await?;
+            return Err(UserError::LoginLocked);
+        }
+
+        Ok((attempt_id, (user_failures - 1).max(0)))
+    }
+
+    /// Record result of login attempt in the audit log, failures are
+    /// returned as error
+    async fn record_login(
+        &self,
+        limit: &LoginLimit,
+        attempt_id: i64,
+        username: &str,
+        result: LoginResult,
+        user_failures: i64,
+    ) -> Result<(), UserError> {
+        self.repo
+            .update_login_attempt_result(attempt_id, result)
+            .await?;
+
+        match result {
+            LoginResult::Success => Ok(()),
+            LoginResult::TotpRequired => Err(UserError::TotpRequired),
+            LoginResult::WrongPassword | LoginResult::WrongTotp
+                if user_failures + 1 == limit.max_failures_per_user =>
+            {
+                warn!(
+                    "locked login of user {username} after {} failures",
+                    user_failures + 1
+                );
+                Err(UserError::AccountLocked(username.to_string()))
+            }
+            LoginResult::WrongTotp => Err(UserError::WrongTotpCode),
+            _ => Err(UserError::WrongPassword),
+        }
+    }
+
+    /// Verify password of a login attempt and record it in the audit log.
+    /// Username or ip with too many failures within the window is rejected
+    /// without checking password, a successful login resets the username.
+    /// User with totp enabled also needs `totp_code`, a totp code or one of
+    /// the recovery codes.
+    pub async fn login(
+        &self,
+        limit: &LoginLimit,
+        username: &str,
+        password: &str,
+        totp_code: Option<&str>,
+        ip: Option<&str>,
+        user_agent: Option<&str>,
+    ) -> Result<User, UserError> {
+        let retention = Duration::days(LOGIN_ATTEMPT_RETENTION_DAYS).max(limit.window);
+        if let Err(e) = self
+            .repo
+            .delete_login_attempts_before(Utc::now().naive_utc() - retention)
+            .await
+        {
+            error!("failed to delete old login attempts: {e}");
+        }
+
+        let (attempt_id, user_failures) = self.begin_login(limit, username, ip, user_agent).await?;
+
+        let (result, user) = match self.verify_password(username, password).await {
+            Ok(_) => {
+                let user = self.repo.get_user_by_username(username.to_string()).await?;
+                let result = match totp_code {
+                    _ if !user.totp_enabled => LoginResult::Success,
+                    None => LoginResult::TotpRequired,
+                    Some(code) => {
+                        if self.verify_second_factor(&user, code).await? {
+                            LoginResult::Success
+                        } else {
+                            LoginResult::WrongTotp
+                        }
+                    }
+                };
+                (result, Some(user))
+            }
+            Err(UserError::RepositoryError(UserRepositoryError::NotFound)) => {
+                (LoginResult::UnknownUser, None)
+            }
+            Err(UserError::WrongPassword) => (LoginResult::WrongPassword, None),
+            Err(e) => return Err(e),
+        };
+
+        self.record_login(limit, attempt_id, username, result, user_failures)
+            .await?;
+
+        user.ok_or(UserError::WrongPassword)
+    }
+
+    /// Verify password sent with every request by clients only supporting
+    /// http basic auth. Failures count toward the login limit like `login`
+    /// but successes are not recorded. User with totp enabled has to use an
+    /// api key instead.
+    pub async fn verify_basic_auth(
+        &self,
+        limit: &LoginLimit,
+        username: &str,
+        password: &str,
+        ip: Option<&str>,
+        user_agent: Option<&str>,
+    ) -> Result<User, UserError> {
+        let (attempt_id, user_failures) = self.begin_login(limit, username, ip, user_agent).await?;
+
+        let result = match self.verify_password(username, password).await {
+            Ok(_) => {
+                self.repo.delete_login_attempt(attempt_id).await?;
+                let user = self.repo.get_user_by_username(username.to_string()).await?;
+                if user.totp_enabled {
+                    return Err(UserError::TotpRequired);
+                }
+                return Ok(user);
+            }
+            Err(UserError::RepositoryError(UserRepositoryError::NotFound)) => {
+                LoginResult::UnknownUser
+            }
+            Err(UserError::WrongPassword) => LoginResult::WrongPassword,
+            Err(e) => return Err(e),
+        };
+
+        self.record_login(limit, attempt_id, username, result, user_failures)
+            .await?;
+
+        Err(UserError::WrongPassword)
+    }
+
+    /// Whether `code` is a totp code not accepted before or an unused
+    /// recovery code, which is used up
+    async fn verify_second_factor(&self, user: &User, code: &str) -> Result<bool, UserError> {
+        let secret = match user.totp_secret.as_deref() {
+            Some(secret) => secret,
+            None => return Ok(false),
+        };
+
+        if let Some(step) = totp::verify(secret, code, Utc::now().timestamp()) {
+            return Ok(self.repo.update_user_totp_last_step(user.id, step).await? > 0);
+        }
+
+        let code_hash = hash_token(&totp::normalize_recovery_code(code));
+        Ok(self
+            .repo
+            .delete_totp_recovery_code(user.id, &code_hash)
+            .await?
+            > 0)
+    }
+
+    /// Start enrolling totp with a new secret, replacing the secret of an
+    /// unfinished enrollment. Returns the secret and its otpauth url.
+    pub async fn setup_totp(&self, user_id: i64) -> Result<(String, String), UserError> {
+        let user = self.repo.get_user_by_id(user_id).await?;
+        if user.totp_enabled {
+            return Err(UserError::Other(
+                "two-factor authentication is already enabled".to_string(),
+            ));
+        }
+
+        let secret = totp::generate_secret();
+        self.repo
+            .update_user_totp(user.id, Some(&secret), false)
+            .await?;
+
+        let url = totp::otpauth_url(TOTP_ISSUER, &user.username, &secret);
+
+        Ok((secret, url))
+    }
+
+    /// Finish enrolling with a code from authenticator app, returns recovery codes
+    pub async fn enable_totp(&self, user_id: i64, code: &str) -> Result<Vec<String>, UserError> {
+        let user = self.repo.get_user_by_id(user_id).await?;
+        if user.totp_enabled {
+            return Err(UserError::Other(
+                "two-factor authentication is already enabled".to_string(),
+            ));
+        }
+
+        let secret = user.totp_secret.ok_or_else(|| {
+            UserError::Other("two-factor authentication setup is not started".to_string())
+        })?;
+        let step =
+            totp::verify(&secret, code, Utc::now().timestamp()).ok_or(UserError::WrongTotpCode)?;
+
+        self.repo
+            .update_user_totp(user.id, Some(&secret), true)
+            .await?;
+        self.repo.update_user_totp_last_step(user.id, step).await?;
+
+        self.create_recovery_codes(user.id).await
+    }
+
+    /// Replace recovery codes, `code` is a totp code or a remaining recovery code
+    pub async fn regenerate_totp_recovery_codes(
+        &self,
+        user_id: i64,
+        code: &str,
+    ) -> Result<Vec<String>, UserError> {
+        let user = self.repo.get_user_by_id(user_id).await?;
+        if !user.totp_enabled {
+            return Err(UserError::Other(
+                "two-factor authentication is not enabled".to_string(),
+            ));
+        }
+
+        if !self.verify_second_factor(&user, code).await? {
+            return Err(UserError::WrongTotpCode);
+        }
+
+        self.create_recovery_codes(user.id).await
+    }
+
+    async fn create_recovery_codes(&self, user_id: i64) -> Result<Vec<String>, UserError> {
+        let codes: Vec<String> = (0..TOTP_RECOVERY_CODES)
+            .map(|_| totp::generate_recovery_code())
+            .collect();
+        let code_hashes: Vec<String> = codes
+            .iter()
+            .map(|code| hash_token(&totp::normalize_recovery_code(code)))
+            .collect();
+
+        self.repo
+            .replace_totp_recovery_codes(user_id, &code_hashes)
+            .await?;
+
+        Ok(codes)
+    }
+
+    pub async fn count_totp_recovery_codes(&self, user_id: i64) -> Result<i64, UserError> {
+        Ok(self.repo.count_totp_recovery_codes(user_id).await?)
+    }
+
+    pub async fn disable_totp(&self, user_id: i64, password: &str) -> Result<(), UserError> {
+        let user = self.repo.get_user_by_id(user_id).await?;
+
+        if !argon2::verify_encoded(&user.password, password.as_bytes())
+            .map_err(|e| UserError::Other(format!("{e}")))?
+        {
+            return Err(UserError::WrongPassword);
+        }
+
+        self.clear_totp(user.id).await
+    }
+
+    /// Turn off totp of a user who lost both authenticator app and recovery codes
+    pub async fn reset_totp(&self, manager_id: i64, user_id: i64) -> Result<(), UserError> {
+        self.check_can_manage(manager_id, user_id).await?;
+
+        self.clear_totp(user_id).await?;
+        info!("two-factor authentication of user {user_id} reset by user {manager_id}");
+
+        Ok(())
+    }
+
+    async fn clear_totp(&self, user_id: i64) -> Result<(), UserError> {
+        self.repo.update_user_totp(user_id, None, false).await?;
+        self.repo.replace_totp_recovery_codes(user_id, &[]).await?;
+
+        Ok(())
+    }
+
+    /// Latest login attempts, optionally only for username
+    pub async fn fetch_login_attempts(
+        &self,
+        username: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<LoginAttempt>, UserError> {
+        Ok(self.repo.get_login_attempts(username, limit).await?)
+    }
+
     pub async fn verify_password(&self, username: &str, password: &str) -> Result<(), UserError> {
         let user = self.repo.get_user_by_username(username.to_owned()).await?;
 
@@ -97,42 +621,164 @@ where
             return Err(UserError::Other("Wrong old password".to_string()));
         }
 
-        if new_password.len() < 8 {
-            return Err(UserError::InsufficientPasswordLength);
-        }
+        self.repo
+            .update_password(user.id, hash_password(new_password)?)
+            .await?;
+        self.repo.delete_sessions_by_user_id(user.id).await?;
 
-        let mut salt: [u8; 32] = [0; 32];
-        rand::thread_rng().fill_bytes(&mut salt);
+        Ok(())
+    }
 
-        let hash = {
-            let config = argon2::Config::default();
-            argon2::hash_encoded(new_password.as_bytes(), &salt, &config)
-                .map_err(|e| UserError::Other(format!("{e}")))?
+    pub async fn fetch_notification_targets(
+        &self,
+        user_id: i64,
+    ) -> Result<Vec<NotificationTarget>, UserError> {
+        Ok(self.repo.get_notification_targets(user_id).await?)
+    }
+
+    pub async fn add_notification_target(
+        &self,
+        user_id: i64,
+        channel: &str,
+        target: &str,
+    ) -> Result<i64, UserError> {
+        let target = target.trim();
+        if target.is_empty() {
+            return Err(UserError::Other("notification target is empty".to_string()));
+        }
+
+        // a telegram chat belongs to one user, other targets like a shared
+        // email address can be added by several users but once by each
+        let added = if channel == telegram::NAME {
+            match self
+                .repo
+                .get_user_id_by_notification_target(channel, target)
+                .await
+            {
+                Ok(_) => true,
+                Err(UserRepositoryError::NotFound) => false,
+                Err(e) => return Err(e.into()),
+            }
+        } else {
+            self.repo
+                .get_notification_targets(user_id)
+                .await?
+                .iter()
+                .any(|t| t.channel == channel && t.target == target)
         };
+        if added {
+            return Err(UserError::Other(
+                "notification target is already added".to_string(),
+            ));
+        }
+
+        Ok(self
+            .repo
+            .insert_notification_target(user_id, channel, target)
+            .await?)
+    }
 
-        self.repo.update_password(user.id, hash).await?;
+    pub async fn remove_notification_target(&self, user_id: i64, id: i64) -> Result<(), UserError> {
+        let rows = self.repo.delete_notification_target(user_id, id).await?;
+        if rows == 0 {
+            return Err(UserError::Other(
+                "notification target not found".to_string(),
+            ));
+        }
 
         Ok(())
     }
 
-    pub async fn update_profile(
+    /// Create one-time code used to link a chat with this user, replaces
+    /// any previous code
+    pub async fn create_notification_link_code(&self, user_id: i64) -> Result<String, UserError> {
+        let mut bytes: [u8; 6] = [0; 6];
+        rand::thread_rng().fill_bytes(&mut bytes);
+
+        let code = bytes.iter().map(|b| format!("{b:02x}")).collect::<String>();
+
+        self.repo
+            .insert_notification_link_code(user_id, &code)
+            .await?;
+
+        Ok(code)
+    }
+
+    /// Redeem link code and add `target` for its user, a target linked to
+    /// another user is moved. Returns the linked user.
+    pub async fn link_notification_target(
+        &self,
+        code: &str,
+        channel: &str,
+        target: &str,
+    ) -> Result<User, UserError> {
+        let (user_id, created_at) = match self.repo.take_notification_link_code(code).await {
+            Ok(res) => res,
+            Err(UserRepositoryError::NotFound) => {
+                return Err(UserError::Other("invalid link code".to_string()));
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        if Utc::now().naive_utc() - created_at > Duration::minutes(LINK_CODE_EXPIRY_MINUTES) {
+            return Err(UserError::Other("link code expired".to_string()));
+        }
+
+        self.repo
+            .upsert_notification_target(user_id, channel, target)
+            .await?;
+
+        Ok(self.repo.get_user_by_id(user_id).await?)
+    }
+
+    pub async fn fetch_user_id_by_notification_target(
+        &self,
+        channel: &str,
+        target: &str,
+    ) -> Result<i64, UserError> {
+        match self
+            .repo
+            .get_user_id_by_notification_target(channel, target)
+            .await
+        {
+            Ok(user_id) => Ok(user_id),
+            Err(UserRepositoryError::NotFound) => Err(UserError::UserNotFound),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub async fn fetch_notification_template(
         &self,
         user_id: i64,
-        telegram_chat_id: Option<i64>,
-        pushover_user_key: Option<String>,
-        gotify_token: Option<String>,
-    ) -> Result<(), UserError> {
-        debug!("update_profile");
+    ) -> Result<Option<NotificationTemplate>, UserError> {
+        Ok(self.repo.get_notification_template(user_id).await?)
+    }
 
-        let mut user = self.repo.get_user_by_id(user_id).await?;
+    pub async fn update_notification_template(
+        &self,
+        user_id: i64,
+        title: &str,
+        body: &str,
+    ) -> Result<(), UserError> {
+        if title.trim().is_empty() && body.trim().is_empty() {
+            return Err(UserError::Other(
+                "notification template is empty".to_string(),
+            ));
+        }
 
-        user.telegram_chat_id = telegram_chat_id;
-        user.pushover_user_key = pushover_user_key;
-        user.gotify_token = gotify_token;
+        let template = NotificationTemplate {
+            title: title.to_string(),
+            body: body.to_string(),
+        };
 
-        self.repo.update_user_setting(&user).await?;
+        Ok(self
+            .repo
+            .upsert_notification_template(user_id, &template)
+            .await?)
+    }
 
-        Ok(())
+    pub async fn reset_notification_template(&self, user_id: i64) -> Result<(), UserError> {
+        Ok(self.repo.delete_notification_template(user_id).await?)
     }
 
     pub async fn fetch_all_users(&self) -> Result<Vec<User>, UserError> {
@@ -146,4 +792,778 @@ where
     pub async fn fetch_user_by_username(&self, username: &str) -> Result<User, UserError> {
         Ok(self.repo.get_user_by_username(username.to_string()).await?)
     }
+
+    /// Find user authenticated by a trusted proxy, creating it on first login
+    /// if `auto_register` is set. Admin flag is replaced with `is_admin` when
+    /// proxy tells group membership.
+    pub async fn provision_external_user(
+        &self,
+        username: &str,
+        is_admin: Option<bool>,
+        auto_register: bool,
+    ) -> Result<User, UserError> {
+        let username = username.trim();
+        if username.is_empty() {
+            return Err(UserError::Other("empty username".to_string()));
+        }
+
+        match self.repo.get_user_by_username(username.to_string()).await {
+            Ok(user) => self.sync_external_admin(user, is_admin).await,
+            Err(UserRepositoryError::NotFound) if auto_register => {
+                self.register_external_user(username, is_admin).await
+            }
+            Err(UserRepositoryError::NotFound) => Err(UserError::UserNotFound),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Find user of identity `subject` at OpenID Connect `issuer`. On first
+    /// login a local user with the same name is only linked to the identity
+    /// if `link_existing` is set, otherwise a new user is created if
+    /// `auto_register` is set. Admin flag is replaced with `is_admin` when
+    /// provider tells group membership.
+    pub async fn provision_oidc_user(
+        &self,
+        issuer: &str,
+        subject: &str,
+        username: &str,
+        is_admin: Option<bool>,
+        auto_register: bool,
+        link_existing: bool,
+    ) -> Result<User, UserError> {
+        if issuer.is_empty() || subject.is_empty() {
+            return Err(UserError::Other("empty issuer or subject".to_string()));
+        }
+
+        match self.repo.get_user_by_identity(issuer, subject).await {
+            Ok(user) => return self.sync_external_admin(user, is_admin).await,
+            Err(UserRepositoryError::NotFound) => {}
+            Err(e) => return Err(e.into()),
+        }
+
+        let username = username.trim();
+        if username.is_empty() {
+            return Err(UserError::Other("empty username".to_string()));
+        }
+
+        let user = match self.repo.get_user_by_username(username.to_string()).await {
+            // another identity could have been given the same name, so name
+            // alone does not prove this is the same person
+            Ok(_) if !link_existing => {
+                return Err(UserError::Other(format!(
+                    "user {username} already exists and is not linked to identity provider"
+                )));
+            }
+            Ok(user) => {
+                info!("linked user {username} to identity provider");
+                self.sync_external_admin(user, is_admin).await?
+            }
+            Err(UserRepositoryError::NotFound) if auto_register => {
+                self.register_external_user(username, is_admin).await?
+            }
+            Err(UserRepositoryError::NotFound) => return Err(UserError::UserNotFound),
+            Err(e) => return Err(e.into()),
+        };
+
+        self.repo
+            .insert_user_identity(user.id, issuer, subject)
+            .await?;
+
+        Ok(user)
+    }
+
+    async fn sync_external_admin(
+        &self,
+        mut user: User,
+        is_admin: Option<bool>,
+    ) -> Result<User, UserError> {
+        if let Some(is_admin) = is_admin.filter(|is_admin| *is_admin != user.is_admin) {
+            info!(
+                "set admin of user {} to {is_admin} from provider groups",
+                user.username
+            );
+            self.repo.update_user_is_admin(user.id, is_admin).await?;
+            user.is_admin = is_admin;
+        }
+
+        Ok(user)
+    }
+
+    async fn register_external_user(
+        &self,
+        username: &str,
+        is_admin: Option<bool>,
+    ) -> Result<User, UserError> {
+        // first user administers the server, same as with registration
+        let is_admin = match is_admin {
+            Some(is_admin) => is_admin,
+            None => self.repo.get_users().await?.is_empty(),
+        };
+
+        // user logs in through provider, so password is random and never shown
+        let id = self
+            .create_user(username, &generate_token(), is_admin)
+            .await?;
+        info!("registered user {username} from identity provider");
+
+        Ok(self.repo.get_user_by_id(id).await?)
+    }
+
+    /// Start a session for user, returns it with its refresh token which is
+    /// only stored hashed
+    pub async fn create_session(
+        &self,
+        user_id: i64,
+        user_agent: Option<&str>,
+    ) -> Result<(Session, String), UserError> {
+        let now = Utc::now().naive_utc();
+        if let Err(e) = self.repo.delete_expired_sessions(now).await {
+            error!("failed to delete expired sessions: {e}");
+        }
+
+        let refresh_token = generate_token();
+        let expires_at = now + Duration::days(REFRESH_TOKEN_EXPIRY_DAYS);
+        let id = self
+            .repo
+            .insert_session(user_id, &hash_token(&refresh_token), user_agent, expires_at)
+            .await?;
+
+        Ok((self.repo.get_session_by_id(id).await?, refresh_token))
+    }
+
+    /// Exchange refresh token for a new one, a refresh token can only be used once
+    pub async fn refresh_session(
+        &self,
+        refresh_token: &str,
+    ) -> Result<(Session, String), UserError> {
+        let hash = hash_token(refresh_token);
+        let session = match self.repo.get_session_by_refresh_token_hash(&hash).await {
+            Ok(session) => session,
+            Err(UserRepositoryError::NotFound) => return Err(UserError::SessionExpired),
+            Err(e) => return Err(e.into()),
+        };
+
+        let now = Utc::now().naive_utc();
+        if session.refresh_token_hash != hash {
+            if now - session.refreshed_at > Duration::seconds(REFRESH_TOKEN_REUSE_GRACE_SECONDS) {
+                warn!(
+                    "replaced refresh token of session {} used again, revoking session",
+                    session.id
+                );
+                self.repo
+                    .delete_session(session.user_id, session.id)
+                    .await?;
+                return Err(UserError::SessionExpired);
+            }
+
+            return Err(UserError::RefreshTokenReused);
+        }
+
+        if session.expires_at < now {
+            self.repo
+                .delete_session(session.user_id, session.id)
+                .await?;
+            return Err(UserError::SessionExpired);
+        }
+
+        let new_refresh_token = generate_token();
+        let rows_affected = self
+            .repo
+            .rotate_session_refresh_token(
+                session.id,
+                &hash,
+                &hash_token(&new_refresh_token),
+                now,
+                now + Duration::days(REFRESH_TOKEN_EXPIRY_DAYS),
+            )
+            .await?;
+        if rows_affected == 0 {
+            return Err(UserError::RefreshTokenReused);
+        }
+
+        Ok((
+            self.repo.get_session_by_id(session.id).await?,
+            new_refresh_token,
+        ))
+    }
+
+    /// Check session of an access token has not been revoked or expired
+    pub async fn verify_session(&self, user_id: i64, session_id: i64) -> Result<(), UserError> {
+        let session = match self.repo.get_session_by_id(session_id).await {
+            Ok(session) => session,
+            Err(UserRepositoryError::NotFound) => return Err(UserError::SessionExpired),
+            Err(e) => return Err(e.into()),
+        };
+
+        if session.user_id != user_id || session.expires_at < Utc::now().naive_utc() {
+            return Err(UserError::SessionExpired);
+        }
+
+        Ok(())
+    }
+
+    pub async fn fetch_sessions(&self, user_id: i64) -> Result<Vec<Session>, UserError> {
+        let now = Utc::now().naive_utc();
+
+        Ok(self
+            .repo
+            .get_sessions_by_user_id(user_id)
+            .await?
+            .into_iter()
+            .filter(|session| session.expires_at >= now)
+            .collect())
+    }
+
+    pub async fn revoke_session(&self, user_id: i64, session_id: i64) -> Result<(), UserError> {
+        if self.repo.delete_session(user_id, session_id).await? == 0 {
+            return Err(UserError::Other("session not found".to_string()));
+        }
+
+        Ok(())
+    }
+
+    /// Log out user from every device, returns number of sessions revoked
+    pub async fn revoke_all_sessions(&self, user_id: i64) -> Result<u64, UserError> {
+        Ok(self.repo.delete_sessions_by_user_id(user_id).await?)
+    }
+
+    /// Create api key for user, returns it with the key which is only stored hashed.
+    /// Every key can read, `scopes` grants additional mutations.
+    pub async fn create_api_key(
+        &self,
+        user_id: i64,
+        name: &str,
+        scopes: &[ApiKeyScope],
+    ) -> Result<(ApiKey, String), UserError> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(UserError::Other("api key name is empty".to_string()));
+        }
+
+        let user = self.repo.get_user_by_id(user_id).await?;
+        // admin scope covers managing sources and users
+        let can_manage = user.has_permission(Permission::ManageSources)
+            || user.has_permission(Permission::ManageUsers);
+        if scopes.contains(&ApiKeyScope::Admin) && !can_manage {
+            return Err(UserError::Forbidden);
+        }
+
+        let mut key_scopes = vec![ApiKeyScope::Read];
+        for scope in scopes {
+            if !key_scopes.contains(scope) {
+                key_scopes.push(*scope);
+            }
+        }
+
+        let key = format!("{API_KEY_PREFIX}{}", generate_token());
+        let prefix = key
+            .chars()
+            .take(API_KEY_PREFIX.len() + 4)
+            .collect::<String>();
+        let id = self
+            .repo
+            .insert_api_key(user.id, name, &hash_token(&key), &prefix, &key_scopes)
+            .await?;
+
+        Ok((self.repo.get_api_key_by_id(id).await?, key))
+    }
+
+    /// Find api key and its user, recording when the key was used
+    pub async fn verify_api_key(&self, key: &str) -> Result<(User, ApiKey), UserError> {
+        let api_key = match self.repo.get_api_key_by_hash(&hash_token(key)).await {
+            Ok(api_key) => api_key,
+            Err(UserRepositoryError::NotFound) => return Err(UserError::InvalidApiKey),
+            Err(e) => return Err(e.into()),
+        };
+
+        let now = Utc::now().naive_utc();
+        let last_used_outdated = api_key
+            .last_used_at
+            .map(|last_used_at| {
+                now - last_used_at > Duration::seconds(API_KEY_LAST_USED_INTERVAL_SECONDS)
+            })
+            .unwrap_or(true);
+        if last_used_outdated {
+            if let Err(e) = self.repo.update_api_key_last_used_at(api_key.id, now).await {
+                error!("failed to update last use of api key {}: {e}", api_key.id);
+            }
+        }
+
+        let user = self.repo.get_user_by_id(api_key.user_id).await?;
+
+        Ok((user, api_key))
+    }
+
+    pub async fn fetch_api_keys(&self, user_id: i64) -> Result<Vec<ApiKey>, UserError> {
+        Ok(self.repo.get_api_keys_by_user_id(user_id).await?)
+    }
+
+    pub async fn revoke_api_key(&self, user_id: i64, id: i64) -> Result<(), UserError> {
+        if self.repo.delete_api_key(user_id, id).await? == 0 {
+            return Err(UserError::Other("api key not found".to_string()));
+        }
+
+        Ok(())
+    }
+}
+
+fn check_can_grant(manager: &User, permissions: &[Permission]) -> Result<(), UserError> {
+    if permissions
+        .iter()
+        .any(|permission| !manager.has_permission(*permission))
+    {
+        return Err(UserError::Forbidden);
+    }
+
+    Ok(())
+}
+
+fn hash_password(password: &str) -> Result<String, UserError> {
+    if password.len() < 8 {
+        return Err(UserError::InsufficientPasswordLength);
+    }
+
+    let mut salt: [u8; 32] = [0; 32];
+    rand::thread_rng().fill_bytes(&mut salt);
+
+    let config = argon2::Config::default();
+    argon2::hash_encoded(password.as_bytes(), &salt, &config)
+        .map_err(|e| UserError::Other(format!("{e}")))
+}
+
+fn generate_token() -> String {
+    let mut bytes: [u8; 32] = [0; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+
+    general_purpose::URL_SAFE_NO_PAD.encode(bytes)
+}
+
+fn hash_token(token: &str) -> String {
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::{sqlite::SqlitePoolOptions, SqlitePool};
+
+    use super::*;
+    use crate::infrastructure::domain::repositories::user::UserRepositoryImpl;
+
+    async fn pool() -> SqlitePool {
+        // in-memory database lives as long as its only connection
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .idle_timeout(None)
+            .max_lifetime(None)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        sqlx::migrate!("./migrations").run(&pool).await.unwrap();
+
+        pool
+    }
+
+    async fn user_service() -> UserService<UserRepositoryImpl> {
+        UserService::new(UserRepositoryImpl::new(pool().await))
+    }
+
+    #[tokio::test]
+    async fn test_update_permissions_only_held_by_manager() {
+        let svc = user_service().await;
+        let admin_id = svc.create_user("admin", "password", true).await.unwrap();
+        let manager_id = svc.create_user("manager", "password", false).await.unwrap();
+        let user_id = svc.create_user("user", "password", false).await.unwrap();
+        svc.update_permissions(
+            admin_id,
+            manager_id,
+            &[Permission::ManageUsers, Permission::Download],
+        )
+        .await
+        .unwrap();
+
+        assert!(matches!(
+            svc.update_permissions(
+                manager_id,
+                user_id,
+                &[Permission::Download, Permission::ManageSources]
+            )
+            .await,
+            Err(UserError::Forbidden)
+        ));
+        assert_eq!(
+            svc.fetch_user_by_id(user_id).await.unwrap().permissions,
+            vec![Permission::BrowseNsfw]
+        );
+
+        svc.update_permissions(manager_id, user_id, &[Permission::Download])
+            .await
+            .unwrap();
+        assert_eq!(
+            svc.fetch_user_by_id(user_id).await.unwrap().permissions,
+            vec![Permission::Download]
+        );
+
+        svc.update_permissions(admin_id, user_id, &[Permission::ManageSources])
+            .await
+            .unwrap();
+        assert_eq!(
+            svc.fetch_user_by_id(user_id).await.unwrap().permissions,
+            vec![Permission::ManageSources]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_invites_restricted_to_manager() {
+        let svc = user_service().await;
+        let admin_id = svc.create_user("admin", "password", true).await.unwrap();
+        let manager_id = svc.create_user("manager", "password", false).await.unwrap();
+        svc.update_permissions(admin_id, manager_id, &[Permission::ManageUsers])
+            .await
+            .unwrap();
+
+        let admin_invite = svc
+            .create_invite(
+                admin_id,
+                Invite {
+                    is_admin: true,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        for invite in [
+            Invite {
+                is_admin: true,
+                ..Default::default()
+            },
+            Invite {
+                permissions: vec![Permission::Download],
+                ..Default::default()
+            },
+        ] {
+            assert!(matches!(
+                svc.create_invite(manager_id, invite).await,
+                Err(UserError::Forbidden)
+            ));
+        }
+        let invite = svc
+            .create_invite(
+                manager_id,
+                Invite {
+                    permissions: vec![Permission::ManageUsers],
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        let invites = svc.fetch_invites(manager_id).await.unwrap();
+        assert_eq!(invites.len(), 1);
+        assert_eq!(invites[0].id, invite.id);
+        assert_eq!(svc.fetch_invites(admin_id).await.unwrap().len(), 2);
+
+        assert!(svc
+            .delete_invite(manager_id, admin_invite.id)
+            .await
+            .is_err());
+        svc.delete_invite(admin_id, admin_invite.id).await.unwrap();
+    }
+
+    fn login_limit(max_failures_per_user: i64, max_failures_per_ip: i64) -> LoginLimit {
+        LoginLimit {
+            max_failures_per_user,
+            max_failures_per_ip,
+            window: Duration::hours(1),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_login_locked_after_user_failures() {
+        let svc = user_service().await;
+        let limit = login_limit(3, 0);
+        svc.create_user("user", "password", false).await.unwrap();
+
+        for _ in 0..2 {
+            assert!(matches!(
+                svc.login(&limit, "user", "wrong", None, Some("10.0.0.1"), None)
+                    .await,
+                Err(UserError::WrongPassword)
+            ));
+        }
+        assert!(matches!(
+            svc.login(&limit, "user", "wrong", None, Some("10.0.0.2"), None).await,
+            Err(UserError::AccountLocked(username)) if username == "user"
+        ));
+        assert!(matches!(
+            svc.login(&limit, "user", "password", None, Some("10.0.0.3"), None)
+                .await,
+            Err(UserError::LoginLocked)
+        ));
+
+        let attempts = svc.fetch_login_attempts(Some("user"), 10).await.unwrap();
+        assert_eq!(attempts.len(), 4);
+        assert_eq!(attempts[0].result, LoginResult::Locked);
+        assert_eq!(attempts[1].result, LoginResult::WrongPassword);
+    }
+
+    #[tokio::test]
+    async fn test_login_locked_after_ip_failures() {
+        let svc = user_service().await;
+        let limit = login_limit(0, 2);
+        svc.create_user("user", "password", false).await.unwrap();
+
+        for username in ["admin", "root"] {
+            assert!(svc
+                .login(&limit, username, "password", None, Some("10.0.0.1"), None)
+                .await
+                .is_err());
+        }
+        assert!(matches!(
+            svc.login(&limit, "user", "password", None, Some("10.0.0.1"), None)
+                .await,
+            Err(UserError::LoginLocked)
+        ));
+        svc.login(&limit, "user", "password", None, Some("10.0.0.2"), None)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_login_success_resets_user_failures() {
+        let svc = user_service().await;
+        let limit = login_limit(3, 0);
+        svc.create_user("user", "password", false).await.unwrap();
+
+        for password in ["wrong", "wrong", "password", "wrong", "wrong"] {
+            let res = svc.login(&limit, "user", password, None, None, None).await;
+            assert_eq!(res.is_ok(), password == "password");
+        }
+        svc.login(&limit, "user", "password", None, None, None)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_login_counts_pending_attempts() {
+        let svc = user_service().await;
+        let limit = login_limit(2, 0);
+        svc.create_user("user", "password", false).await.unwrap();
+
+        // attempts of concurrent logins still verifying their password
+        let now = Utc::now().naive_utc();
+        for _ in 0..2 {
+            svc.repo
+                .insert_login_attempt("user", None, None, LoginResult::Pending, now)
+                .await
+                .unwrap();
+        }
+
+        assert!(matches!(
+            svc.login(&limit, "user", "password", None, None, None)
+                .await,
+            Err(UserError::LoginLocked)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_login_deletes_old_attempts() {
+        let svc = user_service().await;
+        let limit = login_limit(3, 0);
+        svc.create_user("user", "password", false).await.unwrap();
+
+        let created_at = Utc::now().naive_utc() - Duration::days(LOGIN_ATTEMPT_RETENTION_DAYS + 1);
+        svc.repo
+            .insert_login_attempt("user", None, None, LoginResult::WrongPassword, created_at)
+            .await
+            .unwrap();
+
+        svc.login(&limit, "user", "password", None, None, None)
+            .await
+            .unwrap();
+
+        let attempts = svc.fetch_login_attempts(Some("user"), 10).await.unwrap();
+        assert_eq!(attempts.len(), 1);
+        assert_eq!(attempts[0].result, LoginResult::Success);
+    }
+
+    #[tokio::test]
+    async fn test_provision_oidc_user_by_issuer_and_subject() {
+        let svc = user_service().await;
+        let local_id = svc.create_user("alice", "password", false).await.unwrap();
+
+        // same name at provider does not take over local user unless linking is enabled
+        assert!(svc
+            .provision_oidc_user("https://idp", "sub-1", "alice", None, true, false)
+            .await
+            .is_err());
+
+        let user = svc
+            .provision_oidc_user("https://idp", "sub-1", "alice", None, true, true)
+            .await
+            .unwrap();
+        assert_eq!(user.id, local_id);
+
+        // identity is found by subject even after renaming at provider
+        let user = svc
+            .provision_oidc_user("https://idp", "sub-1", "alice2", None, true, false)
+            .await
+            .unwrap();
+        assert_eq!(user.id, local_id);
+
+        let user = svc
+            .provision_oidc_user("https://idp", "sub-2", "bob", Some(true), true, false)
+            .await
+            .unwrap();
+        assert_ne!(user.id, local_id);
+        assert!(user.is_admin);
+
+        assert!(matches!(
+            svc.provision_oidc_user("https://other", "sub-1", "carol", None, false, false)
+                .await,
+            Err(UserError::UserNotFound)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_refresh_session_rotates_refresh_token() {
+        let svc = user_service().await;
+        let user_id = svc.create_user("user", "password", false).await.unwrap();
+        let (session, refresh_token) = svc.create_session(user_id, Some("test")).await.unwrap();
+        svc.verify_session(user_id, session.id).await.unwrap();
+
+        let (refreshed, new_refresh_token) = svc.refresh_session(&refresh_token).await.unwrap();
+        assert_eq!(refreshed.id, session.id);
+        assert_ne!(new_refresh_token, refresh_token);
+        svc.verify_session(user_id, session.id).await.unwrap();
+
+        // replaced token used again within grace window, e.g. by a concurrent
+        // request, is rejected without ending the session
+        assert!(matches!(
+            svc.refresh_session(&refresh_token).await,
+            Err(UserError::RefreshTokenReused)
+        ));
+        svc.verify_session(user_id, session.id).await.unwrap();
+
+        svc.refresh_session(&new_refresh_token).await.unwrap();
+        assert!(matches!(
+            svc.refresh_session(&new_refresh_token).await,
+            Err(UserError::RefreshTokenReused)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_refresh_session_reused_after_grace_window_revokes_session() {
+        let pool = pool().await;
+        let svc = UserService::new(UserRepositoryImpl::new(pool.clone()));
+        let user_id = svc.create_user("user", "password", false).await.unwrap();
+        let (session, refresh_token) = svc.create_session(user_id, None).await.unwrap();
+        let (_, new_refresh_token) = svc.refresh_session(&refresh_token).await.unwrap();
+
+        let refreshed_at =
+            Utc::now().naive_utc() - Duration::seconds(REFRESH_TOKEN_REUSE_GRACE_SECONDS + 1);
+        sqlx::query("UPDATE session SET refreshed_at = ? WHERE id = ?")
+            .bind(refreshed_at)
+            .bind(session.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        // replaced token may have been stolen, so session is ended for everyone
+        assert!(matches!(
+            svc.refresh_session(&refresh_token).await,
+            Err(UserError::SessionExpired)
+        ));
+        assert!(matches!(
+            svc.verify_session(user_id, session.id).await,
+            Err(UserError::SessionExpired)
+        ));
+        assert!(matches!(
+            svc.refresh_session(&new_refresh_token).await,
+            Err(UserError::SessionExpired)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_revoked_and_expired_sessions() {
+        let pool = pool().await;
+        let svc = UserService::new(UserRepositoryImpl::new(pool.clone()));
+        let user_id = svc.create_user("user", "password", false).await.unwrap();
+        let other_id = svc.create_user("other", "password", false).await.unwrap();
+
+        let (session, refresh_token) = svc.create_session(user_id, None).await.unwrap();
+        assert!(matches!(
+            svc.verify_session(other_id, session.id).await,
+            Err(UserError::SessionExpired)
+        ));
+        assert!(svc.revoke_session(other_id, session.id).await.is_err());
+
+        svc.revoke_session(user_id, session.id).await.unwrap();
+        assert!(matches!(
+            svc.verify_session(user_id, session.id).await,
+            Err(UserError::SessionExpired)
+        ));
+        assert!(matches!(
+            svc.refresh_session(&refresh_token).await,
+            Err(UserError::SessionExpired)
+        ));
+
+        let (session, refresh_token) = svc.create_session(user_id, None).await.unwrap();
+        sqlx::query("UPDATE session SET expires_at = ? WHERE id = ?")
+            .bind(Utc::now().naive_utc() - Duration::seconds(1))
+            .bind(session.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert!(matches!(
+            svc.verify_session(user_id, session.id).await,
+            Err(UserError::SessionExpired)
+        ));
+        assert!(matches!(
+            svc.refresh_session(&refresh_token).await,
+            Err(UserError::SessionExpired)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_notification_targets_shared_except_telegram() {
+        let svc = user_service().await;
+        let user_id = svc.create_user("user", "password", false).await.unwrap();
+        let other_id = svc.create_user("other", "password", false).await.unwrap();
+
+        svc.add_notification_target(user_id, "email", "home@example.com")
+            .await
+            .unwrap();
+        svc.add_notification_target(other_id, "email", "home@example.com")
+            .await
+            .unwrap();
+        assert!(svc
+            .add_notification_target(user_id, "email", "home@example.com")
+            .await
+            .is_err());
+
+        let code = svc.create_notification_link_code(user_id).await.unwrap();
+        svc.link_notification_target(&code, telegram::NAME, "1")
+            .await
+            .unwrap();
+        let code = svc.create_notification_link_code(other_id).await.unwrap();
+        svc.link_notification_target(&code, telegram::NAME, "1")
+            .await
+            .unwrap();
+        assert_eq!(
+            svc.fetch_user_id_by_notification_target(telegram::NAME, "1")
+                .await
+                .unwrap(),
+            other_id
+        );
+        assert!(!svc
+            .fetch_notification_targets(user_id)
+            .await
+            .unwrap()
+            .iter()
+            .any(|target| target.channel == telegram::NAME));
+    }
 }
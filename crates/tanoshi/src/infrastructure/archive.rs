@@ -0,0 +1,544 @@
+use anyhow::{anyhow, bail, Result};
+use chrono::{Datelike, NaiveDateTime};
+use serde::Deserialize;
+
+use crate::domain::entities::image::Image;
+
+/// File extension of image content type, `None` if it is not known
+pub fn image_extension(content_type: &str) -> Option<&'static str> {
+    match content_type {
+        "image/jpeg" => Some("jpg"),
+        "image/png" => Some("png"),
+        "image/webp" => Some("webp"),
+        "image/avif" => Some("avif"),
+        "image/gif" => Some("gif"),
+        _ => None,
+    }
+}
+
+/// Escape text for xml element content and attribute values
+pub fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ArchiveFormat {
+    #[default]
+    Cbz,
+    Epub,
+}
+
+impl ArchiveFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ArchiveFormat::Cbz => "application/vnd.comicbook+zip",
+            ArchiveFormat::Epub => "application/epub+zip",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ArchiveFormat::Cbz => "cbz",
+            ArchiveFormat::Epub => "epub",
+        }
+    }
+}
+
+/// Metadata of archive, written as `ComicInfo.xml` in cbz and as package
+/// metadata in epub
+#[derive(Debug, Default, Clone)]
+pub struct ComicInfo {
+    pub title: String,
+    pub series: String,
+    /// chapter number, only set when archive has a single chapter
+    pub number: Option<String>,
+    pub summary: Option<String>,
+    pub writers: Vec<String>,
+    pub genres: Vec<String>,
+    pub scan_information: Option<String>,
+    pub released: Option<NaiveDateTime>,
+    pub page_count: usize,
+}
+
+impl ComicInfo {
+    pub fn to_xml(&self) -> String {
+        let mut xml = String::from(
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+             <ComicInfo xmlns:xsd=\"http://www.w3.org/2001/XMLSchema\" \
+             xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\">\n",
+        );
+        let mut element = |name: &str, value: &str| {
+            if !value.is_empty() {
+                xml.push_str(&format!("  <{name}>{}</{name}>\n", escape(value)));
+            }
+        };
+
+        element("Title", &self.title);
+        element("Series", &self.series);
+        element("Number", self.number.as_deref().unwrap_or_default());
+        element("Summary", self.summary.as_deref().unwrap_or_default());
+        if let Some(released) = &self.released {
+            element("Year", &released.year().to_string());
+            element("Month", &released.month().to_string());
+            element("Day", &released.day().to_string());
+        }
+        element("Writer", &self.writers.join(", "));
+        element("Genre", &self.genres.join(", "));
+        element(
+            "ScanInformation",
+            self.scan_information.as_deref().unwrap_or_default(),
+        );
+        element("PageCount", &self.page_count.to_string());
+        element("Manga", "Yes");
+
+        xml.push_str("</ComicInfo>");
+        xml
+    }
+}
+
+/// Entry of zip, kept for central directory
+struct ZipEntry {
+    name: String,
+    crc: u32,
+    size: u32,
+    offset: u32,
+}
+
+/// Zip of uncompressed entries written front to back. Size and checksum of an
+/// entry are known before it's written, so no header has to be rewritten and
+/// output can be sent while archive is being written. Archive is limited to
+/// 4 GiB and 65535 entries, zip64 is not supported.
+struct StreamingZip {
+    output: Vec<u8>,
+    /// bytes written including those already taken
+    offset: u64,
+    entries: Vec<ZipEntry>,
+}
+
+impl StreamingZip {
+    const VERSION: u16 = 20;
+    /// 1980-01-01 in dos format, same as zip crate default
+    const DATE: u16 = (1 << 5) | 1;
+
+    fn new() -> Self {
+        Self {
+            output: vec![],
+            offset: 0,
+            entries: vec![],
+        }
+    }
+
+    fn too_large() -> anyhow::Error {
+        anyhow!("archive is larger than 4 GiB")
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        self.output.extend_from_slice(bytes);
+        self.offset += bytes.len() as u64;
+    }
+
+    fn write_u16(&mut self, value: u16) {
+        self.write(&value.to_le_bytes());
+    }
+
+    fn write_u32(&mut self, value: u32) {
+        self.write(&value.to_le_bytes());
+    }
+
+    fn add_file(&mut self, name: &str, data: &[u8]) -> Result<()> {
+        let offset = u32::try_from(self.offset).map_err(|_| Self::too_large())?;
+        let size = u32::try_from(data.len()).map_err(|_| Self::too_large())?;
+        if self.entries.len() >= u16::MAX as usize {
+            bail!("archive has too many files");
+        }
+        let crc = crc32fast::hash(data);
+
+        // local file header
+        self.write_u32(0x04034b50);
+        self.write_u16(Self::VERSION);
+        self.write_u16(0); // flags
+        self.write_u16(0); // stored
+        self.write_u16(0); // time
+        self.write_u16(Self::DATE);
+        self.write_u32(crc);
+        self.write_u32(size);
+        self.write_u32(size);
+        self.write_u16(name.len() as u16);
+        self.write_u16(0); // extra field length
+        self.write(name.as_bytes());
+        self.write(data);
+
+        self.entries.push(ZipEntry {
+            name: name.to_string(),
+            crc,
+            size,
+            offset,
+        });
+
+        Ok(())
+    }
+
+    /// Bytes written since last taken
+    fn take_output(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.output)
+    }
+
+    /// Write central directory, returning rest of output
+    fn finish(mut self) -> Result<Vec<u8>> {
+        let directory_offset = u32::try_from(self.offset).map_err(|_| Self::too_large())?;
+
+        let entries = std::mem::take(&mut self.entries);
+        for entry in &entries {
+            self.write_u32(0x02014b50);
+            self.write_u16(Self::VERSION); // made by
+            self.write_u16(Self::VERSION); // needed to extract
+            self.write_u16(0); // flags
+            self.write_u16(0); // stored
+            self.write_u16(0); // time
+            self.write_u16(Self::DATE);
+            self.write_u32(entry.crc);
+            self.write_u32(entry.size);
+            self.write_u32(entry.size);
+            self.write_u16(entry.name.len() as u16);
+            self.write_u16(0); // extra field length
+            self.write_u16(0); // comment length
+            self.write_u16(0); // disk number
+            self.write_u16(0); // internal attributes
+            self.write_u32(0); // external attributes
+            self.write_u32(entry.offset);
+            self.write(entry.name.as_bytes());
+        }
+
+        let directory_size =
+            u32::try_from(self.offset - directory_offset as u64).map_err(|_| Self::too_large())?;
+
+        // end of central directory
+        self.write_u32(0x06054b50);
+        self.write_u16(0); // disk number
+        self.write_u16(0); // disk with central directory
+        self.write_u16(entries.len() as u16);
+        self.write_u16(entries.len() as u16);
+        self.write_u32(directory_size);
+        self.write_u32(directory_offset);
+        self.write_u16(0); // comment length
+
+        Ok(self.output)
+    }
+}
+
+/// Archive of chapters written a chapter at a time, written output can be
+/// taken between chapters. Pages are named by their position so readers keep
+/// the order and are stored as is since they are already compressed.
+/// Chapters get their own folder when there are more than one.
+pub struct ArchiveWriter {
+    format: ArchiveFormat,
+    zip: StreamingZip,
+    chapter_count: usize,
+    /// title and page file names of each chapter added, for epub package
+    chapters: Vec<(String, Vec<String>)>,
+}
+
+impl ArchiveWriter {
+    pub fn new(format: ArchiveFormat, chapter_count: usize) -> Result<Self> {
+        let mut zip = StreamingZip::new();
+        if format == ArchiveFormat::Epub {
+            // mimetype has to be the first entry
+            zip.add_file("mimetype", b"application/epub+zip")?;
+            zip.add_file("META-INF/container.xml", EPUB_CONTAINER.as_bytes())?;
+        }
+
+        Ok(Self {
+            format,
+            zip,
+            chapter_count,
+            chapters: vec![],
+        })
+    }
+
+    pub fn add_chapter(&mut self, title: &str, pages: &[Image]) -> Result<()> {
+        let chapter = self.chapters.len() + 1;
+        let folder = if self.chapter_count > 1 {
+            let width = self.chapter_count.to_string().len().max(3);
+            format!("{chapter:0width$}/")
+        } else {
+            "".to_string()
+        };
+        let prefix = match self.format {
+            ArchiveFormat::Cbz => folder,
+            ArchiveFormat::Epub => format!("OEBPS/images/{folder}"),
+        };
+
+        let width = pages.len().to_string().len().max(3);
+        let mut file_names = vec![];
+        for (index, page) in pages.iter().enumerate() {
+            let extension = image_extension(&page.content_type).unwrap_or("jpg");
+            let file_name = format!("{prefix}{:0width$}.{extension}", index + 1);
+            self.zip.add_file(&file_name, &page.data)?;
+            file_names.push(file_name);
+        }
+
+        if self.format == ArchiveFormat::Epub {
+            for (page, file_name) in file_names.iter().enumerate() {
+                let image = file_name.trim_start_matches("OEBPS/");
+                self.zip.add_file(
+                    &format!("OEBPS/pages/{chapter}-{}.xhtml", page + 1),
+                    epub_page(title, &format!("../{image}")).as_bytes(),
+                )?;
+            }
+        }
+
+        self.chapters.push((title.to_string(), file_names));
+
+        Ok(())
+    }
+
+    /// Archive written since output was last taken
+    pub fn take_output(&mut self) -> Vec<u8> {
+        self.zip.take_output()
+    }
+
+    /// Write metadata and finish archive, returning output not taken yet
+    pub fn finish(mut self, info: &ComicInfo) -> Result<Vec<u8>> {
+        match self.format {
+            ArchiveFormat::Cbz => {
+                self.zip
+                    .add_file("ComicInfo.xml", info.to_xml().as_bytes())?;
+            }
+            ArchiveFormat::Epub => {
+                let package = self.epub_package(info);
+                let nav = self.epub_nav(info);
+                self.zip.add_file("OEBPS/content.opf", package.as_bytes())?;
+                self.zip.add_file("OEBPS/nav.xhtml", nav.as_bytes())?;
+            }
+        }
+
+        self.zip.finish()
+    }
+
+    fn epub_package(&self, info: &ComicInfo) -> String {
+        let mut manifest = String::from(
+            "    <item id=\"nav\" href=\"nav.xhtml\" media-type=\"application/xhtml+xml\" properties=\"nav\"/>\n",
+        );
+        let mut spine = String::new();
+        for (chapter, (_, file_names)) in self.chapters.iter().enumerate() {
+            for (page, file_name) in file_names.iter().enumerate() {
+                let id = format!("c{}-p{}", chapter + 1, page + 1);
+                let media_type = match file_name.rsplit_once('.') {
+                    Some((_, "png")) => "image/png",
+                    Some((_, "webp")) => "image/webp",
+                    Some((_, "avif")) => "image/avif",
+                    Some((_, "gif")) => "image/gif",
+                    _ => "image/jpeg",
+                };
+                manifest.push_str(&format!(
+                    "    <item id=\"img-{id}\" href=\"{}\" media-type=\"{media_type}\"/>\n",
+                    escape(file_name.trim_start_matches("OEBPS/"))
+                ));
+                manifest.push_str(&format!(
+                    "    <item id=\"{id}\" href=\"pages/{}-{}.xhtml\" media-type=\"application/xhtml+xml\"/>\n",
+                    chapter + 1,
+                    page + 1
+                ));
+                spine.push_str(&format!("    <itemref idref=\"{id}\"/>\n"));
+            }
+        }
+
+        let mut metadata = format!(
+            "    <dc:identifier id=\"id\">urn:tanoshi:{}</dc:identifier>\n\
+             \x20   <dc:title>{}</dc:title>\n\
+             \x20   <dc:language>und</dc:language>\n\
+             \x20   <meta property=\"dcterms:modified\">{}</meta>\n",
+            escape(&format!("{}:{}", info.series, info.title)),
+            escape(&info.title),
+            chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ")
+        );
+        for writer in &info.writers {
+            metadata.push_str(&format!(
+                "    <dc:creator>{}</dc:creator>\n",
+                escape(writer)
+            ));
+        }
+        for genre in &info.genres {
+            metadata.push_str(&format!("    <dc:subject>{}</dc:subject>\n", escape(genre)));
+        }
+        if let Some(summary) = &info.summary {
+            metadata.push_str(&format!(
+                "    <dc:description>{}</dc:description>\n",
+                escape(summary)
+            ));
+        }
+        if let Some(released) = &info.released {
+            metadata.push_str(&format!(
+                "    <dc:date>{}</dc:date>\n",
+                released.format("%Y-%m-%d")
+            ));
+        }
+        metadata.push_str("    <meta property=\"rendition:layout\">pre-paginated</meta>\n");
+
+        format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <package xmlns=\"http://www.idpf.org/2007/opf\" version=\"3.0\" unique-identifier=\"id\">\n\
+             \x20 <metadata xmlns:dc=\"http://purl.org/dc/elements/1.1/\">\n{metadata}  </metadata>\n\
+             \x20 <manifest>\n{manifest}  </manifest>\n\
+             \x20 <spine>\n{spine}  </spine>\n\
+             </package>"
+        )
+    }
+
+    fn epub_nav(&self, info: &ComicInfo) -> String {
+        let items: String = self
+            .chapters
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, file_names))| !file_names.is_empty())
+            .map(|(index, (title, _))| {
+                format!(
+                    "      <li><a href=\"pages/{}-1.xhtml\">{}</a></li>\n",
+                    index + 1,
+                    escape(title)
+                )
+            })
+            .collect();
+
+        format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <html xmlns=\"http://www.w3.org/1999/xhtml\" xmlns:epub=\"http://www.idpf.org/2007/ops\">\n\
+             <head><title>{}</title></head>\n\
+             <body>\n\
+             \x20 <nav epub:type=\"toc\">\n\
+             \x20   <ol>\n{items}    </ol>\n\
+             \x20 </nav>\n\
+             </body>\n\
+             </html>",
+            escape(&info.title)
+        )
+    }
+}
+
+const EPUB_CONTAINER: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<container version=\"1.0\" xmlns=\"urn:oasis:names:tc:opendocument:xmlns:container\">
+  <rootfiles>
+    <rootfile full-path=\"OEBPS/content.opf\" media-type=\"application/oebps-package+xml\"/>
+  </rootfiles>
+</container>";
+
+fn epub_page(title: &str, image: &str) -> String {
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <html xmlns=\"http://www.w3.org/1999/xhtml\">\n\
+         <head><title>{}</title>\
+         <style>body{{margin:0}}img{{display:block;width:100%;height:100%;object-fit:contain}}</style>\
+         </head>\n\
+         <body><img src=\"{}\" alt=\"\"/></body>\n\
+         </html>",
+        escape(title),
+        escape(image)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+    use std::io::{Cursor, Read};
+    use zip::ZipArchive;
+
+    fn pages() -> Vec<Image> {
+        vec![
+            Image {
+                content_type: "image/png".to_string(),
+                data: Bytes::from_static(b"first"),
+            },
+            Image {
+                content_type: "application/octet-stream".to_string(),
+                data: Bytes::from_static(b"second"),
+            },
+        ]
+    }
+
+    #[test]
+    fn test_write_cbz() {
+        let info = ComicInfo {
+            title: "Chapter 1 & 2".to_string(),
+            series: "Series".to_string(),
+            page_count: 2,
+            ..Default::default()
+        };
+
+        let mut writer = ArchiveWriter::new(ArchiveFormat::Cbz, 1).unwrap();
+        writer.add_chapter("Chapter 1 & 2", &pages()).unwrap();
+        let data = writer.finish(&info).unwrap();
+        let mut zip = ZipArchive::new(Cursor::new(data)).unwrap();
+
+        assert_eq!(zip.len(), 3);
+        assert_eq!(zip.by_index(0).unwrap().name(), "001.png");
+        assert_eq!(zip.by_index(1).unwrap().name(), "002.jpg");
+
+        let mut comic_info = String::new();
+        zip.by_name("ComicInfo.xml")
+            .unwrap()
+            .read_to_string(&mut comic_info)
+            .unwrap();
+        assert!(comic_info.contains("<Title>Chapter 1 &amp; 2</Title>"));
+        assert!(comic_info.contains("<PageCount>2</PageCount>"));
+        assert!(!comic_info.contains("<Number>"));
+    }
+
+    #[test]
+    fn test_write_cbz_chapter_folders() {
+        let mut writer = ArchiveWriter::new(ArchiveFormat::Cbz, 2).unwrap();
+        writer.add_chapter("Chapter 1", &pages()).unwrap();
+        writer.add_chapter("Chapter 2", &pages()[..1]).unwrap();
+        let data = writer.finish(&ComicInfo::default()).unwrap();
+        let zip = ZipArchive::new(Cursor::new(data)).unwrap();
+
+        let names: Vec<&str> = zip.file_names().collect();
+        assert!(names.contains(&"001/001.png"));
+        assert!(names.contains(&"001/002.jpg"));
+        assert!(names.contains(&"002/001.png"));
+    }
+
+    #[test]
+    fn test_write_cbz_taken_output() {
+        let mut writer = ArchiveWriter::new(ArchiveFormat::Cbz, 2).unwrap();
+        writer.add_chapter("Chapter 1", &pages()).unwrap();
+        let mut data = writer.take_output();
+        writer.add_chapter("Chapter 2", &pages()).unwrap();
+        data.extend(writer.take_output());
+        assert!(writer.take_output().is_empty());
+        data.extend(writer.finish(&ComicInfo::default()).unwrap());
+        let mut zip = ZipArchive::new(Cursor::new(data)).unwrap();
+
+        assert_eq!(zip.len(), 5);
+        let mut page = vec![];
+        zip.by_name("002/002.jpg")
+            .unwrap()
+            .read_to_end(&mut page)
+            .unwrap();
+        assert_eq!(page, b"second");
+    }
+
+    #[test]
+    fn test_write_epub() {
+        let mut writer = ArchiveWriter::new(ArchiveFormat::Epub, 1).unwrap();
+        writer.add_chapter("Chapter 1", &pages()).unwrap();
+        let data = writer.finish(&ComicInfo::default()).unwrap();
+        let mut zip = ZipArchive::new(Cursor::new(data)).unwrap();
+
+        assert_eq!(zip.by_index(0).unwrap().name(), "mimetype");
+
+        let mut package = String::new();
+        zip.by_name("OEBPS/content.opf")
+            .unwrap()
+            .read_to_string(&mut package)
+            .unwrap();
+        assert!(package.contains("href=\"images/001.png\" media-type=\"image/png\""));
+        assert!(package.contains("<itemref idref=\"c1-p2\"/>"));
+        assert!(zip.by_name("OEBPS/pages/1-2.xhtml").is_ok());
+    }
+}
@@ -1,6 +1,12 @@
 use anyhow::Result;
 use jsonwebtoken::{DecodingKey, EncodingKey, Header, Validation};
 use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+
+use crate::domain::entities::user::ApiKeyScope;
+
+/// Lifetime of access tokens in seconds, clients renew them with their refresh token
+pub const ACCESS_TOKEN_EXPIRY: u64 = 15 * 60;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
@@ -8,6 +14,12 @@ pub struct Claims {
     pub username: String,
     pub is_admin: bool,
     pub exp: usize,
+    /// session the token is issued for, 0 for users authenticated by trusted proxy header
+    /// or api key
+    pub sid: i64,
+    /// scopes of api key the request is authenticated with, `None` for login sessions
+    #[serde(skip)]
+    pub scopes: Option<Vec<ApiKeyScope>>,
 }
 
 pub fn decode_jwt(secret: &str, token: &str) -> Result<Claims> {
@@ -26,3 +38,96 @@ pub fn encode_jwt(secret: &str, claims: &Claims) -> Result<String> {
         &EncodingKey::from_secret(secret.as_bytes()),
     )?)
 }
+
+/// Whether `ip` matches one of `trusted_proxies`, given as addresses or cidr ranges
+pub fn is_trusted_proxy(ip: IpAddr, trusted_proxies: &[String]) -> bool {
+    // dual stack listener sees ipv4 peers as mapped ipv6 addresses
+    let ip = match ip {
+        IpAddr::V6(ip) => ip
+            .to_ipv4_mapped()
+            .map(IpAddr::V4)
+            .unwrap_or(IpAddr::V6(ip)),
+        ip => ip,
+    };
+
+    trusted_proxies.iter().any(|proxy| {
+        let (addr, prefix) = match proxy.trim().split_once('/') {
+            Some((addr, prefix)) => match prefix.parse::<u32>() {
+                Ok(prefix) => (addr, Some(prefix)),
+                Err(e) => {
+                    warn!("invalid trusted proxy {proxy}: {e}");
+                    return false;
+                }
+            },
+            None => (proxy.trim(), None),
+        };
+
+        match (addr.parse::<IpAddr>(), ip) {
+            (Ok(IpAddr::V4(addr)), IpAddr::V4(ip)) => {
+                let prefix = prefix.unwrap_or(32).min(32);
+                let mask = u32::MAX.checked_shl(32 - prefix).unwrap_or(0);
+                u32::from(addr) & mask == u32::from(ip) & mask
+            }
+            (Ok(IpAddr::V6(addr)), IpAddr::V6(ip)) => {
+                let prefix = prefix.unwrap_or(128).min(128);
+                let mask = u128::MAX.checked_shl(128 - prefix).unwrap_or(0);
+                u128::from(addr) & mask == u128::from(ip) & mask
+            }
+            (Err(e), _) => {
+                warn!("invalid trusted proxy {proxy}: {e}");
+                false
+            }
+            _ => false,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn proxies(proxies: &[&str]) -> Vec<String> {
+        proxies.iter().map(|proxy| proxy.to_string()).collect()
+    }
+
+    #[test]
+    fn test_trusted_proxy_address() {
+        let trusted = proxies(&["10.0.0.2", "::1"]);
+
+        assert!(is_trusted_proxy("10.0.0.2".parse().unwrap(), &trusted));
+        assert!(is_trusted_proxy("::1".parse().unwrap(), &trusted));
+        assert!(!is_trusted_proxy("10.0.0.3".parse().unwrap(), &trusted));
+        assert!(!is_trusted_proxy("127.0.0.1".parse().unwrap(), &trusted));
+    }
+
+    #[test]
+    fn test_trusted_proxy_cidr() {
+        let trusted = proxies(&["172.16.0.0/12", "fd00::/8", "0.0.0.0/0"]);
+
+        assert!(is_trusted_proxy("172.20.1.5".parse().unwrap(), &trusted));
+        assert!(is_trusted_proxy("fd12::1".parse().unwrap(), &trusted));
+        assert!(is_trusted_proxy("8.8.8.8".parse().unwrap(), &trusted));
+        assert!(!is_trusted_proxy("fe80::1".parse().unwrap(), &trusted));
+
+        let trusted = proxies(&["192.168.1.0/24"]);
+        assert!(is_trusted_proxy("192.168.1.200".parse().unwrap(), &trusted));
+        assert!(!is_trusted_proxy("192.168.2.1".parse().unwrap(), &trusted));
+    }
+
+    #[test]
+    fn test_trusted_proxy_ipv4_mapped() {
+        let trusted = proxies(&["127.0.0.1"]);
+
+        assert!(is_trusted_proxy(
+            "::ffff:127.0.0.1".parse().unwrap(),
+            &trusted
+        ));
+    }
+
+    #[test]
+    fn test_trusted_proxy_invalid() {
+        let trusted = proxies(&["proxy.local", "10.0.0.1/abc"]);
+
+        assert!(!is_trusted_proxy("10.0.0.1".parse().unwrap(), &trusted));
+    }
+}
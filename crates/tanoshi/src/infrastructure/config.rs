@@ -4,6 +4,8 @@ use serde::{Deserialize, Serialize};
 use std::path::Path;
 use std::{iter, path::PathBuf};
 
+use crate::domain::services::user::LoginLimit;
+
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct TelegramConfig {
     pub name: String,
@@ -20,6 +22,250 @@ pub struct GotifyConfig {
     pub base_url: String,
 }
 
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct WebhookConfig {
+    #[serde(default)]
+    pub payload_template: Option<String>,
+    /// let users post to loopback and private network addresses
+    #[serde(default)]
+    pub allow_private_addresses: bool,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct DiscordConfig {
+    /// let users post to loopback and private network addresses
+    #[serde(default)]
+    pub allow_private_addresses: bool,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct NtfyConfig {
+    #[serde(default = "default_ntfy_base_url")]
+    pub base_url: String,
+    #[serde(default)]
+    pub token: Option<String>,
+    /// let users publish to topic urls on loopback and private network addresses
+    #[serde(default)]
+    pub allow_private_addresses: bool,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct MatrixConfig {
+    pub homeserver_url: String,
+    pub access_token: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct EmailConfig {
+    pub host: String,
+    #[serde(default)]
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+    pub from: String,
+    #[serde(default)]
+    pub starttls: bool,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct NotificationTemplateConfig {
+    pub title: String,
+    pub body: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct UpdateCheckConfig {
+    /// check for new tanoshi release
+    #[serde(default = "default_true")]
+    pub server: bool,
+    /// url returning latest release in github release api format
+    #[serde(default = "default_server_release_url")]
+    pub server_release_url: String,
+    /// check installed extensions against extension repository
+    #[serde(default = "default_true")]
+    pub extension: bool,
+    /// interval between checks in seconds, 0 to disable
+    #[serde(default = "default_update_check_interval")]
+    pub interval: u64,
+    /// source ids to update automatically when a new version is found
+    #[serde(default)]
+    pub auto_update_sources: Vec<i64>,
+}
+
+impl Default for UpdateCheckConfig {
+    fn default() -> Self {
+        Self {
+            server: true,
+            server_release_url: default_server_release_url(),
+            extension: true,
+            interval: default_update_check_interval(),
+            auto_update_sources: vec![],
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ImageCacheConfig {
+    /// max size of image cache in megabytes, 0 for unlimited
+    #[serde(default = "default_image_cache_max_size")]
+    pub max_size: u64,
+    /// remove images not accessed for this many days, 0 to keep them
+    #[serde(default = "default_image_cache_max_age")]
+    pub max_age: u64,
+}
+
+impl Default for ImageCacheConfig {
+    fn default() -> Self {
+        Self {
+            max_size: default_image_cache_max_size(),
+            max_age: default_image_cache_max_age(),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ImageUrlConfig {
+    /// lifetime of signed image urls in seconds, 0 for urls which never expire
+    #[serde(default = "default_image_url_ttl")]
+    pub ttl: u64,
+    /// accept encrypted image urls issued before urls were signed. They never
+    /// expire and are not bound to a user, only enable while migrating clients
+    /// which stored such urls.
+    #[serde(default)]
+    pub accept_legacy: bool,
+}
+
+impl Default for ImageUrlConfig {
+    fn default() -> Self {
+        Self {
+            ttl: default_image_url_ttl(),
+            accept_legacy: false,
+        }
+    }
+}
+
+/// Which side wins when read progress in tanoshi and tracker differ
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum TrackerSyncPolicy {
+    /// keep the highest progress on both sides
+    #[default]
+    Max,
+    /// overwrite tracker progress with tanoshi history, progress on tracker is
+    /// only lowered by `syncTrackerProgress` with `allowDecrease`
+    Local,
+    /// overwrite tanoshi history with tracker progress
+    Remote,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct TrackerSyncConfig {
+    #[serde(default)]
+    pub policy: TrackerSyncPolicy,
+    /// interval between pulling progress from trackers in seconds, 0 to disable
+    #[serde(default = "default_tracker_sync_interval")]
+    pub interval: u64,
+}
+
+impl Default for TrackerSyncConfig {
+    fn default() -> Self {
+        Self {
+            policy: TrackerSyncPolicy::default(),
+            interval: default_tracker_sync_interval(),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct LoginLimitConfig {
+    /// failed logins of a username before it is locked, 0 to disable
+    #[serde(default = "default_login_max_failures_per_user")]
+    pub max_failures_per_user: i64,
+    /// failed logins from an address before it is locked, 0 to disable
+    #[serde(default = "default_login_max_failures_per_ip")]
+    pub max_failures_per_ip: i64,
+    /// window in seconds in which failures are counted, also the lockout time
+    #[serde(default = "default_login_window")]
+    pub window: i64,
+    /// addresses or cidr ranges of reverse proxies whose X-Forwarded-For
+    /// header is used as client address
+    #[serde(default)]
+    pub trusted_proxies: Vec<String>,
+}
+
+impl Default for LoginLimitConfig {
+    fn default() -> Self {
+        Self {
+            max_failures_per_user: default_login_max_failures_per_user(),
+            max_failures_per_ip: default_login_max_failures_per_ip(),
+            window: default_login_window(),
+            trusted_proxies: vec![],
+        }
+    }
+}
+
+impl LoginLimitConfig {
+    pub fn limit(&self) -> LoginLimit {
+        LoginLimit {
+            max_failures_per_user: self.max_failures_per_user,
+            max_failures_per_ip: self.max_failures_per_ip,
+            window: chrono::Duration::seconds(self.window),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct OidcConfig {
+    /// provider name shown on login page
+    #[serde(default = "default_oidc_name")]
+    pub name: String,
+    /// provider endpoints are discovered from `{issuer_url}/.well-known/openid-configuration`
+    pub issuer_url: String,
+    pub client_id: String,
+    #[serde(default)]
+    pub client_secret: Option<String>,
+    #[serde(default = "default_oidc_scopes")]
+    pub scopes: Vec<String>,
+    /// userinfo claim used as tanoshi username
+    #[serde(default = "default_oidc_username_claim")]
+    pub username_claim: String,
+    /// userinfo claim listing groups of user
+    #[serde(default = "default_oidc_groups_claim")]
+    pub groups_claim: String,
+    /// members of this group are admin and others are not,
+    /// admin flag is left as is when unset
+    #[serde(default)]
+    pub admin_group: Option<String>,
+    /// create user on first login, otherwise only existing users can login
+    #[serde(default = "default_true")]
+    pub auto_register: bool,
+    /// on first login link provider account to existing user with the same
+    /// username. Only enable if provider does not let users pick usernames.
+    #[serde(default)]
+    pub link_existing_users: bool,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct TrustedHeaderConfig {
+    /// header with username of user authenticated by reverse proxy
+    #[serde(default = "default_trusted_username_header")]
+    pub username_header: String,
+    /// header with comma separated groups of user
+    #[serde(default)]
+    pub groups_header: Option<String>,
+    /// members of this group are admin and others are not,
+    /// admin flag is left as is when unset
+    #[serde(default)]
+    pub admin_group: Option<String>,
+    /// addresses or cidr ranges of reverse proxies allowed to set the headers
+    pub trusted_proxies: Vec<String>,
+    /// create user on first request, otherwise only existing users are accepted
+    #[serde(default = "default_true")]
+    pub auto_register: bool,
+}
+
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct MyAnimeListConfig {
     pub client_id: String,
@@ -32,6 +278,24 @@ pub struct AniListConfig {
     pub client_secret: String,
 }
 
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct KitsuConfig {
+    pub client_id: String,
+    pub client_secret: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct MangaUpdatesConfig {
+    #[serde(default = "default_mangaupdates_api_url")]
+    pub api_url: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ShikimoriConfig {
+    pub client_id: String,
+    pub client_secret: String,
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct LocalFolder {
     pub name: String,
@@ -49,7 +313,7 @@ pub enum LocalFolders {
 pub struct Config {
     #[serde(skip)]
     path: PathBuf,
-    #[serde(skip, default = "default_extension_repository")]
+    #[serde(skip_serializing, default = "default_extension_repository")]
     pub extension_repository: String,
     #[serde(default)]
     pub base_url: Option<String>,
@@ -64,6 +328,8 @@ pub struct Config {
     #[serde(default = "default_update_interval")]
     pub update_interval: u64,
     #[serde(default)]
+    pub update_check: UpdateCheckConfig,
+    #[serde(default)]
     pub auto_download_chapters: bool,
     #[serde(default = "default_plugin_path")]
     pub plugin_path: String,
@@ -74,12 +340,32 @@ pub struct Config {
     #[serde(default = "default_cache_path")]
     pub cache_path: String,
     #[serde(default)]
+    pub image_cache: ImageCacheConfig,
+    #[serde(default)]
+    pub image_url: ImageUrlConfig,
+    #[serde(default)]
     pub enable_playground: bool,
     pub telegram: Option<TelegramConfig>,
     pub pushover: Option<PushoverConfig>,
     pub gotify: Option<GotifyConfig>,
+    pub webhook: Option<WebhookConfig>,
+    pub discord: Option<DiscordConfig>,
+    pub ntfy: Option<NtfyConfig>,
+    pub matrix: Option<MatrixConfig>,
+    pub email: Option<EmailConfig>,
+    #[serde(default)]
+    pub notification_template: Option<NotificationTemplateConfig>,
     pub myanimelist: Option<MyAnimeListConfig>,
     pub anilist: Option<AniListConfig>,
+    pub kitsu: Option<KitsuConfig>,
+    pub mangaupdates: Option<MangaUpdatesConfig>,
+    pub shikimori: Option<ShikimoriConfig>,
+    #[serde(default)]
+    pub tracker_sync: TrackerSyncConfig,
+    pub oidc: Option<OidcConfig>,
+    pub trusted_header: Option<TrustedHeaderConfig>,
+    #[serde(default)]
+    pub login_limit: LoginLimitConfig,
 }
 
 impl Default for Config {
@@ -93,17 +379,33 @@ impl Default for Config {
             create_database: default_create_database(),
             secret: default_secret(),
             update_interval: default_update_interval(),
+            update_check: UpdateCheckConfig::default(),
             auto_download_chapters: false,
             plugin_path: default_plugin_path(),
             local_path: default_local_folders(),
             download_path: default_download_path(),
             cache_path: default_cache_path(),
+            image_cache: ImageCacheConfig::default(),
+            image_url: ImageUrlConfig::default(),
             enable_playground: false,
             telegram: None,
             pushover: None,
             gotify: None,
+            webhook: None,
+            discord: None,
+            ntfy: None,
+            matrix: None,
+            email: None,
+            notification_template: None,
             myanimelist: None,
             anilist: None,
+            kitsu: None,
+            mangaupdates: None,
+            shikimori: None,
+            tracker_sync: TrackerSyncConfig::default(),
+            oidc: None,
+            trusted_header: None,
+            login_limit: LoginLimitConfig::default(),
         }
     }
 }
@@ -130,6 +432,77 @@ fn default_update_interval() -> u64 {
     3600
 }
 
+fn default_update_check_interval() -> u64 {
+    86400
+}
+
+fn default_server_release_url() -> String {
+    "https://api.github.com/repos/faldez/tanoshi/releases/latest".to_string()
+}
+
+fn default_image_cache_max_size() -> u64 {
+    1024
+}
+
+fn default_image_cache_max_age() -> u64 {
+    10
+}
+
+fn default_image_url_ttl() -> u64 {
+    604800
+}
+
+fn default_tracker_sync_interval() -> u64 {
+    21600
+}
+
+fn default_login_max_failures_per_user() -> i64 {
+    5
+}
+
+fn default_login_max_failures_per_ip() -> i64 {
+    20
+}
+
+fn default_login_window() -> i64 {
+    900
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_ntfy_base_url() -> String {
+    tanoshi_notifier::ntfy::DEFAULT_BASE_URL.to_string()
+}
+
+fn default_mangaupdates_api_url() -> String {
+    tanoshi_tracker::mangaupdates::DEFAULT_API_URL.to_string()
+}
+
+fn default_oidc_name() -> String {
+    "OpenID Connect".to_string()
+}
+
+fn default_oidc_scopes() -> Vec<String> {
+    ["openid", "profile", "email", "groups"]
+        .iter()
+        .map(|scope| scope.to_string())
+        .collect()
+}
+
+fn default_oidc_username_claim() -> String {
+    "preferred_username".to_string()
+}
+
+fn default_oidc_groups_claim() -> String {
+    "groups".to_string()
+}
+
+fn default_trusted_username_header() -> String {
+    "Remote-User".to_string()
+}
+
 fn default_secret() -> String {
     let mut rng = thread_rng();
     let chars = iter::repeat(())
@@ -30,38 +30,48 @@ impl DownloadRepository for DownloadRepositoryImpl {
         before_timestamp: i64,
         before_id: i64,
         first: i32,
+        source_ids: Option<&[i64]>,
     ) -> Result<Vec<DownloadedChapter>, DownloadRepositoryError> {
-        let chapters = sqlx::query(
+        let source_filter = super::source_id_filter(source_ids);
+        let query_str = format!(
             r#"
-        SELECT * FROM chapter
+        SELECT chapter.* FROM chapter
+        JOIN manga ON manga.id = chapter.manga_id {source_filter}
         WHERE
-            (date_added, id) < (datetime(?, 'unixepoch'), ?) AND
-            (date_added, id) > (datetime(?, 'unixepoch'), ?)
-        WHERE downloaded_path IS NOT NULL
-        ORDER BY date_added DESC, id DESC
-        LIMIT ?"#,
-        )
-        .bind(after_timestamp)
-        .bind(after_id)
-        .bind(before_timestamp)
-        .bind(before_id)
-        .bind(first)
-        .fetch_all(&self.pool as &SqlitePool)
-        .await?
-        .into_par_iter()
-        .map(|row| DownloadedChapter {
-            id: row.get(0),
-            source_id: row.get(1),
-            manga_id: row.get(2),
-            title: row.get(3),
-            path: row.get(4),
-            number: row.get(5),
-            scanlator: row.get(6),
-            uploaded: row.get(7),
-            date_added: row.get(8),
-            downloaded_path: row.get(9),
-        })
-        .collect();
+            chapter.downloaded_path IS NOT NULL AND
+            (chapter.date_added, chapter.id) < (datetime(?, 'unixepoch'), ?) AND
+            (chapter.date_added, chapter.id) > (datetime(?, 'unixepoch'), ?)
+        ORDER BY chapter.date_added DESC, chapter.id DESC
+        LIMIT ?"#
+        );
+
+        let mut query = sqlx::query(&query_str);
+        for source_id in source_ids.unwrap_or_default() {
+            query = query.bind(source_id);
+        }
+
+        let chapters = query
+            .bind(after_timestamp)
+            .bind(after_id)
+            .bind(before_timestamp)
+            .bind(before_id)
+            .bind(first)
+            .fetch_all(&self.pool as &SqlitePool)
+            .await?
+            .into_par_iter()
+            .map(|row| DownloadedChapter {
+                id: row.get(0),
+                source_id: row.get(1),
+                manga_id: row.get(2),
+                title: row.get(3),
+                path: row.get(4),
+                number: row.get(5),
+                scanlator: row.get(6),
+                uploaded: row.get(7),
+                date_added: row.get(8),
+                downloaded_path: row.get(9),
+            })
+            .collect();
 
         Ok(chapters)
     }
@@ -73,40 +83,50 @@ impl DownloadRepository for DownloadRepositoryImpl {
         before_timestamp: i64,
         before_id: i64,
         last: i32,
+        source_ids: Option<&[i64]>,
     ) -> Result<Vec<DownloadedChapter>, DownloadRepositoryError> {
-        let chapters = sqlx::query(
+        let source_filter = super::source_id_filter(source_ids);
+        let query_str = format!(
             r#"
             SELECT * FROM (
-                SELECT * FROM chapter
+                SELECT chapter.* FROM chapter
+                JOIN manga ON manga.id = chapter.manga_id {source_filter}
                 WHERE
-                    (date_added, id) < (datetime(?, 'unixepoch'), ?) AND
-                    (date_added, id) > (datetime(?, 'unixepoch'), ?)
-                WHERE downloaded_path IS NOT NULL
-                ORDER BY date_added ASC, id ASC
+                    chapter.downloaded_path IS NOT NULL AND
+                    (chapter.date_added, chapter.id) < (datetime(?, 'unixepoch'), ?) AND
+                    (chapter.date_added, chapter.id) > (datetime(?, 'unixepoch'), ?)
+                ORDER BY chapter.date_added ASC, chapter.id ASC
                 LIMIT ?) c
-            ORDER BY c.date_added DESC, c.id DESC"#,
-        )
-        .bind(after_timestamp)
-        .bind(after_id)
-        .bind(before_timestamp)
-        .bind(before_id)
-        .bind(last)
-        .fetch_all(&self.pool as &SqlitePool)
-        .await?
-        .into_par_iter()
-        .map(|row| DownloadedChapter {
-            id: row.get(0),
-            source_id: row.get(1),
-            manga_id: row.get(2),
-            title: row.get(3),
-            path: row.get(4),
-            number: row.get(5),
-            scanlator: row.get(6),
-            uploaded: row.get(7),
-            date_added: row.get(8),
-            downloaded_path: row.get(9),
-        })
-        .collect();
+            ORDER BY c.date_added DESC, c.id DESC"#
+        );
+
+        let mut query = sqlx::query(&query_str);
+        for source_id in source_ids.unwrap_or_default() {
+            query = query.bind(source_id);
+        }
+
+        let chapters = query
+            .bind(after_timestamp)
+            .bind(after_id)
+            .bind(before_timestamp)
+            .bind(before_id)
+            .bind(last)
+            .fetch_all(&self.pool as &SqlitePool)
+            .await?
+            .into_par_iter()
+            .map(|row| DownloadedChapter {
+                id: row.get(0),
+                source_id: row.get(1),
+                manga_id: row.get(2),
+                title: row.get(3),
+                path: row.get(4),
+                number: row.get(5),
+                scanlator: row.get(6),
+                uploaded: row.get(7),
+                date_added: row.get(8),
+                downloaded_path: row.get(9),
+            })
+            .collect();
 
         Ok(chapters)
     }
@@ -117,37 +137,46 @@ impl DownloadRepository for DownloadRepositoryImpl {
         after_id: i64,
         before_timestamp: i64,
         before_id: i64,
+        source_ids: Option<&[i64]>,
     ) -> Result<Vec<DownloadedChapter>, DownloadRepositoryError> {
-        let chapters = sqlx::query(
+        let source_filter = super::source_id_filter(source_ids);
+        let query_str = format!(
             r#"
-            SELECT * FROM chapter
+            SELECT chapter.* FROM chapter
+            JOIN manga ON manga.id = chapter.manga_id {source_filter}
             WHERE
-                (date_added, id) < (datetime(?, 'unixepoch'), ?) AND
-                (date_added, id) > (datetime(?, 'unixepoch'), ?)
-            WHERE downloaded_path IS NOT NULL
-            ORDER BY date_added DESC, id DESC
-            LIMIT ?"#,
-        )
-        .bind(after_timestamp)
-        .bind(after_id)
-        .bind(before_timestamp)
-        .bind(before_id)
-        .fetch_all(&self.pool as &SqlitePool)
-        .await?
-        .into_par_iter()
-        .map(|row| DownloadedChapter {
-            id: row.get(0),
-            source_id: row.get(1),
-            manga_id: row.get(2),
-            title: row.get(3),
-            path: row.get(4),
-            number: row.get(5),
-            scanlator: row.get(6),
-            uploaded: row.get(7),
-            date_added: row.get(8),
-            downloaded_path: row.get(9),
-        })
-        .collect();
+                chapter.downloaded_path IS NOT NULL AND
+                (chapter.date_added, chapter.id) < (datetime(?, 'unixepoch'), ?) AND
+                (chapter.date_added, chapter.id) > (datetime(?, 'unixepoch'), ?)
+            ORDER BY chapter.date_added DESC, chapter.id DESC"#
+        );
+
+        let mut query = sqlx::query(&query_str);
+        for source_id in source_ids.unwrap_or_default() {
+            query = query.bind(source_id);
+        }
+
+        let chapters = query
+            .bind(after_timestamp)
+            .bind(after_id)
+            .bind(before_timestamp)
+            .bind(before_id)
+            .fetch_all(&self.pool as &SqlitePool)
+            .await?
+            .into_par_iter()
+            .map(|row| DownloadedChapter {
+                id: row.get(0),
+                source_id: row.get(1),
+                manga_id: row.get(2),
+                title: row.get(3),
+                path: row.get(4),
+                number: row.get(5),
+                scanlator: row.get(6),
+                uploaded: row.get(7),
+                date_added: row.get(8),
+                downloaded_path: row.get(9),
+            })
+            .collect();
 
         Ok(chapters)
     }
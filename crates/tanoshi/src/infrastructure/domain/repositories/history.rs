@@ -32,8 +32,10 @@ impl HistoryRepository for HistoryRepositoryImpl {
         after_timestamp: i64,
         before_timestamp: i64,
         first: i32,
+        source_ids: Option<&[i64]>,
     ) -> Result<Vec<HistoryChapter>, HistoryRepositoryError> {
-        let chapters = sqlx::query(
+        let source_filter = super::source_id_filter(source_ids);
+        let query_str = format!(
             r#"
         SELECT
             manga.id,
@@ -48,32 +50,38 @@ impl HistoryRepository for HistoryRepositoryImpl {
         JOIN chapter ON 
             user_history.user_id = ? AND
             chapter.id = user_history.chapter_id
-        JOIN manga ON manga.id = chapter.manga_id
+        JOIN manga ON manga.id = chapter.manga_id {source_filter}
         GROUP BY manga.id
         HAVING
             read_at < datetime(?, 'unixepoch') AND
             read_at > datetime(?, 'unixepoch')
         ORDER BY user_history.read_at DESC, manga.id DESC
-        LIMIT ?"#,
-        )
-        .bind(user_id)
-        .bind(after_timestamp)
-        .bind(before_timestamp)
-        .bind(first)
-        .fetch_all(&self.pool as &SqlitePool)
-        .await?
-        .into_par_iter()
-        .map(|row| HistoryChapter {
-            manga_id: row.get(0),
-            chapter_id: row.get(1),
-            manga_title: row.get(2),
-            cover_url: row.get(3),
-            chapter_title: row.get(4),
-            read_at: row.get(5),
-            last_page_read: row.get(6),
-            is_complete: row.get(7),
-        })
-        .collect();
+        LIMIT ?"#
+        );
+
+        let mut query = sqlx::query(&query_str).bind(user_id);
+        for source_id in source_ids.unwrap_or_default() {
+            query = query.bind(source_id);
+        }
+
+        let chapters = query
+            .bind(after_timestamp)
+            .bind(before_timestamp)
+            .bind(first)
+            .fetch_all(&self.pool as &SqlitePool)
+            .await?
+            .into_par_iter()
+            .map(|row| HistoryChapter {
+                manga_id: row.get(0),
+                chapter_id: row.get(1),
+                manga_title: row.get(2),
+                cover_url: row.get(3),
+                chapter_title: row.get(4),
+                read_at: row.get(5),
+                last_page_read: row.get(6),
+                is_complete: row.get(7),
+            })
+            .collect();
 
         Ok(chapters)
     }
@@ -84,8 +92,10 @@ impl HistoryRepository for HistoryRepositoryImpl {
         after_timestamp: i64,
         before_timestamp: i64,
         last: i32,
+        source_ids: Option<&[i64]>,
     ) -> Result<Vec<HistoryChapter>, HistoryRepositoryError> {
-        let chapters = sqlx::query(
+        let source_filter = super::source_id_filter(source_ids);
+        let query_str = format!(
             r#"
         SELECT * FROM (
             SELECT
@@ -101,32 +111,38 @@ impl HistoryRepository for HistoryRepositoryImpl {
             JOIN chapter ON 
                 user_history.user_id = ? AND
                 chapter.id = user_history.chapter_id
-            JOIN manga ON manga.id = chapter.manga_id
+            JOIN manga ON manga.id = chapter.manga_id {source_filter}
             GROUP BY manga.id
             HAVING
                 read_at < datetime(?, 'unixepoch') AND
                 read_at > datetime(?, 'unixepoch')
             ORDER BY user_history.read_at ASC, manga.id ASC
-            LIMIT ?) c ORDER BY c.read_at DESC, c.id DESC"#,
-        )
-        .bind(user_id)
-        .bind(after_timestamp)
-        .bind(before_timestamp)
-        .bind(last)
-        .fetch_all(&self.pool as &SqlitePool)
-        .await?
-        .into_par_iter()
-        .map(|row| HistoryChapter {
-            manga_id: row.get(0),
-            chapter_id: row.get(1),
-            manga_title: row.get(2),
-            cover_url: row.get(3),
-            chapter_title: row.get(4),
-            read_at: row.get(5),
-            last_page_read: row.get(6),
-            is_complete: row.get(7),
-        })
-        .collect();
+            LIMIT ?) c ORDER BY c.read_at DESC, c.id DESC"#
+        );
+
+        let mut query = sqlx::query(&query_str).bind(user_id);
+        for source_id in source_ids.unwrap_or_default() {
+            query = query.bind(source_id);
+        }
+
+        let chapters = query
+            .bind(after_timestamp)
+            .bind(before_timestamp)
+            .bind(last)
+            .fetch_all(&self.pool as &SqlitePool)
+            .await?
+            .into_par_iter()
+            .map(|row| HistoryChapter {
+                manga_id: row.get(0),
+                chapter_id: row.get(1),
+                manga_title: row.get(2),
+                cover_url: row.get(3),
+                chapter_title: row.get(4),
+                read_at: row.get(5),
+                last_page_read: row.get(6),
+                is_complete: row.get(7),
+            })
+            .collect();
 
         Ok(chapters)
     }
@@ -136,8 +152,10 @@ impl HistoryRepository for HistoryRepositoryImpl {
         user_id: i64,
         after_timestamp: i64,
         before_timestamp: i64,
+        source_ids: Option<&[i64]>,
     ) -> Result<Vec<HistoryChapter>, HistoryRepositoryError> {
-        let chapters = sqlx::query(
+        let source_filter = super::source_id_filter(source_ids);
+        let query_str = format!(
             r#"
         SELECT
             manga.id,
@@ -152,30 +170,36 @@ impl HistoryRepository for HistoryRepositoryImpl {
         JOIN chapter ON 
             user_history.user_id = ? AND
             chapter.id = user_history.chapter_id
-        JOIN manga ON manga.id = chapter.manga_id
+        JOIN manga ON manga.id = chapter.manga_id {source_filter}
         GROUP BY manga.id
         HAVING
             read_at < datetime(?, 'unixepoch') AND
             read_at > datetime(?, 'unixepoch')
-        ORDER BY user_history.read_at DESC, manga.id DESC"#,
-        )
-        .bind(user_id)
-        .bind(after_timestamp)
-        .bind(before_timestamp)
-        .fetch_all(&self.pool as &SqlitePool)
-        .await?
-        .into_par_iter()
-        .map(|row| HistoryChapter {
-            manga_id: row.get(0),
-            chapter_id: row.get(1),
-            manga_title: row.get(2),
-            cover_url: row.get(3),
-            chapter_title: row.get(4),
-            read_at: row.get(5),
-            last_page_read: row.get(6),
-            is_complete: row.get(7),
-        })
-        .collect();
+        ORDER BY user_history.read_at DESC, manga.id DESC"#
+        );
+
+        let mut query = sqlx::query(&query_str).bind(user_id);
+        for source_id in source_ids.unwrap_or_default() {
+            query = query.bind(source_id);
+        }
+
+        let chapters = query
+            .bind(after_timestamp)
+            .bind(before_timestamp)
+            .fetch_all(&self.pool as &SqlitePool)
+            .await?
+            .into_par_iter()
+            .map(|row| HistoryChapter {
+                manga_id: row.get(0),
+                chapter_id: row.get(1),
+                manga_title: row.get(2),
+                cover_url: row.get(3),
+                chapter_title: row.get(4),
+                read_at: row.get(5),
+                last_page_read: row.get(6),
+                is_complete: row.get(7),
+            })
+            .collect();
 
         Ok(chapters)
     }
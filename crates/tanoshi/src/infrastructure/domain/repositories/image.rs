@@ -1,14 +1,26 @@
-use std::path::Path;
+use std::{io::Cursor, path::Path};
 
 use async_trait::async_trait;
 
 use http::{HeaderMap, HeaderValue};
+use image::{
+    codecs::{
+        avif::AvifEncoder,
+        jpeg::JpegEncoder,
+        webp::{WebPEncoder, WebPQuality},
+    },
+    imageops::FilterType,
+    ColorType, ImageEncoder, ImageOutputFormat,
+};
 
 use crate::domain::{
-    entities::image::Image,
+    entities::image::{Image, ImageFormat, ImageTransform},
     repositories::image::{ImageRepository, ImageRepositoryError},
 };
 
+/// Encoder speed from 1 to 10, avif is slow to encode on lower speed
+const AVIF_SPEED: u8 = 8;
+
 #[derive(Default, Clone)]
 pub struct ImageRepositoryImpl {
     client: reqwest::Client,
@@ -41,6 +53,10 @@ impl ImageRepository for ImageRepositoryImpl {
         }
 
         let source_res = self.client.get(url).headers(headers).send().await?;
+        if source_res.status() == reqwest::StatusCode::NOT_FOUND {
+            return Err(ImageRepositoryError::NotFound);
+        }
+        let source_res = source_res.error_for_status()?;
 
         let content_type = source_res
             .headers()
@@ -62,9 +78,10 @@ impl ImageRepository for ImageRepositoryImpl {
         let content_type = mime_guess::from_path(&path)
             .first_or_octet_stream()
             .to_string();
-        let data = tokio::fs::read(path)
-            .await
-            .map_err(|e| ImageRepositoryError::Other(format!("{e}")))?;
+        let data = tokio::fs::read(path).await.map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => ImageRepositoryError::NotFound,
+            _ => ImageRepositoryError::Other(format!("{e}")),
+        })?;
 
         Ok(Image {
             content_type,
@@ -85,22 +102,93 @@ impl ImageRepository for ImageRepositoryImpl {
             .first_or_octet_stream()
             .to_string();
 
-        let source = std::fs::File::open(archive)
-            .map_err(|e| ImageRepositoryError::Other(format!("{e}")))?;
-        let (content_type, data) =
-            tokio::task::spawn_blocking(move || -> Result<(String, Vec<u8>), anyhow::Error> {
+        let source = std::fs::File::open(archive).map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => ImageRepositoryError::NotFound,
+            _ => ImageRepositoryError::Other(format!("{e}")),
+        })?;
+        let (content_type, data) = tokio::task::spawn_blocking(
+            move || -> Result<(String, Vec<u8>), ImageRepositoryError> {
                 let mut buf: Vec<u8> = vec![];
-                compress_tools::uncompress_archive_file(source, &mut buf, &filename)?;
+                compress_tools::uncompress_archive_file(source, &mut buf, &filename).map_err(
+                    |e| match e {
+                        compress_tools::Error::FileNotFound => ImageRepositoryError::NotFound,
+                        e => ImageRepositoryError::Other(format!("{e}")),
+                    },
+                )?;
 
                 Ok((content_type, buf))
-            })
-            .await
-            .map_err(|e| ImageRepositoryError::Other(format!("{e}")))?
-            .map_err(|e| ImageRepositoryError::Other(format!("{e}")))?;
+            },
+        )
+        .await
+        .map_err(|e| ImageRepositoryError::Other(format!("{e}")))??;
 
         Ok(Image {
             content_type,
             data: data.into(),
         })
     }
+
+    async fn transform_image(
+        &self,
+        image: Image,
+        transform: ImageTransform,
+    ) -> Result<Image, ImageRepositoryError> {
+        tokio::task::spawn_blocking(move || transform_image(image, transform))
+            .await
+            .map_err(|e| ImageRepositoryError::Other(format!("{e}")))?
+    }
+}
+
+fn transform_image(image: Image, transform: ImageTransform) -> Result<Image, ImageRepositoryError> {
+    let input_format = image::guess_format(&image.data)?;
+    let mut img = image::load_from_memory_with_format(&image.data, input_format)?;
+
+    let width = transform.width.unwrap_or(u32::MAX).min(img.width());
+    let height = transform.height.unwrap_or(u32::MAX).min(img.height());
+    if width < img.width() || height < img.height() {
+        img = img.resize(width, height, FilterType::Lanczos3);
+    }
+
+    // keep png and webp when no format requested, everything else becomes jpeg
+    let format = match (transform.format, input_format) {
+        (Some(format), _) => Some(format),
+        (None, image::ImageFormat::Png) => None,
+        (None, image::ImageFormat::WebP) => Some(ImageFormat::Webp),
+        (None, _) => Some(ImageFormat::Jpeg),
+    };
+
+    let mut data = vec![];
+    let content_type = match format {
+        Some(ImageFormat::Jpeg) => {
+            let rgb = img.to_rgb8();
+            JpegEncoder::new_with_quality(&mut data, transform.quality()).encode(
+                rgb.as_raw(),
+                rgb.width(),
+                rgb.height(),
+                ColorType::Rgb8,
+            )?;
+            "image/jpeg"
+        }
+        Some(ImageFormat::Webp) => {
+            let rgba = img.to_rgba8();
+            WebPEncoder::new_with_quality(&mut data, WebPQuality::lossy(transform.quality()))
+                .encode(rgba.as_raw(), rgba.width(), rgba.height(), ColorType::Rgba8)?;
+            "image/webp"
+        }
+        Some(ImageFormat::Avif) => {
+            let rgba = img.to_rgba8();
+            AvifEncoder::new_with_speed_quality(&mut data, AVIF_SPEED, transform.quality())
+                .write_image(rgba.as_raw(), rgba.width(), rgba.height(), ColorType::Rgba8)?;
+            "image/avif"
+        }
+        None => {
+            img.write_to(&mut Cursor::new(&mut data), ImageOutputFormat::Png)?;
+            "image/png"
+        }
+    };
+
+    Ok(Image {
+        content_type: content_type.to_string(),
+        data: data.into(),
+    })
 }
@@ -1,21 +1,206 @@
 use async_trait::async_trait;
-use std::path::{Path, PathBuf};
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime},
+};
+use tokio::sync::{Mutex, MutexGuard};
 
 use crate::domain::{
-    entities::image::Image,
+    entities::image::{Image, ImageCacheStats},
     repositories::image_cache::{ImageCacheRepository, ImageCacheRepositoryError},
 };
 
+struct CacheEntry {
+    size: u64,
+    last_accessed: SystemTime,
+}
+
+#[derive(Default)]
+struct CacheIndex {
+    entries: HashMap<String, CacheEntry>,
+    pinned: HashSet<String>,
+    size: u64,
+}
+
+impl CacheIndex {
+    fn insert(&mut self, key: &str, size: u64) {
+        if let Some(old) = self.entries.insert(
+            key.to_string(),
+            CacheEntry {
+                size,
+                last_accessed: SystemTime::now(),
+            },
+        ) {
+            self.size -= old.size;
+        }
+        self.size += size;
+    }
+
+    fn remove(&mut self, key: &str) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.size -= entry.size;
+        }
+    }
+
+    /// Whether key or the original image of a `{key}-{suffix}` variant is pinned
+    fn is_pinned(&self, key: &str) -> bool {
+        self.pinned.contains(key)
+            || key
+                .split_once('-')
+                .is_some_and(|(key, _)| self.pinned.contains(key))
+    }
+}
+
+/// Disk cache of remote images, tracks size and access time of every entry
+/// in memory so it can evict least recently used entries
 #[derive(Clone)]
 pub struct ImageCacheRepositoryImpl {
     path: PathBuf,
+    max_size: u64,
+    max_age: Option<Duration>,
+    index: Arc<Mutex<Option<CacheIndex>>>,
+    pinned: Arc<Mutex<HashSet<String>>>,
+    hits: Arc<AtomicU64>,
+    misses: Arc<AtomicU64>,
 }
 
 impl ImageCacheRepositoryImpl {
-    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+    /// `max_size` is in bytes and `max_age` in days, 0 means no limit
+    pub fn new<P: AsRef<Path>>(path: P, max_size: u64, max_age: u64) -> Self {
         Self {
             path: PathBuf::new().join(path),
+            max_size,
+            max_age: (max_age > 0).then(|| Duration::from_secs(max_age * 86400)),
+            index: Arc::new(Mutex::new(None)),
+            pinned: Arc::new(Mutex::new(HashSet::new())),
+            hits: Arc::new(AtomicU64::new(0)),
+            misses: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    async fn index(&self) -> Result<MutexGuard<'_, Option<CacheIndex>>, ImageCacheRepositoryError> {
+        let mut index = self.index.lock().await;
+        if index.is_none() {
+            *index = Some(self.load_index().await?);
+        }
+
+        Ok(index)
+    }
+
+    /// Build index from files in cache directory, access time falls back to
+    /// modified time on filesystems that don't record it
+    async fn load_index(&self) -> Result<CacheIndex, ImageCacheRepositoryError> {
+        let mut index = CacheIndex::default();
+
+        let mut read_dir = tokio::fs::read_dir(&self.path).await?;
+        while let Some(entry) = read_dir.next_entry().await? {
+            let metadata = entry.metadata().await?;
+            if !metadata.is_file() {
+                continue;
+            }
+
+            let last_accessed = metadata
+                .accessed()
+                .or_else(|_| metadata.modified())
+                .unwrap_or(SystemTime::UNIX_EPOCH);
+
+            index.size += metadata.len();
+            index.entries.insert(
+                entry.file_name().to_string_lossy().to_string(),
+                CacheEntry {
+                    size: metadata.len(),
+                    last_accessed,
+                },
+            );
+        }
+
+        index.pinned = self.pinned.lock().await.clone();
+
+        debug!(
+            "loaded image cache index, {} entries, {} bytes",
+            index.entries.len(),
+            index.size
+        );
+
+        Ok(index)
+    }
+
+    /// Remove `keys` from index, returning them with their size. Files are
+    /// deleted with [`Self::remove_files`] after index lock is released.
+    fn take_entries(index: &mut CacheIndex, keys: Vec<String>) -> Vec<(String, u64)> {
+        keys.into_iter()
+            .filter_map(|key| {
+                let size = index.entries.get(&key)?.size;
+                index.remove(&key);
+                Some((key, size))
+            })
+            .collect()
+    }
+
+    /// Delete files of entries taken from index, entries whose file can't be
+    /// deleted are put back. Returns number of entries removed.
+    async fn remove_files(&self, entries: Vec<(String, u64)>) -> u64 {
+        let mut removed = 0;
+        let mut failed = vec![];
+        for (key, size) in entries {
+            match tokio::fs::remove_file(self.path.join(&key)).await {
+                Ok(_) => removed += 1,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => removed += 1,
+                Err(e) => {
+                    error!("failed to remove cache {key}: {e}");
+                    failed.push((key, size));
+                }
+            }
+        }
+
+        if !failed.is_empty() {
+            if let Some(index) = self.index.lock().await.as_mut() {
+                for (key, size) in failed {
+                    index.insert(&key, size);
+                }
+            }
         }
+
+        removed
+    }
+
+    /// Take expired entries and least recently used entries over max size
+    /// from index
+    fn evict_entries(&self, index: &mut CacheIndex) -> Vec<(String, u64)> {
+        let now = SystemTime::now();
+
+        let mut candidates: Vec<(&String, &CacheEntry)> = index
+            .entries
+            .iter()
+            .filter(|(key, _)| !index.is_pinned(key))
+            .collect();
+        candidates.sort_by_key(|(_, entry)| entry.last_accessed);
+
+        let mut size = index.size;
+        let mut keys = vec![];
+        for (key, entry) in candidates {
+            let expired = self
+                .max_age
+                .zip(now.duration_since(entry.last_accessed).ok())
+                .map(|(max_age, age)| age > max_age)
+                .unwrap_or(false);
+            let oversized = self.max_size > 0 && size > self.max_size;
+
+            if !expired && !oversized {
+                // entries are sorted by access time, the rest are newer
+                break;
+            }
+
+            size -= entry.size;
+            keys.push(key.clone());
+        }
+
+        Self::take_entries(index, keys)
     }
 }
 
@@ -28,16 +213,130 @@ impl ImageCacheRepository for ImageCacheRepositoryImpl {
 
         tokio::fs::write(&path, &encoded).await?;
 
+        // files are deleted after releasing index so other requests aren't held up
+        let evicted = match self.index().await?.as_mut() {
+            Some(index) => {
+                index.insert(key, encoded.len() as u64);
+
+                if self.max_size > 0 && index.size > self.max_size {
+                    self.evict_entries(index)
+                } else {
+                    vec![]
+                }
+            }
+            None => vec![],
+        };
+
+        if !evicted.is_empty() {
+            let removed = self.remove_files(evicted).await;
+            debug!("cache exceeds max size, evicted {removed} entries");
+        }
+
         Ok(())
     }
 
     async fn get(&self, key: &str) -> Result<Image, ImageCacheRepositoryError> {
         let path = self.path.join(key);
 
-        let encoded = tokio::fs::read(path).await?;
+        let encoded = match tokio::fs::read(path).await {
+            Ok(encoded) => encoded,
+            Err(e) => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                if let Some(index) = self.index().await?.as_mut() {
+                    index.remove(key);
+                }
+                return Err(e.into());
+            }
+        };
 
         let decoded = bincode::deserialize(&encoded)?;
 
+        self.hits.fetch_add(1, Ordering::Relaxed);
+        if let Some(index) = self.index().await?.as_mut() {
+            match index.entries.get_mut(key) {
+                Some(entry) => entry.last_accessed = SystemTime::now(),
+                None => index.insert(key, encoded.len() as u64),
+            }
+        }
+
         Ok(decoded)
     }
+
+    async fn set_pinned(&self, keys: HashSet<String>) -> Result<(), ImageCacheRepositoryError> {
+        if let Some(index) = self.index.lock().await.as_mut() {
+            index.pinned = keys.clone();
+        }
+        *self.pinned.lock().await = keys;
+
+        Ok(())
+    }
+
+    async fn evict(&self) -> Result<u64, ImageCacheRepositoryError> {
+        let evicted = match self.index().await?.as_mut() {
+            Some(index) => self.evict_entries(index),
+            None => vec![],
+        };
+
+        Ok(self.remove_files(evicted).await)
+    }
+
+    async fn purge(&self, include_pinned: bool) -> Result<u64, ImageCacheRepositoryError> {
+        let purged = match self.index().await?.as_mut() {
+            Some(index) => {
+                let keys = index
+                    .entries
+                    .keys()
+                    .filter(|key| include_pinned || !index.is_pinned(key))
+                    .cloned()
+                    .collect();
+                Self::take_entries(index, keys)
+            }
+            None => vec![],
+        };
+
+        Ok(self.remove_files(purged).await)
+    }
+
+    async fn stats(&self) -> Result<ImageCacheStats, ImageCacheRepositoryError> {
+        let guard = self.index().await?;
+        let (size, entries, pinned_entries) = guard
+            .as_ref()
+            .map(|index| {
+                (
+                    index.size,
+                    index.entries.len() as u64,
+                    index
+                        .entries
+                        .keys()
+                        .filter(|key| index.is_pinned(key))
+                        .count() as u64,
+                )
+            })
+            .unwrap_or_default();
+
+        Ok(ImageCacheStats {
+            size,
+            max_size: self.max_size,
+            entries,
+            pinned_entries,
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_pinned_variants() {
+        let mut index = CacheIndex::default();
+        index.pinned.insert("abc".to_string());
+
+        assert!(index.is_pinned("abc"));
+        assert!(index.is_pinned("abc-320x0-q80-webp"));
+        assert!(!index.is_pinned("abcd"));
+        assert!(!index.is_pinned("def-320x0-q80-webp"));
+    }
 }
@@ -17,6 +17,8 @@ use crate::{
     infrastructure::database::Pool,
 };
 
+use super::user::{user_from_row, USER_COLUMNS};
+
 #[derive(Clone)]
 pub struct LibraryRepositoryImpl {
     pool: Pool,
@@ -143,31 +145,40 @@ impl LibraryRepository for LibraryRepositoryImpl {
         &self,
         manga_id: i64,
     ) -> Result<Vec<User>, LibraryRepositoryError> {
-        let users = sqlx::query(
-            r#"SELECT user.* FROM user_library
-                    JOIN user ON user_library.user_id = user.id
-                    WHERE user_library.manga_id = ?"#,
-        )
+        let users = sqlx::query(&format!(
+            r#"SELECT {USER_COLUMNS} FROM user
+                    WHERE id IN (SELECT user_id FROM user_library WHERE manga_id = ?)"#
+        ))
         .bind(manga_id)
         .fetch_all(&self.pool as &SqlitePool)
         .await?
         .into_par_iter()
-        .map(|row| User {
-            id: row.get(0),
-            username: row.get(1),
-            password: "".to_string(),
-            is_admin: row.get(3),
-            created_at: row.get(4),
-            updated_at: row.get(5),
-            telegram_chat_id: row.get(6),
-            pushover_user_key: row.get(7),
-            gotify_token: row.get(8),
-        })
+        .map(user_from_row)
         .collect();
 
         Ok(users)
     }
 
+    async fn get_unread_chapter_count(
+        &self,
+        user_id: i64,
+        manga_id: i64,
+    ) -> Result<i64, LibraryRepositoryError> {
+        let row = sqlx::query(
+            r#"SELECT COUNT(1) FROM chapter
+            LEFT JOIN user_history ON
+                user_history.user_id = ? AND
+                user_history.chapter_id = chapter.id
+            WHERE chapter.manga_id = ? AND IFNULL(user_history.is_complete, false) = false"#,
+        )
+        .bind(user_id)
+        .bind(manga_id)
+        .fetch_one(&self.pool as &SqlitePool)
+        .await?;
+
+        Ok(row.get(0))
+    }
+
     fn get_manga_from_all_users_library_stream(
         &self,
     ) -> BoxStream<Result<Manga, LibraryRepositoryError>> {
@@ -383,8 +394,10 @@ impl LibraryRepository for LibraryRepositoryImpl {
         before_timestamp: i64,
         before_id: i64,
         first: i32,
+        source_ids: Option<&[i64]>,
     ) -> Result<Vec<LibraryUpdate>, LibraryRepositoryError> {
-        let chapters = sqlx::query(
+        let source_filter = super::source_id_filter(source_ids);
+        let query_str = format!(
             r#"
         SELECT
             manga.id,
@@ -401,27 +414,35 @@ impl LibraryRepository for LibraryRepositoryImpl {
         WHERE
             (uploaded, chapter.id) < (datetime(?, 'unixepoch'), ?) AND
             (uploaded, chapter.id) > (datetime(?, 'unixepoch'), ?)
+        {source_filter}
         ORDER BY chapter.uploaded DESC, chapter.number DESC
-        LIMIT ?"#,
-        )
-        .bind(user_id)
-        .bind(after_timestamp)
-        .bind(after_id)
-        .bind(before_timestamp)
-        .bind(before_id)
-        .bind(first)
-        .fetch_all(&self.pool as &SqlitePool)
-        .await?
-        .into_par_iter()
-        .map(|row| LibraryUpdate {
-            manga_id: row.get(0),
-            chapter_id: row.get(1),
-            manga_title: row.get(2),
-            cover_url: row.get(3),
-            chapter_title: row.get(4),
-            uploaded: row.get(5),
-        })
-        .collect();
+        LIMIT ?"#
+        );
+
+        let mut query = sqlx::query(&query_str)
+            .bind(user_id)
+            .bind(after_timestamp)
+            .bind(after_id)
+            .bind(before_timestamp)
+            .bind(before_id);
+        for source_id in source_ids.unwrap_or_default() {
+            query = query.bind(source_id);
+        }
+
+        let chapters = query
+            .bind(first)
+            .fetch_all(&self.pool as &SqlitePool)
+            .await?
+            .into_par_iter()
+            .map(|row| LibraryUpdate {
+                manga_id: row.get(0),
+                chapter_id: row.get(1),
+                manga_title: row.get(2),
+                cover_url: row.get(3),
+                chapter_title: row.get(4),
+                uploaded: row.get(5),
+            })
+            .collect();
 
         Ok(chapters)
     }
@@ -434,8 +455,10 @@ impl LibraryRepository for LibraryRepositoryImpl {
         before_timestamp: i64,
         before_id: i64,
         last: i32,
+        source_ids: Option<&[i64]>,
     ) -> Result<Vec<LibraryUpdate>, LibraryRepositoryError> {
-        let chapters = sqlx::query(
+        let source_filter = super::source_id_filter(source_ids);
+        let query_str = format!(
             r#"
         SELECT * FROM (
             SELECT
@@ -454,28 +477,36 @@ impl LibraryRepository for LibraryRepositoryImpl {
             WHERE
                 (uploaded, chapter.id) < (datetime(?, 'unixepoch'), ?) AND
                 (uploaded, chapter.id) > (datetime(?, 'unixepoch'), ?)
+            {source_filter}
             ORDER BY chapter.uploaded ASC, chapter.number DESC
             LIMIT ?) c
-        ORDER BY c.uploaded DESC, c.number DESC"#,
-        )
-        .bind(user_id)
-        .bind(after_timestamp)
-        .bind(after_id)
-        .bind(before_timestamp)
-        .bind(before_id)
-        .bind(last)
-        .fetch_all(&self.pool as &SqlitePool)
-        .await?
-        .into_par_iter()
-        .map(|row| LibraryUpdate {
-            manga_id: row.get(0),
-            chapter_id: row.get(1),
-            manga_title: row.get(2),
-            cover_url: row.get(3),
-            chapter_title: row.get(4),
-            uploaded: row.get(5),
-        })
-        .collect();
+        ORDER BY c.uploaded DESC, c.number DESC"#
+        );
+
+        let mut query = sqlx::query(&query_str)
+            .bind(user_id)
+            .bind(after_timestamp)
+            .bind(after_id)
+            .bind(before_timestamp)
+            .bind(before_id);
+        for source_id in source_ids.unwrap_or_default() {
+            query = query.bind(source_id);
+        }
+
+        let chapters = query
+            .bind(last)
+            .fetch_all(&self.pool as &SqlitePool)
+            .await?
+            .into_par_iter()
+            .map(|row| LibraryUpdate {
+                manga_id: row.get(0),
+                chapter_id: row.get(1),
+                manga_title: row.get(2),
+                cover_url: row.get(3),
+                chapter_title: row.get(4),
+                uploaded: row.get(5),
+            })
+            .collect();
 
         Ok(chapters)
     }
@@ -487,8 +518,10 @@ impl LibraryRepository for LibraryRepositoryImpl {
         after_id: i64,
         before_timestamp: i64,
         before_id: i64,
+        source_ids: Option<&[i64]>,
     ) -> Result<Vec<LibraryUpdate>, LibraryRepositoryError> {
-        let chapters = sqlx::query(
+        let source_filter = super::source_id_filter(source_ids);
+        let query_str = format!(
             r#"
         SELECT
             manga.id,
@@ -505,25 +538,33 @@ impl LibraryRepository for LibraryRepositoryImpl {
         WHERE
             (uploaded, chapter.id) < (datetime(?, 'unixepoch'), ?) AND
             (uploaded, chapter.id) > (datetime(?, 'unixepoch'), ?)
-        ORDER BY chapter.uploaded DESC, chapter.number DESC"#,
-        )
-        .bind(user_id)
-        .bind(after_timestamp)
-        .bind(after_id)
-        .bind(before_timestamp)
-        .bind(before_id)
-        .fetch_all(&self.pool as &SqlitePool)
-        .await?
-        .into_par_iter()
-        .map(|row| LibraryUpdate {
-            manga_id: row.get(0),
-            chapter_id: row.get(1),
-            manga_title: row.get(2),
-            cover_url: row.get(3),
-            chapter_title: row.get(4),
-            uploaded: row.get(5),
-        })
-        .collect();
+        {source_filter}
+        ORDER BY chapter.uploaded DESC, chapter.number DESC"#
+        );
+
+        let mut query = sqlx::query(&query_str)
+            .bind(user_id)
+            .bind(after_timestamp)
+            .bind(after_id)
+            .bind(before_timestamp)
+            .bind(before_id);
+        for source_id in source_ids.unwrap_or_default() {
+            query = query.bind(source_id);
+        }
+
+        let chapters = query
+            .fetch_all(&self.pool as &SqlitePool)
+            .await?
+            .into_par_iter()
+            .map(|row| LibraryUpdate {
+                manga_id: row.get(0),
+                chapter_id: row.get(1),
+                manga_title: row.get(2),
+                cover_url: row.get(3),
+                chapter_title: row.get(4),
+                uploaded: row.get(5),
+            })
+            .collect();
 
         Ok(chapters)
     }
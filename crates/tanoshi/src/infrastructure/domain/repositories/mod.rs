@@ -8,3 +8,16 @@ pub mod manga;
 pub mod source;
 pub mod tracker;
 pub mod user;
+
+/// Condition restricting a query joined with manga to `source_ids`, empty when
+/// not restricted. Source ids are bound in the order given.
+fn source_id_filter(source_ids: Option<&[i64]>) -> String {
+    source_ids
+        .map(|source_ids| {
+            format!(
+                "AND manga.source_id IN ({})",
+                vec!["?"; source_ids.len()].join(",")
+            )
+        })
+        .unwrap_or_default()
+}
@@ -19,6 +19,8 @@ pub struct SourceDto {
     pub rustc_version: String,
     pub lib_version: String,
     pub icon: String,
+    #[serde(default)]
+    pub nsfw: bool,
 }
 
 #[derive(Clone)]
@@ -75,6 +77,7 @@ impl SourceRepository for SourceRepositoryImpl {
                 lib_version: index.lib_version,
                 icon: index.icon,
                 has_update: false,
+                nsfw: index.nsfw,
             });
         }
 
@@ -142,10 +145,7 @@ impl SourceRepository for SourceRepositoryImpl {
             ));
         }
 
-        self.extension_manager.remove(id).await?;
-        self.extension_manager
-            .install(repo_url, &source.name)
-            .await?;
+        self.extension_manager.update(repo_url, id).await?;
 
         Ok(())
     }
@@ -1,13 +1,19 @@
 use std::{collections::HashMap, sync::Arc};
 
 use async_trait::async_trait;
-use chrono::NaiveDateTime;
+use chrono::{Duration, NaiveDateTime, Utc};
 use sqlx::{Row, SqlitePool};
-use tanoshi_tracker::{anilist, myanimelist, AniList, MyAnimeList, Session, Tracker, TrackerManga};
+use tanoshi_tracker::{
+    anilist, kitsu, mangaupdates, myanimelist, shikimori, AniList, Kitsu, MangaUpdates,
+    MyAnimeList, Session, Shikimori, Tracker, TrackerManga,
+};
 
 use crate::{
     domain::{
-        entities::tracker::{Token, TrackedManga},
+        entities::tracker::{
+            Token, TrackedManga, TrackerCredential, TrackerImportCandidate, TrackerMatchCandidate,
+            TrackerSyncQueue, UntrackedManga,
+        },
         repositories::tracker::{TrackerRepository, TrackerRepositoryError},
     },
     infrastructure::database::Pool,
@@ -20,7 +26,14 @@ pub struct TrackerRepositoryImpl {
 }
 
 impl TrackerRepositoryImpl {
-    pub fn new<P: Into<Pool>>(pool: P, mal: Option<MyAnimeList>, anilist: Option<AniList>) -> Self {
+    pub fn new<P: Into<Pool>>(
+        pool: P,
+        mal: Option<MyAnimeList>,
+        anilist: Option<AniList>,
+        kitsu: Option<Kitsu>,
+        mangaupdates: Option<MangaUpdates>,
+        shikimori: Option<Shikimori>,
+    ) -> Self {
         let mut clients = HashMap::new();
         if let Some(mal) = mal {
             clients.insert(myanimelist::NAME, Box::new(mal) as Box<dyn Tracker>);
@@ -28,6 +41,18 @@ impl TrackerRepositoryImpl {
         if let Some(anilist) = anilist {
             clients.insert(anilist::NAME, Box::new(anilist) as Box<dyn Tracker>);
         }
+        if let Some(kitsu) = kitsu {
+            clients.insert(kitsu::NAME, Box::new(kitsu) as Box<dyn Tracker>);
+        }
+        if let Some(mangaupdates) = mangaupdates {
+            clients.insert(
+                mangaupdates::NAME,
+                Box::new(mangaupdates) as Box<dyn Tracker>,
+            );
+        }
+        if let Some(shikimori) = shikimori {
+            clients.insert(shikimori::NAME, Box::new(shikimori) as Box<dyn Tracker>);
+        }
 
         Self {
             pool: pool.into(),
@@ -70,9 +95,35 @@ impl TrackerRepository for TrackerRepositoryImpl {
             access_token: token.access_token,
             refresh_token: token.refresh_token,
             expires_in: token.expires_in,
+            expires_at: None,
         })
     }
 
+    async fn login(
+        &self,
+        tracker: &str,
+        username: String,
+        password: String,
+    ) -> Result<Token, TrackerRepositoryError> {
+        match self
+            .clients
+            .get(tracker)
+            .ok_or(TrackerRepositoryError::NoTracker)?
+            .login(username, password)
+            .await
+        {
+            Ok(token) => Ok(Token {
+                token_type: token.token_type,
+                access_token: token.access_token,
+                refresh_token: token.refresh_token,
+                expires_in: token.expires_in,
+                expires_at: None,
+            }),
+            Err(tanoshi_tracker::Error::Unauthorized) => Err(TrackerRepositoryError::Unauthorized),
+            Err(e) => Err(TrackerRepositoryError::Other(anyhow::anyhow!("{e}"))),
+        }
+    }
+
     async fn refresh_token(
         &self,
         tracker: &str,
@@ -90,6 +141,7 @@ impl TrackerRepository for TrackerRepositoryImpl {
                 access_token: token.access_token,
                 refresh_token: token.refresh_token,
                 expires_in: token.expires_in,
+                expires_at: None,
             }),
             Err(tanoshi_tracker::Error::Unauthorized) => Err(TrackerRepositoryError::Unauthorized),
             Err(e) => Err(TrackerRepositoryError::Other(anyhow::anyhow!("{e}"))),
@@ -102,25 +154,33 @@ impl TrackerRepository for TrackerRepositoryImpl {
         tracker: &str,
         token: Token,
     ) -> Result<(), TrackerRepositoryError> {
+        // trackers without expiry return 0
+        let expires_at = (token.expires_in > 0)
+            .then(|| Utc::now().naive_utc() + Duration::seconds(token.expires_in));
+
         sqlx::query(
             r#"INSERT INTO tracker_credential(
                 user_id,
                 tracker,
                 token_type,
                 expires_in,
+                expires_at,
                 access_token,
                 refresh_token
-            ) VALUES (?, ?, ?, ?, ?, ?)
+            ) VALUES (?, ?, ?, ?, ?, ?, ?)
             ON CONFLICT(user_id, tracker) DO UPDATE SET
             token_type = excluded.token_type,
             expires_in = excluded.expires_in,
+            expires_at = excluded.expires_at,
             access_token = excluded.access_token,
-            refresh_token = excluded.refresh_token"#,
+            refresh_token = excluded.refresh_token,
+            revoked_at = NULL"#,
         )
         .bind(user_id)
         .bind(tracker)
         .bind(token.token_type)
         .bind(token.expires_in)
+        .bind(expires_at)
         .bind(token.access_token)
         .bind(token.refresh_token)
         .execute(&self.pool as &SqlitePool)
@@ -135,7 +195,7 @@ impl TrackerRepository for TrackerRepositoryImpl {
         user_id: i64,
     ) -> Result<Token, TrackerRepositoryError> {
         let row = sqlx::query(
-            r#"SELECT token_type, access_token, refresh_token, expires_in FROM tracker_credential WHERE user_id = ? AND tracker = ?"#,
+            r#"SELECT token_type, access_token, refresh_token, expires_in, expires_at FROM tracker_credential WHERE user_id = ? AND tracker = ?"#,
         )
         .bind(user_id)
         .bind(tracker)
@@ -147,7 +207,67 @@ impl TrackerRepository for TrackerRepositoryImpl {
             access_token: row.get(1),
             refresh_token: row.get(2),
             expires_in: row.get(3),
+            expires_at: row.get(4),
+        })
+    }
+
+    async fn get_expiring_tracker_credentials(
+        &self,
+        before: NaiveDateTime,
+    ) -> Result<Vec<TrackerCredential>, TrackerRepositoryError> {
+        let credentials = sqlx::query(
+            r#"
+            SELECT user_id, tracker, expires_at FROM tracker_credential
+            WHERE expires_at IS NOT NULL AND expires_at < ? AND revoked_at IS NULL
+            "#,
+        )
+        .bind(before)
+        .fetch_all(&self.pool as &SqlitePool)
+        .await?
+        .iter()
+        .map(|row| TrackerCredential {
+            user_id: row.get(0),
+            tracker: row.get(1),
+            expires_at: row.get(2),
         })
+        .collect();
+
+        Ok(credentials)
+    }
+
+    async fn revoke_tracker_credential(
+        &self,
+        user_id: i64,
+        tracker: &str,
+    ) -> Result<(), TrackerRepositoryError> {
+        sqlx::query(
+            r#"UPDATE tracker_credential SET revoked_at = CURRENT_TIMESTAMP WHERE user_id = ? AND tracker = ?"#,
+        )
+        .bind(user_id)
+        .bind(tracker)
+        .execute(&self.pool as &SqlitePool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_revoked_tracker_credentials(
+        &self,
+    ) -> Result<Vec<TrackerCredential>, TrackerRepositoryError> {
+        let credentials = sqlx::query(
+            r#"SELECT user_id, tracker, expires_at FROM tracker_credential WHERE revoked_at IS NOT NULL"#,
+        )
+        .fetch_all(&self.pool as &SqlitePool)
+        .await?
+        .iter()
+        .map(|row| TrackerCredential {
+            user_id: row.get(0),
+            tracker: row.get(1),
+            expires_at: row.get(2),
+        })
+        .collect();
+
+        Ok(credentials)
     }
 
     async fn delete_user_tracker_login(
@@ -248,6 +368,40 @@ impl TrackerRepository for TrackerRepositoryImpl {
         Ok(rows)
     }
 
+    async fn get_tracked_manga_by_user_id(
+        &self,
+        user_id: i64,
+    ) -> Result<Vec<TrackedManga>, TrackerRepositoryError> {
+        let rows = sqlx::query(
+            r#"SELECT tm.manga_id, tm.tracker, tm.tracker_manga_id FROM tracker_manga tm
+                JOIN tracker_credential tc ON tc.user_id = tm.user_id AND tc.tracker = tm.tracker
+                WHERE tm.user_id = ?"#,
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool as &SqlitePool)
+        .await?
+        .iter()
+        .map(|row| TrackedManga {
+            manga_id: row.get(0),
+            tracker: row.get(1),
+            tracker_manga_id: row.get(2),
+        })
+        .collect();
+
+        Ok(rows)
+    }
+
+    async fn get_user_ids_with_tracked_manga(&self) -> Result<Vec<i64>, TrackerRepositoryError> {
+        let rows = sqlx::query(r#"SELECT DISTINCT user_id FROM tracker_manga"#)
+            .fetch_all(&self.pool as &SqlitePool)
+            .await?
+            .iter()
+            .map(|row| row.get(0))
+            .collect();
+
+        Ok(rows)
+    }
+
     async fn fetch_manga_details(
         &self,
         token: &str,
@@ -267,6 +421,25 @@ impl TrackerRepository for TrackerRepositoryImpl {
         }
     }
 
+    async fn fetch_user_manga_list(
+        &self,
+        token: &str,
+        tracker: &str,
+        status: Option<String>,
+    ) -> Result<Vec<TrackerManga>, TrackerRepositoryError> {
+        match self
+            .clients
+            .get(tracker)
+            .ok_or(TrackerRepositoryError::NoTracker)?
+            .get_user_manga_list(token.to_string(), status)
+            .await
+        {
+            Ok(manga) => Ok(manga),
+            Err(tanoshi_tracker::Error::Unauthorized) => Err(TrackerRepositoryError::Unauthorized),
+            Err(e) => Err(TrackerRepositoryError::Other(anyhow::anyhow!("{e}"))),
+        }
+    }
+
     async fn update_manga_tracking_status(
         &self,
         token: &str,
@@ -345,4 +518,341 @@ impl TrackerRepository for TrackerRepositoryImpl {
 
         Ok(())
     }
+
+    async fn insert_tracker_sync_queue(
+        &self,
+        user_id: i64,
+        manga_id: i64,
+        tracker: &str,
+        progress: i64,
+        completed: bool,
+        error: &str,
+    ) -> Result<(), TrackerRepositoryError> {
+        sqlx::query(
+            r#"
+            INSERT INTO tracker_sync_queue(
+                user_id,
+                manga_id,
+                tracker,
+                progress,
+                completed,
+                last_error
+            ) VALUES (?, ?, ?, ?, ?, ?)
+            ON CONFLICT(user_id, manga_id, tracker) DO UPDATE SET
+            progress = MAX(progress, excluded.progress),
+            completed = completed OR excluded.completed,
+            last_error = excluded.last_error,
+            updated_at = CURRENT_TIMESTAMP"#,
+        )
+        .bind(user_id)
+        .bind(manga_id)
+        .bind(tracker)
+        .bind(progress)
+        .bind(completed)
+        .bind(error)
+        .execute(&self.pool as &SqlitePool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_tracker_sync_queue(
+        &self,
+    ) -> Result<Vec<TrackerSyncQueue>, TrackerRepositoryError> {
+        let queue = sqlx::query(
+            r#"
+            SELECT user_id, manga_id, tracker, progress, completed, attempts, last_error
+            FROM tracker_sync_queue
+            ORDER BY updated_at ASC
+            "#,
+        )
+        .fetch_all(&self.pool as &SqlitePool)
+        .await?
+        .iter()
+        .map(|row| TrackerSyncQueue {
+            user_id: row.get(0),
+            manga_id: row.get(1),
+            tracker: row.get(2),
+            progress: row.get(3),
+            completed: row.get(4),
+            attempts: row.get(5),
+            last_error: row.get(6),
+        })
+        .collect();
+
+        Ok(queue)
+    }
+
+    async fn increase_tracker_sync_queue_attempts(
+        &self,
+        user_id: i64,
+        manga_id: i64,
+        tracker: &str,
+        error: &str,
+    ) -> Result<(), TrackerRepositoryError> {
+        sqlx::query(
+            r#"
+            UPDATE tracker_sync_queue
+            SET attempts = attempts + 1, last_error = ?, updated_at = CURRENT_TIMESTAMP
+            WHERE user_id = ? AND manga_id = ? AND tracker = ?
+            "#,
+        )
+        .bind(error)
+        .bind(user_id)
+        .bind(manga_id)
+        .bind(tracker)
+        .execute(&self.pool as &SqlitePool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn delete_tracker_sync_queue(
+        &self,
+        user_id: i64,
+        manga_id: i64,
+        tracker: &str,
+    ) -> Result<(), TrackerRepositoryError> {
+        sqlx::query(
+            r#"
+            DELETE FROM tracker_sync_queue
+            WHERE user_id = ? AND manga_id = ? AND tracker = ?
+            "#,
+        )
+        .bind(user_id)
+        .bind(manga_id)
+        .bind(tracker)
+        .execute(&self.pool as &SqlitePool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_untracked_library_manga(
+        &self,
+        user_id: i64,
+    ) -> Result<Vec<UntrackedManga>, TrackerRepositoryError> {
+        let manga = sqlx::query(
+            r#"
+            SELECT m.id, m.title, tc.tracker FROM user_library ul
+            JOIN manga m ON m.id = ul.manga_id
+            JOIN tracker_credential tc ON tc.user_id = ul.user_id
+            LEFT JOIN tracker_manga tm ON
+                tm.user_id = ul.user_id AND
+                tm.manga_id = ul.manga_id AND
+                tm.tracker = tc.tracker
+            WHERE ul.user_id = ? AND tm.id IS NULL
+            ORDER BY m.title
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool as &SqlitePool)
+        .await?
+        .iter()
+        .map(|row| UntrackedManga {
+            manga_id: row.get(0),
+            title: row.get(1),
+            tracker: row.get(2),
+        })
+        .collect();
+
+        Ok(manga)
+    }
+
+    async fn insert_tracker_match_candidates(
+        &self,
+        user_id: i64,
+        manga_id: i64,
+        tracker: &str,
+        candidates: &[TrackerMatchCandidate],
+    ) -> Result<(), TrackerRepositoryError> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query(
+            r#"DELETE FROM tracker_match_candidate WHERE user_id = ? AND manga_id = ? AND tracker = ?"#,
+        )
+        .bind(user_id)
+        .bind(manga_id)
+        .bind(tracker)
+        .execute(&mut tx)
+        .await?;
+
+        for candidate in candidates {
+            sqlx::query(
+                r#"
+                INSERT OR REPLACE INTO tracker_match_candidate(
+                    user_id,
+                    manga_id,
+                    tracker,
+                    tracker_manga_id,
+                    title,
+                    cover_url,
+                    score
+                ) VALUES (?, ?, ?, ?, ?, ?, ?)"#,
+            )
+            .bind(user_id)
+            .bind(manga_id)
+            .bind(tracker)
+            .bind(&candidate.tracker_manga_id)
+            .bind(&candidate.title)
+            .bind(&candidate.cover_url)
+            .bind(candidate.score)
+            .execute(&mut tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn get_tracker_match_candidates(
+        &self,
+        user_id: i64,
+    ) -> Result<Vec<TrackerMatchCandidate>, TrackerRepositoryError> {
+        let candidates = sqlx::query(
+            r#"
+            SELECT manga_id, tracker, tracker_manga_id, title, cover_url, score
+            FROM tracker_match_candidate
+            WHERE user_id = ?
+            ORDER BY manga_id, tracker, score DESC
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool as &SqlitePool)
+        .await?
+        .iter()
+        .map(|row| TrackerMatchCandidate {
+            manga_id: row.get(0),
+            tracker: row.get(1),
+            tracker_manga_id: row.get(2),
+            title: row.get(3),
+            cover_url: row.get(4),
+            score: row.get(5),
+        })
+        .collect();
+
+        Ok(candidates)
+    }
+
+    async fn delete_tracker_match_candidates(
+        &self,
+        user_id: i64,
+        manga_id: i64,
+        tracker: &str,
+    ) -> Result<(), TrackerRepositoryError> {
+        sqlx::query(
+            r#"DELETE FROM tracker_match_candidate WHERE user_id = ? AND manga_id = ? AND tracker = ?"#,
+        )
+        .bind(user_id)
+        .bind(manga_id)
+        .bind(tracker)
+        .execute(&self.pool as &SqlitePool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn insert_tracker_import_candidates(
+        &self,
+        user_id: i64,
+        tracker: &str,
+        tracker_manga_id: &str,
+        candidates: &[TrackerImportCandidate],
+    ) -> Result<(), TrackerRepositoryError> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query(
+            r#"DELETE FROM tracker_import_candidate WHERE user_id = ? AND tracker = ? AND tracker_manga_id = ?"#,
+        )
+        .bind(user_id)
+        .bind(tracker)
+        .bind(tracker_manga_id)
+        .execute(&mut tx)
+        .await?;
+
+        for candidate in candidates {
+            sqlx::query(
+                r#"
+                INSERT OR REPLACE INTO tracker_import_candidate(
+                    user_id,
+                    tracker,
+                    tracker_manga_id,
+                    tracker_title,
+                    progress,
+                    source_id,
+                    path,
+                    title,
+                    cover_url,
+                    score
+                ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"#,
+            )
+            .bind(user_id)
+            .bind(tracker)
+            .bind(tracker_manga_id)
+            .bind(&candidate.tracker_title)
+            .bind(candidate.progress)
+            .bind(candidate.source_id)
+            .bind(&candidate.path)
+            .bind(&candidate.title)
+            .bind(&candidate.cover_url)
+            .bind(candidate.score)
+            .execute(&mut tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn get_tracker_import_candidates(
+        &self,
+        user_id: i64,
+    ) -> Result<Vec<TrackerImportCandidate>, TrackerRepositoryError> {
+        let candidates = sqlx::query(
+            r#"
+            SELECT tracker, tracker_manga_id, tracker_title, progress, source_id, path, title, cover_url, score
+            FROM tracker_import_candidate
+            WHERE user_id = ?
+            ORDER BY tracker, tracker_manga_id, score DESC
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool as &SqlitePool)
+        .await?
+        .iter()
+        .map(|row| TrackerImportCandidate {
+            tracker: row.get(0),
+            tracker_manga_id: row.get(1),
+            tracker_title: row.get(2),
+            progress: row.get(3),
+            source_id: row.get(4),
+            path: row.get(5),
+            title: row.get(6),
+            cover_url: row.get(7),
+            score: row.get(8),
+        })
+        .collect();
+
+        Ok(candidates)
+    }
+
+    async fn delete_tracker_import_candidates(
+        &self,
+        user_id: i64,
+        tracker: &str,
+        tracker_manga_id: &str,
+    ) -> Result<(), TrackerRepositoryError> {
+        sqlx::query(
+            r#"DELETE FROM tracker_import_candidate WHERE user_id = ? AND tracker = ? AND tracker_manga_id = ?"#,
+        )
+        .bind(user_id)
+        .bind(tracker)
+        .bind(tracker_manga_id)
+        .execute(&self.pool as &SqlitePool)
+        .await?;
+
+        Ok(())
+    }
 }
@@ -1,13 +1,18 @@
 use crate::{
     domain::{
-        entities::user::User,
+        entities::{
+            notification::{NotificationTarget, NotificationTemplate},
+            user::{
+                ApiKey, ApiKeyScope, Invite, LoginAttempt, LoginResult, Permission, Session, User,
+            },
+        },
         repositories::user::{UserRepository, UserRepositoryError},
     },
     infrastructure::database::Pool,
 };
-use anyhow::anyhow;
 use async_trait::async_trait;
-use sqlx::{sqlite::SqliteArguments, Arguments, Row, SqlitePool};
+use chrono::NaiveDateTime;
+use sqlx::{sqlite::SqliteRow, Row, SqlitePool};
 use tokio_stream::StreamExt;
 
 #[derive(Clone)]
@@ -21,17 +26,181 @@ impl UserRepositoryImpl {
     }
 }
 
+pub(super) const USER_COLUMNS: &str = "id, username, password, is_admin, created_at, updated_at, permissions, allowed_sources, show_nsfw, totp_secret, totp_enabled, totp_last_step";
+
+fn join_permissions(permissions: &[Permission]) -> String {
+    permissions
+        .iter()
+        .map(|permission| permission.as_str())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn split_permissions(permissions: &str) -> Vec<Permission> {
+    permissions
+        .split(',')
+        .filter_map(|permission| permission.parse().ok())
+        .collect()
+}
+
+fn join_source_ids(source_ids: &[i64]) -> String {
+    source_ids
+        .iter()
+        .map(|source_id| source_id.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn split_source_ids(source_ids: &str) -> Vec<i64> {
+    source_ids
+        .split(',')
+        .filter_map(|source_id| source_id.parse().ok())
+        .collect()
+}
+
+pub(super) fn user_from_row(row: SqliteRow) -> User {
+    let permissions: String = row.get(6);
+    let allowed_sources: Option<String> = row.get(7);
+
+    User {
+        id: row.get(0),
+        username: row.get(1),
+        password: row.get(2),
+        is_admin: row.get(3),
+        created_at: row.get(4),
+        updated_at: row.get(5),
+        permissions: split_permissions(&permissions),
+        allowed_sources: allowed_sources.as_deref().map(split_source_ids),
+        show_nsfw: row.get(8),
+        totp_secret: row.get(9),
+        totp_enabled: row.get(10),
+        totp_last_step: row.get(11),
+    }
+}
+
+const INSERT_USER: &str = r#"INSERT INTO user(username, password, is_admin, permissions, allowed_sources) VALUES (?, ?, ?, ?, ?)"#;
+
+const INVITE_COLUMNS: &str = "id, code, created_by, is_admin, permissions, allowed_sources, max_uses, uses, expires_at, created_at";
+
+fn invite_from_row(row: SqliteRow) -> Invite {
+    let permissions: String = row.get(4);
+    let allowed_sources: Option<String> = row.get(5);
+
+    Invite {
+        id: row.get(0),
+        code: row.get(1),
+        created_by: row.get(2),
+        is_admin: row.get(3),
+        permissions: split_permissions(&permissions),
+        allowed_sources: allowed_sources.as_deref().map(split_source_ids),
+        max_uses: row.get(6),
+        uses: row.get(7),
+        expires_at: row.get(8),
+        created_at: row.get(9),
+    }
+}
+
+const SESSION_COLUMNS: &str = "id, user_id, refresh_token_hash, previous_refresh_token_hash, user_agent, created_at, refreshed_at, expires_at";
+
+fn session_from_row(row: SqliteRow) -> Session {
+    Session {
+        id: row.get(0),
+        user_id: row.get(1),
+        refresh_token_hash: row.get(2),
+        previous_refresh_token_hash: row.get(3),
+        user_agent: row.get(4),
+        created_at: row.get(5),
+        refreshed_at: row.get(6),
+        expires_at: row.get(7),
+    }
+}
+
+const API_KEY_COLUMNS: &str =
+    "id, user_id, name, key_hash, prefix, scopes, created_at, last_used_at";
+
+fn api_key_from_row(row: SqliteRow) -> ApiKey {
+    let scopes: String = row.get(5);
+
+    ApiKey {
+        id: row.get(0),
+        user_id: row.get(1),
+        name: row.get(2),
+        key_hash: row.get(3),
+        prefix: row.get(4),
+        scopes: scopes
+            .split(',')
+            .filter_map(|scope| scope.parse().ok())
+            .collect(),
+        created_at: row.get(6),
+        last_used_at: row.get(7),
+    }
+}
+
+const LOGIN_ATTEMPT_COLUMNS: &str = "id, username, ip, user_agent, result, created_at";
+
+fn login_attempt_from_row(row: SqliteRow) -> LoginAttempt {
+    let result: String = row.get(4);
+
+    LoginAttempt {
+        id: row.get(0),
+        username: row.get(1),
+        ip: row.get(2),
+        user_agent: row.get(3),
+        result: result.parse().unwrap_or(LoginResult::Locked),
+        created_at: row.get(5),
+    }
+}
+
 #[async_trait]
 impl UserRepository for UserRepositoryImpl {
     async fn insert_user(&self, user: User) -> Result<i64, UserRepositoryError> {
-        let row_id =
-            sqlx::query(r#"INSERT INTO user(username, password,is_admin) VALUES (?, ?, ?)"#)
-                .bind(&user.username)
-                .bind(&user.password)
-                .bind(user.is_admin)
-                .execute(&self.pool as &SqlitePool)
-                .await?
-                .last_insert_rowid();
+        let row_id = sqlx::query(INSERT_USER)
+            .bind(&user.username)
+            .bind(&user.password)
+            .bind(user.is_admin)
+            .bind(join_permissions(&user.permissions))
+            .bind(user.allowed_sources.as_deref().map(join_source_ids))
+            .execute(&self.pool as &SqlitePool)
+            .await?
+            .last_insert_rowid();
+
+        Ok(row_id)
+    }
+
+    async fn insert_user_with_invite(
+        &self,
+        user: User,
+        invite_id: i64,
+        now: NaiveDateTime,
+    ) -> Result<i64, UserRepositoryError> {
+        let mut tx = self.pool.begin().await?;
+
+        let rows_affected = sqlx::query(
+            r#"UPDATE invite SET uses = uses + 1
+            WHERE id = ?
+            AND (max_uses IS NULL OR uses < max_uses)
+            AND (expires_at IS NULL OR expires_at > ?)"#,
+        )
+        .bind(invite_id)
+        .bind(now)
+        .execute(&mut tx)
+        .await?
+        .rows_affected();
+        if rows_affected == 0 {
+            return Err(UserRepositoryError::NotFound);
+        }
+
+        let row_id = sqlx::query(INSERT_USER)
+            .bind(&user.username)
+            .bind(&user.password)
+            .bind(user.is_admin)
+            .bind(join_permissions(&user.permissions))
+            .bind(user.allowed_sources.as_deref().map(join_source_ids))
+            .execute(&mut tx)
+            .await?
+            .last_insert_rowid();
+
+        tx.commit().await?;
 
         Ok(row_id)
     }
@@ -47,7 +216,6 @@ impl UserRepository for UserRepositoryImpl {
         Ok(row_id)
     }
 
-    #[allow(dead_code)]
     async fn update_user_is_admin(
         &self,
         id: i64,
@@ -63,22 +231,145 @@ impl UserRepository for UserRepositoryImpl {
         Ok(row_id)
     }
 
+    async fn update_user_permissions(
+        &self,
+        id: i64,
+        permissions: &[Permission],
+    ) -> Result<u64, UserRepositoryError> {
+        let row_id = sqlx::query(r#"UPDATE user SET permissions = ? WHERE id = ?"#)
+            .bind(join_permissions(permissions))
+            .bind(id)
+            .execute(&self.pool as &SqlitePool)
+            .await?
+            .rows_affected();
+
+        Ok(row_id)
+    }
+
+    async fn update_user_allowed_sources(
+        &self,
+        id: i64,
+        allowed_sources: Option<&[i64]>,
+    ) -> Result<u64, UserRepositoryError> {
+        let row_id = sqlx::query(r#"UPDATE user SET allowed_sources = ? WHERE id = ?"#)
+            .bind(allowed_sources.map(join_source_ids))
+            .bind(id)
+            .execute(&self.pool as &SqlitePool)
+            .await?
+            .rows_affected();
+
+        Ok(row_id)
+    }
+
+    async fn update_user_show_nsfw(
+        &self,
+        id: i64,
+        show_nsfw: bool,
+    ) -> Result<u64, UserRepositoryError> {
+        let row_id = sqlx::query(r#"UPDATE user SET show_nsfw = ? WHERE id = ?"#)
+            .bind(show_nsfw)
+            .bind(id)
+            .execute(&self.pool as &SqlitePool)
+            .await?
+            .rows_affected();
+
+        Ok(row_id)
+    }
+
+    async fn update_user_totp(
+        &self,
+        id: i64,
+        secret: Option<&str>,
+        enabled: bool,
+    ) -> Result<u64, UserRepositoryError> {
+        let row_id = sqlx::query(
+            r#"UPDATE user SET totp_secret = ?, totp_enabled = ?, totp_last_step = NULL WHERE id = ?"#,
+        )
+        .bind(secret)
+        .bind(enabled)
+        .bind(id)
+        .execute(&self.pool as &SqlitePool)
+        .await?
+        .rows_affected();
+
+        Ok(row_id)
+    }
+
+    async fn update_user_totp_last_step(
+        &self,
+        id: i64,
+        step: i64,
+    ) -> Result<u64, UserRepositoryError> {
+        let row_id = sqlx::query(
+            r#"UPDATE user SET totp_last_step = ?
+            WHERE id = ? AND (totp_last_step IS NULL OR totp_last_step < ?)"#,
+        )
+        .bind(step)
+        .bind(id)
+        .bind(step)
+        .execute(&self.pool as &SqlitePool)
+        .await?
+        .rows_affected();
+
+        Ok(row_id)
+    }
+
+    async fn replace_totp_recovery_codes(
+        &self,
+        user_id: i64,
+        code_hashes: &[String],
+    ) -> Result<(), UserRepositoryError> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query("DELETE FROM totp_recovery_code WHERE user_id = ?")
+            .bind(user_id)
+            .execute(&mut tx)
+            .await?;
+
+        for code_hash in code_hashes {
+            sqlx::query("INSERT INTO totp_recovery_code(user_id, code_hash) VALUES (?, ?)")
+                .bind(user_id)
+                .bind(code_hash)
+                .execute(&mut tx)
+                .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn delete_totp_recovery_code(
+        &self,
+        user_id: i64,
+        code_hash: &str,
+    ) -> Result<u64, UserRepositoryError> {
+        let row_id =
+            sqlx::query("DELETE FROM totp_recovery_code WHERE user_id = ? AND code_hash = ?")
+                .bind(user_id)
+                .bind(code_hash)
+                .execute(&self.pool as &SqlitePool)
+                .await?
+                .rows_affected();
+
+        Ok(row_id)
+    }
+
+    async fn count_totp_recovery_codes(&self, user_id: i64) -> Result<i64, UserRepositoryError> {
+        let row = sqlx::query("SELECT COUNT(1) FROM totp_recovery_code WHERE user_id = ?")
+            .bind(user_id)
+            .fetch_one(&self.pool as &SqlitePool)
+            .await?;
+
+        Ok(row.get(0))
+    }
+
     async fn get_users(&self) -> Result<Vec<User>, UserRepositoryError> {
-        let users = sqlx::query(r#"SELECT * FROM user"#)
+        let users = sqlx::query(&format!("SELECT {USER_COLUMNS} FROM user"))
             .fetch_all(&self.pool as &SqlitePool)
             .await?
             .into_iter()
-            .map(|row| User {
-                id: row.get(0),
-                username: row.get(1),
-                password: row.get(2),
-                is_admin: row.get(3),
-                created_at: row.get(4),
-                updated_at: row.get(5),
-                telegram_chat_id: row.get(6),
-                pushover_user_key: row.get(7),
-                gotify_token: row.get(8),
-            })
+            .map(user_from_row)
             .collect();
 
         Ok(users)
@@ -93,93 +384,264 @@ impl UserRepository for UserRepositoryImpl {
     }
 
     async fn get_admins(&self) -> Result<Vec<User>, UserRepositoryError> {
-        let mut stream = sqlx::query(r#"SELECT * FROM user WHERE is_admin = true"#)
-            .fetch(&self.pool as &SqlitePool);
+        let query = format!("SELECT {USER_COLUMNS} FROM user WHERE is_admin = true");
+        let mut stream = sqlx::query(&query).fetch(&self.pool as &SqlitePool);
 
         let mut users = vec![];
         while let Some(row) = stream.try_next().await? {
-            users.push(User {
-                id: row.get(0),
-                username: row.get(1),
-                password: row.get(2),
-                is_admin: row.get(3),
-                created_at: row.get(4),
-                updated_at: row.get(5),
-                telegram_chat_id: row.get(6),
-                pushover_user_key: row.get(7),
-                gotify_token: row.get(8),
-            });
+            users.push(user_from_row(row));
         }
         Ok(users)
     }
 
     async fn get_user_by_id(&self, id: i64) -> Result<User, UserRepositoryError> {
-        let row = sqlx::query(r#"SELECT * FROM user WHERE id = ?"#)
+        let row = sqlx::query(&format!("SELECT {USER_COLUMNS} FROM user WHERE id = ?"))
             .bind(id)
             .fetch_one(&self.pool as &SqlitePool)
             .await?;
 
-        Ok(User {
-            id: row.get(0),
-            username: row.get(1),
-            password: row.get(2),
-            is_admin: row.get(3),
-            created_at: row.get(4),
-            updated_at: row.get(5),
-            telegram_chat_id: row.get(6),
-            pushover_user_key: row.get(7),
-            gotify_token: row.get(8),
-        })
+        Ok(user_from_row(row))
     }
 
     async fn get_user_by_username(&self, username: String) -> Result<User, UserRepositoryError> {
-        let row = sqlx::query(r#"SELECT * FROM user WHERE username = ?"#)
-            .bind(&username)
-            .fetch_one(&self.pool as &SqlitePool)
-            .await?;
+        let row = sqlx::query(&format!(
+            "SELECT {USER_COLUMNS} FROM user WHERE username = ?"
+        ))
+        .bind(&username)
+        .fetch_optional(&self.pool as &SqlitePool)
+        .await?
+        .ok_or(UserRepositoryError::NotFound)?;
 
-        Ok(User {
+        Ok(user_from_row(row))
+    }
+
+    async fn get_user_by_identity(
+        &self,
+        issuer: &str,
+        subject: &str,
+    ) -> Result<User, UserRepositoryError> {
+        let row = sqlx::query(&format!(
+            r#"SELECT {USER_COLUMNS} FROM user
+            WHERE id = (SELECT user_id FROM user_identity WHERE issuer = ? AND subject = ?)"#
+        ))
+        .bind(issuer)
+        .bind(subject)
+        .fetch_optional(&self.pool as &SqlitePool)
+        .await?
+        .ok_or(UserRepositoryError::NotFound)?;
+
+        Ok(user_from_row(row))
+    }
+
+    async fn insert_user_identity(
+        &self,
+        user_id: i64,
+        issuer: &str,
+        subject: &str,
+    ) -> Result<i64, UserRepositoryError> {
+        let row_id =
+            sqlx::query(r#"INSERT INTO user_identity(user_id, issuer, subject) VALUES (?, ?, ?)"#)
+                .bind(user_id)
+                .bind(issuer)
+                .bind(subject)
+                .execute(&self.pool as &SqlitePool)
+                .await?
+                .last_insert_rowid();
+
+        Ok(row_id)
+    }
+
+    async fn get_notification_targets(
+        &self,
+        user_id: i64,
+    ) -> Result<Vec<NotificationTarget>, UserRepositoryError> {
+        let targets = sqlx::query(
+            r#"SELECT id, user_id, channel, target, created_at FROM notification_target
+            WHERE user_id = ?
+            ORDER BY id"#,
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool as &SqlitePool)
+        .await?
+        .into_iter()
+        .map(|row| NotificationTarget {
             id: row.get(0),
-            username: row.get(1),
-            password: row.get(2),
-            is_admin: row.get(3),
+            user_id: row.get(1),
+            channel: row.get(2),
+            target: row.get(3),
             created_at: row.get(4),
-            updated_at: row.get(5),
-            telegram_chat_id: row.get(6),
-            pushover_user_key: row.get(7),
-            gotify_token: row.get(8),
         })
+        .collect();
+
+        Ok(targets)
     }
 
-    async fn update_user_setting(&self, user: &User) -> Result<u64, UserRepositoryError> {
-        let mut column_to_update = vec![];
-        let mut arguments = SqliteArguments::default();
+    async fn insert_notification_target(
+        &self,
+        user_id: i64,
+        channel: &str,
+        target: &str,
+    ) -> Result<i64, UserRepositoryError> {
+        let row_id = sqlx::query(
+            r#"INSERT INTO notification_target(user_id, channel, target) VALUES (?, ?, ?)"#,
+        )
+        .bind(user_id)
+        .bind(channel)
+        .bind(target)
+        .execute(&self.pool as &SqlitePool)
+        .await?
+        .last_insert_rowid();
 
-        column_to_update.push("telegram_chat_id = ?");
-        column_to_update.push("pushover_user_key = ?");
-        column_to_update.push("gotify_token = ?");
-        arguments.add(user.telegram_chat_id);
-        arguments.add(user.pushover_user_key.clone());
-        arguments.add(user.gotify_token.clone());
-        arguments.add(user.id);
+        Ok(row_id)
+    }
 
-        if column_to_update.is_empty() {
-            return Err(UserRepositoryError::Other(anyhow!("Nothing to update")));
-        }
+    async fn upsert_notification_target(
+        &self,
+        user_id: i64,
+        channel: &str,
+        target: &str,
+    ) -> Result<u64, UserRepositoryError> {
+        let rows_affected = sqlx::query(
+            r#"INSERT INTO notification_target(user_id, channel, target) VALUES (?, ?, ?)
+            ON CONFLICT(channel, target) WHERE channel = 'telegram'
+            DO UPDATE SET user_id = excluded.user_id"#,
+        )
+        .bind(user_id)
+        .bind(channel)
+        .bind(target)
+        .execute(&self.pool as &SqlitePool)
+        .await?
+        .rows_affected();
 
-        let query = format!(
-            r#"UPDATE user SET {} WHERE id = ?"#,
-            column_to_update.join(",")
-        );
+        Ok(rows_affected)
+    }
 
-        let rows_affected = sqlx::query_with(&query, arguments)
-            .execute(&self.pool as &SqlitePool)
-            .await?
-            .rows_affected();
+    async fn delete_notification_target(
+        &self,
+        user_id: i64,
+        id: i64,
+    ) -> Result<u64, UserRepositoryError> {
+        let rows_affected =
+            sqlx::query(r#"DELETE FROM notification_target WHERE user_id = ? AND id = ?"#)
+                .bind(user_id)
+                .bind(id)
+                .execute(&self.pool as &SqlitePool)
+                .await?
+                .rows_affected();
 
         Ok(rows_affected)
     }
 
+    async fn get_user_id_by_notification_target(
+        &self,
+        channel: &str,
+        target: &str,
+    ) -> Result<i64, UserRepositoryError> {
+        let row = sqlx::query(
+            r#"SELECT user_id FROM notification_target WHERE channel = ? AND target = ?"#,
+        )
+        .bind(channel)
+        .bind(target)
+        .fetch_optional(&self.pool as &SqlitePool)
+        .await?
+        .ok_or(UserRepositoryError::NotFound)?;
+
+        Ok(row.get(0))
+    }
+
+    async fn insert_notification_link_code(
+        &self,
+        user_id: i64,
+        code: &str,
+    ) -> Result<(), UserRepositoryError> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query(r#"DELETE FROM notification_link_code WHERE user_id = ?"#)
+            .bind(user_id)
+            .execute(&mut tx)
+            .await?;
+
+        sqlx::query(r#"INSERT INTO notification_link_code(code, user_id) VALUES (?, ?)"#)
+            .bind(code)
+            .bind(user_id)
+            .execute(&mut tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn take_notification_link_code(
+        &self,
+        code: &str,
+    ) -> Result<(i64, NaiveDateTime), UserRepositoryError> {
+        let mut tx = self.pool.begin().await?;
+
+        let row =
+            sqlx::query(r#"SELECT user_id, created_at FROM notification_link_code WHERE code = ?"#)
+                .bind(code)
+                .fetch_optional(&mut tx)
+                .await?
+                .ok_or(UserRepositoryError::NotFound)?;
+
+        sqlx::query(r#"DELETE FROM notification_link_code WHERE code = ?"#)
+            .bind(code)
+            .execute(&mut tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok((row.get(0), row.get(1)))
+    }
+
+    async fn get_notification_template(
+        &self,
+        user_id: i64,
+    ) -> Result<Option<NotificationTemplate>, UserRepositoryError> {
+        let template =
+            sqlx::query(r#"SELECT title, body FROM notification_template WHERE user_id = ?"#)
+                .bind(user_id)
+                .fetch_optional(&self.pool as &SqlitePool)
+                .await?
+                .map(|row| NotificationTemplate {
+                    title: row.get(0),
+                    body: row.get(1),
+                });
+
+        Ok(template)
+    }
+
+    async fn upsert_notification_template(
+        &self,
+        user_id: i64,
+        template: &NotificationTemplate,
+    ) -> Result<(), UserRepositoryError> {
+        sqlx::query(
+            r#"INSERT INTO notification_template(user_id, title, body) VALUES (?, ?, ?)
+            ON CONFLICT(user_id) DO UPDATE SET
+            title = excluded.title,
+            body = excluded.body,
+            updated_at = CURRENT_TIMESTAMP"#,
+        )
+        .bind(user_id)
+        .bind(&template.title)
+        .bind(&template.body)
+        .execute(&self.pool as &SqlitePool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn delete_notification_template(&self, user_id: i64) -> Result<(), UserRepositoryError> {
+        sqlx::query(r#"DELETE FROM notification_template WHERE user_id = ?"#)
+            .bind(user_id)
+            .execute(&self.pool as &SqlitePool)
+            .await?;
+
+        Ok(())
+    }
+
     async fn delete_user(&self, id: i64) -> Result<(), UserRepositoryError> {
         sqlx::query(r#"DELETE FROM user WHERE id = ?"#)
             .bind(id)
@@ -188,4 +650,435 @@ impl UserRepository for UserRepositoryImpl {
 
         Ok(())
     }
+
+    async fn insert_session(
+        &self,
+        user_id: i64,
+        refresh_token_hash: &str,
+        user_agent: Option<&str>,
+        expires_at: NaiveDateTime,
+    ) -> Result<i64, UserRepositoryError> {
+        let row_id = sqlx::query(
+            r#"INSERT INTO session(user_id, refresh_token_hash, user_agent, expires_at) VALUES (?, ?, ?, ?)"#,
+        )
+        .bind(user_id)
+        .bind(refresh_token_hash)
+        .bind(user_agent)
+        .bind(expires_at)
+        .execute(&self.pool as &SqlitePool)
+        .await?
+        .last_insert_rowid();
+
+        Ok(row_id)
+    }
+
+    async fn get_session_by_id(&self, id: i64) -> Result<Session, UserRepositoryError> {
+        let row = sqlx::query(&format!(
+            "SELECT {SESSION_COLUMNS} FROM session WHERE id = ?"
+        ))
+        .bind(id)
+        .fetch_optional(&self.pool as &SqlitePool)
+        .await?
+        .ok_or(UserRepositoryError::NotFound)?;
+
+        Ok(session_from_row(row))
+    }
+
+    async fn get_session_by_refresh_token_hash(
+        &self,
+        refresh_token_hash: &str,
+    ) -> Result<Session, UserRepositoryError> {
+        let row = sqlx::query(&format!(
+            "SELECT {SESSION_COLUMNS} FROM session WHERE refresh_token_hash = ? OR previous_refresh_token_hash = ?"
+        ))
+        .bind(refresh_token_hash)
+        .bind(refresh_token_hash)
+        .fetch_optional(&self.pool as &SqlitePool)
+        .await?
+        .ok_or(UserRepositoryError::NotFound)?;
+
+        Ok(session_from_row(row))
+    }
+
+    async fn get_sessions_by_user_id(
+        &self,
+        user_id: i64,
+    ) -> Result<Vec<Session>, UserRepositoryError> {
+        let sessions = sqlx::query(&format!(
+            "SELECT {SESSION_COLUMNS} FROM session WHERE user_id = ? ORDER BY refreshed_at DESC"
+        ))
+        .bind(user_id)
+        .fetch_all(&self.pool as &SqlitePool)
+        .await?
+        .into_iter()
+        .map(session_from_row)
+        .collect();
+
+        Ok(sessions)
+    }
+
+    async fn rotate_session_refresh_token(
+        &self,
+        id: i64,
+        old_hash: &str,
+        new_hash: &str,
+        refreshed_at: NaiveDateTime,
+        expires_at: NaiveDateTime,
+    ) -> Result<u64, UserRepositoryError> {
+        let rows_affected = sqlx::query(
+            r#"UPDATE session SET
+            refresh_token_hash = ?,
+            previous_refresh_token_hash = refresh_token_hash,
+            refreshed_at = ?,
+            expires_at = ?
+            WHERE id = ? AND refresh_token_hash = ?"#,
+        )
+        .bind(new_hash)
+        .bind(refreshed_at)
+        .bind(expires_at)
+        .bind(id)
+        .bind(old_hash)
+        .execute(&self.pool as &SqlitePool)
+        .await?
+        .rows_affected();
+
+        Ok(rows_affected)
+    }
+
+    async fn delete_session(&self, user_id: i64, id: i64) -> Result<u64, UserRepositoryError> {
+        let rows_affected = sqlx::query(r#"DELETE FROM session WHERE user_id = ? AND id = ?"#)
+            .bind(user_id)
+            .bind(id)
+            .execute(&self.pool as &SqlitePool)
+            .await?
+            .rows_affected();
+
+        Ok(rows_affected)
+    }
+
+    async fn delete_sessions_by_user_id(&self, user_id: i64) -> Result<u64, UserRepositoryError> {
+        let rows_affected = sqlx::query(r#"DELETE FROM session WHERE user_id = ?"#)
+            .bind(user_id)
+            .execute(&self.pool as &SqlitePool)
+            .await?
+            .rows_affected();
+
+        Ok(rows_affected)
+    }
+
+    async fn delete_expired_sessions(
+        &self,
+        before: NaiveDateTime,
+    ) -> Result<u64, UserRepositoryError> {
+        let rows_affected = sqlx::query(r#"DELETE FROM session WHERE expires_at < ?"#)
+            .bind(before)
+            .execute(&self.pool as &SqlitePool)
+            .await?
+            .rows_affected();
+
+        Ok(rows_affected)
+    }
+
+    async fn insert_api_key(
+        &self,
+        user_id: i64,
+        name: &str,
+        key_hash: &str,
+        prefix: &str,
+        scopes: &[ApiKeyScope],
+    ) -> Result<i64, UserRepositoryError> {
+        let scopes = scopes
+            .iter()
+            .map(|scope| scope.as_str())
+            .collect::<Vec<_>>()
+            .join(",");
+
+        let row_id = sqlx::query(
+            r#"INSERT INTO api_key(user_id, name, key_hash, prefix, scopes) VALUES (?, ?, ?, ?, ?)"#,
+        )
+        .bind(user_id)
+        .bind(name)
+        .bind(key_hash)
+        .bind(prefix)
+        .bind(scopes)
+        .execute(&self.pool as &SqlitePool)
+        .await?
+        .last_insert_rowid();
+
+        Ok(row_id)
+    }
+
+    async fn get_api_key_by_id(&self, id: i64) -> Result<ApiKey, UserRepositoryError> {
+        let row = sqlx::query(&format!(
+            "SELECT {API_KEY_COLUMNS} FROM api_key WHERE id = ?"
+        ))
+        .bind(id)
+        .fetch_optional(&self.pool as &SqlitePool)
+        .await?
+        .ok_or(UserRepositoryError::NotFound)?;
+
+        Ok(api_key_from_row(row))
+    }
+
+    async fn get_api_key_by_hash(&self, key_hash: &str) -> Result<ApiKey, UserRepositoryError> {
+        let row = sqlx::query(&format!(
+            "SELECT {API_KEY_COLUMNS} FROM api_key WHERE key_hash = ?"
+        ))
+        .bind(key_hash)
+        .fetch_optional(&self.pool as &SqlitePool)
+        .await?
+        .ok_or(UserRepositoryError::NotFound)?;
+
+        Ok(api_key_from_row(row))
+    }
+
+    async fn get_api_keys_by_user_id(
+        &self,
+        user_id: i64,
+    ) -> Result<Vec<ApiKey>, UserRepositoryError> {
+        let api_keys = sqlx::query(&format!(
+            "SELECT {API_KEY_COLUMNS} FROM api_key WHERE user_id = ? ORDER BY created_at DESC"
+        ))
+        .bind(user_id)
+        .fetch_all(&self.pool as &SqlitePool)
+        .await?
+        .into_iter()
+        .map(api_key_from_row)
+        .collect();
+
+        Ok(api_keys)
+    }
+
+    async fn update_api_key_last_used_at(
+        &self,
+        id: i64,
+        last_used_at: NaiveDateTime,
+    ) -> Result<u64, UserRepositoryError> {
+        let rows_affected = sqlx::query(r#"UPDATE api_key SET last_used_at = ? WHERE id = ?"#)
+            .bind(last_used_at)
+            .bind(id)
+            .execute(&self.pool as &SqlitePool)
+            .await?
+            .rows_affected();
+
+        Ok(rows_affected)
+    }
+
+    async fn delete_api_key(&self, user_id: i64, id: i64) -> Result<u64, UserRepositoryError> {
+        let rows_affected = sqlx::query(r#"DELETE FROM api_key WHERE user_id = ? AND id = ?"#)
+            .bind(user_id)
+            .bind(id)
+            .execute(&self.pool as &SqlitePool)
+            .await?
+            .rows_affected();
+
+        Ok(rows_affected)
+    }
+
+    async fn insert_login_attempt(
+        &self,
+        username: &str,
+        ip: Option<&str>,
+        user_agent: Option<&str>,
+        result: LoginResult,
+        created_at: NaiveDateTime,
+    ) -> Result<i64, UserRepositoryError> {
+        let row_id = sqlx::query(
+            r#"INSERT INTO login_attempt(username, ip, user_agent, result, created_at) VALUES (?, ?, ?, ?, ?)"#,
+        )
+        .bind(username)
+        .bind(ip)
+        .bind(user_agent)
+        .bind(result.as_str())
+        .bind(created_at)
+        .execute(&self.pool as &SqlitePool)
+        .await?
+        .last_insert_rowid();
+
+        Ok(row_id)
+    }
+
+    async fn update_login_attempt_result(
+        &self,
+        id: i64,
+        result: LoginResult,
+    ) -> Result<u64, UserRepositoryError> {
+        let rows_affected = sqlx::query(r#"UPDATE login_attempt SET result = ? WHERE id = ?"#)
+            .bind(result.as_str())
+            .bind(id)
+            .execute(&self.pool as &SqlitePool)
+            .await?
+            .rows_affected();
+
+        Ok(rows_affected)
+    }
+
+    async fn delete_login_attempt(&self, id: i64) -> Result<u64, UserRepositoryError> {
+        let rows_affected = sqlx::query(r#"DELETE FROM login_attempt WHERE id = ?"#)
+            .bind(id)
+            .execute(&self.pool as &SqlitePool)
+            .await?
+            .rows_affected();
+
+        Ok(rows_affected)
+    }
+
+    async fn delete_login_attempts_before(
+        &self,
+        before: NaiveDateTime,
+    ) -> Result<u64, UserRepositoryError> {
+        let rows_affected = sqlx::query(r#"DELETE FROM login_attempt WHERE created_at < ?"#)
+            .bind(before)
+            .execute(&self.pool as &SqlitePool)
+            .await?
+            .rows_affected();
+
+        Ok(rows_affected)
+    }
+
+    async fn count_login_failures_by_username(
+        &self,
+        username: &str,
+        since: NaiveDateTime,
+    ) -> Result<i64, UserRepositoryError> {
+        let row = sqlx::query(
+            r#"SELECT COUNT(1) FROM login_attempt
+            WHERE username = ? AND result IN (?, ?, ?, ?) AND created_at > ?"#,
+        )
+        .bind(username)
+        .bind(LoginResult::WrongPassword.as_str())
+        .bind(LoginResult::UnknownUser.as_str())
+        .bind(LoginResult::WrongTotp.as_str())
+        .bind(LoginResult::Pending.as_str())
+        .bind(since)
+        .fetch_one(&self.pool as &SqlitePool)
+        .await?;
+
+        Ok(row.get(0))
+    }
+
+    async fn count_login_failures_by_ip(
+        &self,
+        ip: &str,
+        since: NaiveDateTime,
+    ) -> Result<i64, UserRepositoryError> {
+        let row = sqlx::query(
+            r#"SELECT COUNT(1) FROM login_attempt
+            WHERE ip = ? AND result IN (?, ?, ?, ?) AND created_at > ?"#,
+        )
+        .bind(ip)
+        .bind(LoginResult::WrongPassword.as_str())
+        .bind(LoginResult::UnknownUser.as_str())
+        .bind(LoginResult::WrongTotp.as_str())
+        .bind(LoginResult::Pending.as_str())
+        .bind(since)
+        .fetch_one(&self.pool as &SqlitePool)
+        .await?;
+
+        Ok(row.get(0))
+    }
+
+    async fn get_last_login_success_at(
+        &self,
+        username: &str,
+    ) -> Result<Option<NaiveDateTime>, UserRepositoryError> {
+        let row = sqlx::query(
+            r#"SELECT created_at FROM login_attempt
+            WHERE username = ? AND result = ?
+            ORDER BY created_at DESC
+            LIMIT 1"#,
+        )
+        .bind(username)
+        .bind(LoginResult::Success.as_str())
+        .fetch_optional(&self.pool as &SqlitePool)
+        .await?;
+
+        Ok(row.map(|row| row.get(0)))
+    }
+
+    async fn get_login_attempts(
+        &self,
+        username: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<LoginAttempt>, UserRepositoryError> {
+        let attempts = sqlx::query(&format!(
+            "SELECT {LOGIN_ATTEMPT_COLUMNS} FROM login_attempt
+            WHERE ? IS NULL OR username = ?
+            ORDER BY created_at DESC, id DESC
+            LIMIT ?"
+        ))
+        .bind(username)
+        .bind(username)
+        .bind(limit)
+        .fetch_all(&self.pool as &SqlitePool)
+        .await?
+        .into_iter()
+        .map(login_attempt_from_row)
+        .collect();
+
+        Ok(attempts)
+    }
+
+    async fn insert_invite(&self, invite: &Invite) -> Result<i64, UserRepositoryError> {
+        let row_id = sqlx::query(
+            r#"INSERT INTO invite(code, created_by, is_admin, permissions, allowed_sources, max_uses, expires_at) VALUES (?, ?, ?, ?, ?, ?, ?)"#,
+        )
+        .bind(&invite.code)
+        .bind(invite.created_by)
+        .bind(invite.is_admin)
+        .bind(join_permissions(&invite.permissions))
+        .bind(invite.allowed_sources.as_deref().map(join_source_ids))
+        .bind(invite.max_uses)
+        .bind(invite.expires_at)
+        .execute(&self.pool as &SqlitePool)
+        .await?
+        .last_insert_rowid();
+
+        Ok(row_id)
+    }
+
+    async fn get_invite_by_id(&self, id: i64) -> Result<Invite, UserRepositoryError> {
+        let row = sqlx::query(&format!("SELECT {INVITE_COLUMNS} FROM invite WHERE id = ?"))
+            .bind(id)
+            .fetch_optional(&self.pool as &SqlitePool)
+            .await?
+            .ok_or(UserRepositoryError::NotFound)?;
+
+        Ok(invite_from_row(row))
+    }
+
+    async fn get_invite_by_code(&self, code: &str) -> Result<Invite, UserRepositoryError> {
+        let row = sqlx::query(&format!(
+            "SELECT {INVITE_COLUMNS} FROM invite WHERE code = ?"
+        ))
+        .bind(code)
+        .fetch_optional(&self.pool as &SqlitePool)
+        .await?
+        .ok_or(UserRepositoryError::NotFound)?;
+
+        Ok(invite_from_row(row))
+    }
+
+    async fn get_invites(&self) -> Result<Vec<Invite>, UserRepositoryError> {
+        let invites = sqlx::query(&format!(
+            "SELECT {INVITE_COLUMNS} FROM invite ORDER BY created_at DESC, id DESC"
+        ))
+        .fetch_all(&self.pool as &SqlitePool)
+        .await?
+        .into_iter()
+        .map(invite_from_row)
+        .collect();
+
+        Ok(invites)
+    }
+
+    async fn delete_invite(&self, id: i64) -> Result<u64, UserRepositoryError> {
+        let row_id = sqlx::query("DELETE FROM invite WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool as &SqlitePool)
+            .await?
+            .rows_affected();
+
+        Ok(row_id)
+    }
 }
@@ -1,6 +1,9 @@
+pub mod archive;
 pub mod auth;
 pub mod config;
 pub mod database;
 pub mod domain;
 pub mod local;
 pub mod notification;
+pub mod oidc;
+pub mod totp;
@@ -1,14 +1,70 @@
-use crate::domain::repositories::user::UserRepository;
-use tanoshi_notifier::{gotify::Gotify, pushover::Pushover, telegram::Telegram, Notifier};
+use std::{collections::HashMap, sync::Arc};
+
+use crate::domain::{
+    entities::notification::NotificationTemplate, repositories::user::UserRepository,
+};
+use tanoshi_notifier::{
+    discord::{self, Discord},
+    email::{self, Email},
+    gotify::{self, Gotify},
+    matrix::{self, Matrix},
+    ntfy::{self, Ntfy},
+    pushover::{self, Pushover},
+    telegram::{self, Telegram},
+    template,
+    webhook::{self, Webhook},
+    Message, Notifier,
+};
+
+/// Placeholders available in notification templates
+pub const PLACEHOLDERS: &[&str] = &[
+    "manga",
+    "chapter",
+    "chapter_number",
+    "scanlator",
+    "source",
+    "cover_url",
+    "unread_count",
+    "url",
+];
+
+#[derive(Debug, Clone, Default)]
+pub struct ChapterNotification {
+    pub chapter_id: i64,
+    pub manga_title: String,
+    pub chapter_title: String,
+    pub chapter_number: f64,
+    pub scanlator: String,
+    pub source: String,
+    pub cover_url: String,
+    pub unread_count: i64,
+}
+
+impl ChapterNotification {
+    fn placeholder(&self, name: &str, url: Option<&str>) -> Option<String> {
+        let value = match name {
+            "manga" => self.manga_title.clone(),
+            "chapter" => self.chapter_title.clone(),
+            "chapter_number" => self.chapter_number.to_string(),
+            "scanlator" => self.scanlator.clone(),
+            "source" => self.source.clone(),
+            "cover_url" => self.cover_url.clone(),
+            "unread_count" => self.unread_count.to_string(),
+            "url" => url.unwrap_or_default().to_string(),
+            _ => return None,
+        };
+
+        Some(value)
+    }
+}
 
 pub struct Builder<R>
 where
     R: UserRepository,
 {
     user_repo: R,
-    pushover: Option<Pushover>,
-    telegram: Option<Telegram>,
-    gotify: Option<Gotify>,
+    notifiers: HashMap<&'static str, Box<dyn Notifier>>,
+    template: NotificationTemplate,
     base_url: Option<String>,
 }
 
@@ -19,32 +75,51 @@ where
     pub fn new(user_repo: R) -> Self {
         Self {
             user_repo,
-            pushover: None,
-            telegram: None,
-            gotify: None,
+            notifiers: HashMap::new(),
+            template: NotificationTemplate::default(),
             base_url: None,
         }
     }
 
+    fn notifier(mut self, name: &'static str, notifier: Box<dyn Notifier>) -> Self {
+        self.notifiers.insert(name, notifier);
+        self
+    }
+
     pub fn telegram(self, telegram: Telegram) -> Self {
-        Self {
-            telegram: Some(telegram),
-            ..self
-        }
+        self.notifier(telegram::NAME, Box::new(telegram))
     }
 
     pub fn pushover(self, pushover: Pushover) -> Self {
-        Self {
-            pushover: Some(pushover),
-            ..self
-        }
+        self.notifier(pushover::NAME, Box::new(pushover))
     }
 
     pub fn gotify(self, gotify: Gotify) -> Self {
-        Self {
-            gotify: Some(gotify),
-            ..self
-        }
+        self.notifier(gotify::NAME, Box::new(gotify))
+    }
+
+    pub fn webhook(self, webhook: Webhook) -> Self {
+        self.notifier(webhook::NAME, Box::new(webhook))
+    }
+
+    pub fn ntfy(self, ntfy: Ntfy) -> Self {
+        self.notifier(ntfy::NAME, Box::new(ntfy))
+    }
+
+    pub fn discord(self, discord: Discord) -> Self {
+        self.notifier(discord::NAME, Box::new(discord))
+    }
+
+    pub fn matrix(self, matrix: Matrix) -> Self {
+        self.notifier(matrix::NAME, Box::new(matrix))
+    }
+
+    pub fn email(self, email: Email) -> Self {
+        self.notifier(email::NAME, Box::new(email))
+    }
+
+    pub fn template(self, template: NotificationTemplate) -> Self {
+        Self { template, ..self }
     }
 
     pub fn base_url(self, base_url: String) -> Self {
@@ -57,9 +132,8 @@ where
     pub fn finish(self) -> Notification<R> {
         Notification {
             user_repo: self.user_repo,
-            telegram: self.telegram,
-            pushover: self.pushover,
-            gotify: self.gotify,
+            notifiers: Arc::new(self.notifiers),
+            template: self.template,
             base_url: self.base_url,
         }
     }
@@ -71,35 +145,51 @@ where
     R: UserRepository,
 {
     user_repo: R,
-    pushover: Option<Pushover>,
-    telegram: Option<Telegram>,
+    notifiers: Arc<HashMap<&'static str, Box<dyn Notifier>>>,
+    template: NotificationTemplate,
     base_url: Option<String>,
-    gotify: Option<Gotify>,
 }
 
 impl<R> Notification<R>
 where
     R: UserRepository,
 {
+    /// Names of notification channels configured on this server
+    pub fn channels(&self) -> Vec<&'static str> {
+        let mut channels: Vec<&'static str> = self.notifiers.keys().copied().collect();
+        channels.sort_unstable();
+        channels
+    }
+
+    pub fn has_channel(&self, channel: &str) -> bool {
+        self.notifiers.contains_key(channel)
+    }
+
+    /// Template used when user has not set their own
+    pub fn default_template(&self) -> &NotificationTemplate {
+        &self.template
+    }
+
     pub async fn send_all_to_user(
         &self,
         user_id: i64,
         title: Option<String>,
         body: &str,
     ) -> Result<(), anyhow::Error> {
-        let user = self.user_repo.get_user_by_id(user_id).await?;
-        if let Some(user_key) = user.pushover_user_key {
-            let _ = self
-                .send_message_to_pushover(&user_key, title.clone(), body)
-                .await;
-        }
-        if let Some(chat_id) = user.telegram_chat_id {
-            let mut message = "".to_string();
-            if let Some(title) = title {
-                message = format!("<b>{}</b>\n", title);
+        let targets = self.user_repo.get_notification_targets(user_id).await?;
+        for target in targets {
+            if let Err(e) = self
+                .send_to_target(
+                    &target.channel,
+                    &target.target,
+                    title.as_deref(),
+                    body,
+                    None,
+                )
+                .await
+            {
+                error!("failed to send notification to {}: {e}", target.channel);
             }
-            message = format!("{}{}", message, body);
-            let _ = self.send_message_to_telegram(chat_id, &message).await;
         }
 
         Ok(())
@@ -121,127 +211,104 @@ where
     pub async fn send_chapter_notification(
         &self,
         user_id: i64,
-        manga_title: &str,
-        chapter_title: &str,
-        chapter_id: i64,
+        chapter: &ChapterNotification,
     ) -> Result<(), anyhow::Error> {
-        let user = self.user_repo.get_user_by_id(user_id).await?;
+        let targets = self.user_repo.get_notification_targets(user_id).await?;
+        if targets.is_empty() {
+            return Ok(());
+        }
+
+        let template = self
+            .user_repo
+            .get_notification_template(user_id)
+            .await?
+            .unwrap_or_else(|| self.template.clone());
 
         let url = self
             .base_url
             .as_ref()
-            .map(|base_url| format!("{base_url}/chapter/{chapter_id}"));
-
-        if let Some((user_key, pushover)) = user.pushover_user_key.zip(self.pushover.as_ref()) {
-            if let Some(url) = &url {
-                pushover
-                    .send_notification_with_title_and_url(
-                        &user_key,
-                        manga_title,
-                        chapter_title,
-                        url,
-                        "Read",
-                    )
-                    .await?;
-            } else {
-                pushover
-                    .send_notification_with_title(&user_key, manga_title, chapter_title)
-                    .await?;
-            }
-        }
+            .map(|base_url| format!("{base_url}/chapter/{}", chapter.chapter_id));
 
-        if let Some((chat_id, telegram)) = user.telegram_chat_id.zip(self.telegram.as_ref()) {
-            let chat_id = &format!("{chat_id}");
-            if let Some(url) = &url {
-                telegram
-                    .send_notification_with_title_and_url(
-                        chat_id,
-                        manga_title,
-                        chapter_title,
-                        url,
-                        "Read",
-                    )
-                    .await?;
-            } else {
-                telegram
-                    .send_notification_with_title(chat_id, manga_title, chapter_title)
-                    .await?;
-            }
-        }
+        for target in targets {
+            let notifier = match self.notifiers.get(target.channel.as_str()) {
+                Some(notifier) => notifier,
+                None => {
+                    debug!("{} not set, skip notification", target.channel);
+                    continue;
+                }
+            };
+
+            let format = notifier.format();
+            let render = |text: &str| {
+                template::render(text, |name| {
+                    chapter
+                        .placeholder(name, url.as_deref())
+                        .map(|value| format.escape(&value))
+                })
+            };
 
-        if let Some((token, gotify)) = user.gotify_token.zip(self.gotify.as_ref()) {
-            if let Some(url) = &url {
-                gotify
-                    .send_notification_with_title_and_url(
-                        &token,
-                        manga_title,
-                        chapter_title,
-                        url,
-                        "Read",
-                    )
-                    .await?;
-            } else {
-                gotify
-                    .send_notification_with_title(&token, manga_title, chapter_title)
-                    .await?;
+            let title = render(&template.title);
+            let message = Message {
+                title: (!title.is_empty()).then_some(title),
+                body: render(&template.body),
+                url: url.clone(),
+                url_title: Some("Read".to_string()),
+                image_url: (!chapter.cover_url.is_empty()).then(|| chapter.cover_url.clone()),
+                chapter_id: Some(chapter.chapter_id),
+            };
+
+            if let Err(e) = notifier
+                .send_notification_message(&target.target, &message)
+                .await
+            {
+                error!(
+                    "failed to send chapter notification to {}: {e}",
+                    target.channel
+                );
             }
         }
 
         Ok(())
     }
 
-    pub async fn send_message_to_telegram(
+    pub async fn send_test_notification(
         &self,
-        chat_id: i64,
-        message: &str,
+        channel: &str,
+        target: &str,
     ) -> Result<(), anyhow::Error> {
-        self.telegram
-            .as_ref()
-            .ok_or_else(|| anyhow::anyhow!("telegram bot not set"))?
-            .send_message(chat_id, message)
-            .await?;
-        Ok(())
+        self.send_to_target(channel, target, Some("Tanoshi"), "Test Notification", None)
+            .await
     }
 
-    pub async fn send_message_to_pushover(
+    async fn send_to_target(
         &self,
-        user_key: &str,
-        title: Option<String>,
+        channel: &str,
+        target: &str,
+        title: Option<&str>,
         body: &str,
+        url: Option<&str>,
     ) -> Result<(), anyhow::Error> {
-        let pushover = self
-            .pushover
-            .as_ref()
-            .ok_or_else(|| anyhow::anyhow!("pushover not set"))?;
-        if let Some(title) = title {
-            pushover
-                .send_notification_with_title(user_key, &title, body)
-                .await?;
-        } else {
-            pushover.send_notification(user_key, body).await?;
-        }
+        let notifier = self
+            .notifiers
+            .get(channel)
+            .ok_or_else(|| anyhow::anyhow!("{channel} not set"))?;
 
-        Ok(())
-    }
+        let format = notifier.format();
+        let title = title.map(|title| format.escape(title));
+        let body = &format.escape(body);
 
-    pub async fn send_message_to_gotify(
-        &self,
-        token: &str,
-        title: Option<String>,
-        body: &str,
-    ) -> Result<(), anyhow::Error> {
-        let gotify = self
-            .gotify
-            .as_ref()
-            .ok_or_else(|| anyhow::anyhow!("gotify not set"))?;
-        if let Some(title) = title {
-            gotify
-                .send_notification_with_title(token, &title, body)
-                .await?;
-        } else {
-            gotify.send_notification(token, body).await?;
+        match (title.as_deref(), url) {
+            (Some(title), Some(url)) => {
+                notifier
+                    .send_notification_with_title_and_url(target, title, body, url, "Read")
+                    .await
+            }
+            (Some(title), None) => {
+                notifier
+                    .send_notification_with_title(target, title, body)
+                    .await
+            }
+            (None, _) => notifier.send_notification(target, body).await,
         }
-
-        Ok(())
     }
 }
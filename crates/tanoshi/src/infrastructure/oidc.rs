@@ -0,0 +1,353 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, bail, Result};
+use oauth2::{
+    basic::BasicClient, reqwest::async_http_client, AuthUrl, AuthorizationCode, ClientId,
+    ClientSecret, CsrfToken, PkceCodeChallenge, PkceCodeVerifier, RedirectUrl, Scope,
+    TokenResponse, TokenUrl,
+};
+use serde::Deserialize;
+use serde_json::Value;
+
+use super::config::OidcConfig;
+
+/// Subset of provider metadata needed for authorization code flow
+#[derive(Debug, Clone, Deserialize)]
+struct ProviderMetadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    userinfo_endpoint: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct AuthorizeRequest {
+    pub authorize_url: String,
+    pub csrf_state: String,
+    pub pkce_code_verifier: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OidcUser {
+    /// issuer and subject together identify user at provider
+    pub issuer: String,
+    pub subject: String,
+    pub username: String,
+    pub groups: Vec<String>,
+}
+
+impl OidcUser {
+    /// Whether user is admin according to its groups, `None` if no admin group configured
+    pub fn is_admin(&self, admin_group: Option<&str>) -> Option<bool> {
+        admin_group.map(|admin_group| self.groups.iter().any(|group| group == admin_group))
+    }
+}
+
+/// Logins not completed within this time have to be started again
+const PENDING_LOGIN_EXPIRY: Duration = Duration::from_secs(10 * 60);
+
+/// Limit of started logins kept at once, login start is unauthenticated
+const MAX_PENDING_LOGINS: usize = 1000;
+
+/// Logins started on this server waiting for provider to redirect back, by
+/// csrf state. Pkce code verifier never leaves the server and each state can
+/// only be used once.
+#[derive(Debug, Clone, Default)]
+pub struct PendingLogins(Arc<Mutex<HashMap<String, (String, Instant)>>>);
+
+impl PendingLogins {
+    pub fn insert(&self, request: &AuthorizeRequest) -> Result<()> {
+        let mut logins = self.0.lock().unwrap();
+        logins.retain(|_, (_, started_at)| started_at.elapsed() < PENDING_LOGIN_EXPIRY);
+        if logins.len() >= MAX_PENDING_LOGINS {
+            bail!("too many pending logins");
+        }
+
+        logins.insert(
+            request.csrf_state.clone(),
+            (request.pkce_code_verifier.clone(), Instant::now()),
+        );
+
+        Ok(())
+    }
+
+    /// Remove login started with `csrf_state`, returning its pkce code verifier
+    pub fn take(&self, csrf_state: &str) -> Option<String> {
+        self.0
+            .lock()
+            .unwrap()
+            .remove(csrf_state)
+            .filter(|(_, started_at)| started_at.elapsed() < PENDING_LOGIN_EXPIRY)
+            .map(|(pkce_code_verifier, _)| pkce_code_verifier)
+    }
+}
+
+/// OpenID Connect client using authorization code flow with PKCE. User claims
+/// are read from userinfo endpoint with the access token received directly from
+/// the provider, so id token signature is not verified.
+#[derive(Debug, Clone)]
+pub struct OidcClient {
+    oauth_client: BasicClient,
+    issuer: String,
+    userinfo_endpoint: String,
+    scopes: Vec<String>,
+    username_claim: String,
+    groups_claim: String,
+    http: reqwest::Client,
+}
+
+impl OidcClient {
+    /// Fetch provider metadata from issuer and create client redirecting to `redirect_url`
+    pub async fn discover(config: &OidcConfig, redirect_url: &str) -> Result<Self> {
+        let issuer_url = config.issuer_url.trim_end_matches('/');
+        let http = reqwest::Client::new();
+
+        let metadata: ProviderMetadata = http
+            .get(format!("{issuer_url}/.well-known/openid-configuration"))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        if metadata.issuer.trim_end_matches('/') != issuer_url {
+            bail!(
+                "issuer mismatch, expected {issuer_url} got {}",
+                metadata.issuer
+            );
+        }
+
+        let oauth_client = BasicClient::new(
+            ClientId::new(config.client_id.clone()),
+            config.client_secret.clone().map(ClientSecret::new),
+            AuthUrl::new(metadata.authorization_endpoint)?,
+            Some(TokenUrl::new(metadata.token_endpoint)?),
+        )
+        .set_redirect_uri(RedirectUrl::new(redirect_url.to_string())?);
+
+        Ok(Self {
+            oauth_client,
+            issuer: metadata.issuer,
+            userinfo_endpoint: metadata.userinfo_endpoint,
+            scopes: config.scopes.clone(),
+            username_claim: config.username_claim.clone(),
+            groups_claim: config.groups_claim.clone(),
+            http,
+        })
+    }
+
+    pub fn authorize_url(&self) -> AuthorizeRequest {
+        let (pkce_code_challenge, pkce_code_verifier) = PkceCodeChallenge::new_random_sha256();
+        let (authorize_url, csrf_state) = self
+            .oauth_client
+            .authorize_url(CsrfToken::new_random)
+            .add_scopes(self.scopes.iter().cloned().map(Scope::new))
+            .set_pkce_challenge(pkce_code_challenge)
+            .url();
+
+        AuthorizeRequest {
+            authorize_url: authorize_url.to_string(),
+            csrf_state: csrf_state.secret().to_owned(),
+            pkce_code_verifier: pkce_code_verifier.secret().to_owned(),
+        }
+    }
+
+    /// Exchange authorization code and return user it was issued for
+    pub async fn exchange_code(&self, code: &str, pkce_code_verifier: &str) -> Result<OidcUser> {
+        let token = self
+            .oauth_client
+            .exchange_code(AuthorizationCode::new(code.to_string()))
+            .set_pkce_verifier(PkceCodeVerifier::new(pkce_code_verifier.to_string()))
+            .request_async(async_http_client)
+            .await
+            .map_err(|e| anyhow!("{e}"))?;
+
+        self.fetch_user(token.access_token().secret()).await
+    }
+
+    async fn fetch_user(&self, access_token: &str) -> Result<OidcUser> {
+        let claims: Value = self
+            .http
+            .get(&self.userinfo_endpoint)
+            .bearer_auth(access_token)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        let subject = claims
+            .get("sub")
+            .and_then(|subject| subject.as_str())
+            .filter(|subject| !subject.is_empty())
+            .ok_or_else(|| anyhow!("userinfo has no sub claim"))?
+            .to_string();
+
+        let username = claims
+            .get(&self.username_claim)
+            .and_then(|username| username.as_str())
+            .filter(|username| !username.is_empty())
+            .ok_or_else(|| anyhow!("userinfo has no {} claim", self.username_claim))?
+            .to_string();
+
+        // some providers send a single group as string
+        let groups = match claims.get(&self.groups_claim) {
+            Some(Value::Array(groups)) => groups
+                .iter()
+                .filter_map(|group| group.as_str().map(|group| group.to_string()))
+                .collect(),
+            Some(Value::String(group)) => vec![group.clone()],
+            _ => vec![],
+        };
+
+        Ok(OidcUser {
+            issuer: self.issuer.clone(),
+            subject,
+            username,
+            groups,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::{
+        matchers::{body_string_contains, header, method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    fn fixture(name: &str) -> serde_json::Value {
+        let path = format!("{}/tests/fixtures/oidc/{name}", env!("CARGO_MANIFEST_DIR"));
+        serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap()
+    }
+
+    fn config(issuer_url: &str) -> OidcConfig {
+        OidcConfig {
+            name: "Mock".to_string(),
+            issuer_url: issuer_url.to_string(),
+            client_id: "tanoshi".to_string(),
+            client_secret: Some("secret".to_string()),
+            scopes: vec!["openid".to_string(), "groups".to_string()],
+            username_claim: "preferred_username".to_string(),
+            groups_claim: "groups".to_string(),
+            admin_group: Some("tanoshi-admins".to_string()),
+            auto_register: true,
+            link_existing_users: false,
+        }
+    }
+
+    async fn mock_discovery(server: &MockServer, issuer: &str) {
+        Mock::given(method("GET"))
+            .and(path("/.well-known/openid-configuration"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "issuer": issuer,
+                "authorization_endpoint": format!("{}/authorize", server.uri()),
+                "token_endpoint": format!("{}/token", server.uri()),
+                "userinfo_endpoint": format!("{}/userinfo", server.uri()),
+                "jwks_uri": format!("{}/jwks", server.uri()),
+            })))
+            .mount(server)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn test_authorize_url() {
+        let server = MockServer::start().await;
+        mock_discovery(&server, &server.uri()).await;
+
+        let client = OidcClient::discover(&config(&server.uri()), "http://tanoshi/login/oidc")
+            .await
+            .unwrap();
+        let request = client.authorize_url();
+
+        assert!(request
+            .authorize_url
+            .starts_with(&format!("{}/authorize?", server.uri())));
+        assert!(request.authorize_url.contains("client_id=tanoshi"));
+        assert!(request.authorize_url.contains("scope=openid+groups"));
+        assert!(request.authorize_url.contains("code_challenge_method=S256"));
+        assert!(request
+            .authorize_url
+            .contains(&format!("state={}", request.csrf_state)));
+    }
+
+    #[test]
+    fn test_pending_logins() {
+        let logins = PendingLogins::default();
+        let request = AuthorizeRequest {
+            authorize_url: "http://idp/authorize".to_string(),
+            csrf_state: "state".to_string(),
+            pkce_code_verifier: "verifier".to_string(),
+        };
+        logins.insert(&request).unwrap();
+
+        assert_eq!(logins.take("other"), None);
+        assert_eq!(logins.take("state"), Some("verifier".to_string()));
+        assert_eq!(logins.take("state"), None);
+    }
+
+    #[tokio::test]
+    async fn test_discover_issuer_mismatch() {
+        let server = MockServer::start().await;
+        mock_discovery(&server, "https://other.example.com").await;
+
+        let res = OidcClient::discover(&config(&server.uri()), "http://tanoshi/login/oidc").await;
+
+        assert!(res.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_exchange_code() {
+        let server = MockServer::start().await;
+        mock_discovery(&server, &server.uri()).await;
+        Mock::given(method("POST"))
+            .and(path("/token"))
+            .and(body_string_contains("grant_type=authorization_code"))
+            .and(body_string_contains("code=auth-code"))
+            .and(body_string_contains("code_verifier=verifier"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(fixture("token.json")))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/userinfo"))
+            .and(header("authorization", "Bearer oidc-access-token"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(fixture("userinfo.json")))
+            .mount(&server)
+            .await;
+
+        let config = config(&server.uri());
+        let client = OidcClient::discover(&config, "http://tanoshi/login/oidc")
+            .await
+            .unwrap();
+        let user = client.exchange_code("auth-code", "verifier").await.unwrap();
+
+        assert_eq!(user.issuer, server.uri());
+        assert_eq!(user.subject, "5c1d6a0e-0f5e-4f3c-9d8b-6f2f0a1b2c3d");
+        assert_eq!(user.username, "alice");
+        assert_eq!(user.groups, vec!["users", "tanoshi-admins"]);
+        assert_eq!(user.is_admin(config.admin_group.as_deref()), Some(true));
+        assert_eq!(user.is_admin(None), None);
+    }
+
+    #[tokio::test]
+    async fn test_exchange_code_invalid_grant() {
+        let server = MockServer::start().await;
+        mock_discovery(&server, &server.uri()).await;
+        Mock::given(method("POST"))
+            .and(path("/token"))
+            .respond_with(ResponseTemplate::new(400).set_body_json(fixture("invalid_grant.json")))
+            .mount(&server)
+            .await;
+
+        let client = OidcClient::discover(&config(&server.uri()), "http://tanoshi/login/oidc")
+            .await
+            .unwrap();
+
+        assert!(client.exchange_code("auth-code", "verifier").await.is_err());
+    }
+}
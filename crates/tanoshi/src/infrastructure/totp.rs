@@ -0,0 +1,121 @@
+use data_encoding::BASE32_NOPAD;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use reqwest::Url;
+use sha1::Sha1;
+
+/// Seconds each code is valid for
+pub const STEP: i64 = 30;
+const DIGITS: u32 = 6;
+/// Codes of adjacent steps are accepted to allow for clock drift
+const SKEW: i64 = 1;
+
+/// Random base32 secret shared with authenticator app
+pub fn generate_secret() -> String {
+    let mut bytes: [u8; 20] = [0; 20];
+    rand::thread_rng().fill_bytes(&mut bytes);
+
+    BASE32_NOPAD.encode(&bytes)
+}
+
+/// Random one-time code to login when authenticator app is lost
+pub fn generate_recovery_code() -> String {
+    let mut bytes: [u8; 6] = [0; 6];
+    rand::thread_rng().fill_bytes(&mut bytes);
+
+    let code = BASE32_NOPAD.encode(&bytes).to_lowercase();
+    format!("{}-{}", &code[..5], &code[5..])
+}
+
+/// Recovery code as typed by user without separator, spaces and case
+pub fn normalize_recovery_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+/// `otpauth://` url to add secret to authenticator app, usually shown as qr code
+pub fn otpauth_url(issuer: &str, account: &str, secret: &str) -> String {
+    let mut url = Url::parse("otpauth://totp").expect("should be valid url");
+    url.set_path(&format!("{issuer}:{account}"));
+    url.query_pairs_mut()
+        .append_pair("secret", secret)
+        .append_pair("issuer", issuer)
+        .append_pair("algorithm", "SHA1")
+        .append_pair("digits", &DIGITS.to_string())
+        .append_pair("period", &STEP.to_string());
+
+    url.to_string()
+}
+
+/// Step matching `code` for `secret` at unix `time`, if any within allowed drift
+pub fn verify(secret: &str, code: &str, time: i64) -> Option<i64> {
+    let key = BASE32_NOPAD.decode(secret.as_bytes()).ok()?;
+    let code = code.trim();
+    let current = time / STEP;
+
+    (current - SKEW..=current + SKEW).find(|step| generate(&key, *step) == code)
+}
+
+fn generate(key: &[u8], step: i64) -> String {
+    let mut mac = Hmac::<Sha1>::new_from_slice(key).expect("hmac should accept any key length");
+    mac.update(&step.to_be_bytes());
+    let hash = mac.finalize().into_bytes();
+
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([
+        hash[offset] & 0x7f,
+        hash[offset + 1],
+        hash[offset + 2],
+        hash[offset + 3],
+    ]);
+
+    format!(
+        "{:0width$}",
+        binary % 10_u32.pow(DIGITS),
+        width = DIGITS as usize
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // rfc 6238 sha1 secret
+    const SECRET: &[u8] = b"12345678901234567890";
+
+    #[test]
+    fn test_generate() {
+        assert_eq!(generate(SECRET, 59 / STEP), "287082");
+        assert_eq!(generate(SECRET, 1111111109 / STEP), "081804");
+        assert_eq!(generate(SECRET, 1234567890 / STEP), "005924");
+        assert_eq!(generate(SECRET, 2000000000 / STEP), "279037");
+    }
+
+    #[test]
+    fn test_verify() {
+        let secret = BASE32_NOPAD.encode(SECRET);
+
+        assert_eq!(
+            verify(&secret, "081804", 1111111109),
+            Some(1111111109 / STEP)
+        );
+        assert_eq!(
+            verify(&secret, "081804", 1111111109 + STEP),
+            Some(1111111109 / STEP)
+        );
+        assert_eq!(verify(&secret, "081804", 1111111109 + 3 * STEP), None);
+        assert_eq!(verify(&secret, "000000", 1111111109), None);
+        assert_eq!(verify("not base32!", "081804", 1111111109), None);
+    }
+
+    #[test]
+    fn test_recovery_code() {
+        let code = generate_recovery_code();
+
+        assert_eq!(code.len(), 11);
+        assert_eq!(normalize_recovery_code(&code).len(), 10);
+        assert_eq!(normalize_recovery_code(" ABCDE-fghij "), "abcdefghij");
+    }
+}
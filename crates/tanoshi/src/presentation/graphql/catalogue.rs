@@ -1,4 +1,9 @@
-use super::{chapter::Chapter, common::InputList, guard::AdminGuard, manga::Manga};
+use super::{
+    chapter::Chapter,
+    common::InputList,
+    guard::{check_source_allowed, AdminGuard, SourceGuard},
+    manga::Manga,
+};
 
 use crate::{
     domain::services::{chapter::ChapterService, manga::MangaService},
@@ -15,6 +20,7 @@ pub struct CatalogueRoot;
 
 #[Object]
 impl CatalogueRoot {
+    #[graphql(guard = "SourceGuard::new(source_id)")]
     async fn get_popular_manga(
         &self,
         ctx: &Context<'_>,
@@ -31,6 +37,8 @@ impl CatalogueRoot {
 
         Ok(fetched_manga)
     }
+
+    #[graphql(guard = "SourceGuard::new(source_id)")]
     async fn get_latest_manga(
         &self,
         ctx: &Context<'_>,
@@ -48,6 +56,7 @@ impl CatalogueRoot {
         Ok(fetched_manga)
     }
 
+    #[graphql(guard = "SourceGuard::new(source_id)")]
     async fn browse_source(
         &self,
         ctx: &Context<'_>,
@@ -67,6 +76,7 @@ impl CatalogueRoot {
         Ok(fetched_manga)
     }
 
+    #[graphql(guard = "SourceGuard::new(source_id)")]
     async fn manga_by_source_path(
         &self,
         ctx: &Context<'_>,
@@ -87,10 +97,17 @@ impl CatalogueRoot {
         #[graphql(desc = "manga id")] id: i64,
         #[graphql(desc = "refresh data from source", default = false)] refresh: bool,
     ) -> Result<Manga> {
-        let manga = ctx
-            .data::<MangaService<MangaRepositoryImpl>>()?
-            .fetch_manga_by_id(id, refresh)
-            .await?;
+        let manga_svc = ctx.data::<MangaService<MangaRepositoryImpl>>()?;
+
+        // check before refresh so a disallowed source is never fetched
+        let manga = manga_svc.fetch_manga_by_id(id, false).await?;
+        check_source_allowed(ctx, manga.source_id).await?;
+
+        let manga = if refresh {
+            manga_svc.fetch_manga_by_id(id, true).await?
+        } else {
+            manga
+        };
 
         Ok(manga.into())
     }
@@ -103,10 +120,10 @@ impl CatalogueRoot {
         let chapter = ctx
             .data::<ChapterService<ChapterRepositoryImpl>>()?
             .fetch_chapter_by_id(id)
-            .await?
-            .into();
+            .await?;
+        check_source_allowed(ctx, chapter.source_id).await?;
 
-        Ok(chapter)
+        Ok(chapter.into())
     }
 }
 
@@ -1,7 +1,7 @@
 use crate::{
-    domain::services::library::LibraryService,
+    domain::{entities::user::ApiKeyScope, services::library::LibraryService},
     infrastructure::{auth::Claims, domain::repositories::library::LibraryRepositoryImpl},
-    presentation::graphql::{loader::UserCategoryId, schema::DatabaseLoader},
+    presentation::graphql::{guard::ScopeGuard, loader::UserCategoryId, schema::DatabaseLoader},
 };
 use async_graphql::{dataloader::DataLoader, Context, Object, Result};
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
@@ -93,6 +93,7 @@ pub struct CategoryMutationRoot;
 
 #[Object]
 impl CategoryMutationRoot {
+    #[graphql(guard = "ScopeGuard::new(ApiKeyScope::Library)")]
     async fn create_category(
         &self,
         ctx: &Context<'_>,
@@ -111,6 +112,7 @@ impl CategoryMutationRoot {
         Ok(category)
     }
 
+    #[graphql(guard = "ScopeGuard::new(ApiKeyScope::Library)")]
     async fn update_category(
         &self,
         ctx: &Context<'_>,
@@ -130,6 +132,7 @@ impl CategoryMutationRoot {
         Ok(category)
     }
 
+    #[graphql(guard = "ScopeGuard::new(ApiKeyScope::Library)")]
     async fn delete_category(
         &self,
         ctx: &Context<'_>,
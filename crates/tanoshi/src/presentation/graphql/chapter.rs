@@ -172,7 +172,7 @@ impl Chapter {
         &self,
         ctx: &Context<'_>,
         #[graphql(desc = "fetch from source", default = false)] _fetch: bool,
-        #[graphql(desc = "encrypt url", default = true)] encrypt: bool,
+        #[graphql(desc = "sign url for image proxy", default = true)] encrypt: bool,
     ) -> Result<Vec<String>> {
         let mut pages = ctx
             .data::<ChapterService<ChapterRepositoryImpl>>()?
@@ -183,10 +183,21 @@ impl Chapter {
             ctx.data::<ImageService<ImageCacheRepositoryImpl, ImageRepositoryImpl>>()?;
 
         if encrypt {
-            let secret = &ctx.data::<Config>()?.secret;
-            pages
-                .par_iter_mut()
-                .for_each(|p| *p = image_svc.encrypt_image_url(secret, p).unwrap());
+            let claims = ctx
+                .data::<Claims>()
+                .map_err(|_| "token not exists, please login")?;
+            let config = ctx.data::<Config>()?;
+            pages.par_iter_mut().for_each(|p| {
+                *p = image_svc
+                    .sign_image_url(
+                        &config.secret,
+                        p,
+                        claims.sub,
+                        claims.sid,
+                        config.image_url.ttl,
+                    )
+                    .unwrap()
+            });
         }
 
         Ok(pages)
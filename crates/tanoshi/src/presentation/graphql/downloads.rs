@@ -1,6 +1,13 @@
-use super::{chapter::Chapter, common::Cursor, guard::AdminGuard};
+use super::{
+    chapter::Chapter,
+    common::Cursor,
+    guard::{allowed_source_ids, PermissionGuard},
+};
 use crate::{
-    domain::services::download::DownloadService,
+    domain::{
+        entities::user::{ApiKeyScope, Permission},
+        services::download::DownloadService,
+    },
     infrastructure::{config::Config, domain::repositories::download::DownloadRepositoryImpl},
 };
 use async_graphql::{
@@ -54,7 +61,7 @@ impl DownloadRoot {
         Ok(status)
     }
 
-    #[graphql(guard = "AdminGuard::new()")]
+    #[graphql(guard = "PermissionGuard::new(Permission::Download, ApiKeyScope::Downloads)")]
     async fn download_queue(&self, ctx: &Context<'_>) -> Result<Vec<DownloadQueueEntry>> {
         let queue = ctx
             .data::<DownloadService<DownloadRepositoryImpl>>()?
@@ -67,7 +74,7 @@ impl DownloadRoot {
         Ok(queue)
     }
 
-    #[graphql(guard = "AdminGuard::new()")]
+    #[graphql(guard = "PermissionGuard::new(Permission::Download, ApiKeyScope::Downloads)")]
     async fn get_downloaded_chapters(
         &self,
         ctx: &Context<'_>,
@@ -77,6 +84,11 @@ impl DownloadRoot {
         last: Option<i32>,
     ) -> Result<Connection<Cursor, Chapter, EmptyFields, EmptyFields>> {
         let download_svc = ctx.data::<DownloadService<DownloadRepositoryImpl>>()?;
+        let source_ids: Option<Vec<i64>> = allowed_source_ids(ctx)
+            .await?
+            .map(|source_ids| source_ids.into_iter().collect());
+        let source_ids = source_ids.as_deref();
+
         query(
             after,
             before,
@@ -94,6 +106,7 @@ impl DownloadRoot {
                         before_cursor.1,
                         first,
                         last,
+                        source_ids,
                     )
                     .await
                     .unwrap_or_default();
@@ -108,6 +121,7 @@ impl DownloadRoot {
                             e.id,
                             None,
                             Some(1),
+                            source_ids,
                         )
                         .await?
                         .is_empty();
@@ -123,6 +137,7 @@ impl DownloadRoot {
                             0,
                             Some(1),
                             None,
+                            source_ids,
                         )
                         .await?
                         .is_empty();
@@ -162,6 +177,7 @@ pub struct DownloadMutationRoot;
 
 #[Object]
 impl DownloadMutationRoot {
+    #[graphql(guard = "PermissionGuard::new(Permission::Download, ApiKeyScope::Downloads)")]
     async fn pause_download(&self, ctx: &Context<'_>) -> Result<bool> {
         let download_path = &ctx.data::<Config>()?.download_path;
 
@@ -172,6 +188,7 @@ impl DownloadMutationRoot {
         Ok(true)
     }
 
+    #[graphql(guard = "PermissionGuard::new(Permission::Download, ApiKeyScope::Downloads)")]
     async fn resume_download(&self, ctx: &Context<'_>) -> Result<bool> {
         let download_path = &ctx.data::<Config>()?.download_path;
 
@@ -182,7 +199,7 @@ impl DownloadMutationRoot {
         Ok(true)
     }
 
-    #[graphql(guard = "AdminGuard::new()")]
+    #[graphql(guard = "PermissionGuard::new(Permission::Download, ApiKeyScope::Downloads)")]
     async fn download_chapters(&self, ctx: &Context<'_>, ids: Vec<i64>) -> Result<i64> {
         let len = ids.len() as i64;
         ctx.data::<DownloadService<DownloadRepositoryImpl>>()?
@@ -192,7 +209,7 @@ impl DownloadMutationRoot {
         Ok(len)
     }
 
-    #[graphql(guard = "AdminGuard::new()")]
+    #[graphql(guard = "PermissionGuard::new(Permission::Download, ApiKeyScope::Downloads)")]
     async fn remove_chapters_from_queue(&self, ctx: &Context<'_>, ids: Vec<i64>) -> Result<i64> {
         let len = ids.len() as i64;
         ctx.data::<DownloadService<DownloadRepositoryImpl>>()?
@@ -202,7 +219,7 @@ impl DownloadMutationRoot {
         Ok(len)
     }
 
-    #[graphql(guard = "AdminGuard::new()")]
+    #[graphql(guard = "PermissionGuard::new(Permission::Download, ApiKeyScope::Downloads)")]
     async fn remove_downloaded_chapters(&self, ctx: &Context<'_>, ids: Vec<i64>) -> Result<i64> {
         let len = ids.len() as i64;
         ctx.data::<DownloadService<DownloadRepositoryImpl>>()?
@@ -212,7 +229,7 @@ impl DownloadMutationRoot {
         Ok(len)
     }
 
-    #[graphql(guard = "AdminGuard::new()")]
+    #[graphql(guard = "PermissionGuard::new(Permission::Download, ApiKeyScope::Downloads)")]
     async fn update_chapter_priority(
         &self,
         ctx: &Context<'_>,
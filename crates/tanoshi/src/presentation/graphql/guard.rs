@@ -1,6 +1,28 @@
+use std::collections::HashSet;
+
 use async_graphql::{Context, Guard, Result};
 
-use crate::infrastructure::auth::Claims;
+use crate::{
+    domain::{
+        entities::user::{ApiKeyScope, Permission, User},
+        services::{source::SourceService, user::UserService},
+    },
+    infrastructure::{
+        auth::Claims,
+        domain::repositories::{source::SourceRepositoryImpl, user::UserRepositoryImpl},
+    },
+};
+
+/// Check api key of request has `scope`, admin scope grants every scope.
+/// Requests authenticated with login session are always allowed.
+fn check_scope(claims: &Claims, scope: ApiKeyScope) -> Result<()> {
+    match claims.scopes.as_ref() {
+        Some(scopes) if !scopes.contains(&scope) && !scopes.contains(&ApiKeyScope::Admin) => {
+            Err(format!("api key has no {scope} scope").into())
+        }
+        _ => Ok(()),
+    }
+}
 
 #[derive(Debug, Default)]
 pub struct AdminGuard;
@@ -19,9 +41,125 @@ impl Guard for AdminGuard {
             .map_err(|_| "token not exists, please login")?;
 
         if claims.is_admin {
-            return Ok(());
+            return check_scope(claims, ApiKeyScope::Admin);
         }
 
         Err("Forbidden".into())
     }
 }
+
+/// Limit mutation to api keys with a scope, or to login sessions when no scope given
+#[derive(Debug)]
+pub struct ScopeGuard {
+    scope: Option<ApiKeyScope>,
+}
+
+impl ScopeGuard {
+    pub fn new(scope: ApiKeyScope) -> Self {
+        Self { scope: Some(scope) }
+    }
+
+    /// Account management which api keys are never allowed to do
+    pub fn session_only() -> Self {
+        Self { scope: None }
+    }
+}
+
+#[async_trait::async_trait]
+impl Guard for ScopeGuard {
+    async fn check(&self, ctx: &Context<'_>) -> Result<()> {
+        // resolver tells user to login
+        let claims = match ctx.data_opt::<Claims>() {
+            Some(claims) => claims,
+            None => return Ok(()),
+        };
+
+        match (self.scope, claims.scopes.is_some()) {
+            (Some(scope), _) => check_scope(claims, scope),
+            (None, true) => Err("not allowed with api key".into()),
+            (None, false) => Ok(()),
+        }
+    }
+}
+
+/// Allow admins and users granted `permission`, api keys also need `scope`
+#[derive(Debug)]
+pub struct PermissionGuard {
+    permission: Permission,
+    scope: ApiKeyScope,
+}
+
+impl PermissionGuard {
+    pub fn new(permission: Permission, scope: ApiKeyScope) -> Self {
+        Self { permission, scope }
+    }
+}
+
+#[async_trait::async_trait]
+impl Guard for PermissionGuard {
+    async fn check(&self, ctx: &Context<'_>) -> Result<()> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        if !claims.is_admin && !request_user(ctx).await?.has_permission(self.permission) {
+            return Err("Forbidden".into());
+        }
+
+        check_scope(claims, self.scope)
+    }
+}
+
+/// User making the request, to check its permissions and restrictions
+pub async fn request_user(ctx: &Context<'_>) -> Result<User> {
+    let claims = ctx
+        .data::<Claims>()
+        .map_err(|_| "token not exists, please login")?;
+
+    Ok(ctx
+        .data::<UserService<UserRepositoryImpl>>()?
+        .fetch_user_by_id(claims.sub)
+        .await?)
+}
+
+/// Installed sources user of request may browse, `None` if user is not restricted
+pub async fn allowed_source_ids(ctx: &Context<'_>) -> Result<Option<HashSet<i64>>> {
+    let user = request_user(ctx).await?;
+
+    Ok(ctx
+        .data::<SourceService<SourceRepositoryImpl>>()?
+        .get_allowed_source_ids(&user)
+        .await?)
+}
+
+/// Check user of request is allowed to browse source
+pub async fn check_source_allowed(ctx: &Context<'_>, source_id: i64) -> Result<()> {
+    let user = request_user(ctx).await?;
+    if !ctx
+        .data::<SourceService<SourceRepositoryImpl>>()?
+        .is_source_allowed(&user, source_id)
+        .await?
+    {
+        return Err("source is not allowed".into());
+    }
+
+    Ok(())
+}
+
+#[derive(Debug)]
+pub struct SourceGuard {
+    source_id: i64,
+}
+
+impl SourceGuard {
+    pub fn new(source_id: i64) -> Self {
+        Self { source_id }
+    }
+}
+
+#[async_trait::async_trait]
+impl Guard for SourceGuard {
+    async fn check(&self, ctx: &Context<'_>) -> Result<()> {
+        check_source_allowed(ctx, self.source_id).await
+    }
+}
@@ -0,0 +1,69 @@
+use super::guard::AdminGuard;
+use crate::{
+    domain::services::image::ImageService,
+    infrastructure::domain::repositories::{
+        image::ImageRepositoryImpl, image_cache::ImageCacheRepositoryImpl,
+    },
+};
+use async_graphql::{Context, Object, Result, SimpleObject};
+
+#[derive(Debug, SimpleObject)]
+pub struct ImageCacheStats {
+    /// cache size in bytes
+    pub size: u64,
+    /// max cache size in bytes, 0 if unlimited
+    pub max_size: u64,
+    pub entries: u64,
+    /// entries kept on eviction, e.g. covers of manga in library
+    pub pinned_entries: u64,
+    /// cache hits since server started
+    pub hits: u64,
+    /// cache misses since server started
+    pub misses: u64,
+}
+
+impl From<crate::domain::entities::image::ImageCacheStats> for ImageCacheStats {
+    fn from(val: crate::domain::entities::image::ImageCacheStats) -> Self {
+        Self {
+            size: val.size,
+            max_size: val.max_size,
+            entries: val.entries,
+            pinned_entries: val.pinned_entries,
+            hits: val.hits,
+            misses: val.misses,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct ImageCacheRoot;
+
+#[Object]
+impl ImageCacheRoot {
+    #[graphql(guard = "AdminGuard::new()")]
+    async fn image_cache_stats(&self, ctx: &Context<'_>) -> Result<ImageCacheStats> {
+        Ok(ctx
+            .data::<ImageService<ImageCacheRepositoryImpl, ImageRepositoryImpl>>()?
+            .get_cache_stats()
+            .await?
+            .into())
+    }
+}
+
+#[derive(Default)]
+pub struct ImageCacheMutationRoot;
+
+#[Object]
+impl ImageCacheMutationRoot {
+    #[graphql(guard = "AdminGuard::new()")]
+    async fn purge_image_cache(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "also remove pinned images", default = false)] include_pinned: bool,
+    ) -> Result<u64> {
+        Ok(ctx
+            .data::<ImageService<ImageCacheRepositoryImpl, ImageRepositoryImpl>>()?
+            .purge_cache(include_pinned)
+            .await?)
+    }
+}
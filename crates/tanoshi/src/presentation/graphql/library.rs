@@ -1,21 +1,23 @@
 use super::{
     common::Cursor,
+    guard::{allowed_source_ids, check_source_allowed, ScopeGuard},
     manga::Manga,
     recent::{RecentChapter, RecentUpdate},
 };
 use crate::{
-    application::worker::updates::{
-        ChapterUpdateCommand, ChapterUpdateCommandSender, ChapterUpdateReceiver,
+    application::worker::{
+        prefetch::{Command as PrefetchCommand, PrefetchSender},
+        updates::{ChapterUpdateCommand, ChapterUpdateCommandSender, ChapterUpdateReceiver},
     },
-    domain::services::{
-        chapter::ChapterService, history::HistoryService, library::LibraryService,
-        tracker::TrackerService,
+    domain::{
+        entities::user::ApiKeyScope,
+        services::{history::HistoryService, library::LibraryService, manga::MangaService},
     },
     infrastructure::{
         auth::Claims,
         domain::repositories::{
             chapter::ChapterRepositoryImpl, history::HistoryRepositoryImpl,
-            library::LibraryRepositoryImpl, tracker::TrackerRepositoryImpl,
+            library::LibraryRepositoryImpl, manga::MangaRepositoryImpl,
         },
     },
 };
@@ -44,11 +46,20 @@ impl LibraryRoot {
             .data::<Claims>()
             .map_err(|_| "token not exists, please login")?;
 
+        // manga added before user was restricted stay in library but are hidden
+        let allowed_source_ids = allowed_source_ids(ctx).await?;
+
         let manga = ctx
             .data::<LibraryService<LibraryRepositoryImpl>>()?
             .get_manga_from_library_by_category_id(claims.sub, category_id)
             .await?
             .into_par_iter()
+            .filter(|m| {
+                allowed_source_ids
+                    .as_ref()
+                    .map(|source_ids| source_ids.contains(&m.source_id))
+                    .unwrap_or(true)
+            })
             .map(|m| m.into())
             .collect();
 
@@ -68,6 +79,10 @@ impl LibraryRoot {
             .map_err(|_| "token not exists, please login")?;
 
         let library_svc = ctx.data::<LibraryService<LibraryRepositoryImpl>>()?;
+        let source_ids: Option<Vec<i64>> = allowed_source_ids(ctx)
+            .await?
+            .map(|source_ids| source_ids.into_iter().collect());
+        let source_ids = source_ids.as_deref();
 
         query(
             after,
@@ -87,6 +102,7 @@ impl LibraryRoot {
                         before_cursor.1,
                         first,
                         last,
+                        source_ids,
                     )
                     .await?;
 
@@ -101,6 +117,7 @@ impl LibraryRoot {
                             e.chapter_id,
                             None,
                             Some(1),
+                            source_ids,
                         )
                         .await?
                         .is_empty();
@@ -117,6 +134,7 @@ impl LibraryRoot {
                             0,
                             Some(1),
                             None,
+                            source_ids,
                         )
                         .await?
                         .is_empty();
@@ -149,6 +167,10 @@ impl LibraryRoot {
 
         let history_svc =
             ctx.data::<HistoryService<ChapterRepositoryImpl, HistoryRepositoryImpl>>()?;
+        let source_ids: Option<Vec<i64>> = allowed_source_ids(ctx)
+            .await?
+            .map(|source_ids| source_ids.into_iter().collect());
+        let source_ids = source_ids.as_deref();
 
         query(
             after,
@@ -160,7 +182,14 @@ impl LibraryRoot {
                 let before_cursor = before.unwrap_or(Cursor(0, 0));
 
                 let edges = history_svc
-                    .get_history_chapters(claims.sub, after_cursor.0, before_cursor.0, first, last)
+                    .get_history_chapters(
+                        claims.sub,
+                        after_cursor.0,
+                        before_cursor.0,
+                        first,
+                        last,
+                        source_ids,
+                    )
                     .await?;
 
                 let mut has_previous_page = false;
@@ -172,6 +201,7 @@ impl LibraryRoot {
                             e.read_at.timestamp(),
                             None,
                             Some(1),
+                            source_ids,
                         )
                         .await?
                         .len()
@@ -181,7 +211,14 @@ impl LibraryRoot {
                 let mut has_next_page = false;
                 if let Some(e) = edges.last() {
                     has_next_page = !history_svc
-                        .get_history_chapters(claims.sub, e.read_at.timestamp(), 0, Some(1), None)
+                        .get_history_chapters(
+                            claims.sub,
+                            e.read_at.timestamp(),
+                            0,
+                            Some(1),
+                            None,
+                            source_ids,
+                        )
                         .await?
                         .is_empty();
                 }
@@ -205,6 +242,7 @@ pub struct LibraryMutationRoot;
 
 #[Object]
 impl LibraryMutationRoot {
+    #[graphql(guard = "ScopeGuard::new(ApiKeyScope::Library)")]
     async fn add_to_library(
         &self,
         ctx: &Context<'_>,
@@ -215,6 +253,12 @@ impl LibraryMutationRoot {
             .data::<Claims>()
             .map_err(|_| "token not exists, please login")?;
 
+        let manga = ctx
+            .data::<MangaService<MangaRepositoryImpl>>()?
+            .fetch_manga_by_id(manga_id, false)
+            .await?;
+        check_source_allowed(ctx, manga.source_id).await?;
+
         ctx.data::<LibraryService<LibraryRepositoryImpl>>()?
             .insert_manga_to_library(claims.sub, manga_id, category_ids)
             .await?;
@@ -222,6 +266,7 @@ impl LibraryMutationRoot {
         Ok(1)
     }
 
+    #[graphql(guard = "ScopeGuard::new(ApiKeyScope::Library)")]
     async fn delete_from_library(
         &self,
         ctx: &Context<'_>,
@@ -238,6 +283,7 @@ impl LibraryMutationRoot {
         Ok(1)
     }
 
+    #[graphql(guard = "ScopeGuard::new(ApiKeyScope::Library)")]
     async fn update_page_read_at(
         &self,
         ctx: &Context<'_>,
@@ -253,40 +299,18 @@ impl LibraryMutationRoot {
             .insert_chapter_to_history(claims.sub, chapter_id, page, is_complete)
             .await?;
 
-        let chapter = ctx
-            .data::<ChapterService<ChapterRepositoryImpl>>()?
-            .fetch_chapter_by_id(chapter_id)
-            .await?;
-
-        if is_complete {
-            let tracker_svc = ctx.data::<TrackerService<TrackerRepositoryImpl>>()?;
-
-            let tracked_manga = tracker_svc
-                .get_tracked_manga_id(claims.sub, chapter.manga_id)
-                .await?;
-
-            for manga in tracked_manga {
-                if let Some(tracker_manga_id) = manga.tracker_manga_id {
-                    // TODO: Only update if chapter > then read
-                    tracker_svc
-                        .update_manga_tracking_status(
-                            claims.sub,
-                            &manga.tracker,
-                            tracker_manga_id,
-                            None,
-                            None,
-                            Some(chapter.number as i64),
-                            None,
-                            None,
-                        )
-                        .await?;
-                }
-            }
+        // worker decides from progress whether to warm cache with next chapter
+        if let Err(e) = ctx
+            .data::<PrefetchSender>()?
+            .send(PrefetchCommand::PageRead(chapter_id, page, is_complete))
+        {
+            error!("failed to send prefetch command: {e}");
         }
 
         Ok(1)
     }
 
+    #[graphql(guard = "ScopeGuard::new(ApiKeyScope::Library)")]
     async fn mark_chapter_as_read(
         &self,
         ctx: &Context<'_>,
@@ -303,6 +327,7 @@ impl LibraryMutationRoot {
         Ok(1)
     }
 
+    #[graphql(guard = "ScopeGuard::new(ApiKeyScope::Library)")]
     async fn mark_chapter_as_unread(
         &self,
         ctx: &Context<'_>,
@@ -319,6 +344,7 @@ impl LibraryMutationRoot {
         Ok(1)
     }
 
+    #[graphql(guard = "ScopeGuard::new(ApiKeyScope::Library)")]
     async fn refresh_chapters(
         &self,
         ctx: &Context<'_>,
@@ -369,28 +395,34 @@ impl LibrarySubscriptionRoot {
             .map_err(|_| "token not exists, please login")?
             .sub;
 
+        // updates of sources user may not browse are left out
+        let allowed_source_ids = allowed_source_ids(ctx).await?;
+
         let receiver = ctx.data::<ChapterUpdateReceiver>()?.resubscribe();
 
         let stream = tokio_stream::wrappers::BroadcastStream::new(receiver);
 
-        let stream = stream.filter_map(move |res| async move {
+        let stream = stream.filter_map(move |res| {
             debug!("update: {res:?}");
-            if let Ok(update) = res {
-                if update.users.get(&user_id).is_some() {
-                    Some(RecentUpdate {
-                        manga_id: update.chapter.manga_id,
-                        chapter_id: update.chapter.id,
-                        manga_title: update.manga.title,
-                        cover_url: update.manga.cover_url,
-                        chapter_title: update.chapter.title,
-                        uploaded: update.chapter.uploaded,
-                    })
-                } else {
-                    None
-                }
-            } else {
-                None
-            }
+            let update = res
+                .ok()
+                .filter(|update| {
+                    update.users.contains(&user_id)
+                        && allowed_source_ids
+                            .as_ref()
+                            .map(|source_ids| source_ids.contains(&update.manga.source_id))
+                            .unwrap_or(true)
+                })
+                .map(|update| RecentUpdate {
+                    manga_id: update.chapter.manga_id,
+                    chapter_id: update.chapter.id,
+                    manga_title: update.manga.title,
+                    cover_url: update.manga.cover_url,
+                    chapter_title: update.chapter.title,
+                    uploaded: update.chapter.uploaded,
+                });
+
+            async move { update }
         });
 
         Ok(stream)
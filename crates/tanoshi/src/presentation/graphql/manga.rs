@@ -136,11 +136,20 @@ impl Manga {
     }
 
     async fn cover_url(&self, ctx: &Context<'_>) -> Result<String> {
-        let secret = &ctx.data::<Config>()?.secret;
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+        let config = ctx.data::<Config>()?;
 
         Ok(ctx
             .data::<ImageService<ImageCacheRepositoryImpl, ImageRepositoryImpl>>()?
-            .encrypt_image_url(secret, &self.cover_url)?)
+            .sign_image_url(
+                &config.secret,
+                &self.cover_url,
+                claims.sub,
+                claims.sid,
+                config.image_url.ttl,
+            )?)
     }
 
     async fn is_favorite(&self, ctx: &Context<'_>) -> Result<bool> {
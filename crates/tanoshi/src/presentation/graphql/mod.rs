@@ -4,6 +4,7 @@ pub mod chapter;
 pub mod common;
 pub mod downloads;
 pub mod guard;
+pub mod image_cache;
 pub mod library;
 pub mod loader;
 pub mod manga;
@@ -15,31 +16,192 @@ pub mod status;
 pub mod tracking;
 pub mod user;
 
-use crate::infrastructure::{auth, config::Config};
+use crate::{
+    domain::{
+        entities::user::{ApiKeyScope, User},
+        services::user::UserService,
+    },
+    infrastructure::{
+        auth::{self, Claims},
+        config::Config,
+        domain::repositories::user::UserRepositoryImpl,
+    },
+};
 use async_graphql::http::{playground_source, GraphQLPlaygroundConfig, ALL_WEBSOCKET_PROTOCOLS};
 use async_graphql_axum::{GraphQLProtocol, GraphQLRequest, GraphQLResponse, GraphQLWebSocket};
 use axum::{
-    extract::{Extension, WebSocketUpgrade},
+    extract::{ConnectInfo, Extension, WebSocketUpgrade},
+    http::HeaderMap,
     response::{Html, IntoResponse, Response},
 };
+use headers::{HeaderMapExt, UserAgent};
 use serde::Deserialize;
+use std::net::{IpAddr, SocketAddr};
 
 use self::schema::TanoshiSchema;
 
 use super::token::Token;
 
+/// Address of client sending the request, used to throttle logins
+#[derive(Debug, Clone, Copy)]
+pub struct ClientIp(pub IpAddr);
+
+/// Peer address, or when peer is a trusted proxy the rightmost X-Forwarded-For
+/// address that is not a trusted proxy. Entries left of it are set by the
+/// client and can't be trusted.
+pub(crate) fn client_ip(config: &Config, addr: &SocketAddr, headers: &HeaderMap) -> IpAddr {
+    let is_trusted = |ip: IpAddr| {
+        auth::is_trusted_proxy(ip, &config.login_limit.trusted_proxies)
+            || config
+                .trusted_header
+                .as_ref()
+                .map(|trusted_header| auth::is_trusted_proxy(ip, &trusted_header.trusted_proxies))
+                .unwrap_or(false)
+    };
+
+    let mut ip = addr.ip();
+    if !is_trusted(ip) {
+        return ip;
+    }
+
+    let forwarded = headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .collect::<Vec<_>>();
+    for forwarded_ip in forwarded.into_iter().rev() {
+        match forwarded_ip.trim().parse() {
+            Ok(forwarded_ip) => ip = forwarded_ip,
+            Err(_) => break,
+        }
+        if !is_trusted(ip) {
+            break;
+        }
+    }
+
+    ip
+}
+
+/// Claims for a request authenticated without a login session
+pub(crate) fn sessionless_claims(user: User, scopes: Option<Vec<ApiKeyScope>>) -> Option<Claims> {
+    let current_time = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .ok()?;
+
+    Some(Claims {
+        sub: user.id,
+        username: user.username,
+        is_admin: user.is_admin,
+        exp: (current_time + std::time::Duration::from_secs(auth::ACCESS_TOKEN_EXPIRY)).as_secs()
+            as usize,
+        sid: 0,
+        scopes,
+    })
+}
+
+/// Claims of a valid api key or token whose session has not been revoked
+pub(crate) async fn verify_token(
+    config: &Config,
+    user_svc: &UserService<UserRepositoryImpl>,
+    token: &Token,
+) -> Option<Claims> {
+    if token.is_api_key() {
+        let (user, api_key) = match user_svc.verify_api_key(&token.0).await {
+            Ok(res) => res,
+            Err(e) => {
+                debug!("rejecting api key: {e}");
+                return None;
+            }
+        };
+
+        return sessionless_claims(user, Some(api_key.scopes));
+    }
+
+    let claims = auth::decode_jwt(&config.secret, &token.0).ok()?;
+
+    user_svc
+        .verify_session(claims.sub, claims.sid)
+        .await
+        .ok()
+        .map(|_| claims)
+}
+
+/// Claims of user authenticated by a reverse proxy, headers are only honoured
+/// when request comes from one of `trusted_proxies`
+pub(crate) async fn verify_trusted_header(
+    config: &Config,
+    user_svc: &UserService<UserRepositoryImpl>,
+    addr: &SocketAddr,
+    headers: &HeaderMap,
+) -> Option<Claims> {
+    let trusted_header = config.trusted_header.as_ref()?;
+    let username = headers
+        .get(trusted_header.username_header.as_str())?
+        .to_str()
+        .ok()?;
+
+    if !auth::is_trusted_proxy(addr.ip(), &trusted_header.trusted_proxies) {
+        warn!(
+            "ignoring {} header from untrusted address {}",
+            trusted_header.username_header,
+            addr.ip()
+        );
+        return None;
+    }
+
+    let is_admin = trusted_header
+        .admin_group
+        .as_ref()
+        .zip(trusted_header.groups_header.as_ref())
+        .map(|(admin_group, groups_header)| {
+            headers
+                .get_all(groups_header.as_str())
+                .iter()
+                .filter_map(|value| value.to_str().ok())
+                .flat_map(|value| value.split(','))
+                .any(|group| group.trim() == admin_group)
+        });
+
+    let user = match user_svc
+        .provision_external_user(username, is_admin, trusted_header.auto_register)
+        .await
+    {
+        Ok(user) => user,
+        Err(e) => {
+            warn!("rejecting user {username} from trusted header: {e}");
+            return None;
+        }
+    };
+
+    sessionless_claims(user, None)
+}
+
 pub async fn graphql_handler(
     token: Token,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
     Extension(config): Extension<Config>,
+    Extension(user_svc): Extension<UserService<UserRepositoryImpl>>,
     Extension(schema): Extension<TanoshiSchema>,
     req: GraphQLRequest,
 ) -> GraphQLResponse {
     let mut req = req.into_inner();
 
-    if let Ok(claims) = auth::decode_jwt(&config.secret, &token.0) {
+    let claims = match verify_token(&config, &user_svc, &token).await {
+        Some(claims) => Some(claims),
+        None => verify_trusted_header(&config, &user_svc, &addr, &headers).await,
+    };
+    if let Some(claims) = claims {
         req = req.data(claims);
     }
 
+    // stored with session on login
+    if let Some(user_agent) = headers.typed_get::<UserAgent>() {
+        req = req.data(user_agent);
+    }
+    req = req.data(ClientIp(client_ip(&config, &addr, &headers)));
+
     schema.execute(req).await.into()
 }
 
@@ -50,11 +212,17 @@ pub async fn graphql_playground() -> impl IntoResponse {
 }
 
 pub async fn graphql_ws_handler(
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
     Extension(config): Extension<Config>,
+    Extension(user_svc): Extension<UserService<UserRepositoryImpl>>,
     Extension(schema): Extension<TanoshiSchema>,
     protocol: GraphQLProtocol,
     websocket: WebSocketUpgrade,
 ) -> Response {
+    // proxy authenticates the upgrade request, not the connection init payload
+    let proxy_claims = verify_trusted_header(&config, &user_svc, &addr, &headers).await;
+
     websocket
         .protocols(ALL_WEBSOCKET_PROTOCOLS)
         .on_upgrade(move |stream| {
@@ -67,7 +235,12 @@ pub async fn graphql_ws_handler(
 
                     if let Ok(payload) = serde_json::from_value::<Payload>(value) {
                         let mut data = async_graphql::Data::default();
-                        if let Ok(claims) = auth::decode_jwt(&config.secret, &payload.token) {
+                        let claims =
+                            match verify_token(&config, &user_svc, &Token(payload.token)).await {
+                                Some(claims) => Some(claims),
+                                None => proxy_claims,
+                            };
+                        if let Some(claims) = claims {
                             data.insert(claims);
                         }
                         Ok(data)
@@ -1,53 +1,87 @@
-use crate::infrastructure::{
-    auth::Claims, domain::repositories::user::UserRepositoryImpl, notification::Notification,
+use super::guard::ScopeGuard;
+use crate::{
+    domain::services::user::UserService,
+    infrastructure::{
+        auth::Claims,
+        config::Config,
+        domain::repositories::user::UserRepositoryImpl,
+        notification::{Notification, PLACEHOLDERS},
+    },
 };
-use async_graphql::{Context, Object, Result};
+use async_graphql::{Context, Object, Result, SimpleObject};
+use chrono::NaiveDateTime;
+use tanoshi_notifier::telegram;
+
+#[derive(Debug, SimpleObject)]
+pub struct NotificationTarget {
+    pub id: i64,
+    pub channel: String,
+    pub target: String,
+    pub created_at: NaiveDateTime,
+}
+
+impl From<crate::domain::entities::notification::NotificationTarget> for NotificationTarget {
+    fn from(val: crate::domain::entities::notification::NotificationTarget) -> Self {
+        Self {
+            id: val.id,
+            channel: val.channel,
+            target: val.target,
+            created_at: val.created_at,
+        }
+    }
+}
+
+#[derive(Debug, SimpleObject)]
+pub struct NotificationTemplate {
+    pub title: String,
+    pub body: String,
+    /// true if user has not set their own template
+    pub is_default: bool,
+}
+
+#[derive(Debug, SimpleObject)]
+pub struct TelegramLinkCode {
+    pub code: String,
+    /// deep link opening the bot with the code, requires bot name in config
+    pub bot_url: Option<String>,
+}
 
 #[derive(Default)]
 pub struct NotificationRoot;
 
 #[Object]
 impl NotificationRoot {
-    async fn test_telegram(
-        &self,
-        ctx: &Context<'_>,
-        #[graphql(desc = "telegram chat id")] chat_id: i64,
-    ) -> Result<bool> {
+    async fn notification_channels(&self, ctx: &Context<'_>) -> Result<Vec<String>> {
         let _ = ctx
             .data::<Claims>()
             .map_err(|_| "token not exists, please login")?;
-        ctx.data::<Notification<UserRepositoryImpl>>()?
-            .send_message_to_telegram(chat_id, "Test Notification")
-            .await?;
 
-        Ok(true)
+        Ok(ctx
+            .data::<Notification<UserRepositoryImpl>>()?
+            .channels()
+            .into_iter()
+            .map(|channel| channel.to_string())
+            .collect())
     }
 
-    async fn test_pushover(
-        &self,
-        ctx: &Context<'_>,
-        #[graphql(desc = "pushover user key")] user_key: String,
-    ) -> Result<bool> {
-        let _ = ctx
-            .data::<Claims>()
-            .map_err(|_| "token not exists, please login")?;
-        ctx.data::<Notification<UserRepositoryImpl>>()?
-            .send_message_to_pushover(&user_key, None, "Test Notification")
-            .await?;
-
-        Ok(true)
+    async fn notification_placeholders(&self) -> Vec<String> {
+        PLACEHOLDERS
+            .iter()
+            .map(|placeholder| placeholder.to_string())
+            .collect()
     }
 
-    async fn test_gotify(
+    async fn test_notification(
         &self,
         ctx: &Context<'_>,
-        #[graphql(desc = "gotify app token")] token: String,
+        #[graphql(desc = "notification channel")] channel: String,
+        #[graphql(desc = "chat id, user key, token, topic, url or email address")] target: String,
     ) -> Result<bool> {
         let _ = ctx
             .data::<Claims>()
             .map_err(|_| "token not exists, please login")?;
         ctx.data::<Notification<UserRepositoryImpl>>()?
-            .send_message_to_gotify(&token, None, "Test Notification")
+            .send_test_notification(&channel, &target)
             .await?;
 
         Ok(true)
@@ -66,3 +100,108 @@ impl NotificationRoot {
         Err("desktop notification only available for desktop version".into())
     }
 }
+
+#[derive(Default)]
+pub struct NotificationMutationRoot;
+
+#[Object]
+impl NotificationMutationRoot {
+    #[graphql(guard = "ScopeGuard::session_only()")]
+    async fn add_notification_target(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "notification channel")] channel: String,
+        #[graphql(desc = "chat id, user key, token, topic, url or email address")] target: String,
+    ) -> Result<i64> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        if !ctx
+            .data::<Notification<UserRepositoryImpl>>()?
+            .has_channel(&channel)
+        {
+            return Err(format!("{channel} is not configured on this server").into());
+        }
+        // chat is proven to belong to user by sending a link code from it
+        if channel == telegram::NAME {
+            return Err("telegram chat is linked by sending a link code to the bot".into());
+        }
+
+        Ok(ctx
+            .data::<UserService<UserRepositoryImpl>>()?
+            .add_notification_target(claims.sub, &channel, &target)
+            .await?)
+    }
+
+    #[graphql(guard = "ScopeGuard::session_only()")]
+    async fn create_telegram_link_code(&self, ctx: &Context<'_>) -> Result<TelegramLinkCode> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        let telegram_cfg = ctx
+            .data::<Config>()?
+            .telegram
+            .as_ref()
+            .ok_or("telegram is not configured on this server")?;
+
+        let code = ctx
+            .data::<UserService<UserRepositoryImpl>>()?
+            .create_notification_link_code(claims.sub)
+            .await?;
+
+        let bot_url = (!telegram_cfg.name.is_empty())
+            .then(|| format!("https://t.me/{}?start={code}", telegram_cfg.name));
+
+        Ok(TelegramLinkCode { code, bot_url })
+    }
+
+    #[graphql(guard = "ScopeGuard::session_only()")]
+    async fn update_notification_template(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "notification title template")] title: String,
+        #[graphql(desc = "notification body template")] body: String,
+    ) -> Result<u64> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        ctx.data::<UserService<UserRepositoryImpl>>()?
+            .update_notification_template(claims.sub, &title, &body)
+            .await?;
+
+        Ok(1)
+    }
+
+    #[graphql(guard = "ScopeGuard::session_only()")]
+    async fn reset_notification_template(&self, ctx: &Context<'_>) -> Result<u64> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        ctx.data::<UserService<UserRepositoryImpl>>()?
+            .reset_notification_template(claims.sub)
+            .await?;
+
+        Ok(1)
+    }
+
+    #[graphql(guard = "ScopeGuard::session_only()")]
+    async fn remove_notification_target(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "notification target id")] id: i64,
+    ) -> Result<u64> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        ctx.data::<UserService<UserRepositoryImpl>>()?
+            .remove_notification_target(claims.sub, id)
+            .await?;
+
+        Ok(1)
+    }
+}
@@ -4,6 +4,7 @@ use chrono::NaiveDateTime;
 use crate::{
     domain::services::image::ImageService,
     infrastructure::{
+        auth::Claims,
         config::Config,
         domain::repositories::{image::ImageRepositoryImpl, image_cache::ImageCacheRepositoryImpl},
     },
@@ -48,11 +49,20 @@ impl RecentChapter {
     }
 
     async fn cover_url(&self, ctx: &Context<'_>) -> Result<String> {
-        let secret = &ctx.data::<Config>()?.secret;
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+        let config = ctx.data::<Config>()?;
 
         let cover_url = ctx
             .data::<ImageService<ImageCacheRepositoryImpl, ImageRepositoryImpl>>()?
-            .encrypt_image_url(secret, &self.cover_url)?;
+            .sign_image_url(
+                &config.secret,
+                &self.cover_url,
+                claims.sub,
+                claims.sid,
+                config.image_url.ttl,
+            )?;
 
         Ok(cover_url)
     }
@@ -108,11 +118,20 @@ impl RecentUpdate {
     }
 
     async fn cover_url(&self, ctx: &Context<'_>) -> Result<String> {
-        let secret = &ctx.data::<Config>()?.secret;
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+        let config = ctx.data::<Config>()?;
 
         let cover_url = ctx
             .data::<ImageService<ImageCacheRepositoryImpl, ImageRepositoryImpl>>()?
-            .encrypt_image_url(secret, &self.cover_url)?;
+            .sign_image_url(
+                &config.secret,
+                &self.cover_url,
+                claims.sub,
+                claims.sid,
+                config.image_url.ttl,
+            )?;
 
         Ok(cover_url)
     }
@@ -9,8 +9,9 @@ use super::{
     catalogue::CatalogueRoot,
     categories::{CategoryMutationRoot, CategoryRoot},
     downloads::{DownloadMutationRoot, DownloadRoot},
+    image_cache::{ImageCacheMutationRoot, ImageCacheRoot},
     library::{LibraryMutationRoot, LibraryRoot, LibrarySubscriptionRoot},
-    notification::NotificationRoot,
+    notification::{NotificationMutationRoot, NotificationRoot},
     source::{SourceMutationRoot, SourceRoot},
     status::StatusRoot,
     tracking::{TrackingMutationRoot, TrackingRoot},
@@ -34,6 +35,7 @@ pub struct QueryRoot(
     NotificationRoot,
     DownloadRoot,
     TrackingRoot,
+    ImageCacheRoot,
 );
 
 #[derive(MergedObject, Default)]
@@ -41,9 +43,11 @@ pub struct MutationRoot(
     LibraryMutationRoot,
     CategoryMutationRoot,
     UserMutationRoot,
+    NotificationMutationRoot,
     SourceMutationRoot,
     DownloadMutationRoot,
     TrackingMutationRoot,
+    ImageCacheMutationRoot,
 );
 
 #[derive(MergedSubscription, Default)]
@@ -1,9 +1,13 @@
-use super::{common::InputList, guard::AdminGuard};
+use super::{
+    common::InputList,
+    guard::{request_user, PermissionGuard, SourceGuard},
+};
 use crate::{
-    domain::services::source::SourceService,
-    infrastructure::{
-        auth::Claims, config::Config, domain::repositories::source::SourceRepositoryImpl,
+    domain::{
+        entities::user::{ApiKeyScope, Permission},
+        services::source::SourceService,
     },
+    infrastructure::{config::Config, domain::repositories::source::SourceRepositoryImpl},
 };
 use async_graphql::{Context, Object, Result};
 use serde::Deserialize;
@@ -20,6 +24,8 @@ pub struct Source {
     pub icon: String,
     #[serde(default)]
     pub has_update: bool,
+    #[serde(default)]
+    pub nsfw: bool,
 }
 
 impl From<crate::domain::entities::source::Source> for Source {
@@ -33,6 +39,7 @@ impl From<crate::domain::entities::source::Source> for Source {
             lib_version: s.lib_version,
             icon: s.icon,
             has_update: s.has_update,
+            nsfw: s.nsfw,
         }
     }
 }
@@ -63,6 +70,10 @@ impl Source {
         self.has_update
     }
 
+    async fn nsfw(&self) -> bool {
+        self.nsfw
+    }
+
     async fn filters(&self, ctx: &Context<'_>) -> Result<InputList> {
         let filters = ctx.data::<ExtensionManager>()?.filter_list(self.id)?;
 
@@ -86,7 +97,7 @@ impl SourceRoot {
         ctx: &Context<'_>,
         check_update: bool,
     ) -> Result<Vec<Source>> {
-        let _ = ctx.data::<Claims>()?;
+        let user = request_user(ctx).await?;
 
         let repo_url = &ctx.data::<Config>()?.extension_repository;
 
@@ -95,6 +106,7 @@ impl SourceRoot {
             .get_installed_sources(repo_url, check_update)
             .await?
             .into_iter()
+            .filter(|source| user.can_browse_source(source))
             .map(Source::from)
             .collect();
 
@@ -102,7 +114,7 @@ impl SourceRoot {
     }
 
     async fn available_sources(&self, ctx: &Context<'_>) -> Result<Vec<Source>> {
-        let _ = ctx.data::<Claims>()?;
+        let user = request_user(ctx).await?;
 
         let repo_url = &ctx.data::<Config>()?.extension_repository;
 
@@ -111,15 +123,15 @@ impl SourceRoot {
             .get_available_sources(repo_url)
             .await?
             .into_iter()
+            .filter(|source| user.can_browse_source(source))
             .map(Source::from)
             .collect();
 
         Ok(sources)
     }
 
+    #[graphql(guard = "SourceGuard::new(source_id)")]
     async fn source(&self, ctx: &Context<'_>, source_id: i64) -> Result<Source> {
-        let _ = ctx.data::<Claims>()?;
-
         let source = ctx
             .data::<SourceService<SourceRepositoryImpl>>()?
             .get_source_by_id(source_id)
@@ -135,7 +147,7 @@ pub struct SourceMutationRoot;
 
 #[Object]
 impl SourceMutationRoot {
-    #[graphql(guard = "AdminGuard::new()")]
+    #[graphql(guard = "PermissionGuard::new(Permission::ManageSources, ApiKeyScope::Admin)")]
     async fn install_source(&self, ctx: &Context<'_>, source_id: i64) -> Result<i64> {
         if ctx.data::<ExtensionManager>()?.exists(source_id).await? {
             return Err("source installed, use updateSource to update".into());
@@ -150,7 +162,7 @@ impl SourceMutationRoot {
         Ok(source_id)
     }
 
-    #[graphql(guard = "AdminGuard::new()")]
+    #[graphql(guard = "PermissionGuard::new(Permission::ManageSources, ApiKeyScope::Admin)")]
     async fn uninstall_source(&self, ctx: &Context<'_>, source_id: i64) -> Result<i64> {
         ctx.data::<SourceService<SourceRepositoryImpl>>()?
             .uninstall_source(source_id)
@@ -159,7 +171,7 @@ impl SourceMutationRoot {
         Ok(source_id)
     }
 
-    #[graphql(guard = "AdminGuard::new()")]
+    #[graphql(guard = "PermissionGuard::new(Permission::ManageSources, ApiKeyScope::Admin)")]
     async fn update_source(&self, ctx: &Context<'_>, source_id: i64) -> Result<i64> {
         let repo_url = &ctx.data::<Config>()?.extension_repository;
 
@@ -170,7 +182,7 @@ impl SourceMutationRoot {
         Ok(source_id)
     }
 
-    #[graphql(guard = "AdminGuard::new()")]
+    #[graphql(guard = "PermissionGuard::new(Permission::ManageSources, ApiKeyScope::Admin)")]
     async fn set_preferences(
         &self,
         ctx: &Context<'_>,
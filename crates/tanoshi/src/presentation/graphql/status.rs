@@ -2,7 +2,9 @@ use async_graphql::{Context, Object, Result, SimpleObject};
 
 use crate::{
     domain::services::user::UserService,
-    infrastructure::{auth::Claims, domain::repositories::user::UserRepositoryImpl},
+    infrastructure::{
+        auth::Claims, config::Config, domain::repositories::user::UserRepositoryImpl,
+    },
 };
 
 #[derive(Debug, SimpleObject)]
@@ -10,6 +12,8 @@ struct Status {
     activated: bool,
     version: String,
     loggedin: bool,
+    /// name of openid connect provider if login with it is enabled
+    oidc_provider: Option<String>,
 }
 
 #[derive(Default)]
@@ -26,11 +30,17 @@ impl StatusRoot {
             .await?
             .is_empty();
         let version = env!("CARGO_PKG_VERSION").to_string();
+        let oidc_provider = ctx
+            .data::<Config>()?
+            .oidc
+            .as_ref()
+            .map(|oidc| oidc.name.clone());
 
         Ok(Status {
             activated,
             version,
             loggedin,
+            oidc_provider,
         })
     }
 }
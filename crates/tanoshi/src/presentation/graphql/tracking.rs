@@ -1,10 +1,25 @@
-use async_graphql::{Context, InputObject, Object, Result, SimpleObject};
+use async_graphql::{dataloader::DataLoader, Context, InputObject, Object, Result, SimpleObject};
 use chrono::NaiveDateTime;
-
-use crate::domain::services::tracker::TrackerService;
+use itertools::Itertools;
+
+use super::{
+    guard::{allowed_source_ids, check_source_allowed, ScopeGuard},
+    loader::MangaId,
+    manga::Manga,
+    schema::DatabaseLoader,
+};
+use crate::application::worker::tracker::{Command as TrackerSyncCommand, TrackerSyncSender};
+use crate::domain::entities::user::ApiKeyScope;
+use crate::domain::services::{
+    chapter::ChapterService, history::HistoryService, library::LibraryService, manga::MangaService,
+    tracker::TrackerService,
+};
 use crate::infrastructure::auth::Claims;
-use crate::infrastructure::domain::repositories::tracker::TrackerRepositoryImpl;
-use tanoshi_tracker::{anilist, myanimelist};
+use crate::infrastructure::domain::repositories::{
+    chapter::ChapterRepositoryImpl, history::HistoryRepositoryImpl, library::LibraryRepositoryImpl,
+    manga::MangaRepositoryImpl, tracker::TrackerRepositoryImpl,
+};
+use tanoshi_tracker::{anilist, kitsu, mangaupdates, myanimelist, shikimori};
 
 #[derive(SimpleObject)]
 pub struct Session {
@@ -72,6 +87,62 @@ impl From<tanoshi_tracker::TrackerManga> for TrackerManga {
     }
 }
 
+#[derive(Debug, SimpleObject)]
+pub struct TrackerMatchCandidate {
+    pub tracker_manga_id: String,
+    pub title: String,
+    pub cover_url: String,
+    /// title similarity between 0 and 1
+    pub score: f64,
+}
+
+/// Untracked library manga with ambiguous tracker search result
+pub struct TrackerMatchReview {
+    pub manga_id: i64,
+    pub tracker: String,
+    pub candidates: Vec<TrackerMatchCandidate>,
+}
+
+#[Object]
+impl TrackerMatchReview {
+    async fn tracker(&self) -> String {
+        self.tracker.clone()
+    }
+
+    async fn candidates(&self) -> &[TrackerMatchCandidate] {
+        &self.candidates
+    }
+
+    async fn manga(&self, ctx: &Context<'_>) -> Result<Manga> {
+        let loader = ctx.data::<DataLoader<DatabaseLoader>>()?;
+        loader
+            .load_one(MangaId(self.manga_id))
+            .await?
+            .ok_or_else(|| "manga not found".into())
+    }
+}
+
+#[derive(Debug, SimpleObject)]
+pub struct TrackerImportCandidate {
+    pub source_id: i64,
+    pub path: String,
+    pub title: String,
+    pub cover_url: String,
+    /// title similarity between 0 and 1
+    pub score: f64,
+}
+
+/// Manga on user's tracker list with source search results waiting to be imported
+#[derive(Debug, SimpleObject)]
+pub struct TrackerImportProposal {
+    pub tracker: String,
+    pub tracker_manga_id: String,
+    pub tracker_title: String,
+    /// chapters read on tracker, applied to library on import
+    pub progress: i64,
+    pub candidates: Vec<TrackerImportCandidate>,
+}
+
 #[derive(Default)]
 pub struct TrackingRoot;
 
@@ -151,6 +222,36 @@ impl TrackingRoot {
         Ok("Success".to_string())
     }
 
+    async fn shikimori_login_start(&self, ctx: &Context<'_>) -> Result<Session> {
+        let _ = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        let session = ctx
+            .data::<TrackerService<TrackerRepositoryImpl>>()?
+            .login_start(shikimori::NAME)?;
+
+        Ok(Session {
+            authorize_url: session.authorize_url,
+            csrf_state: session.csrf_state.secret().to_owned(),
+            pkce_code_verifier: session
+                .pkce_code_verifier
+                .map(|val| val.secret().to_owned()),
+        })
+    }
+
+    async fn shikimori_login_end(&self, ctx: &Context<'_>, code: String) -> Result<String> {
+        let claim = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        ctx.data::<TrackerService<TrackerRepositoryImpl>>()?
+            .login_end(claim.sub, shikimori::NAME, code, None, None, None)
+            .await?;
+
+        Ok("Success".to_string())
+    }
+
     async fn search_tracker_manga(
         &self,
         ctx: &Context<'_>,
@@ -172,6 +273,83 @@ impl TrackingRoot {
         Ok(manga)
     }
 
+    async fn tracker_match_reviews(&self, ctx: &Context<'_>) -> Result<Vec<TrackerMatchReview>> {
+        let claim = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        let candidates = ctx
+            .data::<TrackerService<TrackerRepositoryImpl>>()?
+            .get_tracker_match_candidates(claim.sub)
+            .await?;
+
+        let reviews = candidates
+            .into_iter()
+            .group_by(|candidate| (candidate.manga_id, candidate.tracker.clone()))
+            .into_iter()
+            .map(|((manga_id, tracker), candidates)| TrackerMatchReview {
+                manga_id,
+                tracker,
+                candidates: candidates
+                    .map(|candidate| TrackerMatchCandidate {
+                        tracker_manga_id: candidate.tracker_manga_id,
+                        title: candidate.title,
+                        cover_url: candidate.cover_url,
+                        score: candidate.score,
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        Ok(reviews)
+    }
+
+    async fn tracker_import_proposals(
+        &self,
+        ctx: &Context<'_>,
+    ) -> Result<Vec<TrackerImportProposal>> {
+        let claim = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        let candidates = ctx
+            .data::<TrackerService<TrackerRepositoryImpl>>()?
+            .get_tracker_import_candidates(claim.sub)
+            .await?;
+
+        let proposals = candidates
+            .into_iter()
+            .group_by(|candidate| {
+                (
+                    candidate.tracker.clone(),
+                    candidate.tracker_manga_id.clone(),
+                )
+            })
+            .into_iter()
+            .map(|((tracker, tracker_manga_id), candidates)| {
+                let candidates: Vec<_> = candidates.collect();
+                TrackerImportProposal {
+                    tracker,
+                    tracker_manga_id,
+                    tracker_title: candidates[0].tracker_title.clone(),
+                    progress: candidates[0].progress,
+                    candidates: candidates
+                        .into_iter()
+                        .map(|candidate| TrackerImportCandidate {
+                            source_id: candidate.source_id,
+                            path: candidate.path,
+                            title: candidate.title,
+                            cover_url: candidate.cover_url,
+                            score: candidate.score,
+                        })
+                        .collect(),
+                }
+            })
+            .collect();
+
+        Ok(proposals)
+    }
+
     async fn manga_tracker_status(
         &self,
         ctx: &Context<'_>,
@@ -198,6 +376,7 @@ pub struct TrackingMutationRoot;
 
 #[Object]
 impl TrackingMutationRoot {
+    #[graphql(guard = "ScopeGuard::new(ApiKeyScope::Library)")]
     async fn track_manga(
         &self,
         ctx: &Context<'_>,
@@ -216,6 +395,7 @@ impl TrackingMutationRoot {
         Ok(1)
     }
 
+    #[graphql(guard = "ScopeGuard::new(ApiKeyScope::Library)")]
     async fn untrack_manga(
         &self,
         ctx: &Context<'_>,
@@ -233,6 +413,7 @@ impl TrackingMutationRoot {
         Ok(1)
     }
 
+    #[graphql(guard = "ScopeGuard::new(ApiKeyScope::Library)")]
     async fn update_tracker_status(
         &self,
         ctx: &Context<'_>,
@@ -260,6 +441,246 @@ impl TrackingMutationRoot {
         Ok(true)
     }
 
+    /// Pull read progress of tracked manga from trackers into history
+    #[graphql(guard = "ScopeGuard::new(ApiKeyScope::Library)")]
+    async fn sync_tracker_progress(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "wait for sync to finish", default = false)] wait: bool,
+        #[graphql(
+            desc = "with local policy, lower progress on trackers to progress in history",
+            default = false
+        )]
+        allow_decrease: bool,
+    ) -> Result<bool> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        ctx.data::<TrackerSyncSender>()?
+            .send(TrackerSyncCommand::Pull(claims.sub, allow_decrease, tx))
+            .map_err(|_| "tracker sync thread is closed")?;
+
+        if wait {
+            rx.await??;
+        }
+
+        Ok(true)
+    }
+
+    /// Search trackers for untracked library manga, confident matches are linked
+    /// and the rest are listed in tracker match reviews
+    #[graphql(guard = "ScopeGuard::new(ApiKeyScope::Library)")]
+    async fn match_library_trackers(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "minimum title similarity to link automatically", default = 0.9)]
+        threshold: f64,
+        #[graphql(desc = "wait for matching to finish", default = false)] wait: bool,
+    ) -> Result<bool> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        ctx.data::<TrackerSyncSender>()?
+            .send(TrackerSyncCommand::Match(claims.sub, threshold, tx))
+            .map_err(|_| "tracker sync thread is closed")?;
+
+        if wait {
+            rx.await??;
+        }
+
+        Ok(true)
+    }
+
+    #[graphql(guard = "ScopeGuard::new(ApiKeyScope::Library)")]
+    async fn dismiss_tracker_match(
+        &self,
+        ctx: &Context<'_>,
+        manga_id: i64,
+        tracker: String,
+    ) -> Result<u64> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        ctx.data::<TrackerService<TrackerRepositoryImpl>>()?
+            .dismiss_tracker_match(claims.sub, manga_id, &tracker)
+            .await?;
+
+        Ok(1)
+    }
+
+    /// Search installed sources for manga on tracker list, results are listed
+    /// in tracker import proposals
+    #[graphql(guard = "ScopeGuard::new(ApiKeyScope::Library)")]
+    async fn import_tracker_list(
+        &self,
+        ctx: &Context<'_>,
+        tracker: String,
+        #[graphql(
+            desc = "status on tracker list e.g. reading, plan_to_read, whole list if null",
+            default_with = "Some(\"reading\".to_string())"
+        )]
+        status: Option<String>,
+        #[graphql(desc = "source ids to search, all installed sources if empty", default)]
+        source_ids: Vec<i64>,
+        #[graphql(desc = "wait for searching to finish", default = false)] wait: bool,
+    ) -> Result<bool> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        // restricted users only search sources they may browse
+        let source_ids = match (allowed_source_ids(ctx).await?, source_ids.is_empty()) {
+            (None, true) => None,
+            (None, false) => Some(source_ids),
+            (Some(allowed), true) => Some(allowed.into_iter().collect()),
+            (Some(allowed), false) => Some(
+                source_ids
+                    .into_iter()
+                    .filter(|source_id| allowed.contains(source_id))
+                    .collect(),
+            ),
+        };
+
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        ctx.data::<TrackerSyncSender>()?
+            .send(TrackerSyncCommand::Import(
+                claims.sub, tracker, status, source_ids, tx,
+            ))
+            .map_err(|_| "tracker sync thread is closed")?;
+
+        if wait {
+            rx.await??;
+        }
+
+        Ok(true)
+    }
+
+    /// Add source manga to library, link it to the tracker and mark chapters read on tracker as read
+    #[graphql(guard = "ScopeGuard::new(ApiKeyScope::Library)")]
+    async fn confirm_tracker_import(
+        &self,
+        ctx: &Context<'_>,
+        tracker: String,
+        tracker_manga_id: String,
+        #[graphql(desc = "source id")] source_id: i64,
+        #[graphql(desc = "path to manga in source")] path: String,
+        #[graphql(desc = "category ids", default)] category_ids: Vec<i64>,
+    ) -> Result<i64> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        let tracker_svc = ctx.data::<TrackerService<TrackerRepositoryImpl>>()?;
+        let candidate = tracker_svc
+            .get_tracker_import_candidates(claims.sub)
+            .await?
+            .into_iter()
+            .find(|candidate| {
+                candidate.tracker == tracker
+                    && candidate.tracker_manga_id == tracker_manga_id
+                    && candidate.source_id == source_id
+                    && candidate.path == path
+            })
+            .ok_or("import proposal not found")?;
+        check_source_allowed(ctx, candidate.source_id).await?;
+
+        let manga = ctx
+            .data::<MangaService<MangaRepositoryImpl>>()?
+            .fetch_manga_by_source_path(source_id, &path)
+            .await?;
+
+        ctx.data::<LibraryService<LibraryRepositoryImpl>>()?
+            .insert_manga_to_library(claims.sub, manga.id, category_ids)
+            .await?;
+
+        tracker_svc
+            .track_manga(claims.sub, manga.id, &tracker, &tracker_manga_id)
+            .await?;
+
+        if candidate.progress > 0 {
+            let chapter_ids: Vec<i64> = ctx
+                .data::<ChapterService<ChapterRepositoryImpl>>()?
+                .fetch_chapters_by_manga_id(source_id, &path, manga.id, false)
+                .await?
+                .into_iter()
+                .filter(|chapter| chapter.number as i64 <= candidate.progress)
+                .map(|chapter| chapter.id)
+                .collect();
+
+            if !chapter_ids.is_empty() {
+                ctx.data::<HistoryService<ChapterRepositoryImpl, HistoryRepositoryImpl>>()?
+                    .insert_chapters_to_history_as_completed(claims.sub, chapter_ids)
+                    .await?;
+            }
+        }
+
+        tracker_svc
+            .dismiss_tracker_import(claims.sub, &tracker, &tracker_manga_id)
+            .await?;
+
+        Ok(manga.id)
+    }
+
+    #[graphql(guard = "ScopeGuard::new(ApiKeyScope::Library)")]
+    async fn dismiss_tracker_import(
+        &self,
+        ctx: &Context<'_>,
+        tracker: String,
+        tracker_manga_id: String,
+    ) -> Result<u64> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        ctx.data::<TrackerService<TrackerRepositoryImpl>>()?
+            .dismiss_tracker_import(claims.sub, &tracker, &tracker_manga_id)
+            .await?;
+
+        Ok(1)
+    }
+
+    #[graphql(guard = "ScopeGuard::session_only()")]
+    async fn kitsu_login(
+        &self,
+        ctx: &Context<'_>,
+        username: String,
+        #[graphql(secret)] password: String,
+    ) -> Result<String> {
+        let claim = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        ctx.data::<TrackerService<TrackerRepositoryImpl>>()?
+            .login_with_password(claim.sub, kitsu::NAME, username, password)
+            .await?;
+
+        Ok("Success".to_string())
+    }
+
+    #[graphql(guard = "ScopeGuard::session_only()")]
+    async fn mangaupdates_login(
+        &self,
+        ctx: &Context<'_>,
+        username: String,
+        #[graphql(secret)] password: String,
+    ) -> Result<String> {
+        let claim = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        ctx.data::<TrackerService<TrackerRepositoryImpl>>()?
+            .login_with_password(claim.sub, mangaupdates::NAME, username, password)
+            .await?;
+
+        Ok("Success".to_string())
+    }
+
+    #[graphql(guard = "ScopeGuard::session_only()")]
     async fn tracker_logout(&self, ctx: &Context<'_>, tracker: String) -> Result<u64> {
         let claims = ctx
             .data::<Claims>()
@@ -1,14 +1,33 @@
-use super::guard::AdminGuard;
+use super::{
+    guard::{AdminGuard, PermissionGuard, ScopeGuard},
+    notification::{NotificationTarget, NotificationTemplate},
+    tracking::Session,
+};
 use crate::{
-    domain::services::{tracker::TrackerService, user::UserService},
+    domain::{
+        entities::user::{ApiKeyScope, Invite as InviteEntity, Permission},
+        services::{
+            tracker::TrackerService,
+            user::{UserError, UserService},
+        },
+    },
     infrastructure::{
         auth::{self, Claims},
         config::Config,
         domain::repositories::{tracker::TrackerRepositoryImpl, user::UserRepositoryImpl},
+        notification::Notification,
+        oidc::{OidcClient, PendingLogins},
     },
 };
-use async_graphql::{Context, InputObject, Object, Result};
-use tanoshi_tracker::{anilist, myanimelist};
+use async_graphql::{Context, InputObject, Object, Result, SimpleObject};
+use chrono::NaiveDateTime;
+use headers::UserAgent;
+use tanoshi_tracker::{anilist, kitsu, mangaupdates, myanimelist, shikimori};
+
+use super::ClientIp;
+
+/// Invites expire at most this many days after creation
+const MAX_INVITE_EXPIRY_DAYS: i64 = 3650;
 
 #[derive(Debug)]
 pub struct User {
@@ -16,9 +35,10 @@ pub struct User {
     pub username: String,
     pub password: String,
     pub is_admin: bool,
-    telegram_chat_id: Option<i64>,
-    pushover_user_key: Option<String>,
-    gotify_token: Option<String>,
+    pub permissions: Vec<Permission>,
+    pub allowed_sources: Option<Vec<i64>>,
+    pub show_nsfw: bool,
+    pub totp_enabled: bool,
 }
 
 impl From<crate::domain::entities::user::User> for User {
@@ -28,9 +48,10 @@ impl From<crate::domain::entities::user::User> for User {
             username: val.username,
             password: val.password,
             is_admin: val.is_admin,
-            telegram_chat_id: val.telegram_chat_id,
-            pushover_user_key: val.pushover_user_key,
-            gotify_token: val.gotify_token,
+            permissions: val.permissions,
+            allowed_sources: val.allowed_sources,
+            show_nsfw: val.show_nsfw,
+            totp_enabled: val.totp_enabled,
         }
     }
 }
@@ -42,6 +63,10 @@ impl From<User> for crate::domain::entities::user::User {
             username: val.username,
             password: val.password,
             is_admin: val.is_admin,
+            permissions: val.permissions,
+            allowed_sources: val.allowed_sources,
+            show_nsfw: val.show_nsfw,
+            totp_enabled: val.totp_enabled,
             ..Default::default()
         }
     }
@@ -61,16 +86,68 @@ impl User {
         self.is_admin
     }
 
-    async fn telegram_chat_id(&self) -> Option<i64> {
-        self.telegram_chat_id
+    /// manage_sources, manage_users, download or browse_nsfw, admins have every permission
+    async fn permissions(&self) -> Vec<String> {
+        self.permissions
+            .iter()
+            .map(|permission| permission.as_str().to_string())
+            .collect()
+    }
+
+    /// sources user may browse, null if every source is allowed
+    async fn allowed_sources(&self) -> Option<Vec<i64>> {
+        self.allowed_sources.clone()
+    }
+
+    async fn show_nsfw(&self) -> bool {
+        self.show_nsfw
+    }
+
+    /// login requires a two-factor code
+    async fn totp_enabled(&self) -> bool {
+        self.totp_enabled
+    }
+
+    /// unused recovery codes of two-factor authentication
+    async fn totp_recovery_codes(&self, ctx: &Context<'_>) -> Result<i64> {
+        Ok(ctx
+            .data::<UserService<UserRepositoryImpl>>()?
+            .count_totp_recovery_codes(self.id)
+            .await?)
     }
 
-    async fn pushover_user_key(&self) -> Option<String> {
-        self.pushover_user_key.clone()
+    async fn notification_targets(&self, ctx: &Context<'_>) -> Result<Vec<NotificationTarget>> {
+        let targets = ctx
+            .data::<UserService<UserRepositoryImpl>>()?
+            .fetch_notification_targets(self.id)
+            .await?;
+
+        Ok(targets.into_iter().map(|target| target.into()).collect())
     }
 
-    async fn gotify_token(&self) -> Option<String> {
-        self.gotify_token.clone()
+    async fn notification_template(&self, ctx: &Context<'_>) -> Result<NotificationTemplate> {
+        let template = ctx
+            .data::<UserService<UserRepositoryImpl>>()?
+            .fetch_notification_template(self.id)
+            .await?;
+
+        Ok(match template {
+            Some(template) => NotificationTemplate {
+                title: template.title,
+                body: template.body,
+                is_default: false,
+            },
+            None => {
+                let template = ctx
+                    .data::<Notification<UserRepositoryImpl>>()?
+                    .default_template();
+                NotificationTemplate {
+                    title: template.title.clone(),
+                    body: template.body.clone(),
+                    is_default: true,
+                }
+            }
+        })
     }
 
     async fn myanimelist_status(&self, ctx: &Context<'_>) -> Result<bool> {
@@ -96,13 +173,215 @@ impl User {
             .await
             .is_ok())
     }
+
+    async fn kitsu_status(&self, ctx: &Context<'_>) -> Result<bool> {
+        let user = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        Ok(ctx
+            .data::<TrackerService<TrackerRepositoryImpl>>()?
+            .check_tracker_login(kitsu::NAME, user.sub)
+            .await
+            .is_ok())
+    }
+
+    async fn mangaupdates_status(&self, ctx: &Context<'_>) -> Result<bool> {
+        let user = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        Ok(ctx
+            .data::<TrackerService<TrackerRepositoryImpl>>()?
+            .check_tracker_login(mangaupdates::NAME, user.sub)
+            .await
+            .is_ok())
+    }
+
+    async fn shikimori_status(&self, ctx: &Context<'_>) -> Result<bool> {
+        let user = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        Ok(ctx
+            .data::<TrackerService<TrackerRepositoryImpl>>()?
+            .check_tracker_login(shikimori::NAME, user.sub)
+            .await
+            .is_ok())
+    }
 }
 
-#[derive(InputObject)]
-struct ProfileInput {
-    pub telegram_chat_id: Option<i64>,
-    pub pushover_user_key: Option<String>,
-    pub gotify_token: Option<String>,
+#[derive(Debug, SimpleObject)]
+pub struct AuthToken {
+    pub access_token: String,
+    /// exchange for new tokens with `refreshToken` before access token expires
+    pub refresh_token: String,
+    /// seconds until access token expires
+    pub expires_in: i64,
+}
+
+#[derive(Debug, SimpleObject)]
+pub struct UserSession {
+    pub id: i64,
+    pub user_agent: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub last_active_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+    /// session of the token making this request
+    pub is_current: bool,
+}
+
+#[derive(Debug, SimpleObject)]
+pub struct ApiKey {
+    pub id: i64,
+    pub name: String,
+    /// start of the key to tell keys apart
+    pub prefix: String,
+    /// read, library, downloads or admin
+    pub scopes: Vec<String>,
+    pub created_at: NaiveDateTime,
+    pub last_used_at: Option<NaiveDateTime>,
+}
+
+impl From<crate::domain::entities::user::ApiKey> for ApiKey {
+    fn from(val: crate::domain::entities::user::ApiKey) -> Self {
+        Self {
+            id: val.id,
+            name: val.name,
+            prefix: val.prefix,
+            scopes: val
+                .scopes
+                .iter()
+                .map(|scope| scope.as_str().to_string())
+                .collect(),
+            created_at: val.created_at,
+            last_used_at: val.last_used_at,
+        }
+    }
+}
+
+#[derive(Debug, SimpleObject)]
+pub struct LoginAttempt {
+    pub id: i64,
+    pub username: String,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    /// success, wrong_password, unknown_user or locked
+    pub result: String,
+    pub created_at: NaiveDateTime,
+}
+
+impl From<crate::domain::entities::user::LoginAttempt> for LoginAttempt {
+    fn from(val: crate::domain::entities::user::LoginAttempt) -> Self {
+        Self {
+            id: val.id,
+            username: val.username,
+            ip: val.ip,
+            user_agent: val.user_agent,
+            result: val.result.to_string(),
+            created_at: val.created_at,
+        }
+    }
+}
+
+#[derive(Debug, SimpleObject)]
+pub struct Invite {
+    pub id: i64,
+    /// register with `registerWithInvite` or `/login?invite=<code>` on web
+    pub code: String,
+    pub created_by: Option<i64>,
+    pub is_admin: bool,
+    pub permissions: Vec<String>,
+    /// source ids, null allows every source
+    pub allowed_sources: Option<Vec<i64>>,
+    /// null for unlimited uses
+    pub max_uses: Option<i64>,
+    pub uses: i64,
+    pub expires_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+impl From<InviteEntity> for Invite {
+    fn from(val: InviteEntity) -> Self {
+        Self {
+            id: val.id,
+            code: val.code,
+            created_by: val.created_by,
+            is_admin: val.is_admin,
+            permissions: val
+                .permissions
+                .iter()
+                .map(|permission| permission.to_string())
+                .collect(),
+            allowed_sources: val.allowed_sources,
+            max_uses: val.max_uses,
+            uses: val.uses,
+            expires_at: val.expires_at,
+            created_at: val.created_at,
+        }
+    }
+}
+
+#[derive(Debug, SimpleObject)]
+pub struct TotpSetup {
+    /// base32 secret for authenticator apps without qr code scanning
+    pub secret: String,
+    /// `otpauth://` url, shown as qr code
+    pub url: String,
+}
+
+#[derive(Debug, SimpleObject)]
+pub struct CreatedApiKey {
+    pub api_key: ApiKey,
+    /// only shown once, send as bearer token or in `X-Api-Key` header
+    pub key: String,
+}
+
+fn issue_token(
+    ctx: &Context<'_>,
+    user: crate::domain::entities::user::User,
+    session_id: i64,
+    refresh_token: String,
+) -> Result<AuthToken> {
+    let secret = &ctx.data::<Config>()?.secret;
+    let current_time = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)?;
+    let claims = Claims {
+        sub: user.id,
+        username: user.username,
+        is_admin: user.is_admin,
+        exp: (current_time + std::time::Duration::from_secs(auth::ACCESS_TOKEN_EXPIRY)).as_secs()
+            as usize,
+        sid: session_id,
+        scopes: None,
+    };
+
+    Ok(AuthToken {
+        access_token: auth::encode_jwt(secret, &claims)?,
+        refresh_token,
+        expires_in: auth::ACCESS_TOKEN_EXPIRY as i64,
+    })
+}
+
+/// Discover configured OpenID Connect provider, redirecting back to web login page
+async fn oidc_client(config: &Config) -> Result<OidcClient> {
+    let oidc_config = config
+        .oidc
+        .as_ref()
+        .ok_or("openid connect login is not configured")?;
+    let base_url = config
+        .base_url
+        .as_ref()
+        .ok_or("base_url is required for openid connect login")?;
+
+    OidcClient::discover(
+        oidc_config,
+        &format!("{}/login/oidc", base_url.trim_end_matches('/')),
+    )
+    .await
+    .map_err(|e| {
+        error!("failed to discover openid connect provider: {e}");
+        "failed to reach openid connect provider".into()
+    })
 }
 
 #[derive(InputObject)]
@@ -110,6 +389,9 @@ struct LoginInput {
     username: String,
     #[graphql(secret)]
     password: String,
+    /// totp or recovery code, required when user enabled two-factor authentication
+    #[graphql(secret)]
+    totp_code: Option<String>,
 }
 
 #[derive(InputObject)]
@@ -125,29 +407,147 @@ pub struct UserRoot;
 
 #[Object]
 impl UserRoot {
-    async fn login(&self, ctx: &Context<'_>, login: LoginInput) -> Result<String> {
+    async fn login(&self, ctx: &Context<'_>, login: LoginInput) -> Result<AuthToken> {
         let user_svc = ctx.data::<UserService<UserRepositoryImpl>>()?;
+        let limit = ctx.data::<Config>()?.login_limit.limit();
+
+        let ip = ctx
+            .data_opt::<ClientIp>()
+            .map(|client_ip| client_ip.0.to_string());
+        let user_agent = ctx
+            .data_opt::<UserAgent>()
+            .map(|user_agent| user_agent.as_str());
+
+        let user = match user_svc
+            .login(
+                &limit,
+                &login.username,
+                &login.password,
+                login.totp_code.as_deref(),
+                ip.as_deref(),
+                user_agent,
+            )
+            .await
+        {
+            Ok(user) => user,
+            Err(UserError::AccountLocked(username)) => {
+                let message = format!(
+                    "login of user {username} is locked after {} failed attempts",
+                    limit.max_failures_per_user
+                );
+                if let Err(e) = ctx
+                    .data::<Notification<UserRepositoryImpl>>()?
+                    .send_all_to_admins(None, &message)
+                    .await
+                {
+                    error!("failed to notify admins of locked user {username}: {e}");
+                }
+                return Err(UserError::AccountLocked(username).into());
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        let (session, refresh_token) = user_svc.create_session(user.id, user_agent).await?;
+
+        issue_token(ctx, user, session.id, refresh_token)
+    }
+
+    async fn oidc_login_start(&self, ctx: &Context<'_>) -> Result<Session> {
+        let request = oidc_client(ctx.data::<Config>()?).await?.authorize_url();
+        ctx.data::<PendingLogins>()?.insert(&request)?;
+
+        // pkce code verifier stays on server with the pending login
+        Ok(Session {
+            authorize_url: request.authorize_url,
+            csrf_state: request.csrf_state,
+            pkce_code_verifier: None,
+        })
+    }
 
-        user_svc
-            .verify_password(&login.username, &login.password)
+    /// Finish login started with `oidc_login_start`, `state` is the one
+    /// provider redirected back with
+    async fn oidc_login_end(
+        &self,
+        ctx: &Context<'_>,
+        code: String,
+        state: String,
+    ) -> Result<AuthToken> {
+        let pkce_code_verifier = ctx
+            .data::<PendingLogins>()?
+            .take(&state)
+            .ok_or("login expired or was not started here, please login again")?;
+
+        let config = ctx.data::<Config>()?;
+        let oidc_user = oidc_client(config)
+            .await?
+            .exchange_code(&code, &pkce_code_verifier)
+            .await
+            .map_err(|e| {
+                error!("failed to exchange openid connect code: {e}");
+                "openid connect login failed"
+            })?;
+
+        let oidc_config = config
+            .oidc
+            .as_ref()
+            .ok_or("openid connect login is not configured")?;
+        let user_svc = ctx.data::<UserService<UserRepositoryImpl>>()?;
+        let user = user_svc
+            .provision_oidc_user(
+                &oidc_user.issuer,
+                &oidc_user.subject,
+                &oidc_user.username,
+                oidc_user.is_admin(oidc_config.admin_group.as_deref()),
+                oidc_config.auto_register,
+                oidc_config.link_existing_users,
+            )
             .await?;
 
-        let user = user_svc.fetch_user_by_username(&login.username).await?;
+        let user_agent = ctx
+            .data_opt::<UserAgent>()
+            .map(|user_agent| user_agent.as_str());
+        let (session, refresh_token) = user_svc.create_session(user.id, user_agent).await?;
 
-        let secret = &ctx.data::<Config>()?.secret;
-        let current_time = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)?;
-        let claims = Claims {
-            sub: user.id,
-            username: user.username,
-            is_admin: user.is_admin,
-            exp: (current_time + std::time::Duration::from_secs(2678400)).as_secs() as usize, // 31 days
-        };
-        let token = auth::encode_jwt(secret, &claims)?;
+        issue_token(ctx, user, session.id, refresh_token)
+    }
+
+    async fn sessions(&self, ctx: &Context<'_>) -> Result<Vec<UserSession>> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        let sessions = ctx
+            .data::<UserService<UserRepositoryImpl>>()?
+            .fetch_sessions(claims.sub)
+            .await?
+            .into_iter()
+            .map(|session| UserSession {
+                id: session.id,
+                user_agent: session.user_agent,
+                created_at: session.created_at,
+                last_active_at: session.refreshed_at,
+                expires_at: session.expires_at,
+                is_current: session.id == claims.sid,
+            })
+            .collect();
 
-        Ok(token)
+        Ok(sessions)
     }
 
-    #[graphql(guard = "AdminGuard::new()")]
+    async fn api_keys(&self, ctx: &Context<'_>) -> Result<Vec<ApiKey>> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        let api_keys = ctx
+            .data::<UserService<UserRepositoryImpl>>()?
+            .fetch_api_keys(claims.sub)
+            .await?;
+
+        Ok(api_keys.into_iter().map(|api_key| api_key.into()).collect())
+    }
+
+    #[graphql(guard = "PermissionGuard::new(Permission::ManageUsers, ApiKeyScope::Admin)")]
     async fn users(&self, ctx: &Context<'_>) -> Result<Vec<User>> {
         let users = ctx
             .data::<UserService<UserRepositoryImpl>>()?
@@ -157,6 +557,35 @@ impl UserRoot {
         Ok(users.into_iter().map(|user| user.into()).collect())
     }
 
+    #[graphql(guard = "PermissionGuard::new(Permission::ManageUsers, ApiKeyScope::Admin)")]
+    async fn invites(&self, ctx: &Context<'_>) -> Result<Vec<Invite>> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        let invites = ctx
+            .data::<UserService<UserRepositoryImpl>>()?
+            .fetch_invites(claims.sub)
+            .await?;
+
+        Ok(invites.into_iter().map(|invite| invite.into()).collect())
+    }
+
+    #[graphql(guard = "AdminGuard::new()")]
+    async fn login_attempts(
+        &self,
+        ctx: &Context<'_>,
+        username: Option<String>,
+        #[graphql(default = 50)] limit: i64,
+    ) -> Result<Vec<LoginAttempt>> {
+        let attempts = ctx
+            .data::<UserService<UserRepositoryImpl>>()?
+            .fetch_login_attempts(username.as_deref(), limit)
+            .await?;
+
+        Ok(attempts.into_iter().map(|attempt| attempt.into()).collect())
+    }
+
     async fn me(&self, ctx: &Context<'_>) -> Result<User> {
         let claim = ctx
             .data::<Claims>()
@@ -177,6 +606,7 @@ pub struct UserMutationRoot;
 
 #[Object]
 impl UserMutationRoot {
+    #[graphql(guard = "ScopeGuard::new(ApiKeyScope::Admin)")]
     async fn register(
         &self,
         ctx: &Context<'_>,
@@ -185,9 +615,17 @@ impl UserMutationRoot {
     ) -> Result<i64> {
         let user_svc = ctx.data::<UserService<UserRepositoryImpl>>()?;
 
+        // anyone can register the first user, later users are created by
+        // admins or registered with an invite
         let user_count = user_svc.fetch_all_users().await?.len();
-        if let Ok(claim) = ctx.data::<Claims>() {
-            if user_count > 0 && !claim.is_admin {
+        if user_count > 0 {
+            let claim = ctx
+                .data::<Claims>()
+                .map_err(|_| "token not exists, please login")?;
+
+            // only admins create other admins
+            let user = user_svc.fetch_user_by_id(claim.sub).await?;
+            if !user.has_permission(Permission::ManageUsers) || (is_admin && !user.is_admin) {
                 return Err("Forbidden".into());
             }
         }
@@ -197,19 +635,269 @@ impl UserMutationRoot {
             .await?)
     }
 
-    #[graphql(guard = "AdminGuard::new()")]
+    /// Register without login using an invite code, admins are notified
+    async fn register_with_invite(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "invite code", secret)] code: String,
+        login: LoginInput,
+    ) -> Result<i64> {
+        let (user_id, invite) = ctx
+            .data::<UserService<UserRepositoryImpl>>()?
+            .register_with_invite(&code, &login.username, &login.password)
+            .await?;
+
+        let message = format!(
+            "{} registered with invite #{} ({} of {} uses)",
+            login.username,
+            invite.id,
+            invite.uses + 1,
+            invite
+                .max_uses
+                .map(|max_uses| max_uses.to_string())
+                .unwrap_or_else(|| "unlimited".to_string())
+        );
+        if let Err(e) = ctx
+            .data::<Notification<UserRepositoryImpl>>()?
+            .send_all_to_admins(None, &message)
+            .await
+        {
+            error!("failed to notify admins of invite {}: {e}", invite.id);
+        }
+
+        Ok(user_id)
+    }
+
+    #[graphql(guard = "PermissionGuard::new(Permission::ManageUsers, ApiKeyScope::Admin)")]
+    async fn create_invite(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "role of registered users", default = false)] is_admin: bool,
+        #[graphql(desc = "permissions of registered users, defaults to browse_nsfw")]
+        permissions: Option<Vec<String>>,
+        #[graphql(desc = "source ids, null allows every source")] allowed_sources: Option<Vec<i64>>,
+        #[graphql(desc = "null for unlimited uses")] max_uses: Option<i64>,
+        #[graphql(desc = "null never expires")] expires_in_days: Option<i64>,
+    ) -> Result<Invite> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        let permissions = match permissions {
+            Some(permissions) => permissions
+                .iter()
+                .map(|permission| permission.parse::<Permission>())
+                .collect::<Result<Vec<_>, _>>()?,
+            None => vec![Permission::BrowseNsfw],
+        };
+
+        let expires_in_days = match expires_in_days {
+            Some(days) if !(1..=MAX_INVITE_EXPIRY_DAYS).contains(&days) => {
+                return Err(format!(
+                    "expires in days must be between 1 and {MAX_INVITE_EXPIRY_DAYS}"
+                )
+                .into());
+            }
+            days => days,
+        };
+
+        let now = chrono::Utc::now().naive_utc();
+        let invite = InviteEntity {
+            is_admin,
+            permissions,
+            allowed_sources,
+            max_uses,
+            expires_at: expires_in_days.map(|days| now + chrono::Duration::days(days)),
+            created_at: now,
+            ..Default::default()
+        };
+
+        let invite = ctx
+            .data::<UserService<UserRepositoryImpl>>()?
+            .create_invite(claims.sub, invite)
+            .await?;
+
+        Ok(invite.into())
+    }
+
+    #[graphql(guard = "PermissionGuard::new(Permission::ManageUsers, ApiKeyScope::Admin)")]
+    async fn delete_invite(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "invite id")] id: i64,
+    ) -> Result<u64> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        ctx.data::<UserService<UserRepositoryImpl>>()?
+            .delete_invite(claims.sub, id)
+            .await?;
+
+        Ok(1)
+    }
+
+    #[graphql(guard = "PermissionGuard::new(Permission::ManageUsers, ApiKeyScope::Admin)")]
     async fn delete_user(
         &self,
         ctx: &Context<'_>,
         #[graphql(desc = "user id")] user_id: i64,
     ) -> Result<i64> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        ctx.data::<UserService<UserRepositoryImpl>>()?
+            .delete_user(claims.sub, user_id)
+            .await?;
+
+        Ok(1)
+    }
+
+    #[graphql(guard = "PermissionGuard::new(Permission::ManageUsers, ApiKeyScope::Admin)")]
+    async fn update_user_permissions(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "user id")] user_id: i64,
+        #[graphql(desc = "manage_sources, manage_users, download or browse_nsfw")] permissions: Vec<
+            String,
+        >,
+    ) -> Result<u64> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        let permissions = permissions
+            .iter()
+            .map(|permission| permission.parse::<Permission>())
+            .collect::<Result<Vec<_>, _>>()?;
+
+        ctx.data::<UserService<UserRepositoryImpl>>()?
+            .update_permissions(claims.sub, user_id, &permissions)
+            .await?;
+
+        Ok(1)
+    }
+
+    #[graphql(guard = "PermissionGuard::new(Permission::ManageUsers, ApiKeyScope::Admin)")]
+    async fn update_user_allowed_sources(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "user id")] user_id: i64,
+        #[graphql(desc = "source ids, null allows every source")] source_ids: Option<Vec<i64>>,
+    ) -> Result<u64> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        ctx.data::<UserService<UserRepositoryImpl>>()?
+            .update_allowed_sources(claims.sub, user_id, source_ids.as_deref())
+            .await?;
+
+        Ok(1)
+    }
+
+    #[graphql(guard = "ScopeGuard::session_only()")]
+    async fn update_show_nsfw(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "show nsfw sources if permitted")] show_nsfw: bool,
+    ) -> Result<u64> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
         ctx.data::<UserService<UserRepositoryImpl>>()?
-            .delete_user(user_id)
+            .update_show_nsfw(claims.sub, show_nsfw)
             .await?;
 
         Ok(1)
     }
 
+    /// Start enrolling two-factor authentication, finished with `enableTotp`
+    #[graphql(guard = "ScopeGuard::session_only()")]
+    async fn setup_totp(&self, ctx: &Context<'_>) -> Result<TotpSetup> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        let (secret, url) = ctx
+            .data::<UserService<UserRepositoryImpl>>()?
+            .setup_totp(claims.sub)
+            .await?;
+
+        Ok(TotpSetup { secret, url })
+    }
+
+    /// Enable two-factor authentication, returns recovery codes which are only shown once
+    #[graphql(guard = "ScopeGuard::session_only()")]
+    async fn enable_totp(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "code from authenticator app")] code: String,
+    ) -> Result<Vec<String>> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        Ok(ctx
+            .data::<UserService<UserRepositoryImpl>>()?
+            .enable_totp(claims.sub, &code)
+            .await?)
+    }
+
+    #[graphql(guard = "ScopeGuard::session_only()")]
+    async fn regenerate_totp_recovery_codes(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "totp or recovery code")] code: String,
+    ) -> Result<Vec<String>> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        Ok(ctx
+            .data::<UserService<UserRepositoryImpl>>()?
+            .regenerate_totp_recovery_codes(claims.sub, &code)
+            .await?)
+    }
+
+    #[graphql(guard = "ScopeGuard::session_only()")]
+    async fn disable_totp(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(secret)] password: String,
+    ) -> Result<u64> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        ctx.data::<UserService<UserRepositoryImpl>>()?
+            .disable_totp(claims.sub, &password)
+            .await?;
+
+        Ok(1)
+    }
+
+    /// Turn off two-factor authentication of a user who lost their authenticator app
+    #[graphql(guard = "PermissionGuard::new(Permission::ManageUsers, ApiKeyScope::Admin)")]
+    async fn reset_user_totp(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "user id")] user_id: i64,
+    ) -> Result<u64> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        ctx.data::<UserService<UserRepositoryImpl>>()?
+            .reset_totp(claims.sub, user_id)
+            .await?;
+
+        Ok(1)
+    }
+
+    #[graphql(guard = "ScopeGuard::session_only()")]
     async fn change_password(&self, ctx: &Context<'_>, input: ChangePasswordInput) -> Result<u64> {
         let claims = ctx
             .data::<Claims>()
@@ -222,23 +910,106 @@ impl UserMutationRoot {
         Ok(1)
     }
 
-    async fn update_profile(&self, ctx: &Context<'_>, input: ProfileInput) -> Result<u64> {
+    async fn refresh_token(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "refresh token", secret)] refresh_token: String,
+    ) -> Result<AuthToken> {
+        let user_svc = ctx.data::<UserService<UserRepositoryImpl>>()?;
+
+        let (session, refresh_token) = user_svc.refresh_session(&refresh_token).await?;
+        let user = user_svc.fetch_user_by_id(session.user_id).await?;
+
+        issue_token(ctx, user, session.id, refresh_token)
+    }
+
+    #[graphql(guard = "ScopeGuard::session_only()")]
+    async fn logout(&self, ctx: &Context<'_>) -> Result<u64> {
         let claims = ctx
             .data::<Claims>()
             .map_err(|_| "token not exists, please login")?;
 
         ctx.data::<UserService<UserRepositoryImpl>>()?
-            .update_profile(
-                claims.sub,
-                input.telegram_chat_id,
-                input.pushover_user_key,
-                input.gotify_token,
-            )
+            .revoke_session(claims.sub, claims.sid)
+            .await?;
+
+        Ok(1)
+    }
+
+    #[graphql(guard = "ScopeGuard::session_only()")]
+    async fn logout_everywhere(&self, ctx: &Context<'_>) -> Result<u64> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        Ok(ctx
+            .data::<UserService<UserRepositoryImpl>>()?
+            .revoke_all_sessions(claims.sub)
+            .await?)
+    }
+
+    #[graphql(guard = "ScopeGuard::session_only()")]
+    async fn revoke_session(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "session id")] session_id: i64,
+    ) -> Result<u64> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        ctx.data::<UserService<UserRepositoryImpl>>()?
+            .revoke_session(claims.sub, session_id)
+            .await?;
+
+        Ok(1)
+    }
+
+    #[graphql(guard = "ScopeGuard::session_only()")]
+    async fn create_api_key(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "name of api key")] name: String,
+        #[graphql(desc = "library, downloads or admin, every key can read")] scopes: Vec<String>,
+    ) -> Result<CreatedApiKey> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        let scopes = scopes
+            .iter()
+            .map(|scope| scope.parse::<ApiKeyScope>())
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let (api_key, key) = ctx
+            .data::<UserService<UserRepositoryImpl>>()?
+            .create_api_key(claims.sub, &name, &scopes)
+            .await?;
+
+        Ok(CreatedApiKey {
+            api_key: api_key.into(),
+            key,
+        })
+    }
+
+    #[graphql(guard = "ScopeGuard::session_only()")]
+    async fn revoke_api_key(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "api key id")] id: i64,
+    ) -> Result<u64> {
+        let claims = ctx
+            .data::<Claims>()
+            .map_err(|_| "token not exists, please login")?;
+
+        ctx.data::<UserService<UserRepositoryImpl>>()?
+            .revoke_api_key(claims.sub, id)
             .await?;
 
         Ok(1)
     }
 
+    #[graphql(guard = "ScopeGuard::session_only()")]
     async fn tracker_logout(&self, ctx: &Context<'_>, tracker: String) -> Result<u64> {
         let claims = ctx
             .data::<Claims>()
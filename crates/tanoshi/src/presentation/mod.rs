@@ -2,6 +2,7 @@
 pub mod assets;
 pub mod graphql;
 pub mod rest;
+pub mod telegram;
 pub mod token;
 
 use anyhow::anyhow;
@@ -21,11 +22,20 @@ use self::{
         graphql_handler, graphql_playground, graphql_ws_handler,
         schema::{DatabaseLoader, SchemaBuilder},
     },
-    rest::{health::health_check, image::fetch_image},
+    rest::{
+        auth::BasicAuthCache,
+        download::{download_chapter, download_manga},
+        health::health_check,
+        image::fetch_image,
+        komga,
+        opds::{self, OpdsVersion},
+    },
 };
 use crate::{
     application::worker::{
         downloads::DownloadSender,
+        prefetch::PrefetchSender,
+        tracker::TrackerSyncSender,
         updates::{ChapterUpdateCommandSender, ChapterUpdateReceiver},
     },
     domain::services::{
@@ -43,6 +53,7 @@ use crate::{
             tracker::TrackerRepositoryImpl, user::UserRepositoryImpl,
         },
         notification::Notification,
+        oidc::PendingLogins,
     },
 };
 use tanoshi_vm::extension::ExtensionManager;
@@ -65,6 +76,8 @@ pub struct ServerBuilder {
     loader: Option<DatabaseLoader>,
     chapter_update_receiver: Option<ChapterUpdateReceiver>,
     chapter_update_command_tx: Option<ChapterUpdateCommandSender>,
+    tracker_sync_tx: Option<TrackerSyncSender>,
+    prefetch_tx: Option<PrefetchSender>,
     enable_playground: bool,
 }
 
@@ -191,6 +204,20 @@ impl ServerBuilder {
         }
     }
 
+    pub fn with_tracker_sync_tx(self, sender: TrackerSyncSender) -> Self {
+        Self {
+            tracker_sync_tx: Some(sender),
+            ..self
+        }
+    }
+
+    pub fn with_prefetch_tx(self, sender: PrefetchSender) -> Self {
+        Self {
+            prefetch_tx: Some(sender),
+            ..self
+        }
+    }
+
     pub fn enable_playground(self) -> Self {
         Self {
             enable_playground: true,
@@ -234,25 +261,34 @@ impl ServerBuilder {
         let chapter_update_command_tx = self
             .chapter_update_command_tx
             .ok_or_else(|| anyhow!("no chapter update command sender"))?;
+        let tracker_sync_tx = self
+            .tracker_sync_tx
+            .ok_or_else(|| anyhow!("no tracker sync sender"))?;
+        let prefetch_tx = self
+            .prefetch_tx
+            .ok_or_else(|| anyhow!("no prefetch sender"))?;
         let loader = self.loader.ok_or_else(|| anyhow!("no loader"))?;
 
         let schema = SchemaBuilder::new()
             .data(config.clone())
-            .data(user_svc)
+            .data(user_svc.clone())
             .data(tracker_svc)
-            .data(source_svc)
-            .data(manga_svc)
-            .data(chapter_svc)
+            .data(source_svc.clone())
+            .data(manga_svc.clone())
+            .data(chapter_svc.clone())
             .data(image_svc.clone())
-            .data(library_svc)
-            .data(history_svc)
-            .data(download_svc)
+            .data(library_svc.clone())
+            .data(history_svc.clone())
+            .data(download_svc.clone())
             .loader(loader)
             .data(extension_manager)
             .data(download_tx)
-            .data(notifier)
+            .data(notifier.clone())
             .data(chapter_update_receiver)
             .data(chapter_update_command_tx)
+            .data(tracker_sync_tx)
+            .data(prefetch_tx)
+            .data(PendingLogins::default())
             .build();
 
         let mut router = Router::new();
@@ -260,7 +296,20 @@ impl ServerBuilder {
         router = router
             .route("/health", get(health_check))
             .route("/image/:url", get(fetch_image))
-            .layer(Extension(image_svc));
+            .route("/download/chapter/:id", get(download_chapter))
+            .route("/download/manga/:id", get(download_manga))
+            .nest("/opds", opds::router(OpdsVersion::V1))
+            .nest("/opds/v2", opds::router(OpdsVersion::V2))
+            .merge(komga::router())
+            .layer(Extension(chapter_svc))
+            .layer(Extension(manga_svc))
+            .layer(Extension(source_svc))
+            .layer(Extension(library_svc))
+            .layer(Extension(history_svc))
+            .layer(Extension(download_svc))
+            .layer(Extension(notifier))
+            .layer(Extension(image_svc))
+            .layer(Extension(BasicAuthCache::default()));
 
         let svc = if self.enable_playground {
             get(graphql_playground).post(graphql_handler)
@@ -275,6 +324,7 @@ impl ServerBuilder {
 
         router = router
             .layer(Extension(config))
+            .layer(Extension(user_svc))
             .layer(Extension(schema))
             .layer(
                 CorsLayer::new()
@@ -290,7 +340,7 @@ impl ServerBuilder {
         }
 
         axum::Server::bind(&addr.into())
-            .serve(router.into_make_service())
+            .serve(router.into_make_service_with_connect_info::<SocketAddr>())
             .await?;
 
         Ok(())
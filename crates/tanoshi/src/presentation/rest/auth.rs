@@ -0,0 +1,272 @@
+use std::{
+    collections::{HashMap, HashSet},
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, Extension, FromRequestParts, TypedHeader},
+    http::{request::Parts, StatusCode},
+    response::{IntoResponse, Response},
+    RequestPartsExt,
+};
+use headers::{authorization::Basic, Authorization, HeaderMapExt, UserAgent};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    domain::{
+        entities::user::{ApiKeyScope, Permission, User},
+        services::{
+            source::SourceService,
+            user::{UserError, UserService, API_KEY_PREFIX},
+        },
+    },
+    infrastructure::{
+        auth::Claims,
+        config::Config,
+        domain::repositories::{source::SourceRepositoryImpl, user::UserRepositoryImpl},
+        notification::Notification,
+    },
+    presentation::{
+        graphql::{client_ip, sessionless_claims, verify_token, verify_trusted_header},
+        token::Token,
+    },
+};
+
+/// E-readers send basic auth with every request and verifying a password is
+/// slow on purpose, so verified credentials are remembered this long
+const BASIC_AUTH_CACHE_TTL: Duration = Duration::from_secs(5 * 60);
+const BASIC_AUTH_CACHE_MAX_ENTRIES: usize = 1000;
+
+/// Recently verified basic auth credentials by their hash, with id and
+/// password hash of the user. An entry is only used while the user's password
+/// is unchanged.
+#[derive(Debug, Clone, Default)]
+pub struct BasicAuthCache(Arc<Mutex<HashMap<String, BasicAuthCacheEntry>>>);
+
+/// User id, password hash and time credentials were verified
+type BasicAuthCacheEntry = (i64, String, Instant);
+
+impl BasicAuthCache {
+    fn key(basic: &Basic) -> String {
+        // username of basic auth can't contain a colon
+        Sha256::digest(format!("{}:{}", basic.username(), basic.password()).as_bytes())
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect()
+    }
+
+    fn get(&self, basic: &Basic) -> Option<(i64, String)> {
+        self.0
+            .lock()
+            .unwrap()
+            .get(&Self::key(basic))
+            .filter(|(_, _, verified_at)| verified_at.elapsed() < BASIC_AUTH_CACHE_TTL)
+            .map(|(user_id, password, _)| (*user_id, password.clone()))
+    }
+
+    fn insert(&self, basic: &Basic, user: &User) {
+        let mut entries = self.0.lock().unwrap();
+        entries.retain(|_, (_, _, verified_at)| verified_at.elapsed() < BASIC_AUTH_CACHE_TTL);
+        if entries.len() < BASIC_AUTH_CACHE_MAX_ENTRIES {
+            entries.insert(
+                Self::key(basic),
+                (user.id, user.password.clone(), Instant::now()),
+            );
+        }
+    }
+}
+
+/// User of a rest request, authenticated like graphql requests or with
+/// http basic auth for clients such as e-readers. Basic auth password may
+/// also be an api key.
+pub struct AuthUser {
+    pub user: User,
+    pub claims: Claims,
+}
+
+impl AuthUser {
+    /// Api key of request has `scope`, login sessions have every scope
+    pub fn has_scope(&self, scope: ApiKeyScope) -> bool {
+        self.claims
+            .scopes
+            .as_ref()
+            .map(|scopes| scopes.contains(&scope) || scopes.contains(&ApiKeyScope::Admin))
+            .unwrap_or(true)
+    }
+
+    /// User may download chapters and api key of request has downloads scope
+    pub fn can_download(&self) -> bool {
+        self.user.has_permission(Permission::Download) && self.has_scope(ApiKeyScope::Downloads)
+    }
+}
+
+fn unauthorized(message: &str) -> Response {
+    (
+        StatusCode::UNAUTHORIZED,
+        [("WWW-Authenticate", "Basic realm=\"Tanoshi\"")],
+        message.to_string(),
+    )
+        .into_response()
+}
+
+async fn verify_basic_auth(parts: &Parts, basic: &Basic) -> Result<Claims, Response> {
+    let config = parts
+        .extensions
+        .get::<Config>()
+        .ok_or_else(|| StatusCode::INTERNAL_SERVER_ERROR.into_response())?;
+    let user_svc = parts
+        .extensions
+        .get::<UserService<UserRepositoryImpl>>()
+        .ok_or_else(|| StatusCode::INTERNAL_SERVER_ERROR.into_response())?;
+
+    // readers without a way to send headers use api key as password
+    if basic.password().starts_with(API_KEY_PREFIX) {
+        return verify_token(config, user_svc, &Token(basic.password().to_string()))
+            .await
+            .ok_or_else(|| unauthorized("invalid api key"));
+    }
+
+    let cache = parts.extensions.get::<BasicAuthCache>();
+    if let Some((user_id, password)) = cache.and_then(|cache| cache.get(basic)) {
+        match user_svc.fetch_user_by_id(user_id).await {
+            Ok(user) if user.password == password && !user.totp_enabled => {
+                return sessionless_claims(user, None)
+                    .ok_or_else(|| StatusCode::INTERNAL_SERVER_ERROR.into_response());
+            }
+            _ => {}
+        }
+    }
+
+    let ip = parts
+        .extensions
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| client_ip(config, addr, &parts.headers).to_string());
+    let user_agent = parts.headers.typed_get::<UserAgent>();
+
+    let user = match user_svc
+        .verify_basic_auth(
+            &config.login_limit.limit(),
+            basic.username(),
+            basic.password(),
+            ip.as_deref(),
+            user_agent.as_ref().map(|user_agent| user_agent.as_str()),
+        )
+        .await
+    {
+        Ok(user) => user,
+        Err(UserError::LoginLocked) => {
+            return Err((
+                StatusCode::TOO_MANY_REQUESTS,
+                UserError::LoginLocked.to_string(),
+            )
+                .into_response())
+        }
+        Err(UserError::AccountLocked(username)) => {
+            if let Some(notifier) = parts.extensions.get::<Notification<UserRepositoryImpl>>() {
+                let message = format!(
+                    "login of user {username} is locked after {} failed attempts",
+                    config.login_limit.max_failures_per_user
+                );
+                if let Err(e) = notifier.send_all_to_admins(None, &message).await {
+                    error!("failed to notify admins of locked user {username}: {e}");
+                }
+            }
+            return Err((StatusCode::TOO_MANY_REQUESTS, "too many failed logins").into_response());
+        }
+        Err(UserError::TotpRequired) => {
+            return Err(unauthorized(
+                "two-factor authentication is enabled, use an api key as password",
+            ))
+        }
+        Err(e) => {
+            debug!("rejecting basic auth of {}: {e}", basic.username());
+            return Err(unauthorized("incorrect username or password"));
+        }
+    };
+
+    if let Some(cache) = cache {
+        cache.insert(basic, &user);
+    }
+
+    sessionless_claims(user, None).ok_or_else(|| StatusCode::INTERNAL_SERVER_ERROR.into_response())
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for AuthUser
+where
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let Extension(config) = parts
+            .extract::<Extension<Config>>()
+            .await
+            .map_err(IntoResponse::into_response)?;
+        let Extension(user_svc) = parts
+            .extract::<Extension<UserService<UserRepositoryImpl>>>()
+            .await
+            .map_err(IntoResponse::into_response)?;
+
+        let claims = if let Ok(TypedHeader(Authorization(basic))) =
+            parts.extract::<TypedHeader<Authorization<Basic>>>().await
+        {
+            verify_basic_auth(parts, &basic).await?
+        } else {
+            let token = parts
+                .extract::<Token>()
+                .await
+                .unwrap_or_else(|_| Token("".to_string()));
+
+            let claims = match verify_token(&config, &user_svc, &token).await {
+                Some(claims) => Some(claims),
+                None => match parts.extensions.get::<ConnectInfo<SocketAddr>>() {
+                    Some(ConnectInfo(addr)) => {
+                        verify_trusted_header(&config, &user_svc, addr, &parts.headers).await
+                    }
+                    None => None,
+                },
+            };
+
+            claims.ok_or_else(|| unauthorized("token not exists, please login"))?
+        };
+
+        let user = user_svc
+            .fetch_user_by_id(claims.sub)
+            .await
+            .map_err(|_| unauthorized("user not found"))?;
+
+        Ok(Self { user, claims })
+    }
+}
+
+/// Installed sources user may browse, `None` if user is not restricted
+pub async fn allowed_source_ids(
+    user: &User,
+    source_svc: &SourceService<SourceRepositoryImpl>,
+) -> Result<Option<HashSet<i64>>, StatusCode> {
+    source_svc.get_allowed_source_ids(user).await.map_err(|e| {
+        error!("failed to get installed sources: {e}");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
+
+/// Check user is allowed to browse source
+pub async fn check_source_allowed(
+    user: &User,
+    source_svc: &SourceService<SourceRepositoryImpl>,
+    source_id: i64,
+) -> Result<(), StatusCode> {
+    if !source_svc
+        .is_source_allowed(user, source_id)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?
+    {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,313 @@
+//! Chapters as cbz or epub archive for reading outside Tanoshi. Pages are read
+//! from downloaded archive when chapter is downloaded, otherwise they are
+//! fetched from source.
+
+use anyhow::anyhow;
+use axum::{
+    body::{Body, Bytes},
+    extract::{Extension, Path, Query},
+    http::{Response, StatusCode},
+};
+use serde::Deserialize;
+use tokio::{fs::File, io::AsyncReadExt};
+
+use super::{
+    ascii_file_name,
+    auth::{check_source_allowed, AuthUser},
+    fetch_chapter_images,
+};
+use crate::{
+    domain::{
+        entities::{chapter::Chapter, manga::Manga},
+        services::{
+            chapter::ChapterService, image::ImageService, manga::MangaService,
+            source::SourceService,
+        },
+    },
+    infrastructure::{
+        archive::{ArchiveFormat, ArchiveWriter, ComicInfo},
+        domain::repositories::{
+            chapter::ChapterRepositoryImpl, image::ImageRepositoryImpl,
+            image_cache::ImageCacheRepositoryImpl, manga::MangaRepositoryImpl,
+            source::SourceRepositoryImpl,
+        },
+    },
+};
+
+type ImageSvc = ImageService<ImageCacheRepositoryImpl, ImageRepositoryImpl>;
+
+/// Size of chunks downloaded chapter is sent in
+const DOWNLOADED_CHUNK_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Deserialize)]
+pub struct DownloadParams {
+    /// `cbz` or `epub`, defaults to `cbz`
+    #[serde(default)]
+    format: ArchiveFormat,
+    /// lowest chapter number of manga to include
+    from: Option<f64>,
+    /// highest chapter number of manga to include
+    to: Option<f64>,
+}
+
+fn internal_error(e: impl std::fmt::Display) -> StatusCode {
+    error!("failed to archive chapters: {e}");
+    StatusCode::INTERNAL_SERVER_ERROR
+}
+
+fn comic_info(manga: &Manga, chapters: &[Chapter], page_count: usize) -> ComicInfo {
+    let chapter = match chapters {
+        [chapter] => Some(chapter),
+        _ => None,
+    };
+
+    ComicInfo {
+        title: chapter
+            .map(|chapter| chapter.title.clone())
+            .unwrap_or_else(|| manga.title.clone()),
+        series: manga.title.clone(),
+        number: chapter.map(|chapter| chapter.number.to_string()),
+        summary: manga.description.clone(),
+        writers: manga.author.clone(),
+        genres: manga.genre.clone(),
+        scan_information: chapter
+            .map(|chapter| chapter.scanlator.clone())
+            .filter(|scanlator| !scanlator.is_empty()),
+        released: chapters.first().map(|chapter| chapter.uploaded),
+        page_count,
+    }
+}
+
+/// Response with archive named after manga or its only chapter as attachment
+fn attachment(
+    manga: &Manga,
+    chapters: &[Chapter],
+    format: ArchiveFormat,
+    content_length: Option<u64>,
+    body: Body,
+) -> Result<Response<Body>, StatusCode> {
+    let file_name = match chapters {
+        [chapter] => format!("{} - {}", manga.title, chapter.title),
+        _ => manga.title.clone(),
+    };
+
+    let mut response = Response::builder()
+        .header("Content-Type", format.content_type())
+        .header(
+            "Content-Disposition",
+            format!(
+                "attachment; filename=\"{}.{}\"",
+                ascii_file_name(&file_name),
+                format.extension()
+            ),
+        );
+    if let Some(content_length) = content_length {
+        response = response.header("Content-Length", content_length);
+    }
+
+    response
+        .body(body)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+/// Archive of chapters of manga as attachment, chapters are archived in the
+/// given order. A single downloaded chapter requested as cbz is sent as it
+/// was downloaded, otherwise archive is sent as it's written and only one
+/// chapter of pages is held at a time.
+pub async fn archive_response(
+    chapter_svc: ChapterService<ChapterRepositoryImpl>,
+    source_svc: SourceService<SourceRepositoryImpl>,
+    image_svc: ImageSvc,
+    manga: Manga,
+    chapters: Vec<Chapter>,
+    format: ArchiveFormat,
+) -> Result<Response<Body>, StatusCode> {
+    if let [chapter] = chapters.as_slice() {
+        let downloaded_path = chapter
+            .downloaded_path
+            .as_ref()
+            .filter(|_| format == ArchiveFormat::Cbz);
+        if let Some(path) = downloaded_path {
+            match File::open(path).await {
+                Ok(file) => return downloaded_response(file, &manga, &chapters).await,
+                Err(e) => warn!("failed to open downloaded chapter {path}: {e}"),
+            }
+        }
+    }
+
+    let first_chapter = chapters.first().ok_or(StatusCode::NOT_FOUND)?;
+    let mut writer = ArchiveWriter::new(format, chapters.len()).map_err(internal_error)?;
+
+    // first chapter is fetched before responding, so a source that can't be
+    // reached is reported with status code instead of a broken archive
+    let pages = fetch_chapter_images(&chapter_svc, &source_svc, &image_svc, first_chapter).await?;
+    let mut page_count = pages.len();
+    writer
+        .add_chapter(&first_chapter.title, &pages)
+        .map_err(internal_error)?;
+    drop(pages);
+
+    let (mut sender, body) = Body::channel();
+    let response = attachment(&manga, &chapters, format, None, body)?;
+
+    tokio::spawn(async move {
+        let res: anyhow::Result<()> = async {
+            sender.send_data(writer.take_output().into()).await?;
+
+            for chapter in &chapters[1..] {
+                let pages = fetch_chapter_images(&chapter_svc, &source_svc, &image_svc, chapter)
+                    .await
+                    .map_err(|status| {
+                        anyhow!("failed to fetch chapter {}: {status}", chapter.id)
+                    })?;
+                page_count += pages.len();
+                writer.add_chapter(&chapter.title, &pages)?;
+                sender.send_data(writer.take_output().into()).await?;
+            }
+
+            let data = writer.finish(&comic_info(&manga, &chapters, page_count))?;
+            sender.send_data(data.into()).await?;
+
+            Ok(())
+        }
+        .await;
+
+        // client sees archive was cut short instead of a complete response
+        if let Err(e) = res {
+            error!("failed to archive chapters of manga {}: {e}", manga.id);
+            sender.abort();
+        }
+    });
+
+    Ok(response)
+}
+
+/// Downloaded chapter archive read from disk as it's sent
+async fn downloaded_response(
+    mut file: File,
+    manga: &Manga,
+    chapters: &[Chapter],
+) -> Result<Response<Body>, StatusCode> {
+    let content_length = file.metadata().await.map_err(internal_error)?.len();
+
+    let (mut sender, body) = Body::channel();
+    let response = attachment(
+        manga,
+        chapters,
+        ArchiveFormat::Cbz,
+        Some(content_length),
+        body,
+    )?;
+
+    tokio::spawn(async move {
+        let mut buf = vec![0; DOWNLOADED_CHUNK_SIZE];
+        loop {
+            match file.read(&mut buf).await {
+                Ok(0) => break,
+                Ok(n) => {
+                    if sender
+                        .send_data(Bytes::copy_from_slice(&buf[..n]))
+                        .await
+                        .is_err()
+                    {
+                        break;
+                    }
+                }
+                Err(e) => {
+                    error!("failed to read downloaded chapter: {e}");
+                    sender.abort();
+                    break;
+                }
+            }
+        }
+    });
+
+    Ok(response)
+}
+
+pub async fn download_chapter(
+    auth: AuthUser,
+    Path(id): Path<i64>,
+    Query(params): Query<DownloadParams>,
+    Extension(chapter_svc): Extension<ChapterService<ChapterRepositoryImpl>>,
+    Extension(manga_svc): Extension<MangaService<MangaRepositoryImpl>>,
+    Extension(source_svc): Extension<SourceService<SourceRepositoryImpl>>,
+    Extension(image_svc): Extension<ImageSvc>,
+) -> Result<Response<Body>, StatusCode> {
+    if !auth.can_download() {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let chapter = chapter_svc
+        .fetch_chapter_by_id(id)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+    check_source_allowed(&auth.user, &source_svc, chapter.source_id).await?;
+
+    let manga = manga_svc
+        .fetch_manga_by_id(chapter.manga_id, false)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+
+    archive_response(
+        chapter_svc,
+        source_svc,
+        image_svc,
+        manga,
+        vec![chapter],
+        params.format,
+    )
+    .await
+}
+
+/// Every chapter of manga, or those numbered from `from` to `to`
+pub async fn download_manga(
+    auth: AuthUser,
+    Path(id): Path<i64>,
+    Query(params): Query<DownloadParams>,
+    Extension(chapter_svc): Extension<ChapterService<ChapterRepositoryImpl>>,
+    Extension(manga_svc): Extension<MangaService<MangaRepositoryImpl>>,
+    Extension(source_svc): Extension<SourceService<SourceRepositoryImpl>>,
+    Extension(image_svc): Extension<ImageSvc>,
+) -> Result<Response<Body>, StatusCode> {
+    if !auth.can_download() {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let manga = manga_svc
+        .fetch_manga_by_id(id, false)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+    check_source_allowed(&auth.user, &source_svc, manga.source_id).await?;
+
+    let mut chapters: Vec<Chapter> = chapter_svc
+        .fetch_chapters_by_manga_id(manga.source_id, &manga.path, manga.id, false)
+        .await
+        .map_err(|e| {
+            error!("failed to get chapters of manga {id}: {e}");
+            StatusCode::BAD_GATEWAY
+        })?
+        .into_iter()
+        .filter(|chapter| {
+            params
+                .from
+                .map(|from| chapter.number >= from)
+                .unwrap_or(true)
+        })
+        .filter(|chapter| params.to.map(|to| chapter.number <= to).unwrap_or(true))
+        .collect();
+    if chapters.is_empty() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    chapters.sort_by(|a, b| a.number.total_cmp(&b.number));
+
+    archive_response(
+        chapter_svc,
+        source_svc,
+        image_svc,
+        manga,
+        chapters,
+        params.format,
+    )
+    .await
+}
@@ -1,40 +1,147 @@
 use axum::{
     body::Body,
-    extract::{Extension, Path, Query},
+    extract::{Extension, Path, Query, TypedHeader},
     http::{Response, StatusCode},
     response::IntoResponse,
 };
 
+use headers::{ETag, IfNoneMatch};
 use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use super::ascii_file_name;
 
 use crate::{
-    domain::services::image::ImageService,
+    domain::{
+        entities::image::{ImageFormat, ImageTransform},
+        repositories::image::ImageRepositoryError,
+        services::{
+            image::{ImageError, ImageService},
+            user::UserService,
+        },
+    },
     infrastructure::{
+        archive,
         config::Config,
-        domain::repositories::{image::ImageRepositoryImpl, image_cache::ImageCacheRepositoryImpl},
+        domain::repositories::{
+            image::ImageRepositoryImpl, image_cache::ImageCacheRepositoryImpl,
+            user::UserRepositoryImpl,
+        },
     },
 };
 
 #[derive(Debug, Deserialize)]
 pub struct Params {
     referer: Option<String>,
+    /// scale down to fit width, keeping aspect ratio
+    width: Option<u32>,
+    /// scale down to fit height, keeping aspect ratio
+    height: Option<u32>,
+    /// encoder quality from 1 to 100
+    quality: Option<u8>,
+    format: Option<ImageFormat>,
+}
+
+const CACHE_CONTROL: &str = "max-age=864000";
+
+fn error_status(e: &ImageError) -> StatusCode {
+    match e {
+        ImageError::InvalidUrl(_) => StatusCode::BAD_REQUEST,
+        ImageError::Forbidden(_) => StatusCode::FORBIDDEN,
+        ImageError::NotFound | ImageError::RepositoryError(ImageRepositoryError::NotFound) => {
+            StatusCode::NOT_FOUND
+        }
+        ImageError::RequestError
+        | ImageError::RepositoryError(ImageRepositoryError::RequestError(_)) => {
+            StatusCode::BAD_GATEWAY
+        }
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+/// Original file name with extension matching content type
+fn content_disposition(file_name: Option<String>, content_type: &str) -> String {
+    let extension = archive::image_extension(content_type);
+
+    let file_name = file_name.unwrap_or_else(|| "image".to_string());
+    let file_name = match (file_name.rsplit_once('.'), extension) {
+        (Some((stem, _)), Some(extension)) => format!("{stem}.{extension}"),
+        (None, Some(extension)) => format!("{file_name}.{extension}"),
+        (_, None) => file_name,
+    };
+
+    format!("inline; filename=\"{}\"", ascii_file_name(&file_name))
 }
 
 pub async fn fetch_image(
     Path(encrypted_url): Path<String>,
     Query(params): Query<Params>,
+    if_none_match: Option<TypedHeader<IfNoneMatch>>,
     Extension(config): Extension<Config>,
     Extension(svc): Extension<ImageService<ImageCacheRepositoryImpl, ImageRepositoryImpl>>,
+    Extension(user_svc): Extension<UserService<UserRepositoryImpl>>,
 ) -> Result<impl IntoResponse, StatusCode> {
+    let (url, claims) = svc
+        .verify_image_url(
+            &config.secret,
+            &encrypted_url,
+            config.image_url.accept_legacy,
+        )
+        .map_err(|e| {
+            debug!("rejected image url: {e}");
+            error_status(&e)
+        })?;
+
+    // url stops working once the session or user it was issued to is gone
+    if let Some(claims) = claims {
+        let res = match claims.sid {
+            Some(session_id) => user_svc.verify_session(claims.sub, session_id).await,
+            None => user_svc.fetch_user_by_id(claims.sub).await.map(|_| ()),
+        };
+        if res.is_err() {
+            return Err(StatusCode::FORBIDDEN);
+        }
+    }
+
+    let transform = ImageTransform::new(params.width, params.height, params.quality, params.format);
+
     let image = svc
-        .fetch_image(&config.secret, &encrypted_url, params.referer.as_ref())
+        .fetch_image(&url, params.referer.as_ref(), transform)
         .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .map_err(|e| {
+            error!("error fetch image: {e}");
+            error_status(&e)
+        })?;
+
+    let hash: String = Sha256::digest(&image.data)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect();
+    let etag = format!("\"{hash}\"");
+
+    if let Some(TypedHeader(if_none_match)) = if_none_match {
+        let parsed: ETag = etag
+            .parse()
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        if !if_none_match.precondition_passes(&parsed) {
+            return Response::builder()
+                .status(StatusCode::NOT_MODIFIED)
+                .header("ETag", etag)
+                .header("Cache-Control", CACHE_CONTROL)
+                .body(Body::empty())
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    }
+
+    let content_disposition =
+        content_disposition(svc.get_image_file_name(&url), &image.content_type);
 
-    Ok(Response::builder()
+    Response::builder()
         .header("Content-Type", image.content_type)
         .header("Content-Length", image.data.len())
-        .header("Cache-Control", "max-age=864000")
+        .header("Cache-Control", CACHE_CONTROL)
+        .header("ETag", etag)
+        .header("Content-Disposition", content_disposition)
         .body(Body::from(image.data))
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
 }
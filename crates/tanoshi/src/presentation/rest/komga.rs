@@ -0,0 +1,896 @@
+//! Read-oriented subset of Komga rest api for readers with a Komga client.
+//! Libraries are categories, series are manga, books are chapters and pages
+//! are chapter pages. Ids are strings like in Komga, the default category is
+//! the library `default`.
+
+use std::collections::HashMap;
+
+use axum::{
+    body::Body,
+    extract::{Extension, Path, Query},
+    http::{Response, StatusCode},
+    routing::get,
+    Json, Router,
+};
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    auth::{allowed_source_ids, check_source_allowed, AuthUser},
+    chapter_page_counts, fetch_chapter_page,
+};
+use crate::{
+    domain::{
+        entities::{
+            chapter::Chapter,
+            history::HistoryChapter,
+            image::{ImageFormat, ImageTransform},
+            manga::Manga,
+            user::ApiKeyScope,
+        },
+        services::{
+            chapter::ChapterService, history::HistoryService, image::ImageService,
+            library::LibraryService, manga::MangaService, source::SourceService,
+        },
+    },
+    infrastructure::domain::repositories::{
+        chapter::ChapterRepositoryImpl, history::HistoryRepositoryImpl, image::ImageRepositoryImpl,
+        image_cache::ImageCacheRepositoryImpl, library::LibraryRepositoryImpl,
+        manga::MangaRepositoryImpl, source::SourceRepositoryImpl,
+    },
+};
+
+const DEFAULT_LIBRARY_ID: &str = "default";
+/// Page size when client does not ask for one
+const DEFAULT_PAGE_SIZE: usize = 20;
+const MAX_PAGE_SIZE: usize = 500;
+
+type ImageSvc = ImageService<ImageCacheRepositoryImpl, ImageRepositoryImpl>;
+type HistorySvc = HistoryService<ChapterRepositoryImpl, HistoryRepositoryImpl>;
+
+pub fn router() -> Router {
+    Router::new()
+        .route("/api/v1/libraries", get(libraries))
+        .route("/api/v1/libraries/:id", get(library))
+        .route("/api/v1/series", get(series_list))
+        .route("/api/v1/series/:id", get(series))
+        .route("/api/v1/series/:id/thumbnail", get(series_thumbnail))
+        .route("/api/v1/series/:id/books", get(series_books))
+        .route("/api/v1/books/:id", get(book))
+        .route("/api/v1/books/:id/thumbnail", get(book_thumbnail))
+        .route("/api/v1/books/:id/pages", get(book_pages))
+        .route("/api/v1/books/:id/pages/:number", get(book_page))
+        .route(
+            "/api/v1/books/:id/read-progress",
+            get(book_read_progress)
+                .patch(update_read_progress)
+                .delete(delete_read_progress),
+        )
+        .route("/api/v1/users/me", get(me))
+        .route("/api/v2/users/me", get(me))
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LibraryDto {
+    id: String,
+    name: String,
+    root: String,
+    unavailable: bool,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuthorDto {
+    name: String,
+    role: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SeriesMetadataDto {
+    status: &'static str,
+    title: String,
+    title_sort: String,
+    summary: String,
+    genres: Vec<String>,
+    tags: Vec<String>,
+    total_book_count: usize,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BooksMetadataDto {
+    authors: Vec<AuthorDto>,
+    summary: String,
+    tags: Vec<String>,
+    release_date: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SeriesDto {
+    id: String,
+    library_id: String,
+    name: String,
+    url: String,
+    created: String,
+    last_modified: String,
+    file_last_modified: String,
+    books_count: usize,
+    books_read_count: usize,
+    books_unread_count: usize,
+    books_in_progress_count: usize,
+    metadata: SeriesMetadataDto,
+    books_metadata: BooksMetadataDto,
+    deleted: bool,
+    oneshot: bool,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MediaDto {
+    status: &'static str,
+    media_type: &'static str,
+    pages_count: usize,
+    comment: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReadProgressDto {
+    /// last page read, from 1
+    page: i64,
+    completed: bool,
+    read_date: String,
+    created: String,
+    last_modified: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BookMetadataDto {
+    title: String,
+    summary: String,
+    number: String,
+    number_sort: f64,
+    release_date: Option<String>,
+    authors: Vec<AuthorDto>,
+    tags: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BookDto {
+    id: String,
+    series_id: String,
+    series_title: String,
+    library_id: String,
+    name: String,
+    url: String,
+    number: i64,
+    created: String,
+    last_modified: String,
+    file_last_modified: String,
+    size_bytes: i64,
+    size: String,
+    media: MediaDto,
+    metadata: BookMetadataDto,
+    read_progress: Option<ReadProgressDto>,
+    deleted: bool,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PageDto {
+    /// page number, from 1
+    number: usize,
+    file_name: String,
+    media_type: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PageOf<T> {
+    content: Vec<T>,
+    number: usize,
+    size: usize,
+    number_of_elements: usize,
+    total_elements: usize,
+    total_pages: usize,
+    first: bool,
+    last: bool,
+    empty: bool,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserDto {
+    id: String,
+    email: String,
+    roles: Vec<&'static str>,
+    shared_all_libraries: bool,
+    shared_libraries_ids: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PageParams {
+    #[serde(default)]
+    page: usize,
+    size: Option<usize>,
+}
+
+impl PageParams {
+    fn size(&self) -> usize {
+        self.size
+            .unwrap_or(DEFAULT_PAGE_SIZE)
+            .clamp(1, MAX_PAGE_SIZE)
+    }
+
+    /// Items of requested page, with total count of items
+    fn slice<T>(&self, items: Vec<T>) -> (Vec<T>, usize) {
+        let total = items.len();
+        let items = items
+            .into_iter()
+            .skip(self.page * self.size())
+            .take(self.size())
+            .collect();
+        (items, total)
+    }
+
+    fn page_of<T>(&self, content: Vec<T>, total: usize) -> PageOf<T> {
+        let size = self.size();
+        let total_pages = total.div_ceil(size);
+        PageOf {
+            number: self.page,
+            size,
+            number_of_elements: content.len(),
+            total_elements: total,
+            total_pages,
+            first: self.page == 0,
+            last: self.page + 1 >= total_pages,
+            empty: content.is_empty(),
+            content,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SeriesParams {
+    library_id: Option<String>,
+    search: Option<String>,
+    #[serde(default)]
+    page: usize,
+    size: Option<usize>,
+}
+
+fn datetime(datetime: &NaiveDateTime) -> String {
+    format!("{}Z", datetime.format("%Y-%m-%dT%H:%M:%S"))
+}
+
+fn date(datetime: &NaiveDateTime) -> String {
+    datetime.format("%Y-%m-%d").to_string()
+}
+
+fn library_id(category_id: Option<i64>) -> String {
+    category_id
+        .map(|id| id.to_string())
+        .unwrap_or_else(|| DEFAULT_LIBRARY_ID.to_string())
+}
+
+fn parse_library_id(id: &str) -> Result<Option<i64>, StatusCode> {
+    if id == DEFAULT_LIBRARY_ID {
+        return Ok(None);
+    }
+
+    id.parse().map(Some).map_err(|_| StatusCode::NOT_FOUND)
+}
+
+/// Komga series status from status reported by source
+fn series_status(status: Option<&str>) -> &'static str {
+    let status = status.unwrap_or_default().to_lowercase();
+    if status.contains("complete") || status.contains("end") {
+        "ENDED"
+    } else if status.contains("hiatus") {
+        "HIATUS"
+    } else if status.contains("cancel") || status.contains("abandon") {
+        "ABANDONED"
+    } else {
+        "ONGOING"
+    }
+}
+
+fn authors(manga: &Manga) -> Vec<AuthorDto> {
+    manga
+        .author
+        .iter()
+        .map(|name| AuthorDto {
+            name: name.clone(),
+            role: "writer".to_string(),
+        })
+        .collect()
+}
+
+fn internal_error(e: impl std::fmt::Display) -> StatusCode {
+    error!("komga api error: {e}");
+    StatusCode::INTERNAL_SERVER_ERROR
+}
+
+/// Chapters of manga in reading order, with read progress of user keyed by chapter id
+async fn chapters_with_progress(
+    chapter_svc: &ChapterService<ChapterRepositoryImpl>,
+    history_svc: &HistorySvc,
+    manga: &Manga,
+    user_id: i64,
+) -> Result<(Vec<Chapter>, HashMap<i64, HistoryChapter>), StatusCode> {
+    let mut chapters = chapter_svc
+        .fetch_chapters_by_manga_id(manga.source_id, &manga.path, manga.id, false)
+        .await
+        .map_err(|e| {
+            error!("failed to get chapters of manga {}: {e}", manga.id);
+            StatusCode::BAD_GATEWAY
+        })?;
+    chapters.sort_by(|a, b| a.number.total_cmp(&b.number));
+
+    let chapter_ids: Vec<i64> = chapters.iter().map(|chapter| chapter.id).collect();
+    let progress = history_svc
+        .get_history_chapters_by_chapter_ids(user_id, &chapter_ids)
+        .await
+        .map_err(internal_error)?
+        .into_iter()
+        .map(|history| (history.chapter_id, history))
+        .collect();
+
+    Ok((chapters, progress))
+}
+
+async fn series_dto(
+    chapter_svc: &ChapterService<ChapterRepositoryImpl>,
+    history_svc: &HistorySvc,
+    manga: Manga,
+    library_id: String,
+    user_id: i64,
+) -> Result<SeriesDto, StatusCode> {
+    let (chapters, progress) =
+        chapters_with_progress(chapter_svc, history_svc, &manga, user_id).await?;
+
+    let books_read_count = progress.values().filter(|p| p.is_complete).count();
+    let books_in_progress_count = progress.len() - books_read_count;
+    let last_modified = manga.last_uploaded_at.unwrap_or(manga.date_added);
+
+    Ok(SeriesDto {
+        id: manga.id.to_string(),
+        library_id,
+        name: manga.title.clone(),
+        url: manga.path.clone(),
+        created: datetime(&manga.date_added),
+        last_modified: datetime(&last_modified),
+        file_last_modified: datetime(&last_modified),
+        books_count: chapters.len(),
+        books_read_count,
+        books_unread_count: chapters.len().saturating_sub(progress.len()),
+        books_in_progress_count,
+        metadata: SeriesMetadataDto {
+            status: series_status(manga.status.as_deref()),
+            title: manga.title.clone(),
+            title_sort: manga.title.clone(),
+            summary: manga.description.clone().unwrap_or_default(),
+            genres: manga.genre.clone(),
+            tags: vec![],
+            total_book_count: chapters.len(),
+        },
+        books_metadata: BooksMetadataDto {
+            authors: authors(&manga),
+            summary: manga.description.clone().unwrap_or_default(),
+            tags: vec![],
+            release_date: chapters.first().map(|chapter| date(&chapter.uploaded)),
+        },
+        deleted: false,
+        oneshot: false,
+    })
+}
+
+fn book_dto(
+    manga: &Manga,
+    chapter: &Chapter,
+    pages_count: usize,
+    progress: Option<&HistoryChapter>,
+) -> BookDto {
+    BookDto {
+        id: chapter.id.to_string(),
+        series_id: manga.id.to_string(),
+        series_title: manga.title.clone(),
+        library_id: DEFAULT_LIBRARY_ID.to_string(),
+        name: chapter.title.clone(),
+        url: chapter.path.clone(),
+        number: chapter.number.floor() as i64,
+        created: datetime(&chapter.date_added),
+        last_modified: datetime(&chapter.uploaded),
+        file_last_modified: datetime(&chapter.uploaded),
+        size_bytes: 0,
+        size: "0 B".to_string(),
+        media: MediaDto {
+            status: "READY",
+            media_type: "application/zip",
+            pages_count,
+            comment: "".to_string(),
+        },
+        metadata: BookMetadataDto {
+            title: chapter.title.clone(),
+            summary: "".to_string(),
+            number: chapter.number.to_string(),
+            number_sort: chapter.number,
+            release_date: Some(date(&chapter.uploaded)),
+            authors: authors(manga),
+            tags: vec![],
+        },
+        read_progress: progress.map(read_progress_dto),
+        deleted: false,
+    }
+}
+
+fn read_progress_dto(history: &HistoryChapter) -> ReadProgressDto {
+    ReadProgressDto {
+        page: history.last_page_read + 1,
+        completed: history.is_complete,
+        read_date: datetime(&history.read_at),
+        created: datetime(&history.read_at),
+        last_modified: datetime(&history.read_at),
+    }
+}
+
+/// Manga of series if user may browse its source
+async fn fetch_series(
+    auth: &AuthUser,
+    manga_svc: &MangaService<MangaRepositoryImpl>,
+    source_svc: &SourceService<SourceRepositoryImpl>,
+    id: i64,
+) -> Result<Manga, StatusCode> {
+    let manga = manga_svc
+        .fetch_manga_by_id(id, false)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+    check_source_allowed(&auth.user, source_svc, manga.source_id).await?;
+
+    Ok(manga)
+}
+
+/// Chapter of book if user may browse its source
+async fn fetch_book(
+    auth: &AuthUser,
+    chapter_svc: &ChapterService<ChapterRepositoryImpl>,
+    source_svc: &SourceService<SourceRepositoryImpl>,
+    id: i64,
+) -> Result<Chapter, StatusCode> {
+    let chapter = chapter_svc
+        .fetch_chapter_by_id(id)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+    check_source_allowed(&auth.user, source_svc, chapter.source_id).await?;
+
+    Ok(chapter)
+}
+
+async fn cover_response(
+    image_svc: &ImageSvc,
+    source_svc: &SourceService<SourceRepositoryImpl>,
+    manga: &Manga,
+) -> Result<Response<Body>, StatusCode> {
+    let source = source_svc
+        .get_source_by_id(manga.source_id)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+    let image = image_svc
+        .fetch_image(
+            &manga.cover_url,
+            Some(&source.url),
+            ImageTransform::default(),
+        )
+        .await
+        .map_err(|e| {
+            error!("failed to fetch cover of manga {}: {e}", manga.id);
+            StatusCode::BAD_GATEWAY
+        })?;
+
+    Response::builder()
+        .header("Content-Type", image.content_type)
+        .header("Content-Length", image.data.len())
+        .body(Body::from(image.data))
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+pub async fn libraries(
+    auth: AuthUser,
+    Extension(library_svc): Extension<LibraryService<LibraryRepositoryImpl>>,
+) -> Result<Json<Vec<LibraryDto>>, StatusCode> {
+    let libraries = library_svc
+        .get_categories_by_user_id(auth.user.id)
+        .await
+        .map_err(internal_error)?
+        .into_iter()
+        .map(|category| LibraryDto {
+            id: library_id(category.id),
+            name: category.name,
+            root: "".to_string(),
+            unavailable: false,
+        })
+        .collect();
+
+    Ok(Json(libraries))
+}
+
+pub async fn library(
+    auth: AuthUser,
+    Path(id): Path<String>,
+    Extension(library_svc): Extension<LibraryService<LibraryRepositoryImpl>>,
+) -> Result<Json<LibraryDto>, StatusCode> {
+    let category_id = parse_library_id(&id)?;
+    let category = library_svc
+        .get_categories_by_user_id(auth.user.id)
+        .await
+        .map_err(internal_error)?
+        .into_iter()
+        .find(|category| category.id == category_id)
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    Ok(Json(LibraryDto {
+        id,
+        name: category.name,
+        root: "".to_string(),
+        unavailable: false,
+    }))
+}
+
+/// Manga in user's library, from one category if `library_id` is set
+pub async fn series_list(
+    auth: AuthUser,
+    Query(params): Query<SeriesParams>,
+    Extension(library_svc): Extension<LibraryService<LibraryRepositoryImpl>>,
+    Extension(source_svc): Extension<SourceService<SourceRepositoryImpl>>,
+    Extension(chapter_svc): Extension<ChapterService<ChapterRepositoryImpl>>,
+    Extension(history_svc): Extension<HistorySvc>,
+) -> Result<Json<PageOf<SeriesDto>>, StatusCode> {
+    let search = params.search.as_deref().unwrap_or_default().to_lowercase();
+    let (library_id, manga) = match params.library_id.as_deref() {
+        Some(id) => {
+            let manga = library_svc
+                .get_manga_from_library_by_category_id(auth.user.id, parse_library_id(id)?)
+                .await
+                .map_err(internal_error)?;
+            (id.to_string(), manga)
+        }
+        None => {
+            let manga = library_svc
+                .search_library(auth.user.id, "")
+                .await
+                .map_err(internal_error)?
+                .into_iter()
+                .map(|(manga, _)| manga)
+                .collect();
+            (DEFAULT_LIBRARY_ID.to_string(), manga)
+        }
+    };
+
+    // manga added before user was restricted stay in library but are hidden
+    let allowed_source_ids = allowed_source_ids(&auth.user, &source_svc).await?;
+    let mut manga: Vec<Manga> = manga
+        .into_iter()
+        .filter(|m| {
+            allowed_source_ids
+                .as_ref()
+                .map(|source_ids| source_ids.contains(&m.source_id))
+                .unwrap_or(true)
+        })
+        .filter(|m| m.title.to_lowercase().contains(&search))
+        .collect();
+    manga.sort_by_key(|m| m.title.to_lowercase());
+
+    let page = PageParams {
+        page: params.page,
+        size: params.size,
+    };
+    let (manga, total) = page.slice(manga);
+    let mut content = vec![];
+    for m in manga {
+        content.push(
+            series_dto(
+                &chapter_svc,
+                &history_svc,
+                m,
+                library_id.clone(),
+                auth.user.id,
+            )
+            .await?,
+        );
+    }
+
+    Ok(Json(page.page_of(content, total)))
+}
+
+pub async fn series(
+    auth: AuthUser,
+    Path(id): Path<i64>,
+    Extension(manga_svc): Extension<MangaService<MangaRepositoryImpl>>,
+    Extension(source_svc): Extension<SourceService<SourceRepositoryImpl>>,
+    Extension(chapter_svc): Extension<ChapterService<ChapterRepositoryImpl>>,
+    Extension(history_svc): Extension<HistorySvc>,
+) -> Result<Json<SeriesDto>, StatusCode> {
+    let manga = fetch_series(&auth, &manga_svc, &source_svc, id).await?;
+    let series = series_dto(
+        &chapter_svc,
+        &history_svc,
+        manga,
+        DEFAULT_LIBRARY_ID.to_string(),
+        auth.user.id,
+    )
+    .await?;
+
+    Ok(Json(series))
+}
+
+pub async fn series_thumbnail(
+    auth: AuthUser,
+    Path(id): Path<i64>,
+    Extension(manga_svc): Extension<MangaService<MangaRepositoryImpl>>,
+    Extension(source_svc): Extension<SourceService<SourceRepositoryImpl>>,
+    Extension(image_svc): Extension<ImageSvc>,
+) -> Result<Response<Body>, StatusCode> {
+    let manga = fetch_series(&auth, &manga_svc, &source_svc, id).await?;
+
+    cover_response(&image_svc, &source_svc, &manga).await
+}
+
+pub async fn series_books(
+    auth: AuthUser,
+    Path(id): Path<i64>,
+    Query(params): Query<PageParams>,
+    Extension(manga_svc): Extension<MangaService<MangaRepositoryImpl>>,
+    Extension(source_svc): Extension<SourceService<SourceRepositoryImpl>>,
+    Extension(chapter_svc): Extension<ChapterService<ChapterRepositoryImpl>>,
+    Extension(history_svc): Extension<HistorySvc>,
+) -> Result<Json<PageOf<BookDto>>, StatusCode> {
+    let manga = fetch_series(&auth, &manga_svc, &source_svc, id).await?;
+    let (chapters, progress) =
+        chapters_with_progress(&chapter_svc, &history_svc, &manga, auth.user.id).await?;
+
+    let (chapters, total) = params.slice(chapters);
+    let page_counts = chapter_page_counts(&chapter_svc, &chapters).await;
+    let content = chapters
+        .iter()
+        .zip(page_counts)
+        .map(|(chapter, pages_count)| {
+            book_dto(
+                &manga,
+                chapter,
+                pages_count.unwrap_or_default(),
+                progress.get(&chapter.id),
+            )
+        })
+        .collect();
+
+    Ok(Json(params.page_of(content, total)))
+}
+
+pub async fn book(
+    auth: AuthUser,
+    Path(id): Path<i64>,
+    Extension(manga_svc): Extension<MangaService<MangaRepositoryImpl>>,
+    Extension(source_svc): Extension<SourceService<SourceRepositoryImpl>>,
+    Extension(chapter_svc): Extension<ChapterService<ChapterRepositoryImpl>>,
+    Extension(history_svc): Extension<HistorySvc>,
+) -> Result<Json<BookDto>, StatusCode> {
+    let chapter = fetch_book(&auth, &chapter_svc, &source_svc, id).await?;
+    let manga = manga_svc
+        .fetch_manga_by_id(chapter.manga_id, false)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+
+    let progress = history_svc
+        .get_history_chapters_by_chapter_ids(auth.user.id, &[chapter.id])
+        .await
+        .map_err(internal_error)?;
+    let pages_count = chapter_page_counts(&chapter_svc, std::slice::from_ref(&chapter))
+        .await
+        .into_iter()
+        .flatten()
+        .next()
+        .unwrap_or_default();
+
+    Ok(Json(book_dto(
+        &manga,
+        &chapter,
+        pages_count,
+        progress.first(),
+    )))
+}
+
+pub async fn book_thumbnail(
+    auth: AuthUser,
+    Path(id): Path<i64>,
+    Extension(manga_svc): Extension<MangaService<MangaRepositoryImpl>>,
+    Extension(source_svc): Extension<SourceService<SourceRepositoryImpl>>,
+    Extension(chapter_svc): Extension<ChapterService<ChapterRepositoryImpl>>,
+    Extension(image_svc): Extension<ImageSvc>,
+) -> Result<Response<Body>, StatusCode> {
+    let chapter = fetch_book(&auth, &chapter_svc, &source_svc, id).await?;
+    let manga = manga_svc
+        .fetch_manga_by_id(chapter.manga_id, false)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+
+    // chapters have no cover of their own
+    cover_response(&image_svc, &source_svc, &manga).await
+}
+
+pub async fn book_pages(
+    auth: AuthUser,
+    Path(id): Path<i64>,
+    Extension(source_svc): Extension<SourceService<SourceRepositoryImpl>>,
+    Extension(chapter_svc): Extension<ChapterService<ChapterRepositoryImpl>>,
+    Extension(image_svc): Extension<ImageSvc>,
+) -> Result<Json<Vec<PageDto>>, StatusCode> {
+    let chapter = fetch_book(&auth, &chapter_svc, &source_svc, id).await?;
+    let pages = chapter_svc
+        .fetch_chapter_pages(chapter.source_id, &chapter.path, &chapter.downloaded_path)
+        .await
+        .map_err(|e| {
+            error!("failed to get pages of chapter {id}: {e}");
+            StatusCode::BAD_GATEWAY
+        })?;
+
+    // media type is guessed from extension of page, fetching every page to
+    // know it would be too slow
+    let pages = pages
+        .iter()
+        .enumerate()
+        .map(|(index, url)| {
+            let file_name = image_svc
+                .get_image_file_name(url)
+                .unwrap_or_else(|| format!("{}.jpg", index + 1));
+            let media_type = mime_guess::from_path(&file_name)
+                .first()
+                .filter(|mime| mime.type_() == mime_guess::mime::IMAGE)
+                .map(|mime| mime.essence_str().to_string())
+                .unwrap_or_else(|| "image/jpeg".to_string());
+
+            PageDto {
+                number: index + 1,
+                file_name,
+                media_type,
+            }
+        })
+        .collect();
+
+    Ok(Json(pages))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BookPageParams {
+    /// `jpeg` or `png`, format to convert page to
+    convert: Option<String>,
+}
+
+pub async fn book_page(
+    auth: AuthUser,
+    Path((id, number)): Path<(i64, usize)>,
+    Query(params): Query<BookPageParams>,
+    Extension(source_svc): Extension<SourceService<SourceRepositoryImpl>>,
+    Extension(chapter_svc): Extension<ChapterService<ChapterRepositoryImpl>>,
+    Extension(image_svc): Extension<ImageSvc>,
+) -> Result<Response<Body>, StatusCode> {
+    let chapter = fetch_book(&auth, &chapter_svc, &source_svc, id).await?;
+    let index = number.checked_sub(1).ok_or(StatusCode::NOT_FOUND)?;
+
+    // png is served as is, readers which ask for it can read any format
+    let format = match params.convert.as_deref() {
+        Some("jpeg") => Some(ImageFormat::Jpeg),
+        _ => None,
+    };
+    let transform = ImageTransform::new(None, None, None, format);
+    let image = fetch_chapter_page(
+        &chapter_svc,
+        &source_svc,
+        &image_svc,
+        &chapter,
+        index,
+        transform,
+    )
+    .await?;
+
+    Response::builder()
+        .header("Content-Type", image.content_type)
+        .header("Content-Length", image.data.len())
+        .body(Body::from(image.data))
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+pub async fn book_read_progress(
+    auth: AuthUser,
+    Path(id): Path<i64>,
+    Extension(source_svc): Extension<SourceService<SourceRepositoryImpl>>,
+    Extension(chapter_svc): Extension<ChapterService<ChapterRepositoryImpl>>,
+    Extension(history_svc): Extension<HistorySvc>,
+) -> Result<Json<ReadProgressDto>, StatusCode> {
+    let chapter = fetch_book(&auth, &chapter_svc, &source_svc, id).await?;
+    let progress = history_svc
+        .get_history_chapters_by_chapter_ids(auth.user.id, &[chapter.id])
+        .await
+        .map_err(internal_error)?;
+
+    progress
+        .first()
+        .map(read_progress_dto)
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReadProgressUpdate {
+    /// last page read, from 1
+    page: Option<i64>,
+    completed: Option<bool>,
+}
+
+pub async fn update_read_progress(
+    auth: AuthUser,
+    Path(id): Path<i64>,
+    Extension(source_svc): Extension<SourceService<SourceRepositoryImpl>>,
+    Extension(chapter_svc): Extension<ChapterService<ChapterRepositoryImpl>>,
+    Extension(history_svc): Extension<HistorySvc>,
+    Json(update): Json<ReadProgressUpdate>,
+) -> Result<StatusCode, StatusCode> {
+    if !auth.has_scope(ApiKeyScope::Library) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let chapter = fetch_book(&auth, &chapter_svc, &source_svc, id).await?;
+    let completed = update.completed.unwrap_or(false);
+    match update.page {
+        Some(page) => history_svc
+            .insert_chapter_to_history(auth.user.id, chapter.id, (page - 1).max(0), completed)
+            .await
+            .map_err(internal_error)?,
+        None if completed => history_svc
+            .insert_chapters_to_history_as_completed(auth.user.id, vec![chapter.id])
+            .await
+            .map_err(internal_error)?,
+        None => return Err(StatusCode::BAD_REQUEST),
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn delete_read_progress(
+    auth: AuthUser,
+    Path(id): Path<i64>,
+    Extension(source_svc): Extension<SourceService<SourceRepositoryImpl>>,
+    Extension(chapter_svc): Extension<ChapterService<ChapterRepositoryImpl>>,
+    Extension(history_svc): Extension<HistorySvc>,
+) -> Result<StatusCode, StatusCode> {
+    if !auth.has_scope(ApiKeyScope::Library) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let chapter = fetch_book(&auth, &chapter_svc, &source_svc, id).await?;
+    history_svc
+        .delete_chapters_from_history(auth.user.id, vec![chapter.id])
+        .await
+        .map_err(internal_error)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn me(auth: AuthUser) -> Json<UserDto> {
+    let mut roles = vec!["USER", "PAGE_STREAMING"];
+    if auth.user.is_admin {
+        roles.push("ADMIN");
+    }
+
+    Json(UserDto {
+        id: auth.user.id.to_string(),
+        email: auth.user.username,
+        roles,
+        shared_all_libraries: true,
+        shared_libraries_ids: vec![],
+    })
+}
@@ -1,2 +1,135 @@
+pub mod auth;
+pub mod download;
 pub mod health;
 pub mod image;
+pub mod komga;
+pub mod opds;
+
+use axum::http::StatusCode;
+use futures::{stream, StreamExt, TryStreamExt};
+
+use crate::{
+    domain::{
+        entities::{
+            chapter::Chapter,
+            image::{Image, ImageTransform},
+            source::Source,
+        },
+        services::{chapter::ChapterService, image::ImageService, source::SourceService},
+    },
+    infrastructure::domain::repositories::{
+        chapter::ChapterRepositoryImpl, image::ImageRepositoryImpl,
+        image_cache::ImageCacheRepositoryImpl, source::SourceRepositoryImpl,
+    },
+};
+
+/// Chapters or pages fetched from source at the same time
+const FETCH_CONCURRENCY: usize = 4;
+
+/// File name for `Content-Disposition`, non ascii characters and quotes are
+/// replaced as header value has to be ascii
+pub fn ascii_file_name(file_name: &str) -> String {
+    file_name
+        .chars()
+        .map(|c| {
+            if (c.is_ascii_graphic() || c == ' ') && !matches!(c, '"' | '\\') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Source of chapter and urls of its pages, which are in downloaded archive
+/// if chapter is downloaded
+async fn chapter_pages(
+    chapter_svc: &ChapterService<ChapterRepositoryImpl>,
+    source_svc: &SourceService<SourceRepositoryImpl>,
+    chapter: &Chapter,
+) -> Result<(Source, Vec<String>), StatusCode> {
+    let source = source_svc
+        .get_source_by_id(chapter.source_id)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+    let pages = chapter_svc
+        .fetch_chapter_pages(chapter.source_id, &chapter.path, &chapter.downloaded_path)
+        .await
+        .map_err(|e| {
+            error!("failed to get pages of chapter {}: {e}", chapter.id);
+            StatusCode::BAD_GATEWAY
+        })?;
+
+    Ok((source, pages))
+}
+
+/// Page of chapter at `index` from 0, fetched with source url as referer
+pub async fn fetch_chapter_page(
+    chapter_svc: &ChapterService<ChapterRepositoryImpl>,
+    source_svc: &SourceService<SourceRepositoryImpl>,
+    image_svc: &ImageService<ImageCacheRepositoryImpl, ImageRepositoryImpl>,
+    chapter: &Chapter,
+    index: usize,
+    transform: ImageTransform,
+) -> Result<Image, StatusCode> {
+    let (source, pages) = chapter_pages(chapter_svc, source_svc, chapter).await?;
+    let url = pages.get(index).ok_or(StatusCode::NOT_FOUND)?;
+
+    image_svc
+        .fetch_image(url, Some(&source.url), transform)
+        .await
+        .map_err(|e| {
+            error!(
+                "failed to fetch page {index} of chapter {}: {e}",
+                chapter.id
+            );
+            StatusCode::BAD_GATEWAY
+        })
+}
+
+/// Every page of chapter in order, fetched a few at a time
+pub async fn fetch_chapter_images(
+    chapter_svc: &ChapterService<ChapterRepositoryImpl>,
+    source_svc: &SourceService<SourceRepositoryImpl>,
+    image_svc: &ImageService<ImageCacheRepositoryImpl, ImageRepositoryImpl>,
+    chapter: &Chapter,
+) -> Result<Vec<Image>, StatusCode> {
+    let (source, pages) = chapter_pages(chapter_svc, source_svc, chapter).await?;
+
+    let fetches: Vec<_> = pages
+        .iter()
+        .map(|page| image_svc.fetch_image(page, Some(&source.url), ImageTransform::default()))
+        .collect();
+
+    stream::iter(fetches)
+        .buffered(FETCH_CONCURRENCY)
+        .try_collect()
+        .await
+        .map_err(|e| {
+            error!("failed to fetch pages of chapter {}: {e}", chapter.id);
+            StatusCode::BAD_GATEWAY
+        })
+}
+
+/// Page count of each chapter fetched a few at a time, `None` if pages can't
+/// be fetched
+pub async fn chapter_page_counts(
+    chapter_svc: &ChapterService<ChapterRepositoryImpl>,
+    chapters: &[Chapter],
+) -> Vec<Option<usize>> {
+    let fetches: Vec<_> = chapters
+        .iter()
+        .map(|chapter| async move {
+            chapter_svc
+                .fetch_chapter_pages(chapter.source_id, &chapter.path, &chapter.downloaded_path)
+                .await
+                .map(|pages| pages.len())
+                .ok()
+        })
+        .collect();
+
+    stream::iter(fetches)
+        .buffered(FETCH_CONCURRENCY)
+        .collect()
+        .await
+}
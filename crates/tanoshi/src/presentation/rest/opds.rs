@@ -0,0 +1,786 @@
+//! OPDS catalog for e-readers and comic apps. `/opds` serves OPDS 1.2 atom
+//! feeds with OPDS-PSE page streaming, `/opds/v2` serves the same catalog as
+//! OPDS 2.0 json.
+
+use axum::{
+    body::Body,
+    extract::{Extension, Path, Query},
+    http::{Response, StatusCode},
+    routing::get,
+    Router,
+};
+use chrono::{NaiveDateTime, Utc};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use super::{
+    auth::{allowed_source_ids, check_source_allowed, AuthUser},
+    chapter_page_counts,
+    download::archive_response,
+    fetch_chapter_page,
+};
+use crate::{
+    domain::{
+        entities::{chapter::Chapter, image::ImageTransform, manga::Manga},
+        services::{
+            chapter::ChapterService, download::DownloadService, image::ImageService,
+            library::LibraryService, manga::MangaService, source::SourceService,
+        },
+    },
+    infrastructure::{
+        archive::{escape, ArchiveFormat},
+        auth::Claims,
+        config::Config,
+        domain::repositories::{
+            chapter::ChapterRepositoryImpl, download::DownloadRepositoryImpl,
+            image::ImageRepositoryImpl, image_cache::ImageCacheRepositoryImpl,
+            library::LibraryRepositoryImpl, manga::MangaRepositoryImpl,
+            source::SourceRepositoryImpl,
+        },
+    },
+};
+
+/// Entries per page of paginated feeds
+const PAGE_SIZE: usize = 20;
+
+const REL_ACQUISITION: &str = "http://opds-spec.org/acquisition";
+const REL_IMAGE: &str = "http://opds-spec.org/image";
+const REL_THUMBNAIL: &str = "http://opds-spec.org/image/thumbnail";
+const REL_PSE_STREAM: &str = "http://vaemendis.net/opds-pse/stream";
+
+const TYPE_NAVIGATION: &str = "application/atom+xml;profile=opds-catalog;kind=navigation";
+const TYPE_ACQUISITION: &str = "application/atom+xml;profile=opds-catalog;kind=acquisition";
+const TYPE_OPDS_JSON: &str = "application/opds+json";
+
+type ImageSvc = ImageService<ImageCacheRepositoryImpl, ImageRepositoryImpl>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OpdsVersion {
+    V1,
+    V2,
+}
+
+impl OpdsVersion {
+    fn base(&self) -> &'static str {
+        match self {
+            OpdsVersion::V1 => "/opds",
+            OpdsVersion::V2 => "/opds/v2",
+        }
+    }
+
+    fn feed_type(&self, kind: FeedKind) -> &'static str {
+        match (self, kind) {
+            (OpdsVersion::V1, FeedKind::Navigation) => TYPE_NAVIGATION,
+            (OpdsVersion::V1, FeedKind::Acquisition) => TYPE_ACQUISITION,
+            (OpdsVersion::V2, _) => TYPE_OPDS_JSON,
+        }
+    }
+}
+
+/// Routes of catalog, nested under `/opds` with [`OpdsVersion::V1`] and
+/// `/opds/v2` with [`OpdsVersion::V2`]
+pub fn router(version: OpdsVersion) -> Router {
+    Router::new()
+        .route("/", get(root))
+        .route("/library", get(library))
+        .route("/library/:category_id", get(library))
+        .route("/manga/:id", get(manga))
+        .route("/updates", get(updates))
+        .route("/downloads", get(downloads))
+        .route("/chapters/:id/download", get(download_chapter))
+        .route("/chapters/:id/pages/:page", get(chapter_page))
+        .layer(Extension(version))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FeedKind {
+    Navigation,
+    Acquisition,
+}
+
+struct Link {
+    rel: &'static str,
+    href: String,
+    media_type: String,
+    /// page count of OPDS-PSE stream link
+    count: Option<usize>,
+}
+
+impl Link {
+    fn new(rel: &'static str, href: String, media_type: &str) -> Self {
+        Self {
+            rel,
+            href,
+            media_type: media_type.to_string(),
+            count: None,
+        }
+    }
+
+    fn is_acquisition(&self) -> bool {
+        self.rel.starts_with(REL_ACQUISITION)
+    }
+
+    fn to_atom(&self) -> String {
+        let mut link = format!(
+            "<link rel=\"{}\" href=\"{}\" type=\"{}\"",
+            escape(self.rel),
+            escape(&self.href),
+            escape(&self.media_type)
+        );
+        if let Some(count) = self.count {
+            link.push_str(&format!(" pse:count=\"{count}\""));
+        }
+        link.push_str("/>");
+        link
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "rel": self.rel,
+            "href": self.href,
+            "type": self.media_type,
+        })
+    }
+}
+
+struct Entry {
+    id: String,
+    title: String,
+    updated: NaiveDateTime,
+    summary: Option<String>,
+    authors: Vec<String>,
+    links: Vec<Link>,
+}
+
+struct Feed {
+    id: String,
+    title: String,
+    kind: FeedKind,
+    links: Vec<Link>,
+    entries: Vec<Entry>,
+}
+
+impl Feed {
+    fn new(version: OpdsVersion, kind: FeedKind, path: &str, title: &str) -> Self {
+        let base = version.base();
+        Self {
+            id: format!("urn:tanoshi:opds{path}"),
+            title: title.to_string(),
+            kind,
+            links: vec![
+                Link::new("self", format!("{base}{path}"), version.feed_type(kind)),
+                Link::new(
+                    "start",
+                    base.to_string(),
+                    version.feed_type(FeedKind::Navigation),
+                ),
+            ],
+            entries: vec![],
+        }
+    }
+
+    fn to_atom(&self) -> String {
+        let mut feed = String::from(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <feed xmlns=\"http://www.w3.org/2005/Atom\" \
+             xmlns:opds=\"http://opds-spec.org/2010/catalog\" \
+             xmlns:pse=\"http://vaemendis.net/opds-pse/ns\">\n",
+        );
+        feed.push_str(&format!("<id>{}</id>\n", escape(&self.id)));
+        feed.push_str(&format!("<title>{}</title>\n", escape(&self.title)));
+        feed.push_str(&format!(
+            "<updated>{}</updated>\n",
+            timestamp(&Utc::now().naive_utc())
+        ));
+        feed.push_str("<author><name>Tanoshi</name></author>\n");
+        for link in &self.links {
+            feed.push_str(&link.to_atom());
+            feed.push('\n');
+        }
+
+        for entry in &self.entries {
+            feed.push_str("<entry>\n");
+            feed.push_str(&format!("<id>{}</id>\n", escape(&entry.id)));
+            feed.push_str(&format!("<title>{}</title>\n", escape(&entry.title)));
+            feed.push_str(&format!(
+                "<updated>{}</updated>\n",
+                timestamp(&entry.updated)
+            ));
+            for author in &entry.authors {
+                feed.push_str(&format!(
+                    "<author><name>{}</name></author>\n",
+                    escape(author)
+                ));
+            }
+            if let Some(summary) = &entry.summary {
+                feed.push_str(&format!(
+                    "<content type=\"text\">{}</content>\n",
+                    escape(summary)
+                ));
+            }
+            for link in &entry.links {
+                feed.push_str(&link.to_atom());
+                feed.push('\n');
+            }
+            feed.push_str("</entry>\n");
+        }
+
+        feed.push_str("</feed>");
+        feed
+    }
+
+    /// Entries with acquisition links are publications, the rest are
+    /// navigation. OPDS 2.0 has no page streaming so stream links are dropped.
+    fn to_json(&self) -> Value {
+        let mut navigation = vec![];
+        let mut publications = vec![];
+        for entry in &self.entries {
+            if entry.links.iter().any(Link::is_acquisition) {
+                let mut metadata = json!({
+                    "@type": "http://schema.org/Book",
+                    "identifier": entry.id,
+                    "title": entry.title,
+                    "modified": timestamp(&entry.updated),
+                });
+                if !entry.authors.is_empty() {
+                    metadata["author"] = json!(entry.authors);
+                }
+                if let Some(summary) = &entry.summary {
+                    metadata["description"] = json!(summary);
+                }
+
+                let images: Vec<Value> = entry
+                    .links
+                    .iter()
+                    .filter(|link| link.rel == REL_IMAGE || link.rel == REL_THUMBNAIL)
+                    .map(|link| json!({ "href": link.href, "type": link.media_type }))
+                    .collect();
+
+                publications.push(json!({
+                    "metadata": metadata,
+                    "links": entry
+                        .links
+                        .iter()
+                        .filter(|link| link.is_acquisition())
+                        .map(Link::to_json)
+                        .collect::<Vec<_>>(),
+                    "images": images,
+                }));
+            } else if let Some(link) = entry.links.iter().find(|link| link.rel == "subsection") {
+                navigation.push(json!({
+                    "href": link.href,
+                    "title": entry.title,
+                    "type": link.media_type,
+                    "rel": link.rel,
+                }));
+            }
+        }
+
+        let mut feed = json!({
+            "metadata": {
+                "title": self.title,
+                "modified": timestamp(&Utc::now().naive_utc()),
+            },
+            "links": self.links.iter().map(Link::to_json).collect::<Vec<_>>(),
+        });
+        if !navigation.is_empty() || self.kind == FeedKind::Navigation {
+            feed["navigation"] = json!(navigation);
+        }
+        if !publications.is_empty() || self.kind == FeedKind::Acquisition {
+            feed["publications"] = json!(publications);
+        }
+        feed
+    }
+
+    fn into_response(self, version: OpdsVersion) -> Result<Response<Body>, StatusCode> {
+        let body = match version {
+            OpdsVersion::V1 => self.to_atom(),
+            OpdsVersion::V2 => self.to_json().to_string(),
+        };
+
+        Response::builder()
+            .header("Content-Type", version.feed_type(self.kind))
+            .body(Body::from(body))
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+    }
+}
+
+fn timestamp(datetime: &NaiveDateTime) -> String {
+    format!("{}Z", datetime.format("%Y-%m-%dT%H:%M:%S"))
+}
+
+fn navigation_entry(version: OpdsVersion, path: &str, title: &str, kind: FeedKind) -> Entry {
+    Entry {
+        id: format!("urn:tanoshi:opds{path}"),
+        title: title.to_string(),
+        updated: Utc::now().naive_utc(),
+        summary: None,
+        authors: vec![],
+        links: vec![Link::new(
+            "subsection",
+            format!("{}{path}", version.base()),
+            version.feed_type(kind),
+        )],
+    }
+}
+
+fn manga_entry(
+    version: OpdsVersion,
+    manga: Manga,
+    cover_url: Option<String>,
+    user_id: i64,
+) -> Entry {
+    let mut links = vec![Link::new(
+        "subsection",
+        format!("{}/manga/{}", version.base(), manga.id),
+        version.feed_type(FeedKind::Acquisition),
+    )];
+    if let Some(cover_url) = cover_url {
+        links.push(Link::new(REL_IMAGE, cover_url.clone(), "image/jpeg"));
+        links.push(Link::new(REL_THUMBNAIL, cover_url, "image/jpeg"));
+    }
+
+    Entry {
+        id: format!("urn:tanoshi:{user_id}:manga:{}", manga.id),
+        title: manga.title,
+        updated: manga.last_uploaded_at.unwrap_or(manga.date_added),
+        summary: manga.description,
+        authors: manga.author,
+        links,
+    }
+}
+
+/// Chapter with cbz acquisition link, and page stream link if page count is known
+fn chapter_entry(manga_title: Option<&str>, chapter: &Chapter, page_count: Option<usize>) -> Entry {
+    let title = match manga_title {
+        Some(manga_title) => format!("{manga_title} - {}", chapter.title),
+        None => chapter.title.clone(),
+    };
+
+    // chapter files are the same for both versions
+    let base = OpdsVersion::V1.base();
+    let mut links = vec![Link::new(
+        REL_ACQUISITION,
+        format!("{base}/chapters/{}/download", chapter.id),
+        ArchiveFormat::Cbz.content_type(),
+    )];
+    if let Some(count) = page_count {
+        links.push(Link {
+            count: Some(count),
+            ..Link::new(
+                REL_PSE_STREAM,
+                format!(
+                    "{base}/chapters/{}/pages/{{pageNumber}}?width={{maxWidth}}",
+                    chapter.id
+                ),
+                "image/jpeg",
+            )
+        });
+    }
+
+    Entry {
+        id: format!("urn:tanoshi:chapter:{}", chapter.id),
+        title,
+        updated: chapter.uploaded,
+        summary: (!chapter.scanlator.is_empty()).then(|| chapter.scanlator.clone()),
+        authors: vec![],
+        links,
+    }
+}
+
+fn sign_cover_url(
+    config: &Config,
+    image_svc: &ImageSvc,
+    cover_url: &str,
+    claims: &Claims,
+) -> Option<String> {
+    image_svc
+        .sign_image_url(
+            &config.secret,
+            cover_url,
+            claims.sub,
+            claims.sid,
+            config.image_url.ttl,
+        )
+        .map(|token| format!("/image/{token}"))
+        .ok()
+}
+
+pub async fn root(
+    auth: AuthUser,
+    Extension(version): Extension<OpdsVersion>,
+    Extension(library_svc): Extension<LibraryService<LibraryRepositoryImpl>>,
+) -> Result<Response<Body>, StatusCode> {
+    let categories = library_svc
+        .get_categories_by_user_id(auth.user.id)
+        .await
+        .map_err(|e| {
+            error!("failed to get categories: {e}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    let mut feed = Feed::new(version, FeedKind::Navigation, "", "Tanoshi");
+    for category in categories {
+        let path = match category.id {
+            Some(id) => format!("/library/{id}"),
+            None => "/library".to_string(),
+        };
+        feed.entries.push(navigation_entry(
+            version,
+            &path,
+            &category.name,
+            FeedKind::Navigation,
+        ));
+    }
+    feed.entries.push(navigation_entry(
+        version,
+        "/updates",
+        "Recent updates",
+        FeedKind::Acquisition,
+    ));
+    if auth.can_download() {
+        feed.entries.push(navigation_entry(
+            version,
+            "/downloads",
+            "Downloaded chapters",
+            FeedKind::Acquisition,
+        ));
+    }
+
+    feed.into_response(version)
+}
+
+pub async fn library(
+    auth: AuthUser,
+    category_id: Option<Path<i64>>,
+    Extension(version): Extension<OpdsVersion>,
+    Extension(config): Extension<Config>,
+    Extension(library_svc): Extension<LibraryService<LibraryRepositoryImpl>>,
+    Extension(source_svc): Extension<SourceService<SourceRepositoryImpl>>,
+    Extension(image_svc): Extension<ImageSvc>,
+) -> Result<Response<Body>, StatusCode> {
+    let category_id = category_id.map(|Path(id)| id);
+    let category = library_svc
+        .get_category_by_id(category_id)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+
+    // manga added before user was restricted stay in library but are hidden
+    let allowed_source_ids = allowed_source_ids(&auth.user, &source_svc).await?;
+
+    let manga = library_svc
+        .get_manga_from_library_by_category_id(auth.user.id, category_id)
+        .await
+        .map_err(|e| {
+            error!("failed to get library: {e}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    let path = match category_id {
+        Some(id) => format!("/library/{id}"),
+        None => "/library".to_string(),
+    };
+    let mut feed = Feed::new(version, FeedKind::Navigation, &path, &category.name);
+    feed.entries = manga
+        .into_iter()
+        .filter(|m| {
+            allowed_source_ids
+                .as_ref()
+                .map(|source_ids| source_ids.contains(&m.source_id))
+                .unwrap_or(true)
+        })
+        .map(|m| {
+            let cover_url = sign_cover_url(&config, &image_svc, &m.cover_url, &auth.claims);
+            manga_entry(version, m, cover_url, auth.user.id)
+        })
+        .collect();
+
+    feed.into_response(version)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PageParams {
+    #[serde(default)]
+    page: usize,
+}
+
+pub async fn manga(
+    auth: AuthUser,
+    Path(id): Path<i64>,
+    Query(params): Query<PageParams>,
+    Extension(version): Extension<OpdsVersion>,
+    Extension(manga_svc): Extension<MangaService<MangaRepositoryImpl>>,
+    Extension(chapter_svc): Extension<ChapterService<ChapterRepositoryImpl>>,
+    Extension(source_svc): Extension<SourceService<SourceRepositoryImpl>>,
+) -> Result<Response<Body>, StatusCode> {
+    let manga = manga_svc
+        .fetch_manga_by_id(id, false)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+    check_source_allowed(&auth.user, &source_svc, manga.source_id).await?;
+
+    let chapters = chapter_svc
+        .fetch_chapters_by_manga_id(manga.source_id, &manga.path, manga.id, false)
+        .await
+        .map_err(|e| {
+            error!("failed to get chapters of manga {id}: {e}");
+            StatusCode::BAD_GATEWAY
+        })?;
+    let has_next_page = chapters.len() > (params.page + 1) * PAGE_SIZE;
+    let chapters: Vec<Chapter> = chapters
+        .into_iter()
+        .skip(params.page * PAGE_SIZE)
+        .take(PAGE_SIZE)
+        .collect();
+
+    // readers need page count before streaming, chapters whose pages can't
+    // be fetched can still be downloaded
+    let page_counts = chapter_page_counts(&chapter_svc, &chapters).await;
+
+    let path = format!("/manga/{id}");
+    let mut feed = Feed::new(version, FeedKind::Acquisition, &path, &manga.title);
+    if params.page > 0 {
+        feed.links.push(Link::new(
+            "previous",
+            format!("{}{path}?page={}", version.base(), params.page - 1),
+            version.feed_type(FeedKind::Acquisition),
+        ));
+    }
+    if has_next_page {
+        feed.links.push(Link::new(
+            "next",
+            format!("{}{path}?page={}", version.base(), params.page + 1),
+            version.feed_type(FeedKind::Acquisition),
+        ));
+    }
+    feed.entries = chapters
+        .iter()
+        .zip(page_counts)
+        .map(|(chapter, page_count)| chapter_entry(None, chapter, page_count))
+        .collect();
+
+    feed.into_response(version)
+}
+
+/// Cursor of paginated feeds, entries older than `timestamp` and `id`
+#[derive(Debug, Deserialize)]
+pub struct CursorParams {
+    timestamp: Option<i64>,
+    id: Option<i64>,
+}
+
+impl CursorParams {
+    fn after(&self) -> (i64, i64) {
+        match (self.timestamp, self.id) {
+            (Some(timestamp), Some(id)) => (timestamp, id),
+            _ => (Utc::now().timestamp(), 1),
+        }
+    }
+}
+
+fn next_link(version: OpdsVersion, path: &str, timestamp: i64, id: i64) -> Link {
+    Link::new(
+        "next",
+        format!("{}{path}?timestamp={timestamp}&id={id}", version.base()),
+        version.feed_type(FeedKind::Acquisition),
+    )
+}
+
+pub async fn updates(
+    auth: AuthUser,
+    Query(params): Query<CursorParams>,
+    Extension(version): Extension<OpdsVersion>,
+    Extension(library_svc): Extension<LibraryService<LibraryRepositoryImpl>>,
+    Extension(chapter_svc): Extension<ChapterService<ChapterRepositoryImpl>>,
+    Extension(source_svc): Extension<SourceService<SourceRepositoryImpl>>,
+) -> Result<Response<Body>, StatusCode> {
+    let source_ids: Option<Vec<i64>> = allowed_source_ids(&auth.user, &source_svc)
+        .await?
+        .map(|source_ids| source_ids.into_iter().collect());
+
+    let (after_timestamp, after_id) = params.after();
+    let updates = library_svc
+        .get_library_recent_updates(
+            auth.user.id,
+            after_timestamp,
+            after_id,
+            0,
+            0,
+            Some(PAGE_SIZE),
+            None,
+            source_ids.as_deref(),
+        )
+        .await
+        .map_err(|e| {
+            error!("failed to get recent updates: {e}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    let mut feed = Feed::new(version, FeedKind::Acquisition, "/updates", "Recent updates");
+    if let Some(last) = updates.last().filter(|_| updates.len() == PAGE_SIZE) {
+        feed.links.push(next_link(
+            version,
+            "/updates",
+            last.uploaded.timestamp(),
+            last.chapter_id,
+        ));
+    }
+
+    for update in updates {
+        if let Ok(chapter) = chapter_svc.fetch_chapter_by_id(update.chapter_id).await {
+            feed.entries
+                .push(chapter_entry(Some(&update.manga_title), &chapter, None));
+        }
+    }
+
+    feed.into_response(version)
+}
+
+pub async fn downloads(
+    auth: AuthUser,
+    Query(params): Query<CursorParams>,
+    Extension(version): Extension<OpdsVersion>,
+    Extension(download_svc): Extension<DownloadService<DownloadRepositoryImpl>>,
+    Extension(manga_svc): Extension<MangaService<MangaRepositoryImpl>>,
+    Extension(source_svc): Extension<SourceService<SourceRepositoryImpl>>,
+) -> Result<Response<Body>, StatusCode> {
+    if !auth.can_download() {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let source_ids: Option<Vec<i64>> = allowed_source_ids(&auth.user, &source_svc)
+        .await?
+        .map(|source_ids| source_ids.into_iter().collect());
+
+    let (after_timestamp, after_id) = params.after();
+    let downloads = download_svc
+        .get_downloaded_chapters(
+            after_timestamp,
+            after_id,
+            0,
+            0,
+            Some(PAGE_SIZE),
+            None,
+            source_ids.as_deref(),
+        )
+        .await
+        .map_err(|e| {
+            error!("failed to get downloaded chapters: {e}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    let mut feed = Feed::new(
+        version,
+        FeedKind::Acquisition,
+        "/downloads",
+        "Downloaded chapters",
+    );
+    if let Some(last) = downloads.last().filter(|_| downloads.len() == PAGE_SIZE) {
+        feed.links.push(next_link(
+            version,
+            "/downloads",
+            last.date_added.timestamp(),
+            last.id,
+        ));
+    }
+
+    for download in downloads {
+        let manga_title = manga_svc
+            .fetch_manga_by_id(download.manga_id, false)
+            .await
+            .map(|manga| manga.title)
+            .ok();
+        let chapter = Chapter {
+            id: download.id,
+            source_id: download.source_id,
+            manga_id: download.manga_id,
+            title: download.title,
+            path: download.path,
+            number: download.number,
+            scanlator: download.scanlator,
+            uploaded: download.uploaded,
+            date_added: download.date_added,
+            downloaded_path: download.downloaded_path,
+            next: None,
+            prev: None,
+        };
+        feed.entries
+            .push(chapter_entry(manga_title.as_deref(), &chapter, None));
+    }
+
+    feed.into_response(version)
+}
+
+/// Chapter as cbz, see [`archive_response`]
+pub async fn download_chapter(
+    auth: AuthUser,
+    Path(id): Path<i64>,
+    Extension(chapter_svc): Extension<ChapterService<ChapterRepositoryImpl>>,
+    Extension(manga_svc): Extension<MangaService<MangaRepositoryImpl>>,
+    Extension(source_svc): Extension<SourceService<SourceRepositoryImpl>>,
+    Extension(image_svc): Extension<ImageSvc>,
+) -> Result<Response<Body>, StatusCode> {
+    if !auth.can_download() {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let chapter = chapter_svc
+        .fetch_chapter_by_id(id)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+    check_source_allowed(&auth.user, &source_svc, chapter.source_id).await?;
+
+    let manga = manga_svc
+        .fetch_manga_by_id(chapter.manga_id, false)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+
+    archive_response(
+        chapter_svc,
+        source_svc,
+        image_svc,
+        manga,
+        vec![chapter],
+        ArchiveFormat::Cbz,
+    )
+    .await
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StreamParams {
+    /// scale down to fit width, keeping aspect ratio
+    width: Option<u32>,
+}
+
+/// Page of chapter for OPDS-PSE, page numbers start from 0
+pub async fn chapter_page(
+    auth: AuthUser,
+    Path((id, page)): Path<(i64, usize)>,
+    Query(params): Query<StreamParams>,
+    Extension(chapter_svc): Extension<ChapterService<ChapterRepositoryImpl>>,
+    Extension(source_svc): Extension<SourceService<SourceRepositoryImpl>>,
+    Extension(image_svc): Extension<ImageSvc>,
+) -> Result<Response<Body>, StatusCode> {
+    let chapter = chapter_svc
+        .fetch_chapter_by_id(id)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+    check_source_allowed(&auth.user, &source_svc, chapter.source_id).await?;
+
+    let transform = ImageTransform::new(params.width, None, None, None);
+    let image = fetch_chapter_page(
+        &chapter_svc,
+        &source_svc,
+        &image_svc,
+        &chapter,
+        page,
+        transform,
+    )
+    .await?;
+
+    Response::builder()
+        .header("Content-Type", image.content_type)
+        .header("Content-Length", image.data.len())
+        .body(Body::from(image.data))
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
@@ -0,0 +1,192 @@
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use chrono::Utc;
+use flume::TrySendError;
+use tanoshi_notifier::telegram::{
+    self, BotHandler, DownloadItem, DownloadStatus, LibraryItem, UpdateItem,
+};
+
+use crate::{
+    application::worker::updates::{ChapterUpdateCommand, ChapterUpdateCommandSender},
+    domain::services::{
+        download::DownloadService, history::HistoryService, library::LibraryService,
+        source::SourceService, user::UserService,
+    },
+    infrastructure::domain::repositories::{
+        chapter::ChapterRepositoryImpl, download::DownloadRepositoryImpl,
+        history::HistoryRepositoryImpl, library::LibraryRepositoryImpl,
+        source::SourceRepositoryImpl, user::UserRepositoryImpl,
+    },
+};
+
+const RECENT_UPDATES_COUNT: usize = 10;
+
+/// Serve telegram bot commands from chats linked to a tanoshi user
+pub struct TelegramBotHandler {
+    user_svc: UserService<UserRepositoryImpl>,
+    library_svc: LibraryService<LibraryRepositoryImpl>,
+    history_svc: HistoryService<ChapterRepositoryImpl, HistoryRepositoryImpl>,
+    download_svc: DownloadService<DownloadRepositoryImpl>,
+    source_svc: SourceService<SourceRepositoryImpl>,
+    chapter_update_command_tx: ChapterUpdateCommandSender,
+    download_path: PathBuf,
+}
+
+impl TelegramBotHandler {
+    pub fn new<P: AsRef<Path>>(
+        user_svc: UserService<UserRepositoryImpl>,
+        library_svc: LibraryService<LibraryRepositoryImpl>,
+        history_svc: HistoryService<ChapterRepositoryImpl, HistoryRepositoryImpl>,
+        download_svc: DownloadService<DownloadRepositoryImpl>,
+        source_svc: SourceService<SourceRepositoryImpl>,
+        chapter_update_command_tx: ChapterUpdateCommandSender,
+        download_path: P,
+    ) -> Self {
+        Self {
+            user_svc,
+            library_svc,
+            history_svc,
+            download_svc,
+            source_svc,
+            chapter_update_command_tx,
+            download_path: PathBuf::new().join(download_path),
+        }
+    }
+
+    async fn user_id(&self, chat_id: i64) -> Result<i64> {
+        self.user_svc
+            .fetch_user_id_by_notification_target(telegram::NAME, &chat_id.to_string())
+            .await
+            .map_err(|_| anyhow!("This chat is not linked, open tanoshi profile page to link it"))
+    }
+
+    /// Installed sources user may browse, `None` if user is not restricted
+    async fn allowed_source_ids(&self, user_id: i64) -> Result<Option<HashSet<i64>>> {
+        let user = self.user_svc.fetch_user_by_id(user_id).await?;
+
+        Ok(self.source_svc.get_allowed_source_ids(&user).await?)
+    }
+}
+
+#[async_trait]
+impl BotHandler for TelegramBotHandler {
+    async fn link_chat(&self, chat_id: i64, code: &str) -> Result<String> {
+        let user = self
+            .user_svc
+            .link_notification_target(code, telegram::NAME, &chat_id.to_string())
+            .await?;
+
+        Ok(user.username)
+    }
+
+    async fn recent_updates(&self, chat_id: i64) -> Result<Vec<UpdateItem>> {
+        let user_id = self.user_id(chat_id).await?;
+        let source_ids: Option<Vec<i64>> = self
+            .allowed_source_ids(user_id)
+            .await?
+            .map(|source_ids| source_ids.into_iter().collect());
+
+        let updates = self
+            .library_svc
+            .get_library_recent_updates(
+                user_id,
+                Utc::now().timestamp(),
+                1,
+                0,
+                0,
+                Some(RECENT_UPDATES_COUNT),
+                None,
+                source_ids.as_deref(),
+            )
+            .await?
+            .into_iter()
+            .map(|update| UpdateItem {
+                chapter_id: update.chapter_id,
+                manga_title: update.manga_title,
+                chapter_title: update.chapter_title,
+                uploaded: update.uploaded,
+            })
+            .collect();
+
+        Ok(updates)
+    }
+
+    async fn search_library(&self, chat_id: i64, query: &str) -> Result<Vec<LibraryItem>> {
+        let user_id = self.user_id(chat_id).await?;
+        let source_ids = self.allowed_source_ids(user_id).await?;
+
+        let manga = self
+            .library_svc
+            .search_library(user_id, query)
+            .await?
+            .into_iter()
+            .filter(|(manga, _)| {
+                source_ids
+                    .as_ref()
+                    .map(|source_ids| source_ids.contains(&manga.source_id))
+                    .unwrap_or(true)
+            })
+            .map(|(manga, unread_count)| LibraryItem {
+                manga_id: manga.id,
+                title: manga.title,
+                unread_count,
+            })
+            .collect();
+
+        Ok(manga)
+    }
+
+    async fn mark_chapter_as_read(&self, chat_id: i64, chapter_id: i64) -> Result<()> {
+        let user_id = self.user_id(chat_id).await?;
+
+        self.history_svc
+            .insert_chapters_to_history_as_completed(user_id, vec![chapter_id])
+            .await?;
+
+        Ok(())
+    }
+
+    async fn refresh_library(&self, chat_id: i64) -> Result<()> {
+        let user_id = self.user_id(chat_id).await?;
+
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        if let Err(e) = self
+            .chapter_update_command_tx
+            .try_send(ChapterUpdateCommand::Library(user_id, tx))
+        {
+            return Err(match e {
+                TrySendError::Full(_) => anyhow!("chapter updates is ongoing, try again later"),
+                TrySendError::Disconnected(_) => anyhow!("chapter updates thread is closed"),
+            });
+        }
+
+        rx.await?
+    }
+
+    async fn download_status(&self, chat_id: i64) -> Result<DownloadStatus> {
+        let _ = self.user_id(chat_id).await?;
+
+        let queue = self
+            .download_svc
+            .get_download_queue(vec![])
+            .await?
+            .into_iter()
+            .map(|entry| DownloadItem {
+                manga_title: entry.manga_title,
+                chapter_title: entry.chapter_title,
+                downloaded: entry.downloaded,
+                total: entry.total,
+            })
+            .collect();
+
+        Ok(DownloadStatus {
+            running: self.download_svc.get_download_status(&self.download_path),
+            queue,
+        })
+    }
+}
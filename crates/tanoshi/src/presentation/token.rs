@@ -6,8 +6,19 @@ use axum::{
 };
 use headers::{authorization::Bearer, Authorization};
 
+use crate::domain::services::user::API_KEY_PREFIX;
+
+const API_KEY_HEADER: &str = "X-Api-Key";
+
+/// Access token or api key sent with request
 pub struct Token(pub String);
 
+impl Token {
+    pub fn is_api_key(&self) -> bool {
+        self.0.starts_with(API_KEY_PREFIX)
+    }
+}
+
 #[async_trait]
 impl<S> FromRequestParts<S> for Token
 where
@@ -17,11 +28,19 @@ where
 
     async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
         // Extract the token from the authorization header
+        if let Ok(TypedHeader(Authorization(bearer))) =
+            parts.extract::<TypedHeader<Authorization<Bearer>>>().await
+        {
+            return Ok(Token(bearer.token().to_string()));
+        }
+
+        // clients which can't set authorization header send api key in its own header
         let token = parts
-            .extract::<TypedHeader<Authorization<Bearer>>>()
-            .await
-            .map(|TypedHeader(Authorization(bearer))| Token(bearer.token().to_string()))
-            .unwrap_or_else(|_| Token("".to_string()));
+            .headers
+            .get(API_KEY_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| Token(value.to_string()))
+            .unwrap_or_else(|| Token("".to_string()));
 
         Ok(token)
     }